@@ -0,0 +1,48 @@
+use std::net::SocketAddr;
+use nustat_core::flow::collector::FlowCollector;
+
+// Usage: cargo run --example flow_collector [listen_addr]
+// Prints the records received from the IPFIX / NetFlow v9 exporter.
+fn main() {
+    let listen_addr: SocketAddr = match std::env::args().nth(1).unwrap_or(String::from("127.0.0.1:4739")).parse() {
+        Ok(addr) => addr,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
+    let mut collector = match FlowCollector::bind(listen_addr) {
+        Ok(collector) => collector,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
+    println!("Listening on {}", listen_addr);
+    loop {
+        match collector.recv() {
+            Ok(records) => {
+                for record in records {
+                    println!(
+                        "[{:?}][{:?}][{:?}] {}:{} -> {}:{} {} bytes {} packets [{}][{}][{}]",
+                        record.protocol,
+                        record.direction,
+                        record.end_reason,
+                        record.src_ip_addr,
+                        record.src_port,
+                        record.dst_ip_addr,
+                        record.dst_port,
+                        record.octets,
+                        record.packets,
+                        record.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+                        record.process_name.unwrap_or_default(),
+                        record.domain.unwrap_or_default()
+                    );
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+            }
+        }
+    }
+}
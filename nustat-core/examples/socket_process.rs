@@ -2,7 +2,13 @@ use nustat_core::socket::SocketInfoOption;
 
 fn main() {
     let local_ip_map = nustat_core::net::interface::get_local_ip_map();
-    let socket_info = nustat_core::socket::get_sockets_info(SocketInfoOption::default());
+    let socket_info = match nustat_core::socket::get_sockets_info(SocketInfoOption::default()) {
+        Ok(socket_info) => socket_info,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
     for info in socket_info {
         let process_info = info.process.unwrap();
         let mut interface_name = "unknown";
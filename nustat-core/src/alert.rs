@@ -0,0 +1,316 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use crate::net::stat::NetStatData;
+use crate::net::traffic::{Direction, TrafficInfo};
+use crate::notification::{Notification, NotificationType, Severity};
+use crate::socket::TransportProtocol;
+use crate::socket::listener::ListeningPortInfo;
+
+/// Seconds a rule stays quiet for the same key after firing.
+pub const DEFAULT_ALERT_COOLDOWN: u64 = 300;
+/// Seconds of traffic a `HostBytes` rule sums up.
+pub const DEFAULT_ALERT_WINDOW: u64 = 60;
+/// Notifications kept, newest first.
+pub const DEFAULT_MAX_NOTIFICATIONS: usize = 100;
+
+/// What an alert rule watches.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// Bytes exchanged with a remote host within `window` seconds exceed `threshold`.
+    /// `host` None matches every host. `direction` None counts both directions.
+    HostBytes {
+        #[serde(default)]
+        host: Option<IpAddr>,
+        #[serde(default)]
+        direction: Option<Direction>,
+        threshold: usize,
+        #[serde(default = "default_window")]
+        window: u64,
+    },
+    /// First traffic with a country. Countries seen at the first evaluation are the baseline.
+    NewCountry {
+        /// Country codes that never alert.
+        #[serde(default)]
+        ignore: Vec<String>,
+    },
+    /// A process has traffic with a remote port. `process` None matches any process.
+    ProcessPort {
+        #[serde(default)]
+        process: Option<String>,
+        port: u16,
+    },
+    /// A listening port appears. Ports listening at the first evaluation are the baseline.
+    NewListener {
+        /// Ports that never alert.
+        #[serde(default)]
+        ignore: Vec<u16>,
+        /// Only ports reachable from other hosts.
+        #[serde(default)]
+        exposed_only: bool,
+    },
+}
+
+fn default_window() -> u64 {
+    DEFAULT_ALERT_WINDOW
+}
+
+fn default_cooldown() -> u64 {
+    DEFAULT_ALERT_COOLDOWN
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AlertRule {
+    pub name: String,
+    #[serde(default)]
+    pub severity: Severity,
+    /// Seconds before the rule fires again for the same key.
+    #[serde(default = "default_cooldown")]
+    pub cooldown: u64,
+    pub condition: AlertCondition,
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertOption {
+    pub rules: Vec<AlertRule>,
+    pub max_notifications: usize,
+}
+
+impl AlertOption {
+    pub fn new(rules: Vec<AlertRule>) -> AlertOption {
+        AlertOption {
+            rules,
+            max_notifications: DEFAULT_MAX_NOTIFICATIONS,
+        }
+    }
+}
+
+impl Default for AlertOption {
+    fn default() -> Self {
+        AlertOption::new(Vec::new())
+    }
+}
+
+/// A rule match before cooldown and deduplication.
+struct Alert {
+    key: String,
+    body: String,
+    notification_type: NotificationType,
+}
+
+/// Listening socket identity: protocol, bound address and port.
+type ListenerKey = (TransportProtocol, IpAddr, u16);
+/// Bytes of the recent deltas with their time.
+type ByteWindow = VecDeque<(DateTime<Local>, usize)>;
+
+/// Evaluates the alert rules on every traffic delta and keeps the resulting notifications.
+pub struct AlertEngine {
+    option: AlertOption,
+    /// Bytes per rule and host over the rule's window.
+    windows: HashMap<(usize, IpAddr), ByteWindow>,
+    /// None until the baseline is taken.
+    countries: Option<HashSet<String>>,
+    listeners: Option<HashSet<ListenerKey>>,
+    last_fired: HashMap<(usize, String), DateTime<Local>>,
+    notifications: Vec<Notification>,
+}
+
+impl AlertEngine {
+    pub fn new(option: AlertOption) -> AlertEngine {
+        AlertEngine {
+            option,
+            windows: HashMap::new(),
+            countries: None,
+            listeners: None,
+            last_fired: HashMap::new(),
+            notifications: Vec::new(),
+        }
+    }
+    pub fn has_rules(&self) -> bool {
+        !self.option.rules.is_empty()
+    }
+    /// Whether `evaluate` needs the listening ports.
+    pub fn needs_listeners(&self) -> bool {
+        self.option.rules.iter().any(|rule| matches!(rule.condition, AlertCondition::NewListener { .. }))
+    }
+    /// Notifications, newest first.
+    pub fn notifications(&self) -> &[Notification] {
+        &self.notifications
+    }
+    /// Take the countries of data collected before the engine started (e.g. a restored session) as known.
+    pub fn learn(&mut self, data: &NetStatData) {
+        let countries = self.countries.get_or_insert_with(HashSet::new);
+        for host in data.remote_hosts.values() {
+            if !host.country_code.is_empty() {
+                countries.insert(host.country_code.clone());
+            }
+        }
+    }
+    /// Evaluate the rules on the traffic of one interval. `listeners` is only read by `NewListener` rules.
+    /// Returns the number of notifications raised or updated.
+    pub fn evaluate(&mut self, delta: &NetStatData, listeners: Option<&[ListeningPortInfo]>, now: DateTime<Local>) -> usize {
+        let new_countries = self.update_countries(delta);
+        let new_listeners = listeners.map(|listeners| self.update_listeners(listeners)).unwrap_or_default();
+        let mut fired = 0;
+        for index in 0..self.option.rules.len() {
+            let rule = self.option.rules[index].clone();
+            let alerts = match &rule.condition {
+                AlertCondition::HostBytes { host, direction, threshold, window } => self.check_host_bytes(index, delta, *host, *direction, *threshold, *window, now),
+                AlertCondition::NewCountry { ignore } => new_countries
+                    .iter()
+                    .filter(|(country_code, _)| !ignore.iter().any(|c| c.eq_ignore_ascii_case(country_code)))
+                    .map(|(country_code, body)| Alert { key: country_code.clone(), body: body.clone(), notification_type: NotificationType::RemoteHost })
+                    .collect(),
+                AlertCondition::ProcessPort { process, port } => check_process_port(delta, process.as_deref(), *port),
+                AlertCondition::NewListener { ignore, exposed_only } => new_listeners
+                    .iter()
+                    .filter(|listener| !ignore.contains(&listener.local_port) && (!exposed_only || listener.is_exposed()))
+                    .map(listener_alert)
+                    .collect(),
+            };
+            for alert in alerts {
+                if self.fire(index, &rule, alert, now) {
+                    fired += 1;
+                }
+            }
+        }
+        fired
+    }
+    /// Countries first seen in the delta, with the alert text. Empty while taking the baseline.
+    fn update_countries(&mut self, delta: &NetStatData) -> Vec<(String, String)> {
+        let baseline = self.countries.is_none();
+        let countries = self.countries.get_or_insert_with(HashSet::new);
+        let mut new_countries: Vec<(String, String)> = Vec::new();
+        for host in delta.remote_hosts.values() {
+            if host.country_code.is_empty() || !countries.insert(host.country_code.clone()) || baseline {
+                continue;
+            }
+            let country = if host.country_name.is_empty() { host.country_code.clone() } else { format!("{} ({})", host.country_name, host.country_code) };
+            new_countries.push((host.country_code.clone(), format!("First traffic with {} from {}", country, host.ip_addr)));
+        }
+        new_countries
+    }
+    /// Listeners not present at the previous evaluation. Empty while taking the baseline.
+    fn update_listeners(&mut self, listeners: &[ListeningPortInfo]) -> Vec<ListeningPortInfo> {
+        let current: HashSet<ListenerKey> = listeners.iter().map(|l| (l.protocol, l.local_ip_addr, l.local_port)).collect();
+        let new_listeners: Vec<ListeningPortInfo> = match &self.listeners {
+            Some(previous) => listeners.iter().filter(|l| !previous.contains(&(l.protocol, l.local_ip_addr, l.local_port))).cloned().collect(),
+            None => Vec::new(),
+        };
+        // Closed ports are forgotten, so a port opened again alerts again.
+        self.listeners = Some(current);
+        new_listeners
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn check_host_bytes(&mut self, index: usize, delta: &NetStatData, host: Option<IpAddr>, direction: Option<Direction>, threshold: usize, window: u64, now: DateTime<Local>) -> Vec<Alert> {
+        let window = chrono::Duration::seconds(window as i64);
+        for remote_host in delta.remote_hosts.values() {
+            if host.is_some_and(|host| host != remote_host.ip_addr) {
+                continue;
+            }
+            let bytes = direction_bytes(&remote_host.traffic_info, direction);
+            if bytes > 0 {
+                self.windows.entry((index, remote_host.ip_addr)).or_default().push_back((now, bytes));
+            }
+        }
+        let mut alerts: Vec<Alert> = Vec::new();
+        self.windows.retain(|(rule_index, ip_addr), samples| {
+            if *rule_index != index {
+                return true;
+            }
+            while samples.front().is_some_and(|(time, _)| *time <= now - window) {
+                samples.pop_front();
+            }
+            let total: usize = samples.iter().map(|(_, bytes)| bytes).sum();
+            if total > threshold {
+                let host_name = delta.remote_hosts.get(ip_addr).map(|host| host.hostname.clone()).unwrap_or_default();
+                let host = if host_name.is_empty() || host_name == ip_addr.to_string() { ip_addr.to_string() } else { format!("{} ({})", ip_addr, host_name) };
+                let verb = match direction {
+                    Some(Direction::Egress) => "sent to",
+                    Some(Direction::Ingress) => "received from",
+                    None => "exchanged with",
+                };
+                alerts.push(Alert {
+                    key: ip_addr.to_string(),
+                    body: format!("{} bytes {} {} in {}s", total, verb, host, window.num_seconds()),
+                    notification_type: NotificationType::Traffic,
+                });
+            }
+            !samples.is_empty()
+        });
+        alerts
+    }
+    /// Apply the cooldown and deduplication. Returns true if a notification was raised or updated.
+    fn fire(&mut self, index: usize, rule: &AlertRule, alert: Alert, now: DateTime<Local>) -> bool {
+        let fired_key = (index, alert.key.clone());
+        if let Some(last_fired) = self.last_fired.get(&fired_key) {
+            if now - *last_fired < chrono::Duration::seconds(rule.cooldown as i64) {
+                return false;
+            }
+        }
+        self.last_fired.insert(fired_key, now);
+        let mut notification = match self.notifications.iter().position(|n| n.rule == rule.name && n.key == alert.key) {
+            Some(position) => self.notifications.remove(position),
+            None => Notification {
+                title: rule.name.clone(),
+                body: String::new(),
+                notification_type: alert.notification_type,
+                timestamp: String::new(),
+                severity: rule.severity,
+                rule: rule.name.clone(),
+                key: alert.key,
+                count: 0,
+            },
+        };
+        notification.body = alert.body;
+        notification.timestamp = now.to_rfc3339();
+        notification.count += 1;
+        self.notifications.insert(0, notification);
+        self.notifications.truncate(self.option.max_notifications);
+        true
+    }
+}
+
+fn direction_bytes(traffic: &TrafficInfo, direction: Option<Direction>) -> usize {
+    match direction {
+        Some(Direction::Egress) => traffic.bytes_sent,
+        Some(Direction::Ingress) => traffic.bytes_received,
+        None => traffic.total_bytes(),
+    }
+}
+
+fn check_process_port(delta: &NetStatData, process: Option<&str>, port: u16) -> Vec<Alert> {
+    let mut alerts: Vec<Alert> = Vec::new();
+    for conn in delta.connection_map.keys() {
+        if conn.remote_port != port {
+            continue;
+        }
+        // Connections without a process yet are checked again on their next traffic.
+        let Some(socket_process) = delta.get_socket_process(conn).and_then(|socket| socket.process.as_ref()) else {
+            continue;
+        };
+        if process.is_some_and(|name| name != socket_process.name) {
+            continue;
+        }
+        alerts.push(Alert {
+            key: format!("{}:{}:{}", socket_process.name, conn.remote_ip_addr, conn.remote_port),
+            body: format!("{} ({}) connected to {} port {} ({})", socket_process.name, socket_process.pid, conn.remote_ip_addr, conn.remote_port, conn.protocol.as_str()),
+            notification_type: NotificationType::Connection,
+        });
+    }
+    alerts
+}
+
+fn listener_alert(listener: &ListeningPortInfo) -> Alert {
+    let process = match &listener.process {
+        Some(process) => format!(" by {} ({})", process.name, process.pid),
+        None => String::new(),
+    };
+    Alert {
+        key: format!("{} {}:{}", listener.protocol.as_str(), listener.local_ip_addr, listener.local_port),
+        body: format!("{} port {} opened on {}{}", listener.protocol.as_str(), listener.local_port, listener.local_ip_addr, process),
+        notification_type: NotificationType::Listener,
+    }
+}
@@ -0,0 +1,189 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::time::Duration;
+use serde::de::DeserializeOwned;
+use crate::net::host::HostDisplayInfo;
+use crate::net::service::ServiceDisplayInfo;
+use crate::net::stat::Overview;
+use crate::process::ProcessDisplayInfo;
+use crate::socket::SocketTrafficInfo;
+use crate::socket::listener::ListeningPortInfo;
+use super::{ApiData, ApiEndpoint, ApiError, ApiStream, ApiVersion, API_VERSION};
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longer than the server keepalive interval, so a silent stream means a dead daemon.
+const EVENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Status code and headers of a response.
+struct ResponseHead {
+    status: u16,
+    content_length: Option<usize>,
+}
+
+fn read_head(reader: &mut BufReader<Box<dyn ApiStream>>) -> Result<ResponseHead, String> {
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line).map_err(|e| e.to_string())?;
+    let status: u16 = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or(format!("Invalid response: {}", status_line.trim()))?;
+    let mut content_length: Option<usize> = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().ok();
+            }
+        }
+    }
+    Ok(ResponseHead { status, content_length })
+}
+
+/// Client of the daemon API.
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    endpoint: ApiEndpoint,
+}
+
+impl ApiClient {
+    /// Connect and check that the daemon speaks the same API version.
+    pub fn connect(endpoint: ApiEndpoint) -> Result<ApiClient, String> {
+        let client = ApiClient { endpoint };
+        let version: ApiVersion = client.get("version")?;
+        if version.api_version != API_VERSION {
+            return Err(format!("API version mismatch: daemon {} (nustat {}), client {}", version.api_version, version.version, API_VERSION));
+        }
+        Ok(client)
+    }
+    pub fn endpoint(&self) -> &ApiEndpoint {
+        &self.endpoint
+    }
+    fn request(&self, resource: &str) -> Result<(ResponseHead, BufReader<Box<dyn ApiStream>>), String> {
+        let mut stream = self.endpoint.connect().map_err(|e| format!("Failed to connect to {}: {}", self.endpoint, e))?;
+        stream.set_timeout(Some(RESPONSE_TIMEOUT)).map_err(|e| e.to_string())?;
+        write!(stream, "GET /v{}/{} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", API_VERSION, resource).map_err(|e| e.to_string())?;
+        stream.flush().map_err(|e| e.to_string())?;
+        let mut reader = BufReader::new(stream);
+        let head = read_head(&mut reader)?;
+        Ok((head, reader))
+    }
+    /// GET `/v1/{resource}` and decode the JSON body.
+    pub fn get<T: DeserializeOwned>(&self, resource: &str) -> Result<T, String> {
+        let (head, mut reader) = self.request(resource)?;
+        let mut body: Vec<u8> = Vec::new();
+        match head.content_length {
+            Some(len) => {
+                body.resize(len, 0);
+                reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+            }
+            None => {
+                reader.read_to_end(&mut body).map_err(|e| e.to_string())?;
+            }
+        }
+        if head.status != 200 {
+            let message = serde_json::from_slice::<ApiError>(&body).map(|e| e.error).unwrap_or_else(|_| String::from_utf8_lossy(&body).to_string());
+            return Err(format!("{} returned {}: {}", resource, head.status, message));
+        }
+        serde_json::from_slice(&body).map_err(|e| format!("Invalid {} response: {}", resource, e))
+    }
+    pub fn overview(&self) -> Result<Overview, String> {
+        self.get("overview")
+    }
+    pub fn remote_hosts(&self, limit: Option<usize>) -> Result<Vec<HostDisplayInfo>, String> {
+        self.get(&with_limit("remote_hosts", limit))
+    }
+    pub fn connections(&self, limit: Option<usize>) -> Result<Vec<SocketTrafficInfo>, String> {
+        self.get(&with_limit("connections", limit))
+    }
+    pub fn processes(&self, limit: Option<usize>) -> Result<Vec<ProcessDisplayInfo>, String> {
+        self.get(&with_limit("processes", limit))
+    }
+    pub fn app_protocols(&self, limit: Option<usize>) -> Result<Vec<ServiceDisplayInfo>, String> {
+        self.get(&with_limit("app_protocols", limit))
+    }
+    pub fn listeners(&self) -> Result<Vec<ListeningPortInfo>, String> {
+        self.get("listeners")
+    }
+    /// Cumulative data since the daemon started.
+    pub fn data(&self) -> Result<ApiData, String> {
+        self.get("data")
+    }
+    /// Subscribe to the event stream.
+    pub fn events(&self) -> Result<EventStream, String> {
+        let (head, reader) = self.request("events")?;
+        if head.status != 200 {
+            return Err(format!("events returned {}", head.status));
+        }
+        reader.get_ref().set_timeout(Some(EVENT_TIMEOUT)).map_err(|e| e.to_string())?;
+        Ok(EventStream { reader })
+    }
+}
+
+fn with_limit(resource: &str, limit: Option<usize>) -> String {
+    match limit {
+        Some(limit) => format!("{}?limit={}", resource, limit),
+        None => resource.to_string(),
+    }
+}
+
+/// A server-sent event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiEvent {
+    pub id: Option<u64>,
+    pub event: String,
+    pub data: String,
+}
+
+/// `text/event-stream` of the daemon.
+pub struct EventStream {
+    reader: BufReader<Box<dyn ApiStream>>,
+}
+
+impl EventStream {
+    /// Block until the next event. Comments (keepalives) are skipped.
+    pub fn next_event(&mut self) -> Result<ApiEvent, String> {
+        let mut event = ApiEvent { id: None, event: String::from("message"), data: String::new() };
+        let mut has_field = false;
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+                return Err(String::from("Event stream closed"));
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                if has_field {
+                    return Ok(event);
+                }
+                continue;
+            }
+            if line.starts_with(':') {
+                continue;
+            }
+            let (field, value) = line.split_once(':').map(|(f, v)| (f, v.strip_prefix(' ').unwrap_or(v))).unwrap_or((line, ""));
+            has_field = true;
+            match field {
+                "id" => event.id = value.parse().ok(),
+                "event" => event.event = value.to_string(),
+                "data" => {
+                    if !event.data.is_empty() {
+                        event.data.push('\n');
+                    }
+                    event.data.push_str(value);
+                }
+                _ => {}
+            }
+        }
+    }
+    /// Block until the next `delta` event.
+    pub fn next_delta(&mut self) -> Result<ApiData, String> {
+        loop {
+            let event = self.next_event()?;
+            if event.event == "delta" {
+                return serde_json::from_str(&event.data).map_err(|e| format!("Invalid delta event: {}", e));
+            }
+        }
+    }
+}
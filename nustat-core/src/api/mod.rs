@@ -0,0 +1,201 @@
+pub mod server;
+pub mod client;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::time::Duration;
+use chrono::{DateTime, Local};
+use serde::{Serialize, Deserialize};
+use crate::net::host::RemoteHostInfo;
+use crate::net::neighbor::NeighborInfo;
+use crate::net::stat::NetStatData;
+use crate::net::traffic::TrafficInfo;
+use crate::socket::{LocalSocket, SocketConnection, SocketProcess, TcpMetrics};
+
+/// Version of the API. All paths are prefixed with `/v{API_VERSION}`.
+/// Fields are only added within a version. Renames and removals bump it.
+pub const API_VERSION: u32 = 1;
+
+/// File name of the default API socket in the config directory.
+pub const DEFAULT_API_SOCKET_NAME: &str = "nustatd.sock";
+
+/// Default API socket of a daemon running as root, reachable by unprivileged clients.
+pub const DEFAULT_API_SYSTEM_SOCKET_PATH: &str = "/run/nustat/nustatd.sock";
+
+/// Default listen address of the loopback HTTP API. Used where Unix sockets are not available.
+pub const DEFAULT_API_LISTEN_ADDR: &str = "127.0.0.1:7391";
+
+/// Where the API is served.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiEndpoint {
+    Unix(PathBuf),
+    TCP(SocketAddr),
+}
+
+impl ApiEndpoint {
+    /// `unix:///path/to.sock`, `http://127.0.0.1:7391` or `127.0.0.1:7391`.
+    pub fn parse(endpoint: &str) -> Result<ApiEndpoint, String> {
+        if let Some(path) = endpoint.strip_prefix("unix://") {
+            if path.is_empty() {
+                return Err(format!("Missing socket path: {}", endpoint));
+            }
+            Ok(ApiEndpoint::Unix(PathBuf::from(path)))
+        } else {
+            let addr = endpoint.strip_prefix("http://").unwrap_or(endpoint).trim_end_matches('/');
+            addr.parse().map(ApiEndpoint::TCP).map_err(|e| format!("Invalid API endpoint {}: {}", endpoint, e))
+        }
+    }
+    /// Where the daemon listens by default: /run/nustat/nustatd.sock as root, ~/.nustat/nustatd.sock otherwise.
+    /// The loopback address where Unix sockets are not available.
+    pub fn default_server_endpoint() -> Option<ApiEndpoint> {
+        if cfg!(unix) {
+            if crate::sys::is_root() {
+                Some(ApiEndpoint::Unix(PathBuf::from(DEFAULT_API_SYSTEM_SOCKET_PATH)))
+            } else {
+                crate::sys::get_user_file_path(DEFAULT_API_SOCKET_NAME).map(ApiEndpoint::Unix)
+            }
+        } else {
+            DEFAULT_API_LISTEN_ADDR.parse().ok().map(ApiEndpoint::TCP)
+        }
+    }
+    /// Where clients look for the daemon by default: the system socket if a daemon running as root created it,
+    /// else the socket of a daemon running as this user.
+    pub fn default_endpoint() -> Option<ApiEndpoint> {
+        if cfg!(unix) {
+            let system_socket = PathBuf::from(DEFAULT_API_SYSTEM_SOCKET_PATH);
+            if system_socket.exists() {
+                return Some(ApiEndpoint::Unix(system_socket));
+            }
+            crate::sys::get_user_file_path(DEFAULT_API_SOCKET_NAME).map(ApiEndpoint::Unix)
+        } else {
+            DEFAULT_API_LISTEN_ADDR.parse().ok().map(ApiEndpoint::TCP)
+        }
+    }
+    pub(crate) fn connect(&self) -> std::io::Result<Box<dyn ApiStream>> {
+        match self {
+            ApiEndpoint::TCP(addr) => Ok(Box::new(TcpStream::connect_timeout(addr, CONNECT_TIMEOUT)?)),
+            #[cfg(unix)]
+            ApiEndpoint::Unix(path) => Ok(Box::new(std::os::unix::net::UnixStream::connect(path)?)),
+            #[cfg(not(unix))]
+            ApiEndpoint::Unix(_) => Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Unix sockets are not supported on this platform")),
+        }
+    }
+}
+
+impl std::fmt::Display for ApiEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiEndpoint::Unix(path) => write!(f, "unix://{}", path.display()),
+            ApiEndpoint::TCP(addr) => write!(f, "http://{}", addr),
+        }
+    }
+}
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A connection of either transport. The API is plain HTTP/1.1 on both.
+pub(crate) trait ApiStream: Read + Write + Send {
+    fn try_clone_stream(&self) -> std::io::Result<Box<dyn ApiStream>>;
+    fn set_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>;
+}
+
+impl ApiStream for TcpStream {
+    fn try_clone_stream(&self) -> std::io::Result<Box<dyn ApiStream>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+    fn set_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.set_read_timeout(timeout)?;
+        self.set_write_timeout(timeout)
+    }
+}
+
+#[cfg(unix)]
+impl ApiStream for std::os::unix::net::UnixStream {
+    fn try_clone_stream(&self) -> std::io::Result<Box<dyn ApiStream>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+    fn set_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.set_read_timeout(timeout)?;
+        self.set_write_timeout(timeout)
+    }
+}
+
+/// Response of `/v1/version`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ApiVersion {
+    pub api_version: u32,
+    pub version: String,
+}
+
+impl ApiVersion {
+    pub fn new() -> ApiVersion {
+        ApiVersion {
+            api_version: API_VERSION,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+impl Default for ApiVersion {
+    fn default() -> Self {
+        ApiVersion::new()
+    }
+}
+
+/// Body of error responses.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiError {
+    pub error: String,
+}
+
+/// `NetStatData` of an interval on the wire.
+/// Maps keyed by structs are sent as lists of entries since JSON object keys are strings.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiData {
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub if_index: u32,
+    pub if_name: String,
+    pub traffic: TrafficInfo,
+    pub remote_hosts: Vec<RemoteHostInfo>,
+    pub connections: Vec<(SocketConnection, TrafficInfo)>,
+    pub local_sockets: Vec<(LocalSocket, SocketProcess)>,
+    pub connection_sockets: Vec<(SocketConnection, SocketProcess)>,
+    pub local_ips: Vec<(std::net::IpAddr, String)>,
+    pub neighbors: Vec<NeighborInfo>,
+    pub tcp_metrics: Vec<(SocketConnection, TcpMetrics)>,
+}
+
+impl ApiData {
+    pub fn from_data(data: &NetStatData, start: DateTime<Local>, end: DateTime<Local>) -> ApiData {
+        ApiData {
+            start,
+            end,
+            if_index: data.if_index,
+            if_name: data.if_name.clone(),
+            traffic: data.traffic.clone(),
+            remote_hosts: data.remote_hosts.values().cloned().collect(),
+            connections: data.connection_map.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            local_sockets: data.local_socket_map.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            connection_sockets: data.connection_socket_map.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            local_ips: data.local_ip_map.iter().map(|(k, v)| (*k, v.clone())).collect(),
+            neighbors: data.neighbor_map.values().cloned().collect(),
+            tcp_metrics: data.tcp_metrics_map.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        }
+    }
+    pub fn into_data(self) -> NetStatData {
+        let mut data = NetStatData::new();
+        data.if_index = self.if_index;
+        data.if_name = self.if_name;
+        data.traffic = self.traffic;
+        data.remote_hosts = self.remote_hosts.into_iter().map(|host| (host.ip_addr, host)).collect();
+        data.connection_map = self.connections.into_iter().collect();
+        data.local_socket_map = self.local_sockets.into_iter().collect();
+        data.connection_socket_map = self.connection_sockets.into_iter().collect();
+        data.local_ip_map = self.local_ips.into_iter().collect();
+        data.neighbor_map = self.neighbors.into_iter().map(|neighbor| (neighbor.ip_addr, neighbor)).collect();
+        data.tcp_metrics_map = self.tcp_metrics.into_iter().collect();
+        data
+    }
+}
@@ -0,0 +1,388 @@
+use std::io::{BufRead, BufReader};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use crate::alert::{AlertEngine, AlertOption};
+use crate::net::stat::{NetStatData, NetStatStrage};
+use crate::thread_log;
+use super::{ApiData, ApiEndpoint, ApiError, ApiStream, ApiVersion, API_VERSION};
+
+/// Default permissions of the API socket. Owner and group only.
+pub const DEFAULT_API_SOCKET_MODE: u32 = 0o660;
+
+/// Maximum number of concurrent connections, including event streams.
+const MAX_CONNECTIONS: usize = 64;
+/// Deltas queued per event stream. A subscriber falling further behind is disconnected.
+const EVENT_QUEUE_SIZE: usize = 16;
+/// Comment line sent on idle event streams so clients notice dead connections.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_HEADER_LINES: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct ApiServerOption {
+    pub endpoint: ApiEndpoint,
+    /// Interval of the `delta` events.
+    pub tick_rate: Duration,
+    /// Permissions of the Unix socket.
+    pub socket_mode: u32,
+    /// Group owning the Unix socket. None keeps the daemon's group.
+    pub socket_group: Option<u32>,
+    /// Alert rules evaluated on every delta. Their notifications are served in the overview.
+    pub alert: AlertOption,
+}
+
+impl ApiServerOption {
+    pub fn new(endpoint: ApiEndpoint) -> ApiServerOption {
+        ApiServerOption {
+            endpoint,
+            tick_rate: Duration::from_millis(1000),
+            socket_mode: DEFAULT_API_SOCKET_MODE,
+            socket_group: None,
+            alert: AlertOption::default(),
+        }
+    }
+}
+
+/// Event stream subscribers and the position of the drain.
+/// Held while draining, so a new subscriber's snapshot and the following deltas never overlap.
+struct EventHub {
+    /// Each receives the serialized delta of every tick.
+    subscribers: Vec<SyncSender<Arc<String>>>,
+    /// End of the last drained interval.
+    last_drain: DateTime<Local>,
+    sequence: u64,
+}
+
+type SharedEventHub = Arc<Mutex<EventHub>>;
+type SharedAlertEngine = Arc<Mutex<AlertEngine>>;
+
+enum ApiListener {
+    Loopback(TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
+impl ApiListener {
+    fn bind(option: &ApiServerOption) -> Result<ApiListener, String> {
+        match &option.endpoint {
+            ApiEndpoint::TCP(addr) => {
+                // The API is unauthenticated, so it is never exposed beyond the host.
+                if !addr.ip().is_loopback() {
+                    return Err(format!("API listen address must be a loopback address: {}", addr));
+                }
+                let listener = TcpListener::bind(addr).map_err(|e| format!("Failed to bind API server to {}: {}", addr, e))?;
+                Ok(ApiListener::Loopback(listener))
+            }
+            #[cfg(unix)]
+            ApiEndpoint::Unix(path) => {
+                use std::os::unix::fs::PermissionsExt;
+                use std::os::unix::net::{UnixListener, UnixStream};
+                if path.exists() {
+                    if UnixStream::connect(path).is_ok() {
+                        return Err(format!("Another daemon is already listening on {}", path.display()));
+                    }
+                    // Stale socket of a previous run
+                    std::fs::remove_file(path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+                }
+                // e.g. /run/nustat. Clients must be able to traverse it.
+                if let Some(dir) = path.parent() {
+                    if !dir.as_os_str().is_empty() && !dir.exists() {
+                        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+                        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o755)).map_err(|e| format!("Failed to set permissions of {}: {}", dir.display(), e))?;
+                    }
+                }
+                let listener = UnixListener::bind(path).map_err(|e| format!("Failed to bind API server to {}: {}", path.display(), e))?;
+                if let Some(gid) = option.socket_group {
+                    std::os::unix::fs::chown(path, None, Some(gid)).map_err(|e| format!("Failed to change the group of {}: {}", path.display(), e))?;
+                }
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(option.socket_mode)).map_err(|e| format!("Failed to set permissions of {}: {}", path.display(), e))?;
+                Ok(ApiListener::Unix(listener))
+            }
+            #[cfg(not(unix))]
+            ApiEndpoint::Unix(_) => Err(String::from("Unix sockets are not supported on this platform")),
+        }
+    }
+    fn accept(&self) -> std::io::Result<Box<dyn ApiStream>> {
+        match self {
+            ApiListener::Loopback(listener) => Ok(Box::new(listener.accept()?.0)),
+            #[cfg(unix)]
+            ApiListener::Unix(listener) => Ok(Box::new(listener.accept()?.0)),
+        }
+    }
+}
+
+pub(crate) fn write_response(stream: &mut dyn ApiStream, status: &str, body: &str) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+pub(crate) fn write_json<T: Serialize>(stream: &mut dyn ApiStream, value: &T) -> std::io::Result<()> {
+    match serde_json::to_string(value) {
+        Ok(body) => write_response(stream, "200 OK", &body),
+        Err(e) => write_error(stream, "500 Internal Server Error", &e.to_string()),
+    }
+}
+
+pub(crate) fn write_error(stream: &mut dyn ApiStream, status: &str, message: &str) -> std::io::Result<()> {
+    let body = serde_json::to_string(&ApiError { error: message.to_string() }).unwrap_or_default();
+    write_response(stream, status, &body)
+}
+
+/// Value of the `limit` query parameter.
+pub(crate) fn query_limit(query: &str) -> Result<Option<usize>, String> {
+    for pair in query.split('&') {
+        if let Some(value) = pair.strip_prefix("limit=") {
+            return value.parse().map(Some).map_err(|_| format!("Invalid limit: {}", value));
+        }
+    }
+    Ok(None)
+}
+
+/// Serve `text/event-stream` until the client disconnects or falls behind.
+fn stream_events(stream: &mut dyn ApiStream, receiver: Receiver<Arc<String>>) -> std::io::Result<()> {
+    write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n")?;
+    write!(stream, "event: hello\ndata: {}\n\n", serde_json::to_string(&ApiVersion::new()).unwrap_or_default())?;
+    stream.flush()?;
+    loop {
+        match receiver.recv_timeout(KEEPALIVE_INTERVAL) {
+            Ok(event) => stream.write_all(event.as_bytes())?,
+            Err(RecvTimeoutError::Timeout) => stream.write_all(b": keepalive\n\n")?,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        stream.flush()?;
+    }
+}
+
+/// Request line and Host header of an HTTP request. Other headers are read and ignored.
+pub(crate) struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    pub host: Option<String>,
+}
+
+/// Whether the Host header names the local host, with or without a port.
+/// Rejecting other names keeps pages served from a rebound DNS name from reading the TCP endpoint.
+pub(crate) fn is_local_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((addr, port)) if port.is_empty() || port.starts_with(':') => addr,
+            _ => return false,
+        },
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    name.eq_ignore_ascii_case("localhost") || name == "127.0.0.1" || name == "::1"
+}
+
+pub(crate) fn read_request(stream: &mut dyn ApiStream) -> std::io::Result<Request> {
+    stream.set_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone_stream()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the headers, keeping the Host
+    let mut host: Option<String> = None;
+    for _ in 0..MAX_HEADER_LINES {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("host") {
+                host = Some(value.trim().to_string());
+            }
+        }
+    }
+    stream.set_timeout(Some(WRITE_TIMEOUT))?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        host,
+    })
+}
+
+/// `check_host` is set for the TCP endpoint, which browsers can reach. The Unix socket is protected by its permissions.
+fn handle_connection(mut stream: Box<dyn ApiStream>, netstat_strage: &NetStatStrage, event_hub: &SharedEventHub, alert_engine: &SharedAlertEngine, since: DateTime<Local>, check_host: bool) -> std::io::Result<()> {
+    let Request { method, path, query, host } = read_request(stream.as_mut())?;
+    if check_host && !host.as_deref().is_some_and(is_local_host) {
+        return write_error(stream.as_mut(), "403 Forbidden", "Host must be localhost, 127.0.0.1 or [::1]");
+    }
+    if method != "GET" {
+        return write_error(stream.as_mut(), "405 Method Not Allowed", "Method Not Allowed");
+    }
+    let prefix = format!("/v{}/", API_VERSION);
+    let Some(resource) = path.strip_prefix(prefix.as_str()) else {
+        return write_error(stream.as_mut(), "404 Not Found", &format!("Unknown path {}. Paths start with {}", path, prefix));
+    };
+    let limit = match query_limit(&query) {
+        Ok(limit) => limit,
+        Err(e) => return write_error(stream.as_mut(), "400 Bad Request", &e),
+    };
+    match resource {
+        "version" => write_json(stream.as_mut(), &ApiVersion::new()),
+        "overview" => {
+            let mut overview = netstat_strage.get_totals().get_overview();
+            match alert_engine.lock() {
+                Ok(alert_engine) => overview.notificatons = alert_engine.notifications().to_vec(),
+                Err(e) => thread_log!(error, "[api] alert engine error: {:?}", e),
+            }
+            write_json(stream.as_mut(), &overview)
+        }
+        "remote_hosts" => write_json(stream.as_mut(), &netstat_strage.get_totals().get_remote_hosts(limit)),
+        "connections" => write_json(stream.as_mut(), &netstat_strage.get_totals().get_connections(limit)),
+        "processes" => write_json(stream.as_mut(), &netstat_strage.get_totals().get_processes(limit)),
+        "app_protocols" => write_json(stream.as_mut(), &netstat_strage.get_totals().get_app_protocols(limit)),
+        "listeners" => {
+            let totals = netstat_strage.get_totals();
+            write_json(stream.as_mut(), &crate::socket::listener::listening_ports(netstat_strage, &totals.connection_map))
+        }
+        "data" => write_json(stream.as_mut(), &ApiData::from_data(&netstat_strage.get_totals(), since, Local::now())),
+        "events" => {
+            let (sender, receiver) = mpsc::sync_channel(EVENT_QUEUE_SIZE);
+            match event_hub.lock() {
+                Ok(mut event_hub) => {
+                    // Counters up to the last drain. The following deltas continue from there.
+                    let totals = netstat_strage.get_drained_totals().unwrap_or_else(NetStatData::new);
+                    let json = serde_json::to_string(&ApiData::from_data(&totals, since, event_hub.last_drain)).map_err(std::io::Error::other)?;
+                    let _ = sender.try_send(Arc::new(format!("id: {}\nevent: snapshot\ndata: {}\n\n", event_hub.sequence, json)));
+                    event_hub.subscribers.push(sender);
+                }
+                Err(e) => return write_error(stream.as_mut(), "500 Internal Server Error", &e.to_string()),
+            }
+            stream_events(stream.as_mut(), receiver)
+        }
+        _ => write_error(stream.as_mut(), "404 Not Found", &format!("Unknown path {}", path)),
+    }
+}
+
+/// Drain the strage every tick, push the delta to the event streams and evaluate the alert rules on it.
+/// The API server owns the drain, so cumulative endpoints read the totals.
+fn publish_deltas(netstat_strage: Arc<NetStatStrage>, event_hub: SharedEventHub, alert_engine: SharedAlertEngine, tick_rate: Duration) {
+    loop {
+        thread::sleep(tick_rate);
+        let data = match event_hub.lock() {
+            Ok(mut event_hub) => {
+                let data = netstat_strage.clone_data_and_reset();
+                let start = event_hub.last_drain;
+                let end = Local::now();
+                event_hub.last_drain = end;
+                event_hub.sequence += 1;
+                if !event_hub.subscribers.is_empty() {
+                    publish_delta(&mut event_hub, &data, start, end);
+                }
+                data
+            }
+            Err(e) => {
+                thread_log!(error, "[api] event hub error: {:?}", e);
+                continue;
+            }
+        };
+        match alert_engine.lock() {
+            Ok(mut alert_engine) => {
+                if !alert_engine.has_rules() {
+                    continue;
+                }
+                let listeners = if alert_engine.needs_listeners() {
+                    Some(crate::socket::listener::listening_ports(&netstat_strage, &data.connection_map))
+                } else {
+                    None
+                };
+                alert_engine.evaluate(&data, listeners.as_deref(), Local::now());
+            }
+            Err(e) => {
+                thread_log!(error, "[api] alert engine error: {:?}", e);
+            }
+        }
+    }
+}
+
+fn publish_delta(event_hub: &mut EventHub, data: &NetStatData, start: DateTime<Local>, end: DateTime<Local>) {
+    match serde_json::to_string(&ApiData::from_data(data, start, end)) {
+        Ok(json) => {
+            let event = Arc::new(format!("id: {}\nevent: delta\ndata: {}\n\n", event_hub.sequence, json));
+            event_hub.subscribers.retain(|subscriber| match subscriber.try_send(Arc::clone(&event)) {
+                Ok(_) => true,
+                Err(TrySendError::Full(_)) => {
+                    thread_log!(warn, "[api] disconnecting a lagging event subscriber");
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+        }
+        Err(e) => {
+            thread_log!(error, "[api] delta serialize error: {}", e);
+        }
+    }
+}
+
+/// Start the API server. Enables cumulative totals and drains the strage every tick,
+/// so it must be the only consumer of `clone_data_and_reset` in the process.
+pub fn start_api_server(netstat_strage: Arc<NetStatStrage>, option: ApiServerOption) -> Result<thread::JoinHandle<()>, String> {
+    let listener = ApiListener::bind(&option)?;
+    netstat_strage.enable_totals();
+    let since = Local::now();
+    let event_hub: SharedEventHub = Arc::new(Mutex::new(EventHub {
+        subscribers: Vec::new(),
+        last_drain: since,
+        sequence: 0,
+    }));
+    let alert_engine: SharedAlertEngine = Arc::new(Mutex::new(AlertEngine::new(option.alert.clone())));
+    let publisher_strage = Arc::clone(&netstat_strage);
+    let publisher_event_hub = Arc::clone(&event_hub);
+    let publisher_alert_engine = Arc::clone(&alert_engine);
+    let tick_rate = option.tick_rate;
+    thread::Builder::new()
+        .name(String::from("api-events"))
+        .spawn(move || publish_deltas(publisher_strage, publisher_event_hub, publisher_alert_engine, tick_rate))
+        .map_err(|e| e.to_string())?;
+    let connections = Arc::new(AtomicUsize::new(0));
+    let check_host = matches!(listener, ApiListener::Loopback(_));
+    thread::Builder::new()
+        .name(String::from("api-server"))
+        .spawn(move || loop {
+            let mut stream = match listener.accept() {
+                Ok(stream) => stream,
+                Err(e) => {
+                    thread_log!(error, "[api] accept error: {}", e);
+                    continue;
+                }
+            };
+            if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                connections.fetch_sub(1, Ordering::SeqCst);
+                let _ = write_error(stream.as_mut(), "503 Service Unavailable", "Too many connections");
+                continue;
+            }
+            let netstat_strage = Arc::clone(&netstat_strage);
+            let event_hub = Arc::clone(&event_hub);
+            let alert_engine = Arc::clone(&alert_engine);
+            let counter = Arc::clone(&connections);
+            let spawned = thread::Builder::new().name(String::from("api-connection")).spawn(move || {
+                if let Err(e) = handle_connection(stream, &netstat_strage, &event_hub, &alert_engine, since, check_host) {
+                    thread_log!(debug, "[api] connection error: {}", e);
+                }
+                counter.fetch_sub(1, Ordering::SeqCst);
+            });
+            if let Err(e) = spawned {
+                connections.fetch_sub(1, Ordering::SeqCst);
+                thread_log!(error, "[api] spawn error: {}", e);
+            }
+        })
+        .map_err(|e| e.to_string())
+}
@@ -3,6 +3,18 @@ use crate::thread_log;
 use crate::sys;
 use crate::log::LogLevel;
 use crate::log::DEFAULT_LOG_FILE_PATH;
+use crate::socket::{SocketBackendType, SocketUpdateOption};
+use crate::flow::{FlowExportOption, FlowExportProtocol};
+use crate::flow::sflow::SflowExportOption;
+use crate::pcap::{PacketSampling, SamplingMode};
+use crate::stream::{StreamFormat, StreamTarget};
+use crate::api::ApiEndpoint;
+use crate::api::server::ApiServerOption;
+#[cfg(feature = "fleet")]
+use crate::fleet::agent::FleetAgentOption;
+#[cfg(feature = "fleet")]
+use crate::fleet::server::FleetServerOption;
+use crate::alert::{AlertOption, AlertRule};
 pub const NUSTAT_CONFIG_FILE_NAME: &str = "nustat-config.json";
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -13,6 +25,30 @@ pub struct AppConfig {
     pub network: NetworkConfig,
     /// Display configuration.
     pub display: DisplayConfig,
+    /// Session state configuration.
+    #[serde(default)]
+    pub state: StateConfig,
+    /// Prometheus metrics exporter configuration.
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    /// IPFIX / NetFlow v9 flow export configuration.
+    #[serde(default)]
+    pub flow_export: FlowExportConfig,
+    /// sFlow v5 export configuration.
+    #[serde(default)]
+    pub sflow_export: SflowExportConfig,
+    /// Streaming output configuration.
+    #[serde(default)]
+    pub stream: StreamConfig,
+    /// Daemon API configuration.
+    #[serde(default)]
+    pub api: ApiConfig,
+    /// Fleet agent and aggregation server configuration.
+    #[serde(default)]
+    pub fleet: FleetConfig,
+    /// Alert rules configuration.
+    #[serde(default)]
+    pub alert: AlertConfig,
 }
 
 impl AppConfig {
@@ -21,6 +57,14 @@ impl AppConfig {
             logging: LoggingConfig::new(),
             network: NetworkConfig::new(),
             display: DisplayConfig::new(),
+            state: StateConfig::new(),
+            metrics: MetricsConfig::new(),
+            flow_export: FlowExportConfig::new(),
+            sflow_export: SflowExportConfig::new(),
+            stream: StreamConfig::new(),
+            api: ApiConfig::new(),
+            fleet: FleetConfig::new(),
+            alert: AlertConfig::new(),
         }
     }
     pub fn load() -> AppConfig {
@@ -99,6 +143,26 @@ pub struct NetworkConfig {
     pub interfaces: Vec<String>,
     /// Enable reverse DNS lookup.
     pub reverse_dns: bool,
+    /// Socket enumeration backend. Default is Netlink on Linux, Netstat2 on other platforms.
+    #[serde(default)]
+    pub socket_backend: SocketBackendType,
+    /// Network namespaces to capture in addition to the host namespace. Linux only.
+    /// Each entry is a name in /var/run/netns, `pid:<pid>`, the namespace inode, or `*` for all.
+    #[serde(default)]
+    pub netns: Vec<String>,
+    /// Interval between full socket table scans in milliseconds. Default is 10000.
+    #[serde(default = "default_socket_scan_interval")]
+    pub socket_scan_interval: u64,
+    /// Rescan the socket table on process start/exit and unattributed new flows, in addition to polling.
+    /// Process events are Linux only and require CAP_NET_ADMIN. Default is true.
+    #[serde(default = "default_socket_events")]
+    pub socket_events: bool,
+    /// Process only 1 in N captured packets. Counters are scaled up and marked as estimates. Default is 1 (every packet).
+    #[serde(default = "default_sampling_rate")]
+    pub sampling_rate: u32,
+    /// Deterministic (every Nth packet) or Random. Default is Deterministic.
+    #[serde(default)]
+    pub sampling_mode: SamplingMode,
 }
 
 impl NetworkConfig {
@@ -106,8 +170,444 @@ impl NetworkConfig {
         NetworkConfig {
             interfaces: Vec::new(),
             reverse_dns: false,
+            socket_backend: SocketBackendType::default(),
+            netns: Vec::new(),
+            socket_scan_interval: default_socket_scan_interval(),
+            socket_events: default_socket_events(),
+            sampling_rate: default_sampling_rate(),
+            sampling_mode: SamplingMode::default(),
         }
     }
+    pub fn socket_update_option(&self) -> SocketUpdateOption {
+        SocketUpdateOption {
+            interval: std::time::Duration::from_millis(self.socket_scan_interval),
+            event_driven: self.socket_events,
+        }
+    }
+    pub fn packet_sampling(&self) -> Option<PacketSampling> {
+        PacketSampling::new(self.sampling_rate, self.sampling_mode)
+    }
+}
+
+fn default_socket_scan_interval() -> u64 {
+    crate::socket::DEFAULT_SOCKET_SCAN_INTERVAL.as_millis() as u64
+}
+
+fn default_socket_events() -> bool {
+    true
+}
+
+fn default_sampling_rate() -> u32 {
+    1
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StateConfig {
+    /// Save the counters to ~/.nustat/state on exit and at intervals, and restore them on start. Default is false.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Interval between saves in seconds. Default is 60.
+    #[serde(default = "default_state_save_interval")]
+    pub save_interval: u64,
+}
+
+impl StateConfig {
+    pub fn new() -> StateConfig {
+        StateConfig {
+            enabled: false,
+            save_interval: default_state_save_interval(),
+        }
+    }
+}
+
+impl Default for StateConfig {
+    fn default() -> Self {
+        StateConfig::new()
+    }
+}
+
+fn default_state_save_interval() -> u64 {
+    60
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MetricsConfig {
+    /// Serve Prometheus metrics at http://<listen_addr>/metrics. Default is false.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Listen address. Default is 127.0.0.1:9595 (loopback only).
+    #[serde(default = "default_metrics_listen_addr")]
+    pub listen_addr: String,
+    /// Maximum number of series per labeled metric (remote hosts, ASNs, countries, processes, app protocols).
+    /// The rest is aggregated into an `other` series. Default is 20.
+    #[serde(default = "default_metrics_series_limit")]
+    pub series_limit: usize,
+}
+
+impl MetricsConfig {
+    pub fn new() -> MetricsConfig {
+        MetricsConfig {
+            enabled: false,
+            listen_addr: default_metrics_listen_addr(),
+            series_limit: default_metrics_series_limit(),
+        }
+    }
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig::new()
+    }
+}
+
+fn default_metrics_listen_addr() -> String {
+    crate::metrics::DEFAULT_METRICS_LISTEN_ADDR.to_string()
+}
+
+fn default_metrics_series_limit() -> usize {
+    crate::metrics::DEFAULT_METRICS_SERIES_LIMIT
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FlowExportConfig {
+    /// Export connections as flow records over UDP. Default is false.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Collector address. Default is 127.0.0.1:4739.
+    #[serde(default = "default_flow_collector")]
+    pub collector: String,
+    /// Export protocol. IPFIX or NetFlowV9. Default is IPFIX.
+    #[serde(default)]
+    pub protocol: FlowExportProtocol,
+    /// Seconds after which a long-lived flow is exported. Default is 60.
+    #[serde(default = "default_flow_active_timeout")]
+    pub active_timeout: u64,
+    /// Seconds without packets after which a flow is exported. Default is 15.
+    #[serde(default = "default_flow_idle_timeout")]
+    pub idle_timeout: u64,
+    /// Observation domain ID (IPFIX) or source ID (NetFlow v9). Default is 0.
+    #[serde(default)]
+    pub observation_domain_id: u32,
+    /// Private enterprise number of the process and domain fields (IPFIX only). Default is 32473.
+    #[serde(default = "default_flow_enterprise_number")]
+    pub enterprise_number: u32,
+}
+
+impl FlowExportConfig {
+    pub fn new() -> FlowExportConfig {
+        FlowExportConfig {
+            enabled: false,
+            collector: default_flow_collector(),
+            protocol: FlowExportProtocol::IPFIX,
+            active_timeout: default_flow_active_timeout(),
+            idle_timeout: default_flow_idle_timeout(),
+            observation_domain_id: 0,
+            enterprise_number: default_flow_enterprise_number(),
+        }
+    }
+    pub fn to_export_option(&self) -> Result<FlowExportOption, String> {
+        let collector = self.collector.parse().map_err(|e| format!("Invalid flow collector address {}: {}", self.collector, e))?;
+        let mut option = FlowExportOption::new(collector);
+        option.protocol = self.protocol;
+        option.active_timeout = std::time::Duration::from_secs(self.active_timeout);
+        option.idle_timeout = std::time::Duration::from_secs(self.idle_timeout);
+        option.observation_domain_id = self.observation_domain_id;
+        option.enterprise_number = self.enterprise_number;
+        Ok(option)
+    }
+}
+
+impl Default for FlowExportConfig {
+    fn default() -> Self {
+        FlowExportConfig::new()
+    }
+}
+
+fn default_flow_collector() -> String {
+    String::from("127.0.0.1:4739")
+}
+
+fn default_flow_active_timeout() -> u64 {
+    crate::flow::DEFAULT_ACTIVE_TIMEOUT.as_secs()
+}
+
+fn default_flow_idle_timeout() -> u64 {
+    crate::flow::DEFAULT_IDLE_TIMEOUT.as_secs()
+}
+
+fn default_flow_enterprise_number() -> u32 {
+    crate::flow::DEFAULT_ENTERPRISE_NUMBER
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SflowExportConfig {
+    /// Export sampled packet headers as sFlow v5 over UDP. Set `network.sampling_rate` as well. Default is false.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Collector address. Default is 127.0.0.1:6343.
+    #[serde(default = "default_sflow_collector")]
+    pub collector: String,
+    /// Agent address in the datagrams. If empty, the source address of the route to the collector. Default is empty.
+    #[serde(default)]
+    pub agent_addr: String,
+    /// Sub-agent ID. Default is 0.
+    #[serde(default)]
+    pub sub_agent_id: u32,
+}
+
+impl SflowExportConfig {
+    pub fn new() -> SflowExportConfig {
+        SflowExportConfig {
+            enabled: false,
+            collector: default_sflow_collector(),
+            agent_addr: String::new(),
+            sub_agent_id: 0,
+        }
+    }
+    pub fn to_export_option(&self) -> Result<SflowExportOption, String> {
+        let collector = self.collector.parse().map_err(|e| format!("Invalid sFlow collector address {}: {}", self.collector, e))?;
+        let mut option = SflowExportOption::new(collector);
+        if !self.agent_addr.is_empty() {
+            option.agent_addr = Some(self.agent_addr.parse().map_err(|e| format!("Invalid sFlow agent address {}: {}", self.agent_addr, e))?);
+        }
+        option.sub_agent_id = self.sub_agent_id;
+        Ok(option)
+    }
+}
+
+impl Default for SflowExportConfig {
+    fn default() -> Self {
+        SflowExportConfig::new()
+    }
+}
+
+fn default_sflow_collector() -> String {
+    crate::flow::sflow::DEFAULT_SFLOW_COLLECTOR.to_string()
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StreamConfig {
+    /// Stream the traffic delta of every tick while the TUI is running. Default is false.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Output format. Default is JSONLines.
+    #[serde(default)]
+    pub format: StreamFormat,
+    /// File path, `udp://host:port` or `unix:///path/to.sock`. Stdout is only available with `nustat stream`. Default is empty.
+    #[serde(default)]
+    pub output: String,
+}
+
+impl StreamConfig {
+    pub fn new() -> StreamConfig {
+        StreamConfig {
+            enabled: false,
+            format: StreamFormat::JSONLines,
+            output: String::new(),
+        }
+    }
+    pub fn target(&self) -> Result<StreamTarget, String> {
+        match StreamTarget::parse(&self.output)? {
+            StreamTarget::Stdout => Err(String::from("Stream output must be a file or socket while the TUI is running")),
+            target => Ok(target),
+        }
+    }
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig::new()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ApiConfig {
+    /// Where `nustat daemon` serves the API: `unix:///path/to.sock` or a loopback `host:port`.
+    /// If empty, /run/nustat/nustatd.sock as root, else ~/.nustat/nustatd.sock (127.0.0.1:7391 on Windows).
+    /// Clients look for /run/nustat/nustatd.sock first. Default is empty.
+    #[serde(default)]
+    pub listen: String,
+    /// Octal permissions of the Unix socket. Default is 660.
+    #[serde(default = "default_api_socket_mode")]
+    pub socket_mode: String,
+    /// Group owning the Unix socket, by name or GID. Its members can attach without root. Default is empty (the daemon's group).
+    #[serde(default)]
+    pub socket_group: String,
+}
+
+impl ApiConfig {
+    pub fn new() -> ApiConfig {
+        ApiConfig {
+            listen: String::new(),
+            socket_mode: default_api_socket_mode(),
+            socket_group: String::new(),
+        }
+    }
+    /// Where clients attach.
+    pub fn endpoint(&self) -> Result<ApiEndpoint, String> {
+        if self.listen.is_empty() {
+            ApiEndpoint::default_endpoint().ok_or(String::from("Could not get config directory path"))
+        } else {
+            ApiEndpoint::parse(&self.listen)
+        }
+    }
+    /// Where the daemon listens.
+    pub fn server_endpoint(&self) -> Result<ApiEndpoint, String> {
+        if self.listen.is_empty() {
+            ApiEndpoint::default_server_endpoint().ok_or(String::from("Could not get config directory path"))
+        } else {
+            ApiEndpoint::parse(&self.listen)
+        }
+    }
+    pub fn to_server_option(&self, tick_rate: std::time::Duration) -> Result<ApiServerOption, String> {
+        let mut option = ApiServerOption::new(self.server_endpoint()?);
+        option.tick_rate = tick_rate;
+        option.socket_mode = u32::from_str_radix(&self.socket_mode, 8).map_err(|e| format!("Invalid API socket mode {}: {}", self.socket_mode, e))?;
+        if !self.socket_group.is_empty() {
+            option.socket_group = Some(socket_group_id(&self.socket_group)?);
+        }
+        Ok(option)
+    }
+}
+
+#[cfg(unix)]
+fn socket_group_id(group: &str) -> Result<u32, String> {
+    sys::get_group_id(group).ok_or(format!("Unknown API socket group {}", group))
+}
+
+#[cfg(not(unix))]
+fn socket_group_id(group: &str) -> Result<u32, String> {
+    Err(format!("API socket group {} is not supported on this platform", group))
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        ApiConfig::new()
+    }
+}
+
+fn default_api_socket_mode() -> String {
+    format!("{:o}", crate::api::server::DEFAULT_API_SOCKET_MODE)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FleetConfig {
+    /// Aggregation server `nustat agent` sends to (host:port). Default is empty.
+    #[serde(default)]
+    pub server: String,
+    /// Pre-shared key of the agents and the server, at least 16 bytes. Default is empty.
+    #[serde(default)]
+    pub psk: String,
+    /// File containing the pre-shared key. Takes precedence over `psk`. Default is none.
+    #[serde(default)]
+    pub psk_file: Option<String>,
+    /// Agent ID. If empty, the hostname. Default is empty.
+    #[serde(default)]
+    pub agent_id: String,
+    /// Seconds between two deltas of the agent. Default is 10.
+    #[serde(default = "default_fleet_interval")]
+    pub interval: u64,
+    /// Where `nustat aggregate` accepts agents. Default is 0.0.0.0:7392.
+    #[serde(default = "default_fleet_listen_addr")]
+    pub listen_addr: String,
+    /// Listen address of the fleet query API. Loopback only. Default is 127.0.0.1:7393.
+    #[serde(default = "default_fleet_api_addr")]
+    pub api_addr: String,
+}
+
+impl FleetConfig {
+    pub fn new() -> FleetConfig {
+        FleetConfig {
+            server: String::new(),
+            psk: String::new(),
+            psk_file: None,
+            agent_id: String::new(),
+            interval: default_fleet_interval(),
+            listen_addr: default_fleet_listen_addr(),
+            api_addr: default_fleet_api_addr(),
+        }
+    }
+    #[cfg(feature = "fleet")]
+    pub fn psk(&self) -> Result<Vec<u8>, String> {
+        let psk: Vec<u8> = match &self.psk_file {
+            Some(path) => std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?.trim().as_bytes().to_vec(),
+            None => self.psk.as_bytes().to_vec(),
+        };
+        if psk.len() < crate::fleet::MIN_PSK_LEN {
+            return Err(format!("Fleet pre-shared key must be at least {} bytes (fleet.psk or fleet.psk_file)", crate::fleet::MIN_PSK_LEN));
+        }
+        Ok(psk)
+    }
+    #[cfg(feature = "fleet")]
+    pub fn to_agent_option(&self) -> Result<FleetAgentOption, String> {
+        if self.server.is_empty() {
+            return Err(String::from("Fleet server address is not set (fleet.server)"));
+        }
+        let mut option = FleetAgentOption::new(self.server.clone(), self.psk()?);
+        if !self.agent_id.is_empty() {
+            option.agent_id = self.agent_id.clone();
+        }
+        Ok(option)
+    }
+    #[cfg(feature = "fleet")]
+    pub fn to_server_option(&self) -> Result<FleetServerOption, String> {
+        let listen_addr = self.listen_addr.parse().map_err(|e| format!("Invalid fleet listen address {}: {}", self.listen_addr, e))?;
+        Ok(FleetServerOption::new(listen_addr, self.psk()?))
+    }
+}
+
+impl Default for FleetConfig {
+    fn default() -> Self {
+        FleetConfig::new()
+    }
+}
+
+fn default_fleet_interval() -> u64 {
+    10
+}
+
+// The fleet section is parsed and saved in every build, so the defaults do not depend on the `fleet` feature.
+// They match `fleet::DEFAULT_FLEET_LISTEN_ADDR` and `fleet::DEFAULT_FLEET_API_ADDR`.
+fn default_fleet_listen_addr() -> String {
+    String::from("0.0.0.0:7392")
+}
+
+fn default_fleet_api_addr() -> String {
+    String::from("127.0.0.1:7393")
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AlertConfig {
+    /// Rules evaluated on every tick by the TUI and `nustat daemon`. Default is none.
+    #[serde(default)]
+    pub rules: Vec<AlertRule>,
+    /// The number of notifications kept, newest first. Default is 100.
+    #[serde(default = "default_max_notifications")]
+    pub max_notifications: usize,
+}
+
+impl AlertConfig {
+    pub fn new() -> AlertConfig {
+        AlertConfig {
+            rules: Vec::new(),
+            max_notifications: default_max_notifications(),
+        }
+    }
+    pub fn to_alert_option(&self) -> AlertOption {
+        let mut option = AlertOption::new(self.rules.clone());
+        option.max_notifications = self.max_notifications;
+        option
+    }
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        AlertConfig::new()
+    }
+}
+
+fn default_max_notifications() -> usize {
+    crate::alert::DEFAULT_MAX_NOTIFICATIONS
 }
 
 #[derive(Debug, Deserialize, Serialize)]
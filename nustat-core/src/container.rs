@@ -0,0 +1,197 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use crate::net::traffic::TrafficInfo;
+
+/// Kubelet log directory. Pod directories are named `<namespace>_<pod name>_<pod uid>`.
+const KUBELET_POD_LOG_DIR: &str = "/var/log/pods";
+
+/// Pod UID -> (namespace, pod name)
+static POD_NAME_CACHE: OnceLock<Mutex<HashMap<String, (String, String)>>> = OnceLock::new();
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContainerRuntime {
+    Docker,
+    Containerd,
+    Podman,
+    CRIO,
+    Unknown,
+}
+
+impl ContainerRuntime {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Containerd => "containerd",
+            ContainerRuntime::Podman => "podman",
+            ContainerRuntime::CRIO => "cri-o",
+            ContainerRuntime::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContainerInfo {
+    pub runtime: ContainerRuntime,
+    pub container_id: String,
+    pub pod_uid: Option<String>,
+    pub pod_name: Option<String>,
+    pub pod_namespace: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ContainerDisplayInfo {
+    pub container_id: String,
+    pub runtime: ContainerRuntime,
+    pub pod_name: Option<String>,
+    pub pod_namespace: Option<String>,
+    pub netns: Option<u64>,
+    pub process_names: Vec<String>,
+    pub traffic: TrafficInfo,
+}
+
+/// Parse the container runtime, container ID and pod UID from a cgroup path.
+/// Supports both the cgroupfs and systemd cgroup drivers.
+pub fn parse_cgroup_path(cgroup_path: &str) -> Option<ContainerInfo> {
+    let mut container: Option<(ContainerRuntime, String)> = None;
+    let mut pod_uid: Option<String> = None;
+    for segment in cgroup_path.split('/') {
+        let name = segment.strip_suffix(".scope").or_else(|| segment.strip_suffix(".slice")).unwrap_or(segment);
+        if let Some(uid) = parse_pod_uid(name) {
+            pod_uid = Some(uid);
+            continue;
+        }
+        let parsed = if let Some(id) = name.strip_prefix("docker-") {
+            Some((ContainerRuntime::Docker, id))
+        } else if let Some(id) = name.strip_prefix("cri-containerd-") {
+            Some((ContainerRuntime::Containerd, id))
+        } else if let Some(id) = name.strip_prefix("libpod-") {
+            Some((ContainerRuntime::Podman, id))
+        } else if let Some(id) = name.strip_prefix("crio-conmon-") {
+            Some((ContainerRuntime::CRIO, id))
+        } else if let Some(id) = name.strip_prefix("crio-") {
+            Some((ContainerRuntime::CRIO, id))
+        } else if is_container_id(name) {
+            // cgroupfs driver: /docker/<id>, /kubepods/<qos>/pod<uid>/<id>, /libpod_parent/...
+            let runtime = if cgroup_path.contains("/docker/") {
+                ContainerRuntime::Docker
+            } else if cgroup_path.contains("libpod") {
+                ContainerRuntime::Podman
+            } else {
+                ContainerRuntime::Unknown
+            };
+            Some((runtime, name))
+        } else {
+            None
+        };
+        if let Some((runtime, id)) = parsed {
+            if is_container_id(id) {
+                container = Some((runtime, id.to_string()));
+            }
+        }
+    }
+    let (runtime, container_id) = container?;
+    let (pod_namespace, pod_name) = match &pod_uid {
+        Some(uid) => match lookup_pod_name(uid) {
+            Some((namespace, name)) => (Some(namespace), Some(name)),
+            None => (None, None),
+        },
+        None => (None, None),
+    };
+    Some(ContainerInfo {
+        runtime,
+        container_id,
+        pod_uid,
+        pod_name,
+        pod_namespace,
+    })
+}
+
+/// Read /proc/<pid>/cgroup. The content is shared by the container and systemd unit lookups.
+#[cfg(target_os = "linux")]
+pub fn read_cgroup(pid: u32) -> Option<String> {
+    std::fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_cgroup(_pid: u32) -> Option<String> {
+    None
+}
+
+/// Select the cgroup path from the content of /proc/<pid>/cgroup.
+/// Prefers the line that identifies a container, then the cgroup v2 unified hierarchy.
+pub fn select_cgroup_path(content: &str) -> Option<String> {
+    let paths: Vec<(&str, &str)> = content.lines().filter_map(|line| {
+        // hierarchy-ID:controller-list:cgroup-path
+        let mut fields = line.splitn(3, ':');
+        let _id = fields.next()?;
+        let controllers = fields.next()?;
+        let path = fields.next()?;
+        Some((controllers, path))
+    }).collect();
+    if let Some((_, path)) = paths.iter().find(|(_, path)| parse_cgroup_path(path).is_some()) {
+        return Some(path.to_string());
+    }
+    if let Some((_, path)) = paths.iter().find(|(controllers, _)| controllers.is_empty()) {
+        return Some(path.to_string());
+    }
+    paths.first().map(|(_, path)| path.to_string())
+}
+
+/// Read the cgroup path of the process from /proc/<pid>/cgroup.
+pub fn get_cgroup_path(pid: u32) -> Option<String> {
+    select_cgroup_path(&read_cgroup(pid)?)
+}
+
+/// Read the network namespace inode of the process from /proc/<pid>/ns/net.
+#[cfg(target_os = "linux")]
+pub fn get_netns_inode(pid: u32) -> Option<u64> {
+    let link = std::fs::read_link(format!("/proc/{}/ns/net", pid)).ok()?;
+    link.to_str()?.strip_prefix("net:[")?.strip_suffix(']')?.parse().ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get_netns_inode(_pid: u32) -> Option<u64> {
+    None
+}
+
+fn is_container_id(s: &str) -> bool {
+    s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Parse `pod<uid>` or `kubepods-<qos>-pod<uid>` segments.
+/// The systemd driver replaces `-` in the UID with `_`.
+fn parse_pod_uid(name: &str) -> Option<String> {
+    let pos = name.rfind("pod")?;
+    let prefix = &name[..pos];
+    if !(prefix.is_empty() || prefix.starts_with("kubepods")) {
+        return None;
+    }
+    let uid = name[pos + 3..].replace('_', "-");
+    if uid.len() == 36 && uid.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        Some(uid)
+    } else {
+        None
+    }
+}
+
+/// Resolve the pod namespace and name from the kubelet log directory.
+fn lookup_pod_name(pod_uid: &str) -> Option<(String, String)> {
+    let cache = POD_NAME_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut cache = match cache.lock() {
+        Ok(cache) => cache,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if let Some(entry) = cache.get(pod_uid) {
+        return Some(entry.clone());
+    }
+    let entries = std::fs::read_dir(KUBELET_POD_LOG_DIR).ok()?;
+    for entry in entries.flatten() {
+        let dir_name = entry.file_name().to_string_lossy().to_string();
+        let mut fields = dir_name.splitn(3, '_');
+        if let (Some(namespace), Some(name), Some(uid)) = (fields.next(), fields.next(), fields.next()) {
+            cache.insert(uid.to_string(), (namespace.to_string(), name.to_string()));
+        }
+    }
+    cache.get(pod_uid).cloned()
+}
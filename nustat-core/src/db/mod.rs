@@ -1,2 +1,3 @@
 pub mod ip;
 pub mod service;
+pub mod oui;
@@ -0,0 +1,80 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+use nustat_db_oui::db::OUI_BIN;
+
+pub use nustat_db_oui::Oui;
+pub use nustat_db_oui::db::OUI_BIN_NAME;
+
+/// Bit in the first octet that marks a locally administered (e.g. randomized) MAC address.
+const LOCALLY_ADMINISTERED_BIT: u8 = 0x02;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OuiDatabase {
+    pub oui_map: HashMap<u32, String>,
+}
+
+impl OuiDatabase {
+    pub fn new() -> OuiDatabase {
+        OuiDatabase {
+            oui_map: HashMap::new(),
+        }
+    }
+    /// Load the OUI database from the user's config directory. (~/.nustat/oui.bin)
+    pub fn load() -> Result<OuiDatabase, Box<dyn std::error::Error>> {
+        let file_path: PathBuf = match Oui::bin_file_path() {
+            Some(path) => path,
+            None => return Err("Could not get oui.bin file path".into()),
+        };
+        let f = fs::read(file_path)?;
+        let oui_vec: Vec<Oui> = bincode::deserialize(&f)?;
+        let mut oui_db = OuiDatabase::new();
+        oui_db.insert_all(oui_vec);
+        Ok(oui_db)
+    }
+    /// Load the OUI database bundled with nustat-db-oui.
+    pub fn load_from_crate() -> Result<OuiDatabase, Box<dyn std::error::Error>> {
+        let oui_vec: Vec<Oui> = bincode::deserialize(OUI_BIN)?;
+        let mut oui_db = OuiDatabase::new();
+        oui_db.insert_all(oui_vec);
+        Ok(oui_db)
+    }
+    /// Load from the user's config directory if the file exists, otherwise use the bundled database.
+    pub fn load_or_bundled() -> Result<OuiDatabase, Box<dyn std::error::Error>> {
+        match Oui::bin_file_path() {
+            Some(path) if path.exists() => OuiDatabase::load(),
+            _ => OuiDatabase::load_from_crate(),
+        }
+    }
+    fn insert_all(&mut self, oui_vec: Vec<Oui>) {
+        for oui in oui_vec {
+            self.oui_map.insert(oui.oui, oui.vendor_name);
+        }
+    }
+    /// Get the vendor name for the MAC address. (colon or hyphen separated hex format)
+    /// Locally administered addresses have no registered vendor.
+    pub fn get_vendor(&self, mac_addr: &str) -> Option<String> {
+        let octets = parse_mac_prefix(mac_addr)?;
+        if octets[0] & LOCALLY_ADMINISTERED_BIT != 0 {
+            return None;
+        }
+        let oui: u32 = (octets[0] as u32) << 16 | (octets[1] as u32) << 8 | octets[2] as u32;
+        self.oui_map.get(&oui).cloned()
+    }
+}
+
+/// Check if the MAC address is locally administered.
+/// Randomized (private) MAC addresses used by mobile devices are locally administered.
+pub fn is_locally_administered(mac_addr: &str) -> bool {
+    match parse_mac_prefix(mac_addr) {
+        Some(octets) => octets[0] & LOCALLY_ADMINISTERED_BIT != 0,
+        None => false,
+    }
+}
+
+fn parse_mac_prefix(mac_addr: &str) -> Option<[u8; 3]> {
+    let mut octets = [0u8; 3];
+    let mut fields = mac_addr.split([':', '-']);
+    for octet in octets.iter_mut() {
+        *octet = u8::from_str_radix(fields.next()?, 16).ok()?;
+    }
+    Some(octets)
+}
@@ -346,7 +346,10 @@ pub fn start_dns_map_update(netstat_strage: &mut Arc<NetStatStrage>) {
         };
         for (ip_addr, hostname) in dns_map {
             if let Some(remote_host) = remote_hosts_inner.get_mut(&ip_addr) {
-                remote_host.hostname = hostname.clone();
+                // Keep the name learned from the neighbor inventory if reverse lookup failed.
+                if !hostname.is_empty() {
+                    remote_host.hostname = hostname.clone();
+                }
             }
             reverse_dns_map_inner.insert(ip_addr, hostname);
         }
@@ -0,0 +1,183 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use sysinfo::{System, SystemExt};
+use crate::net::stat::NetStatData;
+use super::{read_frame, FleetConnection, FleetMessage, AGENT_TO_SERVER, FLEET_PROTOCOL_VERSION, MAX_FRAME_SIZE, MAX_HANDSHAKE_FRAME_SIZE, MIN_PSK_LEN};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const ACK_TIMEOUT: Duration = Duration::from_secs(30);
+const MIN_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct FleetAgentOption {
+    /// Address of the aggregation server (host:port).
+    pub server: String,
+    pub psk: Vec<u8>,
+    pub agent_id: String,
+    pub hostname: String,
+}
+
+impl FleetAgentOption {
+    /// The agent ID defaults to the hostname.
+    pub fn new(server: String, psk: Vec<u8>) -> FleetAgentOption {
+        let hostname = System::new().host_name().unwrap_or_default();
+        FleetAgentOption {
+            server,
+            psk,
+            agent_id: hostname.clone(),
+            hostname,
+        }
+    }
+}
+
+/// Traffic drained but not sent yet.
+struct Pending {
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    data: NetStatData,
+}
+
+/// Sends the traffic deltas of this host to an aggregation server.
+/// Data is kept while the server is unreachable and sent after a reconnect.
+pub struct FleetAgent {
+    option: FleetAgentOption,
+    key: hmac::Key,
+    session: u64,
+    connection: Option<FleetConnection>,
+    sequence: u64,
+    /// Sent but not acknowledged. Resent as is, so the server can drop it if it was merged already.
+    unacked: Option<FleetMessage>,
+    pending: Option<Pending>,
+    retry_interval: Duration,
+    next_retry: Instant,
+}
+
+impl FleetAgent {
+    pub fn new(option: FleetAgentOption) -> Result<FleetAgent, String> {
+        if option.psk.len() < MIN_PSK_LEN {
+            return Err(format!("Pre-shared key must be at least {} bytes", MIN_PSK_LEN));
+        }
+        if option.agent_id.is_empty() {
+            return Err(String::from("Agent ID is empty"));
+        }
+        let mut session = [0u8; 8];
+        SystemRandom::new().fill(&mut session).map_err(|_| String::from("Failed to generate a session ID"))?;
+        Ok(FleetAgent {
+            key: hmac::Key::new(hmac::HMAC_SHA256, &option.psk),
+            option,
+            session: u64::from_be_bytes(session),
+            connection: None,
+            sequence: 0,
+            unacked: None,
+            pending: None,
+            retry_interval: MIN_RETRY_INTERVAL,
+            next_retry: Instant::now(),
+        })
+    }
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+    /// Whether drained traffic has not been acknowledged yet.
+    pub fn has_pending(&self) -> bool {
+        self.unacked.is_some() || self.pending.is_some()
+    }
+    fn connect(&self) -> Result<FleetConnection, String> {
+        let addr = self.option.server.to_socket_addrs().map_err(|e| format!("Invalid server address {}: {}", self.option.server, e))?
+            .next()
+            .ok_or(format!("Could not resolve {}", self.option.server))?;
+        let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).map_err(|e| format!("Failed to connect to {}: {}", addr, e))?;
+        stream.set_read_timeout(Some(ACK_TIMEOUT)).map_err(|e| e.to_string())?;
+        stream.set_write_timeout(Some(ACK_TIMEOUT)).map_err(|e| e.to_string())?;
+        let (payload, _) = read_frame(&mut stream, MAX_HANDSHAKE_FRAME_SIZE)?;
+        let nonce = match bincode::deserialize(&payload).map_err(|e| format!("Invalid challenge: {}", e))? {
+            FleetMessage::Challenge { version, nonce } if version == FLEET_PROTOCOL_VERSION => nonce,
+            FleetMessage::Challenge { version, .. } => return Err(format!("Unsupported protocol version {}", version)),
+            message => return Err(format!("Unexpected message: {:?}", message)),
+        };
+        let mut connection = FleetConnection {
+            stream,
+            key: self.key.clone(),
+            nonce,
+            send_direction: AGENT_TO_SERVER,
+            send_counter: 0,
+            recv_counter: 0,
+            max_frame_size: MAX_HANDSHAKE_FRAME_SIZE,
+        };
+        connection.send(&FleetMessage::Hello {
+            version: FLEET_PROTOCOL_VERSION,
+            agent_id: self.option.agent_id.clone(),
+            hostname: self.option.hostname.clone(),
+            session: self.session,
+        })?;
+        match connection.recv()? {
+            FleetMessage::Welcome => {
+                connection.max_frame_size = MAX_FRAME_SIZE;
+                Ok(connection)
+            }
+            FleetMessage::Rejected { reason } => Err(format!("Rejected by {}: {}", addr, reason)),
+            message => Err(format!("Unexpected message: {:?}", message)),
+        }
+    }
+    /// Queue the traffic of an interval and send everything pending.
+    pub fn push(&mut self, data: NetStatData, start: DateTime<Local>, end: DateTime<Local>) -> Result<(), String> {
+        match self.pending.as_mut() {
+            Some(pending) => {
+                pending.data.merge(data);
+                pending.end = end;
+            }
+            None => self.pending = Some(Pending { start, end, data }),
+        }
+        self.flush()
+    }
+    /// Send the pending traffic. Reconnects with a backoff.
+    pub fn flush(&mut self) -> Result<(), String> {
+        if self.connection.is_none() {
+            if Instant::now() < self.next_retry {
+                return Ok(());
+            }
+            match self.connect() {
+                Ok(connection) => {
+                    self.connection = Some(connection);
+                    self.retry_interval = MIN_RETRY_INTERVAL;
+                }
+                Err(e) => {
+                    self.next_retry = Instant::now() + self.retry_interval;
+                    self.retry_interval = (self.retry_interval * 2).min(MAX_RETRY_INTERVAL);
+                    return Err(e);
+                }
+            }
+        }
+        if let Err(e) = self.send_pending() {
+            self.connection = None;
+            self.next_retry = Instant::now() + self.retry_interval;
+            return Err(e);
+        }
+        Ok(())
+    }
+    fn send_pending(&mut self) -> Result<(), String> {
+        let Some(connection) = self.connection.as_mut() else {
+            return Ok(());
+        };
+        loop {
+            if self.unacked.is_none() {
+                let Some(pending) = self.pending.take() else {
+                    return Ok(());
+                };
+                self.sequence += 1;
+                self.unacked = Some(FleetMessage::Delta { sequence: self.sequence, start: pending.start, end: pending.end, data: Box::new(pending.data) });
+            }
+            let Some(delta) = self.unacked.as_ref() else {
+                return Ok(());
+            };
+            connection.send(delta)?;
+            match connection.recv()? {
+                FleetMessage::Ack { sequence } if sequence == self.sequence => self.unacked = None,
+                message => return Err(format!("Unexpected message: {:?}", message)),
+            }
+        }
+    }
+}
@@ -0,0 +1,408 @@
+pub mod agent;
+pub mod server;
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpStream};
+use std::sync::Mutex;
+use chrono::{DateTime, Local};
+use ring::hmac;
+use serde::{Serialize, Deserialize};
+use crate::net::stat::NetStatData;
+use crate::net::traffic::TrafficInfo;
+use crate::thread_log;
+
+/// Version of the agent protocol. Checked in the handshake.
+pub const FLEET_PROTOCOL_VERSION: u32 = 1;
+
+/// Default address agents connect to. Authenticated with the pre-shared key.
+pub const DEFAULT_FLEET_LISTEN_ADDR: &str = "0.0.0.0:7392";
+
+/// Default listen address of the fleet query API. Loopback only.
+pub const DEFAULT_FLEET_API_ADDR: &str = "127.0.0.1:7393";
+
+/// Minimum length of the pre-shared key.
+pub const MIN_PSK_LEN: usize = 16;
+
+/// Maximum payload size of a frame. Larger frames are rejected before allocation.
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Maximum payload size of a frame before the handshake completed.
+/// Frames of unauthenticated peers are capped to this, deltas are only accepted after `Welcome`.
+pub const MAX_HANDSHAKE_FRAME_SIZE: usize = 4 * 1024;
+
+const FRAME_MAGIC: [u8; 4] = *b"NSFL";
+const NONCE_LEN: usize = 32;
+const MAC_LEN: usize = 32;
+
+/// Direction tags in the MAC, so a frame cannot be reflected back to its sender.
+const AGENT_TO_SERVER: u8 = 1;
+const SERVER_TO_AGENT: u8 = 2;
+
+/// Messages of the agent protocol, encoded with bincode.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum FleetMessage {
+    /// Server to agent. Random challenge of the connection. The only frame without a MAC.
+    Challenge { version: u32, nonce: Vec<u8> },
+    /// Agent to server. `session` is random per agent process, so sequence numbers restart with it.
+    Hello { version: u32, agent_id: String, hostname: String, session: u64 },
+    /// Server to agent.
+    Welcome,
+    /// Server to agent. The connection is closed after it.
+    Rejected { reason: String },
+    /// Agent to server. Traffic of the interval.
+    Delta { sequence: u64, start: DateTime<Local>, end: DateTime<Local>, data: Box<NetStatData> },
+    /// Server to agent. The delta is merged (or was already) and can be dropped.
+    Ack { sequence: u64 },
+}
+
+fn write_frame(stream: &mut TcpStream, payload: &[u8], mac: &[u8]) -> Result<(), String> {
+    let mut frame: Vec<u8> = Vec::with_capacity(8 + payload.len() + MAC_LEN);
+    frame.extend_from_slice(&FRAME_MAGIC);
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame.extend_from_slice(mac);
+    stream.write_all(&frame).and_then(|_| stream.flush()).map_err(|e| e.to_string())
+}
+
+fn read_frame(stream: &mut TcpStream, max_len: usize) -> Result<(Vec<u8>, [u8; MAC_LEN]), String> {
+    let mut header = [0u8; 8];
+    stream.read_exact(&mut header).map_err(|e| e.to_string())?;
+    if header[0..4] != FRAME_MAGIC {
+        return Err(String::from("Invalid frame magic"));
+    }
+    let len = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    if len > max_len {
+        return Err(format!("Frame too large: {} bytes", len));
+    }
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).map_err(|e| e.to_string())?;
+    let mut mac = [0u8; MAC_LEN];
+    stream.read_exact(&mut mac).map_err(|e| e.to_string())?;
+    Ok((payload, mac))
+}
+
+/// An authenticated connection. Every frame after the challenge carries
+/// HMAC-SHA256(psk, nonce || direction || counter || payload).
+pub(crate) struct FleetConnection {
+    stream: TcpStream,
+    key: hmac::Key,
+    nonce: Vec<u8>,
+    send_direction: u8,
+    send_counter: u64,
+    recv_counter: u64,
+    /// `MAX_HANDSHAKE_FRAME_SIZE` until the handshake completed.
+    max_frame_size: usize,
+}
+
+impl FleetConnection {
+    fn mac_input(&self, direction: u8, counter: u64, payload: &[u8]) -> Vec<u8> {
+        let mut input: Vec<u8> = Vec::with_capacity(self.nonce.len() + 9 + payload.len());
+        input.extend_from_slice(&self.nonce);
+        input.push(direction);
+        input.extend_from_slice(&counter.to_be_bytes());
+        input.extend_from_slice(payload);
+        input
+    }
+    fn recv_direction(&self) -> u8 {
+        if self.send_direction == AGENT_TO_SERVER { SERVER_TO_AGENT } else { AGENT_TO_SERVER }
+    }
+    pub(crate) fn send(&mut self, message: &FleetMessage) -> Result<(), String> {
+        let payload = bincode::serialize(message).map_err(|e| e.to_string())?;
+        let tag = hmac::sign(&self.key, &self.mac_input(self.send_direction, self.send_counter, &payload));
+        self.send_counter += 1;
+        write_frame(&mut self.stream, &payload, tag.as_ref())
+    }
+    pub(crate) fn recv(&mut self) -> Result<FleetMessage, String> {
+        let (payload, mac) = read_frame(&mut self.stream, self.max_frame_size)?;
+        let input = self.mac_input(self.recv_direction(), self.recv_counter, &payload);
+        hmac::verify(&self.key, &input, &mac).map_err(|_| String::from("Authentication failed"))?;
+        self.recv_counter += 1;
+        bincode::deserialize(&payload).map_err(|e| format!("Invalid message: {}", e))
+    }
+}
+
+/// Fleet-wide traffic of a remote host.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FleetHost {
+    pub ip_addr: IpAddr,
+    pub host_name: String,
+    pub country_code: String,
+    pub asn: u32,
+    pub as_name: String,
+    /// Agents that talked to the host.
+    pub agents: usize,
+    pub traffic: TrafficInfo,
+}
+
+/// Fleet-wide traffic of an autonomous system.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FleetAsn {
+    pub asn: u32,
+    pub as_name: String,
+    pub hosts: usize,
+    pub agents: usize,
+    pub traffic: TrafficInfo,
+}
+
+/// Fleet-wide traffic of a process name.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FleetProcess {
+    pub name: String,
+    pub agents: usize,
+    pub traffic: TrafficInfo,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AgentInfo {
+    pub agent_id: String,
+    pub hostname: String,
+    /// Address of the last connection.
+    pub addr: String,
+    pub connected: bool,
+    pub first_seen: DateTime<Local>,
+    pub last_seen: DateTime<Local>,
+    /// Merged deltas.
+    pub deltas: u64,
+    pub traffic: TrafficInfo,
+}
+
+struct AgentState {
+    info: AgentInfo,
+    session: u64,
+    last_sequence: u64,
+    data: NetStatData,
+}
+
+fn total_bytes(traffic: &TrafficInfo) -> usize {
+    traffic.bytes_sent + traffic.bytes_received
+}
+
+/// Merged data of every agent.
+pub struct FleetStore {
+    agents: Mutex<HashMap<String, AgentState>>,
+}
+
+impl FleetStore {
+    pub fn new() -> FleetStore {
+        FleetStore {
+            agents: Mutex::new(HashMap::new()),
+        }
+    }
+    /// Register a connection of the agent.
+    pub fn connect(&self, agent_id: &str, hostname: &str, addr: &str, session: u64) {
+        let now = Local::now();
+        match self.agents.lock() {
+            Ok(mut agents) => {
+                let agent = agents.entry(agent_id.to_string()).or_insert_with(|| AgentState {
+                    info: AgentInfo {
+                        agent_id: agent_id.to_string(),
+                        hostname: String::new(),
+                        addr: String::new(),
+                        connected: false,
+                        first_seen: now,
+                        last_seen: now,
+                        deltas: 0,
+                        traffic: TrafficInfo::new(),
+                    },
+                    session,
+                    last_sequence: 0,
+                    data: NetStatData::new(),
+                });
+                if agent.session != session {
+                    // A restarted agent numbers its deltas from 1 again.
+                    agent.session = session;
+                    agent.last_sequence = 0;
+                }
+                agent.info.hostname = hostname.to_string();
+                agent.info.addr = addr.to_string();
+                agent.info.connected = true;
+                agent.info.last_seen = now;
+            }
+            Err(e) => {
+                thread_log!(error, "[fleet] connect error: {:?}", e);
+            }
+        }
+    }
+    pub fn disconnect(&self, agent_id: &str) {
+        match self.agents.lock() {
+            Ok(mut agents) => {
+                if let Some(agent) = agents.get_mut(agent_id) {
+                    agent.info.connected = false;
+                }
+            }
+            Err(e) => {
+                thread_log!(error, "[fleet] disconnect error: {:?}", e);
+            }
+        }
+    }
+    /// Merge a delta of a connected agent. Returns false if the sequence was already merged (a resend).
+    pub fn apply(&self, agent_id: &str, sequence: u64, data: NetStatData) -> bool {
+        match self.agents.lock() {
+            Ok(mut agents) => {
+                let Some(agent) = agents.get_mut(agent_id) else {
+                    return false;
+                };
+                agent.info.last_seen = Local::now();
+                if sequence <= agent.last_sequence {
+                    return false;
+                }
+                agent.last_sequence = sequence;
+                agent.info.deltas += 1;
+                agent.data.merge(data);
+                agent.info.traffic = agent.data.traffic.clone();
+                true
+            }
+            Err(e) => {
+                thread_log!(error, "[fleet] apply error: {:?}", e);
+                false
+            }
+        }
+    }
+    /// Agents sorted by ID.
+    pub fn agents(&self) -> Vec<AgentInfo> {
+        let mut agents: Vec<AgentInfo> = match self.agents.lock() {
+            Ok(agents) => agents.values().map(|agent| agent.info.clone()).collect(),
+            Err(e) => {
+                thread_log!(error, "[fleet] agents error: {:?}", e);
+                Vec::new()
+            }
+        };
+        agents.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+        agents
+    }
+    /// Merged data of an agent.
+    pub fn agent_data(&self, agent_id: &str) -> Option<NetStatData> {
+        match self.agents.lock() {
+            Ok(agents) => agents.get(agent_id).map(|agent| agent.data.clone()),
+            Err(e) => {
+                thread_log!(error, "[fleet] agent_data error: {:?}", e);
+                None
+            }
+        }
+    }
+    /// Remote hosts over all agents, by traffic.
+    pub fn top_hosts(&self, limit: Option<usize>) -> Vec<FleetHost> {
+        let mut hosts: HashMap<IpAddr, FleetHost> = HashMap::new();
+        match self.agents.lock() {
+            Ok(agents) => {
+                for agent in agents.values() {
+                    for host in agent.data.get_remote_hosts(None) {
+                        let entry = hosts.entry(host.ip_addr).or_insert_with(|| FleetHost {
+                            ip_addr: host.ip_addr,
+                            host_name: String::new(),
+                            country_code: String::new(),
+                            asn: 0,
+                            as_name: String::new(),
+                            agents: 0,
+                            traffic: TrafficInfo::new(),
+                        });
+                        // Agents may not all have resolved the name or looked up the AS yet.
+                        if entry.host_name.is_empty() {
+                            entry.host_name = host.host_name;
+                        }
+                        if entry.asn == 0 {
+                            entry.asn = host.asn;
+                            entry.as_name = host.as_name;
+                            entry.country_code = host.country_code;
+                        }
+                        entry.agents += 1;
+                        entry.traffic.add_traffic(&host.traffic);
+                    }
+                }
+            }
+            Err(e) => {
+                thread_log!(error, "[fleet] top_hosts error: {:?}", e);
+            }
+        }
+        let mut hosts: Vec<FleetHost> = hosts.into_values().collect();
+        hosts.sort_by_key(|host| std::cmp::Reverse(total_bytes(&host.traffic)));
+        if let Some(limit) = limit {
+            hosts.truncate(limit);
+        }
+        hosts
+    }
+    /// Autonomous systems over all agents, by traffic. Hosts without an AS are left out.
+    pub fn top_asns(&self, limit: Option<usize>) -> Vec<FleetAsn> {
+        let mut asns: HashMap<u32, FleetAsn> = HashMap::new();
+        let mut agents_by_asn: HashMap<u32, usize> = HashMap::new();
+        match self.agents.lock() {
+            Ok(agents) => {
+                for agent in agents.values() {
+                    let mut seen: std::collections::HashSet<u32> = std::collections::HashSet::new();
+                    for host in agent.data.get_remote_hosts(None) {
+                        if host.asn == 0 {
+                            continue;
+                        }
+                        let entry = asns.entry(host.asn).or_insert_with(|| FleetAsn {
+                            asn: host.asn,
+                            as_name: host.as_name.clone(),
+                            hosts: 0,
+                            agents: 0,
+                            traffic: TrafficInfo::new(),
+                        });
+                        entry.traffic.add_traffic(&host.traffic);
+                        if seen.insert(host.asn) {
+                            *agents_by_asn.entry(host.asn).or_insert(0) += 1;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                thread_log!(error, "[fleet] top_asns error: {:?}", e);
+            }
+        }
+        // Distinct hosts over the fleet
+        for host in self.top_hosts(None) {
+            if let Some(entry) = asns.get_mut(&host.asn) {
+                entry.hosts += 1;
+            }
+        }
+        let mut asns: Vec<FleetAsn> = asns.into_values().map(|mut asn| {
+            asn.agents = agents_by_asn.get(&asn.asn).copied().unwrap_or(0);
+            asn
+        }).collect();
+        asns.sort_by_key(|asn| std::cmp::Reverse(total_bytes(&asn.traffic)));
+        if let Some(limit) = limit {
+            asns.truncate(limit);
+        }
+        asns
+    }
+    /// Processes over all agents grouped by name, by traffic.
+    pub fn top_processes(&self, limit: Option<usize>) -> Vec<FleetProcess> {
+        let mut processes: HashMap<String, FleetProcess> = HashMap::new();
+        match self.agents.lock() {
+            Ok(agents) => {
+                for agent in agents.values() {
+                    let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
+                    for process in agent.data.get_processes(None) {
+                        let entry = processes.entry(process.name.clone()).or_insert_with(|| FleetProcess {
+                            name: process.name.clone(),
+                            agents: 0,
+                            traffic: TrafficInfo::new(),
+                        });
+                        entry.traffic.add_traffic(&process.traffic);
+                        if seen.insert(process.name) {
+                            entry.agents += 1;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                thread_log!(error, "[fleet] top_processes error: {:?}", e);
+            }
+        }
+        let mut processes: Vec<FleetProcess> = processes.into_values().collect();
+        processes.sort_by_key(|process| std::cmp::Reverse(total_bytes(&process.traffic)));
+        if let Some(limit) = limit {
+            processes.truncate(limit);
+        }
+        processes
+    }
+}
+
+impl Default for FleetStore {
+    fn default() -> Self {
+        FleetStore::new()
+    }
+}
@@ -0,0 +1,174 @@
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use crate::api::server::{query_limit, read_request, write_error, write_json, Request};
+use crate::api::ApiStream;
+use crate::thread_log;
+use super::{write_frame, FleetConnection, FleetMessage, FleetStore, FLEET_PROTOCOL_VERSION, MAC_LEN, MAX_FRAME_SIZE, MAX_HANDSHAKE_FRAME_SIZE, MIN_PSK_LEN, NONCE_LEN, SERVER_TO_AGENT};
+
+/// Maximum number of connected agents.
+const MAX_AGENTS: usize = 1024;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// An agent silent for longer is disconnected. Agents send a delta every interval, even an empty one.
+const AGENT_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+const MAX_AGENT_ID_LEN: usize = 128;
+
+#[derive(Debug, Clone)]
+pub struct FleetServerOption {
+    pub listen_addr: SocketAddr,
+    pub psk: Vec<u8>,
+}
+
+impl FleetServerOption {
+    pub fn new(listen_addr: SocketAddr, psk: Vec<u8>) -> FleetServerOption {
+        FleetServerOption { listen_addr, psk }
+    }
+}
+
+/// Challenge the agent and read its identity. Returns the agent ID and the authenticated connection.
+fn handshake(mut stream: TcpStream, key: hmac::Key, store: &FleetStore, addr: &SocketAddr) -> Result<(String, FleetConnection), String> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(HANDSHAKE_TIMEOUT)).map_err(|e| e.to_string())?;
+    let mut nonce = vec![0u8; NONCE_LEN];
+    SystemRandom::new().fill(&mut nonce).map_err(|_| String::from("Failed to generate a nonce"))?;
+    let challenge = bincode::serialize(&FleetMessage::Challenge { version: FLEET_PROTOCOL_VERSION, nonce: nonce.clone() }).map_err(|e| e.to_string())?;
+    write_frame(&mut stream, &challenge, &[0u8; MAC_LEN])?;
+    let mut connection = FleetConnection {
+        stream,
+        key,
+        nonce,
+        send_direction: SERVER_TO_AGENT,
+        send_counter: 0,
+        recv_counter: 0,
+        max_frame_size: MAX_HANDSHAKE_FRAME_SIZE,
+    };
+    let (agent_id, hostname, session) = match connection.recv()? {
+        FleetMessage::Hello { version, agent_id, hostname, session } => {
+            if version != FLEET_PROTOCOL_VERSION {
+                let _ = connection.send(&FleetMessage::Rejected { reason: format!("Unsupported protocol version {}", version) });
+                return Err(format!("Unsupported protocol version {}", version));
+            }
+            if agent_id.is_empty() || agent_id.len() > MAX_AGENT_ID_LEN {
+                let _ = connection.send(&FleetMessage::Rejected { reason: String::from("Invalid agent ID") });
+                return Err(String::from("Invalid agent ID"));
+            }
+            (agent_id, hostname, session)
+        }
+        message => return Err(format!("Unexpected message: {:?}", message)),
+    };
+    connection.send(&FleetMessage::Welcome)?;
+    connection.max_frame_size = MAX_FRAME_SIZE;
+    connection.stream.set_read_timeout(Some(AGENT_IDLE_TIMEOUT)).map_err(|e| e.to_string())?;
+    store.connect(&agent_id, &hostname, &addr.to_string(), session);
+    thread_log!(info, "[fleet] agent {} ({}) connected from {}", agent_id, hostname, addr);
+    Ok((agent_id, connection))
+}
+
+fn handle_agent(stream: TcpStream, key: hmac::Key, store: &FleetStore) -> Result<(), String> {
+    let addr = stream.peer_addr().map_err(|e| e.to_string())?;
+    let (agent_id, mut connection) = handshake(stream, key, store, &addr).map_err(|e| format!("{}: {}", addr, e))?;
+    let result = loop {
+        match connection.recv() {
+            Ok(FleetMessage::Delta { sequence, data, .. }) => {
+                store.apply(&agent_id, sequence, *data);
+                if let Err(e) = connection.send(&FleetMessage::Ack { sequence }) {
+                    break Err(e);
+                }
+            }
+            Ok(message) => break Err(format!("Unexpected message: {:?}", message)),
+            Err(e) => break Err(e),
+        }
+    };
+    store.disconnect(&agent_id);
+    thread_log!(info, "[fleet] agent {} disconnected", agent_id);
+    result.map_err(|e| format!("{} ({}): {}", agent_id, addr, e))
+}
+
+/// Accept agents. The pre-shared key must be at least `MIN_PSK_LEN` bytes.
+pub fn start_fleet_server(store: Arc<FleetStore>, option: FleetServerOption) -> Result<thread::JoinHandle<()>, String> {
+    if option.psk.len() < MIN_PSK_LEN {
+        return Err(format!("Pre-shared key must be at least {} bytes", MIN_PSK_LEN));
+    }
+    let listener = TcpListener::bind(option.listen_addr).map_err(|e| format!("Failed to bind fleet server to {}: {}", option.listen_addr, e))?;
+    let key = hmac::Key::new(hmac::HMAC_SHA256, &option.psk);
+    let connections = Arc::new(AtomicUsize::new(0));
+    thread::Builder::new()
+        .name(String::from("fleet-server"))
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        thread_log!(error, "[fleet] accept error: {}", e);
+                        continue;
+                    }
+                };
+                if connections.fetch_add(1, Ordering::SeqCst) >= MAX_AGENTS {
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                    thread_log!(warn, "[fleet] too many agents, dropping a connection");
+                    continue;
+                }
+                let store = Arc::clone(&store);
+                let key = key.clone();
+                let counter = Arc::clone(&connections);
+                let spawned = thread::Builder::new().name(String::from("fleet-agent")).spawn(move || {
+                    if let Err(e) = handle_agent(stream, key, &store) {
+                        thread_log!(warn, "[fleet] agent error: {}", e);
+                    }
+                    counter.fetch_sub(1, Ordering::SeqCst);
+                });
+                if let Err(e) = spawned {
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                    thread_log!(error, "[fleet] spawn error: {}", e);
+                }
+            }
+        })
+        .map_err(|e| e.to_string())
+}
+
+fn handle_api_connection(mut stream: Box<dyn ApiStream>, store: &FleetStore) -> std::io::Result<()> {
+    let Request { method, path, query, .. } = read_request(stream.as_mut())?;
+    if method != "GET" {
+        return write_error(stream.as_mut(), "405 Method Not Allowed", "Method Not Allowed");
+    }
+    let limit = match query_limit(&query) {
+        Ok(limit) => limit,
+        Err(e) => return write_error(stream.as_mut(), "400 Bad Request", &e),
+    };
+    match path.as_str() {
+        "/v1/fleet/agents" => write_json(stream.as_mut(), &store.agents()),
+        "/v1/fleet/hosts" => write_json(stream.as_mut(), &store.top_hosts(limit)),
+        "/v1/fleet/asns" => write_json(stream.as_mut(), &store.top_asns(limit)),
+        "/v1/fleet/processes" => write_json(stream.as_mut(), &store.top_processes(limit)),
+        _ => write_error(stream.as_mut(), "404 Not Found", &format!("Unknown path {}", path)),
+    }
+}
+
+/// Serve the fleet-wide views as JSON: `/v1/fleet/{agents,hosts,asns,processes}`. Loopback only.
+pub fn start_fleet_api(store: Arc<FleetStore>, listen_addr: SocketAddr) -> Result<thread::JoinHandle<()>, String> {
+    if !listen_addr.ip().is_loopback() {
+        return Err(format!("Fleet API listen address must be a loopback address: {}", listen_addr));
+    }
+    let listener = TcpListener::bind(listen_addr).map_err(|e| format!("Failed to bind fleet API to {}: {}", listen_addr, e))?;
+    thread::Builder::new()
+        .name(String::from("fleet-api"))
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(e) = handle_api_connection(Box::new(stream), &store) {
+                            thread_log!(debug, "[fleet] API connection error: {}", e);
+                        }
+                    }
+                    Err(e) => {
+                        thread_log!(error, "[fleet] API accept error: {}", e);
+                    }
+                }
+            }
+        })
+        .map_err(|e| e.to_string())
+}
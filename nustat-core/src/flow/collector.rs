@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+use chrono::{DateTime, Local, TimeZone};
+use super::ipfix::{self, FieldSpecifier, ENTERPRISE_BIT, VARIABLE_LENGTH};
+use super::netflow;
+use super::{protocol_from_number, FlowDirection, FlowEndReason, FlowRecord, DEFAULT_ENTERPRISE_NUMBER};
+use crate::socket::TransportProtocol;
+
+/// Minimal template-aware IPFIX and NetFlow v9 collector.
+/// Decodes the records of the nustat templates. Used to validate the exporter.
+pub struct FlowCollector {
+    socket: UdpSocket,
+    enterprise_number: u32,
+    /// Templates by (version, observation domain or source ID, template ID)
+    templates: HashMap<(u16, u32, u16), Vec<FieldSpecifier>>,
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Reader { buf, pos: 0 }
+    }
+    fn remaining(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.remaining() < len {
+            return Err(format!("Truncated message: need {} bytes at offset {}", len, self.pos));
+        }
+        let bytes = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+    /// Field value. Variable-length fields are prefixed with their length.
+    fn field(&mut self, length: u16) -> Result<&'a [u8], String> {
+        if length != VARIABLE_LENGTH {
+            return self.bytes(length as usize);
+        }
+        let len = match self.u8()? {
+            255 => self.u16()? as usize,
+            len => len as usize,
+        };
+        self.bytes(len)
+    }
+}
+
+fn to_uint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+}
+
+fn to_ip_addr(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => Some(IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]))),
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(bytes);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+/// Strings are zero padded in fixed-length fields.
+fn to_string(bytes: &[u8]) -> Option<String> {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    if end == 0 {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes[..end]).to_string())
+}
+
+fn millis_to_time(millis: i64) -> DateTime<Local> {
+    Local.timestamp_millis_opt(millis).single().unwrap_or_else(Local::now)
+}
+
+impl FlowCollector {
+    pub fn bind(addr: SocketAddr) -> Result<Self, String> {
+        let socket = UdpSocket::bind(addr).map_err(|e| format!("Failed to bind collector to {}: {}", addr, e))?;
+        Ok(FlowCollector {
+            socket,
+            enterprise_number: DEFAULT_ENTERPRISE_NUMBER,
+            templates: HashMap::new(),
+        })
+    }
+    pub fn set_enterprise_number(&mut self, enterprise_number: u32) {
+        self.enterprise_number = enterprise_number;
+    }
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        self.socket.local_addr().map_err(|e| e.to_string())
+    }
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), String> {
+        self.socket.set_read_timeout(timeout).map_err(|e| e.to_string())
+    }
+    /// Receive one export packet and decode its records.
+    pub fn recv(&mut self) -> Result<Vec<FlowRecord>, String> {
+        let mut buf = [0u8; 65535];
+        let (len, _) = self.socket.recv_from(&mut buf).map_err(|e| e.to_string())?;
+        self.decode(&buf[..len])
+    }
+    /// Decode an IPFIX message or a NetFlow v9 packet.
+    /// Data sets of unknown templates are skipped.
+    pub fn decode(&mut self, buf: &[u8]) -> Result<Vec<FlowRecord>, String> {
+        let mut reader = Reader::new(buf);
+        match reader.u16()? {
+            ipfix::IPFIX_VERSION => self.decode_ipfix(reader),
+            netflow::NETFLOW_V9_VERSION => self.decode_netflow_v9(reader),
+            version => Err(format!("Unsupported version: {}", version)),
+        }
+    }
+    fn decode_ipfix(&mut self, mut reader: Reader) -> Result<Vec<FlowRecord>, String> {
+        let length = reader.u16()? as usize;
+        if length != reader.buf.len() {
+            return Err(format!("Message length mismatch: header {} actual {}", length, reader.buf.len()));
+        }
+        let export_time = reader.u32()?;
+        let _sequence = reader.u32()?;
+        let domain_id = reader.u32()?;
+        let mut records: Vec<FlowRecord> = Vec::new();
+        while reader.remaining() >= ipfix::SET_HEADER_LEN {
+            let set_id = reader.u16()?;
+            let set_len = reader.u16()? as usize;
+            if set_len < ipfix::SET_HEADER_LEN {
+                return Err(format!("Invalid set length: {}", set_len));
+            }
+            let mut set = Reader::new(reader.bytes(set_len - ipfix::SET_HEADER_LEN)?);
+            if set_id == ipfix::TEMPLATE_SET_ID {
+                while set.remaining() >= 4 {
+                    let template_id = set.u16()?;
+                    let field_count = set.u16()?;
+                    let mut fields: Vec<FieldSpecifier> = Vec::new();
+                    for _ in 0..field_count {
+                        let id = set.u16()?;
+                        let length = set.u16()?;
+                        if id & ENTERPRISE_BIT != 0 {
+                            fields.push(FieldSpecifier::enterprise(id & !ENTERPRISE_BIT, length, set.u32()?));
+                        } else {
+                            fields.push(FieldSpecifier::new(id, length));
+                        }
+                    }
+                    self.templates.insert((ipfix::IPFIX_VERSION, domain_id, template_id), fields);
+                }
+            } else if let Some(fields) = self.templates.get(&(ipfix::IPFIX_VERSION, domain_id, set_id)) {
+                let min_len: usize = fields.iter().map(|f| if f.length == VARIABLE_LENGTH { 1 } else { f.length as usize }).sum();
+                while set.remaining() >= min_len.max(1) {
+                    let mut record = empty_record(millis_to_time(export_time as i64 * 1000));
+                    for field in fields {
+                        let value = set.field(field.length)?;
+                        self.apply_ipfix_field(&mut record, field, value);
+                    }
+                    records.push(record);
+                }
+            }
+        }
+        Ok(records)
+    }
+    fn apply_ipfix_field(&self, record: &mut FlowRecord, field: &FieldSpecifier, value: &[u8]) {
+        match field.enterprise_number {
+            None => match field.id {
+                ipfix::IE_FLOW_START_MILLISECONDS => record.start_time = millis_to_time(to_uint(value) as i64),
+                ipfix::IE_FLOW_END_MILLISECONDS => record.end_time = millis_to_time(to_uint(value) as i64),
+                id => apply_common_field(record, id, value),
+            },
+            Some(enterprise_number) if enterprise_number == self.enterprise_number => match field.id {
+                ipfix::IE_PROCESS_ID => record.pid = Some(to_uint(value) as u32).filter(|pid| *pid != 0),
+                ipfix::IE_PROCESS_NAME => record.process_name = to_string(value),
+                ipfix::IE_DOMAIN => record.domain = to_string(value),
+                _ => {}
+            },
+            Some(_) => {}
+        }
+    }
+    fn decode_netflow_v9(&mut self, mut reader: Reader) -> Result<Vec<FlowRecord>, String> {
+        let _count = reader.u16()?;
+        let sys_uptime = reader.u32()? as i64;
+        let unix_secs = reader.u32()? as i64;
+        let _sequence = reader.u32()?;
+        let source_id = reader.u32()?;
+        // Wall clock time of the exporter start. Switched times are relative to it.
+        let boot_millis = unix_secs * 1000 - sys_uptime;
+        let mut records: Vec<FlowRecord> = Vec::new();
+        while reader.remaining() >= netflow::FLOWSET_HEADER_LEN {
+            let flowset_id = reader.u16()?;
+            let flowset_len = reader.u16()? as usize;
+            if flowset_len < netflow::FLOWSET_HEADER_LEN {
+                return Err(format!("Invalid flowset length: {}", flowset_len));
+            }
+            let mut flowset = Reader::new(reader.bytes(flowset_len - netflow::FLOWSET_HEADER_LEN)?);
+            if flowset_id == netflow::TEMPLATE_FLOWSET_ID {
+                while flowset.remaining() >= 4 {
+                    let template_id = flowset.u16()?;
+                    let field_count = flowset.u16()?;
+                    if template_id == 0 {
+                        // Padding
+                        break;
+                    }
+                    let mut fields: Vec<FieldSpecifier> = Vec::new();
+                    for _ in 0..field_count {
+                        let field_type = flowset.u16()?;
+                        let length = flowset.u16()?;
+                        fields.push(FieldSpecifier::new(field_type, length));
+                    }
+                    self.templates.insert((netflow::NETFLOW_V9_VERSION, source_id, template_id), fields);
+                }
+            } else if let Some(fields) = self.templates.get(&(netflow::NETFLOW_V9_VERSION, source_id, flowset_id)) {
+                let record_len: usize = fields.iter().map(|f| f.length as usize).sum();
+                // The rest is padding when shorter than a record.
+                while record_len > 0 && flowset.remaining() >= record_len {
+                    let mut record = empty_record(millis_to_time(unix_secs * 1000));
+                    for field in fields {
+                        let value = flowset.bytes(field.length as usize)?;
+                        match field.id {
+                            netflow::FIELD_FIRST_SWITCHED => record.start_time = millis_to_time(boot_millis + to_uint(value) as i64),
+                            netflow::FIELD_LAST_SWITCHED => record.end_time = millis_to_time(boot_millis + to_uint(value) as i64),
+                            netflow::FIELD_PROCESS_ID => record.pid = Some(to_uint(value) as u32).filter(|pid| *pid != 0),
+                            netflow::FIELD_PROCESS_NAME => record.process_name = to_string(value),
+                            netflow::FIELD_DOMAIN => record.domain = to_string(value),
+                            id => apply_common_field(&mut record, id, value),
+                        }
+                    }
+                    records.push(record);
+                }
+            }
+        }
+        Ok(records)
+    }
+}
+
+fn empty_record(time: DateTime<Local>) -> FlowRecord {
+    FlowRecord {
+        src_ip_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        dst_ip_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        src_port: 0,
+        dst_port: 0,
+        protocol: TransportProtocol::RAW,
+        direction: FlowDirection::Ingress,
+        end_reason: FlowEndReason::ForcedEnd,
+        octets: 0,
+        packets: 0,
+        start_time: time,
+        end_time: time,
+        pid: None,
+        process_name: None,
+        domain: None,
+    }
+}
+
+/// Fields shared by IPFIX and NetFlow v9.
+fn apply_common_field(record: &mut FlowRecord, id: u16, value: &[u8]) {
+    match id {
+        ipfix::IE_SOURCE_IPV4_ADDRESS | ipfix::IE_SOURCE_IPV6_ADDRESS => {
+            if let Some(ip_addr) = to_ip_addr(value) {
+                record.src_ip_addr = ip_addr;
+            }
+        }
+        ipfix::IE_DESTINATION_IPV4_ADDRESS | ipfix::IE_DESTINATION_IPV6_ADDRESS => {
+            if let Some(ip_addr) = to_ip_addr(value) {
+                record.dst_ip_addr = ip_addr;
+            }
+        }
+        ipfix::IE_SOURCE_TRANSPORT_PORT => record.src_port = to_uint(value) as u16,
+        ipfix::IE_DESTINATION_TRANSPORT_PORT => record.dst_port = to_uint(value) as u16,
+        ipfix::IE_PROTOCOL_IDENTIFIER => record.protocol = protocol_from_number(to_uint(value) as u8),
+        ipfix::IE_FLOW_DIRECTION => record.direction = FlowDirection::from_u8(to_uint(value) as u8),
+        ipfix::IE_FLOW_END_REASON => record.end_reason = FlowEndReason::from_u8(to_uint(value) as u8),
+        ipfix::IE_OCTET_DELTA_COUNT => record.octets = to_uint(value),
+        ipfix::IE_PACKET_DELTA_COUNT => record.packets = to_uint(value),
+        _ => {}
+    }
+}
@@ -0,0 +1,209 @@
+use std::net::IpAddr;
+use chrono::{DateTime, Local};
+use super::{FlowEncoder, FlowRecord, protocol_number, MAX_MESSAGE_SIZE, TEMPLATE_ID_IPV4, TEMPLATE_ID_IPV6};
+
+pub const IPFIX_VERSION: u16 = 10;
+pub const IPFIX_HEADER_LEN: usize = 16;
+pub const SET_HEADER_LEN: usize = 4;
+pub const TEMPLATE_SET_ID: u16 = 2;
+/// Field length of variable-length information elements.
+pub const VARIABLE_LENGTH: u16 = 65535;
+/// Enterprise bit of the information element ID.
+pub const ENTERPRISE_BIT: u16 = 0x8000;
+
+// IANA information elements
+pub const IE_OCTET_DELTA_COUNT: u16 = 1;
+pub const IE_PACKET_DELTA_COUNT: u16 = 2;
+pub const IE_PROTOCOL_IDENTIFIER: u16 = 4;
+pub const IE_SOURCE_TRANSPORT_PORT: u16 = 7;
+pub const IE_SOURCE_IPV4_ADDRESS: u16 = 8;
+pub const IE_DESTINATION_TRANSPORT_PORT: u16 = 11;
+pub const IE_DESTINATION_IPV4_ADDRESS: u16 = 12;
+pub const IE_SOURCE_IPV6_ADDRESS: u16 = 27;
+pub const IE_DESTINATION_IPV6_ADDRESS: u16 = 28;
+pub const IE_FLOW_DIRECTION: u16 = 61;
+pub const IE_FLOW_END_REASON: u16 = 136;
+pub const IE_FLOW_START_MILLISECONDS: u16 = 152;
+pub const IE_FLOW_END_MILLISECONDS: u16 = 153;
+
+// nustat enterprise-specific information elements
+pub const IE_PROCESS_ID: u16 = 1;
+pub const IE_PROCESS_NAME: u16 = 2;
+pub const IE_DOMAIN: u16 = 3;
+
+/// Field specifier of a template record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSpecifier {
+    pub id: u16,
+    pub length: u16,
+    pub enterprise_number: Option<u32>,
+}
+
+impl FieldSpecifier {
+    pub fn new(id: u16, length: u16) -> Self {
+        FieldSpecifier { id, length, enterprise_number: None }
+    }
+    pub fn enterprise(id: u16, length: u16, enterprise_number: u32) -> Self {
+        FieldSpecifier { id, length, enterprise_number: Some(enterprise_number) }
+    }
+}
+
+/// Fields of the IPv4 or IPv6 template, in record order.
+pub fn template_fields(ipv6: bool, enterprise_number: u32) -> Vec<FieldSpecifier> {
+    let (src, dst, addr_len) = if ipv6 {
+        (IE_SOURCE_IPV6_ADDRESS, IE_DESTINATION_IPV6_ADDRESS, 16)
+    } else {
+        (IE_SOURCE_IPV4_ADDRESS, IE_DESTINATION_IPV4_ADDRESS, 4)
+    };
+    vec![
+        FieldSpecifier::new(src, addr_len),
+        FieldSpecifier::new(dst, addr_len),
+        FieldSpecifier::new(IE_SOURCE_TRANSPORT_PORT, 2),
+        FieldSpecifier::new(IE_DESTINATION_TRANSPORT_PORT, 2),
+        FieldSpecifier::new(IE_PROTOCOL_IDENTIFIER, 1),
+        FieldSpecifier::new(IE_FLOW_DIRECTION, 1),
+        FieldSpecifier::new(IE_FLOW_END_REASON, 1),
+        FieldSpecifier::new(IE_OCTET_DELTA_COUNT, 8),
+        FieldSpecifier::new(IE_PACKET_DELTA_COUNT, 8),
+        FieldSpecifier::new(IE_FLOW_START_MILLISECONDS, 8),
+        FieldSpecifier::new(IE_FLOW_END_MILLISECONDS, 8),
+        FieldSpecifier::enterprise(IE_PROCESS_ID, 4, enterprise_number),
+        FieldSpecifier::enterprise(IE_PROCESS_NAME, VARIABLE_LENGTH, enterprise_number),
+        FieldSpecifier::enterprise(IE_DOMAIN, VARIABLE_LENGTH, enterprise_number),
+    ]
+}
+
+pub(crate) fn put_ip_addr(buf: &mut Vec<u8>, ip_addr: &IpAddr) {
+    match ip_addr {
+        IpAddr::V4(ip) => buf.extend_from_slice(&ip.octets()),
+        IpAddr::V6(ip) => buf.extend_from_slice(&ip.octets()),
+    }
+}
+
+/// Write a variable-length string. (RFC 7011 section 7)
+fn put_variable_length(buf: &mut Vec<u8>, value: &str) {
+    let value = &value.as_bytes()[..value.len().min(u16::MAX as usize)];
+    if value.len() < 255 {
+        buf.push(value.len() as u8);
+    } else {
+        buf.push(255);
+        buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    }
+    buf.extend_from_slice(value);
+}
+
+/// Write a data record matching `template_fields`.
+fn put_record(buf: &mut Vec<u8>, record: &FlowRecord) {
+    put_ip_addr(buf, &record.src_ip_addr);
+    put_ip_addr(buf, &record.dst_ip_addr);
+    buf.extend_from_slice(&record.src_port.to_be_bytes());
+    buf.extend_from_slice(&record.dst_port.to_be_bytes());
+    buf.push(protocol_number(record.protocol));
+    buf.push(record.direction as u8);
+    buf.push(record.end_reason as u8);
+    buf.extend_from_slice(&record.octets.to_be_bytes());
+    buf.extend_from_slice(&record.packets.to_be_bytes());
+    buf.extend_from_slice(&(record.start_time.timestamp_millis() as u64).to_be_bytes());
+    buf.extend_from_slice(&(record.end_time.timestamp_millis() as u64).to_be_bytes());
+    buf.extend_from_slice(&record.pid.unwrap_or(0).to_be_bytes());
+    put_variable_length(buf, record.process_name.as_deref().unwrap_or_default());
+    put_variable_length(buf, record.domain.as_deref().unwrap_or_default());
+}
+
+fn put_set(buf: &mut Vec<u8>, set_id: u16, body: &[u8]) {
+    buf.extend_from_slice(&set_id.to_be_bytes());
+    buf.extend_from_slice(&((SET_HEADER_LEN + body.len()) as u16).to_be_bytes());
+    buf.extend_from_slice(body);
+}
+
+/// IPFIX (RFC 7011) encoder.
+pub struct IpfixEncoder {
+    observation_domain_id: u32,
+    enterprise_number: u32,
+    /// Number of data records sent so far.
+    sequence: u32,
+}
+
+impl IpfixEncoder {
+    pub fn new(observation_domain_id: u32, enterprise_number: u32) -> Self {
+        IpfixEncoder {
+            observation_domain_id,
+            enterprise_number,
+            sequence: 0,
+        }
+    }
+    fn template_set(&self) -> Vec<u8> {
+        let mut body: Vec<u8> = Vec::new();
+        for (template_id, ipv6) in [(TEMPLATE_ID_IPV4, false), (TEMPLATE_ID_IPV6, true)] {
+            let fields = template_fields(ipv6, self.enterprise_number);
+            body.extend_from_slice(&template_id.to_be_bytes());
+            body.extend_from_slice(&(fields.len() as u16).to_be_bytes());
+            for field in fields {
+                match field.enterprise_number {
+                    Some(enterprise_number) => {
+                        body.extend_from_slice(&(field.id | ENTERPRISE_BIT).to_be_bytes());
+                        body.extend_from_slice(&field.length.to_be_bytes());
+                        body.extend_from_slice(&enterprise_number.to_be_bytes());
+                    }
+                    None => {
+                        body.extend_from_slice(&field.id.to_be_bytes());
+                        body.extend_from_slice(&field.length.to_be_bytes());
+                    }
+                }
+            }
+        }
+        let mut set: Vec<u8> = Vec::new();
+        put_set(&mut set, TEMPLATE_SET_ID, &body);
+        set
+    }
+    fn message(&mut self, sets: &[u8], record_count: u32, export_time: DateTime<Local>) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::with_capacity(IPFIX_HEADER_LEN + sets.len());
+        buf.extend_from_slice(&IPFIX_VERSION.to_be_bytes());
+        buf.extend_from_slice(&((IPFIX_HEADER_LEN + sets.len()) as u16).to_be_bytes());
+        buf.extend_from_slice(&(export_time.timestamp() as u32).to_be_bytes());
+        buf.extend_from_slice(&self.sequence.to_be_bytes());
+        buf.extend_from_slice(&self.observation_domain_id.to_be_bytes());
+        buf.extend_from_slice(sets);
+        self.sequence = self.sequence.wrapping_add(record_count);
+        buf
+    }
+}
+
+impl FlowEncoder for IpfixEncoder {
+    fn name(&self) -> &str {
+        "IPFIX"
+    }
+    fn encode(&mut self, records: &[FlowRecord], with_templates: bool, export_time: DateTime<Local>) -> Vec<Vec<u8>> {
+        let mut messages: Vec<Vec<u8>> = Vec::new();
+        let mut sets: Vec<u8> = if with_templates { self.template_set() } else { Vec::new() };
+        let mut set: Vec<u8> = Vec::new();
+        let mut set_id: u16 = 0;
+        let mut record_count: u32 = 0;
+        for record in records {
+            let template_id = if record.is_ipv6() { TEMPLATE_ID_IPV6 } else { TEMPLATE_ID_IPV4 };
+            let mut data: Vec<u8> = Vec::new();
+            put_record(&mut data, record);
+            if template_id != set_id || IPFIX_HEADER_LEN + sets.len() + SET_HEADER_LEN + set.len() + data.len() > MAX_MESSAGE_SIZE {
+                if !set.is_empty() {
+                    put_set(&mut sets, set_id, &set);
+                    set.clear();
+                }
+                if !sets.is_empty() && IPFIX_HEADER_LEN + sets.len() + SET_HEADER_LEN + data.len() > MAX_MESSAGE_SIZE {
+                    messages.push(self.message(&sets, record_count, export_time));
+                    sets.clear();
+                    record_count = 0;
+                }
+                set_id = template_id;
+            }
+            set.extend_from_slice(&data);
+            record_count += 1;
+        }
+        if !set.is_empty() {
+            put_set(&mut sets, set_id, &set);
+        }
+        if !sets.is_empty() {
+            messages.push(self.message(&sets, record_count, export_time));
+        }
+        messages
+    }
+}
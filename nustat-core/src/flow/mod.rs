@@ -0,0 +1,329 @@
+pub mod ipfix;
+pub mod netflow;
+pub mod collector;
+pub mod sflow;
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use chrono::{DateTime, Local};
+use serde::{Serialize, Deserialize};
+use crate::net::stat::{NetStatData, NetStatStrage};
+use crate::net::traffic::TrafficInfo;
+use crate::socket::{SocketConnection, TransportProtocol};
+use crate::thread_log;
+
+/// Template ID of IPv4 flow records.
+pub const TEMPLATE_ID_IPV4: u16 = 256;
+/// Template ID of IPv6 flow records.
+pub const TEMPLATE_ID_IPV6: u16 = 257;
+
+/// Maximum size of an export packet. Keeps datagrams below the usual path MTU.
+pub const MAX_MESSAGE_SIZE: usize = 1400;
+
+/// Private enterprise number of the nustat specific fields. Defaults to the documentation PEN (RFC 5612).
+pub const DEFAULT_ENTERPRISE_NUMBER: u32 = 32473;
+
+pub const DEFAULT_ACTIVE_TIMEOUT: Duration = Duration::from_secs(60);
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How often templates are resent. Collectors lose them on restart since UDP has no session.
+pub const TEMPLATE_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the flow cache is checked for expired flows.
+const FLOW_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlowExportProtocol {
+    #[default]
+    IPFIX,
+    NetFlowV9,
+}
+
+/// flowDirection (IE 61)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowDirection {
+    Ingress = 0,
+    Egress = 1,
+}
+
+impl FlowDirection {
+    pub fn from_u8(v: u8) -> FlowDirection {
+        if v == 1 { FlowDirection::Egress } else { FlowDirection::Ingress }
+    }
+}
+
+/// flowEndReason (IE 136)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowEndReason {
+    IdleTimeout = 1,
+    ActiveTimeout = 2,
+    EndOfFlow = 3,
+    ForcedEnd = 4,
+}
+
+impl FlowEndReason {
+    pub fn from_u8(v: u8) -> FlowEndReason {
+        match v {
+            1 => FlowEndReason::IdleTimeout,
+            2 => FlowEndReason::ActiveTimeout,
+            3 => FlowEndReason::EndOfFlow,
+            _ => FlowEndReason::ForcedEnd,
+        }
+    }
+}
+
+/// A unidirectional flow record.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FlowRecord {
+    pub src_ip_addr: IpAddr,
+    pub dst_ip_addr: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub protocol: TransportProtocol,
+    pub direction: FlowDirection,
+    pub end_reason: FlowEndReason,
+    pub octets: u64,
+    pub packets: u64,
+    pub start_time: DateTime<Local>,
+    pub end_time: DateTime<Local>,
+    pub pid: Option<u32>,
+    pub process_name: Option<String>,
+    /// Domain of the remote host (reverse DNS or local name). TLS SNI is not captured.
+    pub domain: Option<String>,
+}
+
+impl FlowRecord {
+    pub fn is_ipv6(&self) -> bool {
+        self.src_ip_addr.is_ipv6()
+    }
+}
+
+/// IANA protocol number of the transport protocol.
+pub fn protocol_number(protocol: TransportProtocol) -> u8 {
+    match protocol {
+        TransportProtocol::TCP => 6,
+        TransportProtocol::UDP => 17,
+        TransportProtocol::RAW => 255,
+    }
+}
+
+pub fn protocol_from_number(number: u8) -> TransportProtocol {
+    match number {
+        6 => TransportProtocol::TCP,
+        17 => TransportProtocol::UDP,
+        _ => TransportProtocol::RAW,
+    }
+}
+
+/// Encoder of an export protocol.
+pub trait FlowEncoder: Send {
+    fn name(&self) -> &str;
+    /// Encode the records into one or more export packets.
+    /// Templates are included in the first packet if `with_templates` is true.
+    fn encode(&mut self, records: &[FlowRecord], with_templates: bool, export_time: DateTime<Local>) -> Vec<Vec<u8>>;
+}
+
+pub fn new_encoder(protocol: FlowExportProtocol, observation_domain_id: u32, enterprise_number: u32) -> Box<dyn FlowEncoder> {
+    match protocol {
+        FlowExportProtocol::IPFIX => Box::new(ipfix::IpfixEncoder::new(observation_domain_id, enterprise_number)),
+        FlowExportProtocol::NetFlowV9 => Box::new(netflow::NetflowV9Encoder::new(observation_domain_id)),
+    }
+}
+
+struct FlowEntry {
+    start_time: DateTime<Local>,
+    last_seen: DateTime<Local>,
+    /// Counters not exported yet.
+    traffic: TrafficInfo,
+    /// Process and domain, kept once known since the socket may be gone by the export.
+    process: Option<(u32, String)>,
+    domain: Option<String>,
+}
+
+/// Turns the per-interval connection counters into flow records with active and idle timeouts.
+/// Only active flows are kept. A flow is dropped once exported on the idle timeout.
+pub struct FlowCache {
+    entries: HashMap<SocketConnection, FlowEntry>,
+    active_timeout: Duration,
+    idle_timeout: Duration,
+}
+
+impl FlowCache {
+    pub fn new(active_timeout: Duration, idle_timeout: Duration) -> Self {
+        FlowCache {
+            entries: HashMap::new(),
+            active_timeout,
+            idle_timeout,
+        }
+    }
+    /// Number of active flows.
+    pub fn active_flows(&self) -> usize {
+        self.entries.len()
+    }
+    /// Add the connection counters of the interval in `data.connection_map` and return the records of expired flows.
+    /// The socket maps and remote hosts of `data` attribute the flows to processes and domains.
+    /// A flow expires when no packet was seen for the idle timeout, or when it has been active for the active timeout.
+    pub fn update(&mut self, data: &NetStatData, now: DateTime<Local>) -> Vec<FlowRecord> {
+        let active_timeout = chrono::Duration::from_std(self.active_timeout).unwrap_or(chrono::Duration::MAX);
+        let idle_timeout = chrono::Duration::from_std(self.idle_timeout).unwrap_or(chrono::Duration::MAX);
+        for (conn, traffic) in &data.connection_map {
+            if traffic.total_packet() == 0 {
+                continue;
+            }
+            let entry = self.entries.entry(conn.clone()).or_insert_with(|| FlowEntry {
+                start_time: now,
+                last_seen: now,
+                traffic: TrafficInfo::new(),
+                process: None,
+                domain: None,
+            });
+            entry.last_seen = now;
+            entry.traffic.add_traffic(traffic);
+            if let Some(process) = data.get_socket_process(conn).and_then(|socket_process| socket_process.process.as_ref()) {
+                entry.process = Some((process.pid, process.name.clone()));
+            }
+            if let Some(host) = data.remote_hosts.get(&conn.remote_ip_addr).filter(|host| !host.hostname.is_empty()) {
+                entry.domain = Some(host.hostname.clone());
+            }
+        }
+        let mut records: Vec<FlowRecord> = Vec::new();
+        self.entries.retain(|conn, entry| {
+            if now - entry.last_seen >= idle_timeout {
+                let end_time = entry.last_seen;
+                records.extend(export_entry(conn, entry, end_time, FlowEndReason::IdleTimeout));
+                false
+            } else {
+                if now - entry.start_time >= active_timeout {
+                    records.extend(export_entry(conn, entry, now, FlowEndReason::ActiveTimeout));
+                    entry.start_time = now;
+                }
+                true
+            }
+        });
+        records
+    }
+    /// Export every active flow. Used on shutdown.
+    pub fn flush(&mut self) -> Vec<FlowRecord> {
+        let mut records: Vec<FlowRecord> = Vec::new();
+        for (conn, mut entry) in self.entries.drain() {
+            let end_time = entry.last_seen;
+            records.extend(export_entry(&conn, &mut entry, end_time, FlowEndReason::ForcedEnd));
+        }
+        records
+    }
+}
+
+/// Build the egress and ingress records of the counters not exported yet.
+fn export_entry(conn: &SocketConnection, entry: &mut FlowEntry, end_time: DateTime<Local>, end_reason: FlowEndReason) -> Vec<FlowRecord> {
+    let local_ip_addr = conn.local_ip_addr.unwrap_or(match conn.remote_ip_addr {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    });
+    let record = |direction: FlowDirection, octets: usize, packets: usize| {
+        let (src_ip_addr, src_port, dst_ip_addr, dst_port) = match direction {
+            FlowDirection::Egress => (local_ip_addr, conn.local_port, conn.remote_ip_addr, conn.remote_port),
+            FlowDirection::Ingress => (conn.remote_ip_addr, conn.remote_port, local_ip_addr, conn.local_port),
+        };
+        FlowRecord {
+            src_ip_addr,
+            dst_ip_addr,
+            src_port,
+            dst_port,
+            protocol: conn.protocol,
+            direction,
+            end_reason,
+            octets: octets as u64,
+            packets: packets as u64,
+            start_time: entry.start_time,
+            end_time,
+            pid: entry.process.as_ref().map(|(pid, _)| *pid),
+            process_name: entry.process.as_ref().map(|(_, name)| name.clone()),
+            domain: entry.domain.clone(),
+        }
+    };
+    let mut records: Vec<FlowRecord> = Vec::new();
+    if entry.traffic.packet_sent > 0 {
+        records.push(record(FlowDirection::Egress, entry.traffic.bytes_sent, entry.traffic.packet_sent));
+    }
+    if entry.traffic.packet_received > 0 {
+        records.push(record(FlowDirection::Ingress, entry.traffic.bytes_received, entry.traffic.packet_received));
+    }
+    entry.traffic = TrafficInfo::new();
+    records
+}
+
+#[derive(Debug, Clone)]
+pub struct FlowExportOption {
+    pub collector: SocketAddr,
+    pub protocol: FlowExportProtocol,
+    pub active_timeout: Duration,
+    pub idle_timeout: Duration,
+    pub observation_domain_id: u32,
+    pub enterprise_number: u32,
+}
+
+impl FlowExportOption {
+    pub fn new(collector: SocketAddr) -> Self {
+        FlowExportOption {
+            collector,
+            protocol: FlowExportProtocol::default(),
+            active_timeout: DEFAULT_ACTIVE_TIMEOUT,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            observation_domain_id: 0,
+            enterprise_number: DEFAULT_ENTERPRISE_NUMBER,
+        }
+    }
+}
+
+/// Start exporting flows to the collector. Enables flow deltas on the strage, which only this exporter drains.
+pub fn start_flow_export(netstat_strage: Arc<NetStatStrage>, option: FlowExportOption) -> Result<thread::JoinHandle<()>, String> {
+    let bind_addr: SocketAddr = match option.collector {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+    let socket = UdpSocket::bind(bind_addr).map_err(|e| e.to_string())?;
+    socket.connect(option.collector).map_err(|e| format!("Failed to connect to collector {}: {}", option.collector, e))?;
+    netstat_strage.enable_flow_deltas();
+    thread::Builder::new()
+        .name(String::from("flow-export"))
+        .spawn(move || {
+            let mut encoder = new_encoder(option.protocol, option.observation_domain_id, option.enterprise_number);
+            let mut cache = FlowCache::new(option.active_timeout, option.idle_timeout);
+            let mut last_template: Option<std::time::Instant> = None;
+            loop {
+                thread::sleep(FLOW_CHECK_INTERVAL);
+                let now = Local::now();
+                let mut data = NetStatData::new();
+                data.connection_map = netstat_strage.drain_flow_deltas();
+                if !data.connection_map.is_empty() {
+                    // Sockets and names to attribute the flows of the interval
+                    data.connection_socket_map = netstat_strage.get_connection_socket_map();
+                    data.local_socket_map = netstat_strage.get_local_socket_map();
+                    if let Ok(remote_hosts) = netstat_strage.remote_hosts.lock() {
+                        for conn in data.connection_map.keys() {
+                            if let Some(host) = remote_hosts.get(&conn.remote_ip_addr) {
+                                data.remote_hosts.insert(conn.remote_ip_addr, host.clone());
+                            }
+                        }
+                    }
+                }
+                let records = cache.update(&data, now);
+                if records.is_empty() {
+                    continue;
+                }
+                let with_templates = last_template.is_none_or(|sent| sent.elapsed() >= TEMPLATE_REFRESH_INTERVAL);
+                if with_templates {
+                    last_template = Some(std::time::Instant::now());
+                }
+                for packet in encoder.encode(&records, with_templates, now) {
+                    if let Err(e) = socket.send(&packet) {
+                        thread_log!(error, "[flow] {} send error: {}", encoder.name(), e);
+                    }
+                }
+            }
+        })
+        .map_err(|e| e.to_string())
+}
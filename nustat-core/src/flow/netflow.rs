@@ -0,0 +1,168 @@
+use chrono::{DateTime, Local};
+use super::ipfix::{self, put_ip_addr};
+use super::{FlowEncoder, FlowRecord, protocol_number, MAX_MESSAGE_SIZE, TEMPLATE_ID_IPV4, TEMPLATE_ID_IPV6};
+
+pub const NETFLOW_V9_VERSION: u16 = 9;
+pub const NETFLOW_V9_HEADER_LEN: usize = 20;
+pub const FLOWSET_HEADER_LEN: usize = 4;
+pub const TEMPLATE_FLOWSET_ID: u16 = 0;
+
+// Field types that differ from the IPFIX information elements
+pub const FIELD_LAST_SWITCHED: u16 = 21;
+pub const FIELD_FIRST_SWITCHED: u16 = 22;
+
+// nustat vendor-specific field types. NetFlow v9 has no enterprise numbers, so fixed values are used.
+pub const FIELD_PROCESS_ID: u16 = 60001;
+pub const FIELD_PROCESS_NAME: u16 = 60002;
+pub const FIELD_DOMAIN: u16 = 60003;
+
+/// Fixed length of the process name field. Longer names are truncated.
+pub const PROCESS_NAME_LEN: u16 = 32;
+/// Fixed length of the domain field. Longer names are truncated.
+pub const DOMAIN_LEN: u16 = 64;
+
+/// Fields (type, length) of the IPv4 or IPv6 template, in record order.
+pub fn template_fields(ipv6: bool) -> Vec<(u16, u16)> {
+    let (src, dst, addr_len) = if ipv6 {
+        (ipfix::IE_SOURCE_IPV6_ADDRESS, ipfix::IE_DESTINATION_IPV6_ADDRESS, 16)
+    } else {
+        (ipfix::IE_SOURCE_IPV4_ADDRESS, ipfix::IE_DESTINATION_IPV4_ADDRESS, 4)
+    };
+    vec![
+        (src, addr_len),
+        (dst, addr_len),
+        (ipfix::IE_SOURCE_TRANSPORT_PORT, 2),
+        (ipfix::IE_DESTINATION_TRANSPORT_PORT, 2),
+        (ipfix::IE_PROTOCOL_IDENTIFIER, 1),
+        (ipfix::IE_FLOW_DIRECTION, 1),
+        (ipfix::IE_FLOW_END_REASON, 1),
+        (ipfix::IE_OCTET_DELTA_COUNT, 8),
+        (ipfix::IE_PACKET_DELTA_COUNT, 8),
+        (FIELD_FIRST_SWITCHED, 4),
+        (FIELD_LAST_SWITCHED, 4),
+        (FIELD_PROCESS_ID, 4),
+        (FIELD_PROCESS_NAME, PROCESS_NAME_LEN),
+        (FIELD_DOMAIN, DOMAIN_LEN),
+    ]
+}
+
+/// Write a zero padded fixed-length string.
+fn put_fixed_length(buf: &mut Vec<u8>, value: &str, len: u16) {
+    let len = len as usize;
+    let value = &value.as_bytes()[..value.len().min(len)];
+    buf.extend_from_slice(value);
+    buf.resize(buf.len() + len - value.len(), 0);
+}
+
+/// Flowsets are padded to a 4 byte boundary.
+fn put_flowset(buf: &mut Vec<u8>, flowset_id: u16, body: &[u8]) {
+    let padding = (4 - (FLOWSET_HEADER_LEN + body.len()) % 4) % 4;
+    buf.extend_from_slice(&flowset_id.to_be_bytes());
+    buf.extend_from_slice(&((FLOWSET_HEADER_LEN + body.len() + padding) as u16).to_be_bytes());
+    buf.extend_from_slice(body);
+    buf.resize(buf.len() + padding, 0);
+}
+
+/// NetFlow v9 (RFC 3954) encoder.
+pub struct NetflowV9Encoder {
+    source_id: u32,
+    /// Start of the exporter in milliseconds. Uptime and switched times are relative to it.
+    boot_millis: i64,
+    /// Number of export packets sent so far.
+    sequence: u32,
+}
+
+impl NetflowV9Encoder {
+    pub fn new(source_id: u32) -> Self {
+        NetflowV9Encoder {
+            source_id,
+            boot_millis: Local::now().timestamp_millis(),
+            sequence: 0,
+        }
+    }
+    fn uptime_millis(&self, time: DateTime<Local>) -> u32 {
+        (time.timestamp_millis() - self.boot_millis).clamp(0, u32::MAX as i64) as u32
+    }
+    fn put_record(&self, buf: &mut Vec<u8>, record: &FlowRecord) {
+        put_ip_addr(buf, &record.src_ip_addr);
+        put_ip_addr(buf, &record.dst_ip_addr);
+        buf.extend_from_slice(&record.src_port.to_be_bytes());
+        buf.extend_from_slice(&record.dst_port.to_be_bytes());
+        buf.push(protocol_number(record.protocol));
+        buf.push(record.direction as u8);
+        buf.push(record.end_reason as u8);
+        buf.extend_from_slice(&record.octets.to_be_bytes());
+        buf.extend_from_slice(&record.packets.to_be_bytes());
+        buf.extend_from_slice(&self.uptime_millis(record.start_time).to_be_bytes());
+        buf.extend_from_slice(&self.uptime_millis(record.end_time).to_be_bytes());
+        buf.extend_from_slice(&record.pid.unwrap_or(0).to_be_bytes());
+        put_fixed_length(buf, record.process_name.as_deref().unwrap_or_default(), PROCESS_NAME_LEN);
+        put_fixed_length(buf, record.domain.as_deref().unwrap_or_default(), DOMAIN_LEN);
+    }
+    fn template_flowset(&self) -> Vec<u8> {
+        let mut body: Vec<u8> = Vec::new();
+        for (template_id, ipv6) in [(TEMPLATE_ID_IPV4, false), (TEMPLATE_ID_IPV6, true)] {
+            let fields = template_fields(ipv6);
+            body.extend_from_slice(&template_id.to_be_bytes());
+            body.extend_from_slice(&(fields.len() as u16).to_be_bytes());
+            for (field_type, length) in fields {
+                body.extend_from_slice(&field_type.to_be_bytes());
+                body.extend_from_slice(&length.to_be_bytes());
+            }
+        }
+        let mut flowset: Vec<u8> = Vec::new();
+        put_flowset(&mut flowset, TEMPLATE_FLOWSET_ID, &body);
+        flowset
+    }
+    fn packet(&mut self, flowsets: &[u8], record_count: u16, export_time: DateTime<Local>) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::with_capacity(NETFLOW_V9_HEADER_LEN + flowsets.len());
+        buf.extend_from_slice(&NETFLOW_V9_VERSION.to_be_bytes());
+        buf.extend_from_slice(&record_count.to_be_bytes());
+        buf.extend_from_slice(&self.uptime_millis(export_time).to_be_bytes());
+        buf.extend_from_slice(&(export_time.timestamp() as u32).to_be_bytes());
+        buf.extend_from_slice(&self.sequence.to_be_bytes());
+        buf.extend_from_slice(&self.source_id.to_be_bytes());
+        buf.extend_from_slice(flowsets);
+        self.sequence = self.sequence.wrapping_add(1);
+        buf
+    }
+}
+
+impl FlowEncoder for NetflowV9Encoder {
+    fn name(&self) -> &str {
+        "NetFlow v9"
+    }
+    fn encode(&mut self, records: &[FlowRecord], with_templates: bool, export_time: DateTime<Local>) -> Vec<Vec<u8>> {
+        let mut packets: Vec<Vec<u8>> = Vec::new();
+        let (mut flowsets, mut record_count): (Vec<u8>, u16) = if with_templates { (self.template_flowset(), 2) } else { (Vec::new(), 0) };
+        let mut flowset: Vec<u8> = Vec::new();
+        let mut flowset_id: u16 = 0;
+        for record in records {
+            let template_id = if record.is_ipv6() { TEMPLATE_ID_IPV6 } else { TEMPLATE_ID_IPV4 };
+            let mut data: Vec<u8> = Vec::new();
+            self.put_record(&mut data, record);
+            // 3 bytes of headroom for the flowset padding
+            if template_id != flowset_id || NETFLOW_V9_HEADER_LEN + flowsets.len() + FLOWSET_HEADER_LEN + flowset.len() + data.len() + 3 > MAX_MESSAGE_SIZE {
+                if !flowset.is_empty() {
+                    put_flowset(&mut flowsets, flowset_id, &flowset);
+                    flowset.clear();
+                }
+                if !flowsets.is_empty() && NETFLOW_V9_HEADER_LEN + flowsets.len() + FLOWSET_HEADER_LEN + data.len() + 3 > MAX_MESSAGE_SIZE {
+                    packets.push(self.packet(&flowsets, record_count, export_time));
+                    flowsets.clear();
+                    record_count = 0;
+                }
+                flowset_id = template_id;
+            }
+            flowset.extend_from_slice(&data);
+            record_count += 1;
+        }
+        if !flowset.is_empty() {
+            put_flowset(&mut flowsets, flowset_id, &flowset);
+        }
+        if !flowsets.is_empty() {
+            packets.push(self.packet(&flowsets, record_count, export_time));
+        }
+        packets
+    }
+}
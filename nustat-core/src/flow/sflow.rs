@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use xenet::packet::frame::ParseOption;
+use crate::net::stat::NetStatStrage;
+use crate::thread_log;
+use super::MAX_MESSAGE_SIZE;
+
+pub const SFLOW_VERSION: u32 = 5;
+pub const DEFAULT_SFLOW_COLLECTOR: &str = "127.0.0.1:6343";
+
+/// Bytes of each sampled packet sent to the collector.
+pub const SAMPLED_HEADER_LEN: usize = 128;
+
+/// Samples queued for the exporter. Further samples are dropped while the queue is full.
+pub const SAMPLE_QUEUE_SIZE: usize = 4096;
+
+/// How often queued samples are sent.
+const SFLOW_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+const SFLOW_HEADER_LEN_IPV4: usize = 28;
+const SFLOW_HEADER_LEN_IPV6: usize = 40;
+/// Flow sample (enterprise 0, format 1)
+const FLOW_SAMPLE_FORMAT: u32 = 1;
+/// Raw packet header flow record (enterprise 0, format 1)
+const RAW_PACKET_HEADER_FORMAT: u32 = 1;
+
+/// Header protocol of a raw packet header record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderProtocol {
+    Ethernet = 1,
+    IPv4 = 11,
+    IPv6 = 12,
+}
+
+impl HeaderProtocol {
+    pub fn from_u32(v: u32) -> Option<HeaderProtocol> {
+        match v {
+            1 => Some(HeaderProtocol::Ethernet),
+            11 => Some(HeaderProtocol::IPv4),
+            12 => Some(HeaderProtocol::IPv6),
+            _ => None,
+        }
+    }
+}
+
+/// A sampled packet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketSample {
+    pub if_index: u32,
+    pub sampling_rate: u32,
+    /// Packets seen by the sampler, sampled or not.
+    pub sample_pool: u32,
+    /// Samples dropped for lack of resources (exporter queue full) before this one.
+    pub drops: u32,
+    pub header_protocol: HeaderProtocol,
+    pub frame_length: u32,
+    /// Head of the packet, at most `SAMPLED_HEADER_LEN` bytes.
+    pub header: Vec<u8>,
+}
+
+impl PacketSample {
+    pub fn from_packet(packet: &[u8], parse_option: &ParseOption, if_index: u32, sampling_rate: u32, sample_pool: u32, drops: u32) -> PacketSample {
+        let (header_protocol, packet) = if parse_option.from_ip_packet {
+            let packet = packet.get(parse_option.offset..).unwrap_or_default();
+            match packet.first().map(|b| b >> 4) {
+                Some(6) => (HeaderProtocol::IPv6, packet),
+                _ => (HeaderProtocol::IPv4, packet),
+            }
+        } else {
+            (HeaderProtocol::Ethernet, packet)
+        };
+        PacketSample {
+            if_index,
+            sampling_rate,
+            sample_pool,
+            drops,
+            header_protocol,
+            frame_length: packet.len() as u32,
+            header: packet[..packet.len().min(SAMPLED_HEADER_LEN)].to_vec(),
+        }
+    }
+}
+
+fn put_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_be_bytes());
+}
+
+/// Opaque data is padded to 4 bytes. (XDR)
+fn put_opaque(buf: &mut Vec<u8>, data: &[u8]) {
+    put_u32(buf, data.len() as u32);
+    buf.extend_from_slice(data);
+    buf.resize(buf.len() + (4 - data.len() % 4) % 4, 0);
+}
+
+/// sFlow v5 encoder of flow samples with raw packet headers.
+pub struct SflowEncoder {
+    agent_addr: IpAddr,
+    sub_agent_id: u32,
+    boot_time: Instant,
+    /// Number of datagrams sent so far.
+    sequence: u32,
+    /// Number of flow samples sent so far per data source (ifIndex).
+    sample_sequences: HashMap<u32, u32>,
+}
+
+impl SflowEncoder {
+    pub fn new(agent_addr: IpAddr, sub_agent_id: u32) -> Self {
+        SflowEncoder {
+            agent_addr,
+            sub_agent_id,
+            boot_time: Instant::now(),
+            sequence: 0,
+            sample_sequences: HashMap::new(),
+        }
+    }
+    fn header_len(&self) -> usize {
+        if self.agent_addr.is_ipv6() { SFLOW_HEADER_LEN_IPV6 } else { SFLOW_HEADER_LEN_IPV4 }
+    }
+    fn flow_sample(&mut self, sample: &PacketSample) -> Vec<u8> {
+        let sequence = self.sample_sequences.entry(sample.if_index).or_insert(0);
+        *sequence = sequence.wrapping_add(1);
+        let mut record: Vec<u8> = Vec::new();
+        put_u32(&mut record, sample.header_protocol as u32);
+        put_u32(&mut record, sample.frame_length);
+        // Bytes stripped from the frame (FCS is not captured)
+        put_u32(&mut record, 0);
+        put_opaque(&mut record, &sample.header);
+
+        let mut body: Vec<u8> = Vec::new();
+        put_u32(&mut body, *sequence);
+        // Source ID: type 0 (ifIndex)
+        put_u32(&mut body, sample.if_index & 0x00FF_FFFF);
+        put_u32(&mut body, sample.sampling_rate);
+        put_u32(&mut body, sample.sample_pool);
+        put_u32(&mut body, sample.drops);
+        // Input and output interfaces. The direction is not known at capture time.
+        put_u32(&mut body, sample.if_index);
+        put_u32(&mut body, 0);
+        // One flow record
+        put_u32(&mut body, 1);
+        put_u32(&mut body, RAW_PACKET_HEADER_FORMAT);
+        put_u32(&mut body, record.len() as u32);
+        body.extend_from_slice(&record);
+
+        let mut buf: Vec<u8> = Vec::new();
+        put_u32(&mut buf, FLOW_SAMPLE_FORMAT);
+        put_u32(&mut buf, body.len() as u32);
+        buf.extend_from_slice(&body);
+        buf
+    }
+    fn datagram(&mut self, samples: &[u8], sample_count: u32) -> Vec<u8> {
+        self.sequence = self.sequence.wrapping_add(1);
+        let mut buf: Vec<u8> = Vec::with_capacity(self.header_len() + samples.len());
+        put_u32(&mut buf, SFLOW_VERSION);
+        match self.agent_addr {
+            IpAddr::V4(ip) => {
+                put_u32(&mut buf, 1);
+                buf.extend_from_slice(&ip.octets());
+            }
+            IpAddr::V6(ip) => {
+                put_u32(&mut buf, 2);
+                buf.extend_from_slice(&ip.octets());
+            }
+        }
+        put_u32(&mut buf, self.sub_agent_id);
+        put_u32(&mut buf, self.sequence);
+        put_u32(&mut buf, self.boot_time.elapsed().as_millis() as u32);
+        put_u32(&mut buf, sample_count);
+        buf.extend_from_slice(samples);
+        buf
+    }
+    /// Encode the samples into one or more datagrams.
+    pub fn encode(&mut self, samples: &[PacketSample]) -> Vec<Vec<u8>> {
+        let mut datagrams: Vec<Vec<u8>> = Vec::new();
+        let mut body: Vec<u8> = Vec::new();
+        let mut sample_count: u32 = 0;
+        for sample in samples {
+            let flow_sample = self.flow_sample(sample);
+            if sample_count > 0 && self.header_len() + body.len() + flow_sample.len() > MAX_MESSAGE_SIZE {
+                datagrams.push(self.datagram(&body, sample_count));
+                body.clear();
+                sample_count = 0;
+            }
+            body.extend_from_slice(&flow_sample);
+            sample_count += 1;
+        }
+        if sample_count > 0 {
+            datagrams.push(self.datagram(&body, sample_count));
+        }
+        datagrams
+    }
+}
+
+/// Decoded sFlow v5 datagram.
+#[derive(Debug, Clone)]
+pub struct SflowDatagram {
+    pub agent_addr: IpAddr,
+    pub sub_agent_id: u32,
+    pub sequence: u32,
+    pub uptime: u32,
+    /// Flow samples with their sequence numbers.
+    pub samples: Vec<(u32, PacketSample)>,
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.buf.len() - self.pos < len {
+            return Err(format!("Truncated datagram: need {} bytes at offset {}", len, self.pos));
+        }
+        let bytes = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+/// Decode an sFlow v5 datagram. Samples and records other than flow samples with raw packet headers are skipped.
+pub fn decode_datagram(buf: &[u8]) -> Result<SflowDatagram, String> {
+    let mut reader = Reader { buf, pos: 0 };
+    let version = reader.u32()?;
+    if version != SFLOW_VERSION {
+        return Err(format!("Unsupported sFlow version: {}", version));
+    }
+    let agent_addr = match reader.u32()? {
+        1 => {
+            let b = reader.bytes(4)?;
+            IpAddr::V4(Ipv4Addr::new(b[0], b[1], b[2], b[3]))
+        }
+        2 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(reader.bytes(16)?);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        addr_type => return Err(format!("Unknown agent address type: {}", addr_type)),
+    };
+    let sub_agent_id = reader.u32()?;
+    let sequence = reader.u32()?;
+    let uptime = reader.u32()?;
+    let sample_count = reader.u32()?;
+    let mut samples: Vec<(u32, PacketSample)> = Vec::new();
+    for _ in 0..sample_count {
+        let format = reader.u32()?;
+        let len = reader.u32()? as usize;
+        let mut sample = Reader { buf: reader.bytes(len)?, pos: 0 };
+        if format != FLOW_SAMPLE_FORMAT {
+            continue;
+        }
+        let sample_sequence = sample.u32()?;
+        let source_id = sample.u32()?;
+        let sampling_rate = sample.u32()?;
+        let sample_pool = sample.u32()?;
+        let drops = sample.u32()?;
+        let _input = sample.u32()?;
+        let _output = sample.u32()?;
+        let record_count = sample.u32()?;
+        for _ in 0..record_count {
+            let record_format = sample.u32()?;
+            let record_len = sample.u32()? as usize;
+            let mut record = Reader { buf: sample.bytes(record_len)?, pos: 0 };
+            if record_format != RAW_PACKET_HEADER_FORMAT {
+                continue;
+            }
+            let header_protocol = record.u32()?;
+            let frame_length = record.u32()?;
+            let _stripped = record.u32()?;
+            let header_len = record.u32()? as usize;
+            let header = record.bytes(header_len)?.to_vec();
+            let header_protocol = match HeaderProtocol::from_u32(header_protocol) {
+                Some(header_protocol) => header_protocol,
+                None => continue,
+            };
+            samples.push((sample_sequence, PacketSample {
+                if_index: source_id & 0x00FF_FFFF,
+                sampling_rate,
+                sample_pool,
+                drops,
+                header_protocol,
+                frame_length,
+                header,
+            }));
+        }
+    }
+    Ok(SflowDatagram { agent_addr, sub_agent_id, sequence, uptime, samples })
+}
+
+#[derive(Debug, Clone)]
+pub struct SflowExportOption {
+    pub collector: SocketAddr,
+    /// Agent address in the datagram header. None to use the address of the route to the collector.
+    pub agent_addr: Option<IpAddr>,
+    pub sub_agent_id: u32,
+}
+
+impl SflowExportOption {
+    pub fn new(collector: SocketAddr) -> Self {
+        SflowExportOption {
+            collector,
+            agent_addr: None,
+            sub_agent_id: 0,
+        }
+    }
+}
+
+/// Start exporting sampled packets to the sFlow collector.
+/// The capture threads pass their samples through the strage.
+pub fn start_sflow_export(netstat_strage: Arc<NetStatStrage>, option: SflowExportOption) -> Result<thread::JoinHandle<()>, String> {
+    let bind_addr: SocketAddr = match option.collector {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+    let socket = UdpSocket::bind(bind_addr).map_err(|e| e.to_string())?;
+    socket.connect(option.collector).map_err(|e| format!("Failed to connect to sFlow collector {}: {}", option.collector, e))?;
+    let agent_addr = match option.agent_addr {
+        Some(agent_addr) => agent_addr,
+        None => socket.local_addr().map_err(|e| e.to_string())?.ip(),
+    };
+    let (tx, rx) = mpsc::sync_channel(SAMPLE_QUEUE_SIZE);
+    netstat_strage.set_packet_sample_sender(Some(tx));
+    thread::Builder::new()
+        .name(String::from("sflow-export"))
+        .spawn(move || {
+            let mut encoder = SflowEncoder::new(agent_addr, option.sub_agent_id);
+            let mut samples: Vec<PacketSample> = Vec::new();
+            let mut last_flush = Instant::now();
+            loop {
+                let disconnected = match rx.recv_timeout(SFLOW_FLUSH_INTERVAL) {
+                    Ok(sample) => {
+                        samples.push(sample);
+                        false
+                    }
+                    Err(RecvTimeoutError::Timeout) => false,
+                    Err(RecvTimeoutError::Disconnected) => true,
+                };
+                if !samples.is_empty() && (disconnected || last_flush.elapsed() >= SFLOW_FLUSH_INTERVAL || samples.len() >= SAMPLE_QUEUE_SIZE) {
+                    for datagram in encoder.encode(&samples) {
+                        if let Err(e) = socket.send(&datagram) {
+                            thread_log!(error, "[sflow] send error: {}", e);
+                        }
+                    }
+                    samples.clear();
+                    last_flush = Instant::now();
+                }
+                if disconnected {
+                    break;
+                }
+            }
+        })
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,521 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+use chrono::{DateTime, Local};
+use rusqlite::{params, params_from_iter, Connection, Transaction};
+use serde::{Serialize, Deserialize};
+use crate::db::service::ServiceDatabase;
+use crate::net::host::HostDisplayInfo;
+use crate::net::service::ServiceDisplayInfo;
+use crate::net::stat::NetStatData;
+use crate::net::traffic::TrafficInfo;
+use crate::process::ProcessDisplayInfo;
+use crate::socket::TransportProtocol;
+
+pub const DEFAULT_HISTORY_DB_NAME: &str = "history.db";
+
+/// Schema version stored in `PRAGMA user_version`.
+pub const HISTORY_SCHEMA_VERSION: i32 = 3;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS snapshots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    start_time INTEGER NOT NULL,
+    end_time INTEGER NOT NULL,
+    if_name TEXT NOT NULL,
+    packet_sent INTEGER NOT NULL,
+    packet_received INTEGER NOT NULL,
+    bytes_sent INTEGER NOT NULL,
+    bytes_received INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_snapshots_time ON snapshots (start_time, end_time);
+CREATE TABLE IF NOT EXISTS host_traffic (
+    snapshot_id INTEGER NOT NULL REFERENCES snapshots (id) ON DELETE CASCADE,
+    ip_addr TEXT NOT NULL,
+    host_name TEXT NOT NULL,
+    country_code TEXT NOT NULL,
+    country_name TEXT NOT NULL DEFAULT '',
+    asn INTEGER NOT NULL,
+    as_name TEXT NOT NULL,
+    packet_sent INTEGER NOT NULL,
+    packet_received INTEGER NOT NULL,
+    bytes_sent INTEGER NOT NULL,
+    bytes_received INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_host_traffic_snapshot ON host_traffic (snapshot_id);
+CREATE TABLE IF NOT EXISTS connection_traffic (
+    snapshot_id INTEGER NOT NULL REFERENCES snapshots (id) ON DELETE CASCADE,
+    interface_name TEXT NOT NULL,
+    protocol TEXT NOT NULL,
+    local_ip_addr TEXT,
+    local_port INTEGER NOT NULL,
+    remote_ip_addr TEXT NOT NULL,
+    remote_port INTEGER NOT NULL,
+    pid INTEGER,
+    process_name TEXT,
+    packet_sent INTEGER NOT NULL,
+    packet_received INTEGER NOT NULL,
+    bytes_sent INTEGER NOT NULL,
+    bytes_received INTEGER NOT NULL,
+    estimated INTEGER NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS idx_connection_traffic_snapshot ON connection_traffic (snapshot_id);
+CREATE TABLE IF NOT EXISTS process_traffic (
+    snapshot_id INTEGER NOT NULL REFERENCES snapshots (id) ON DELETE CASCADE,
+    pid INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    unit TEXT,
+    packet_sent INTEGER NOT NULL,
+    packet_received INTEGER NOT NULL,
+    bytes_sent INTEGER NOT NULL,
+    bytes_received INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_process_traffic_snapshot ON process_traffic (snapshot_id);
+CREATE TABLE IF NOT EXISTS app_protocol_traffic (
+    snapshot_id INTEGER NOT NULL REFERENCES snapshots (id) ON DELETE CASCADE,
+    protocol TEXT NOT NULL,
+    port INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    packet_sent INTEGER NOT NULL,
+    packet_received INTEGER NOT NULL,
+    bytes_sent INTEGER NOT NULL,
+    bytes_received INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_app_protocol_traffic_snapshot ON app_protocol_traffic (snapshot_id);
+";
+
+/// Migrations from the previous schema version. `MIGRATIONS[n]` upgrades version n + 1 to n + 2.
+const MIGRATIONS: [&str; 2] = [
+    // v1 -> v2: country name of remote hosts
+    "ALTER TABLE host_traffic ADD COLUMN country_name TEXT NOT NULL DEFAULT '';",
+    // v2 -> v3: counters estimated from sampled packets
+    "ALTER TABLE connection_traffic ADD COLUMN estimated INTEGER NOT NULL DEFAULT 0;",
+];
+
+/// Embedded SQLite database of traffic snapshots.
+/// Each snapshot holds the traffic observed between `start_time` and `end_time`,
+/// broken down by remote host, connection, process and app protocol.
+pub struct HistoryDb {
+    conn: Connection,
+}
+
+impl HistoryDb {
+    /// Open the database, creating the file and schema if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<HistoryDb, String> {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        Self::init(conn)
+    }
+    pub fn open_in_memory() -> Result<HistoryDb, String> {
+        let conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+        Self::init(conn)
+    }
+    fn init(conn: Connection) -> Result<HistoryDb, String> {
+        let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).map_err(|e| e.to_string())?;
+        if version > HISTORY_SCHEMA_VERSION {
+            return Err(format!("Unsupported history database version: {} (supported: {})", version, HISTORY_SCHEMA_VERSION));
+        }
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON;").map_err(|e| e.to_string())?;
+        if version == 0 {
+            conn.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+        } else {
+            for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+                conn.execute_batch(migration).map_err(|e| e.to_string())?;
+            }
+        }
+        conn.execute_batch(&format!("PRAGMA user_version = {}", HISTORY_SCHEMA_VERSION)).map_err(|e| e.to_string())?;
+        Ok(HistoryDb { conn })
+    }
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+    /// Store the traffic delta of one interval. Returns the snapshot ID.
+    pub fn insert_snapshot(&mut self, data: &NetStatData, start_time: DateTime<Local>, end_time: DateTime<Local>) -> Result<i64, String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        let snapshot_id = insert_snapshot_rows(&tx, data, start_time, end_time).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(snapshot_id)
+    }
+    pub fn snapshot_count(&self) -> Result<usize, String> {
+        let count: i64 = self.conn.query_row("SELECT COUNT(*) FROM snapshots", [], |row| row.get(0)).map_err(|e| e.to_string())?;
+        Ok(count as usize)
+    }
+    /// Recorded connections matching the filter, joined with the remote host and process of the same snapshot.
+    pub fn query_connections(&self, filter: &ReportFilter) -> Result<Vec<HistoryConnection>, String> {
+        let mut sql = String::from(
+            "SELECT c.protocol, c.local_port, c.remote_ip_addr, c.remote_port, c.pid, c.process_name, p.unit, \
+             COALESCE(h.host_name, ''), COALESCE(h.country_code, ''), COALESCE(h.country_name, ''), COALESCE(h.asn, 0), COALESCE(h.as_name, ''), \
+             c.packet_sent, c.packet_received, c.bytes_sent, c.bytes_received, c.estimated \
+             FROM connection_traffic c \
+             JOIN snapshots s ON s.id = c.snapshot_id \
+             LEFT JOIN host_traffic h ON h.snapshot_id = c.snapshot_id AND h.ip_addr = c.remote_ip_addr \
+             LEFT JOIN process_traffic p ON p.snapshot_id = c.snapshot_id AND p.pid = c.pid \
+             WHERE 1 = 1"
+        );
+        let mut args: Vec<rusqlite::types::Value> = Vec::new();
+        // Snapshots overlapping the time range
+        if let Some(start_time) = filter.start_time {
+            sql.push_str(" AND s.end_time > ?");
+            args.push(start_time.timestamp().into());
+        }
+        if let Some(end_time) = filter.end_time {
+            sql.push_str(" AND s.start_time < ?");
+            args.push(end_time.timestamp().into());
+        }
+        if let Some(ip_addr) = filter.ip_addr {
+            sql.push_str(" AND c.remote_ip_addr = ?");
+            args.push(ip_addr.to_string().into());
+        }
+        if let Some(asn) = filter.asn {
+            sql.push_str(" AND h.asn = ?");
+            args.push((asn as i64).into());
+        }
+        if let Some(country_code) = &filter.country_code {
+            sql.push_str(" AND UPPER(h.country_code) = ?");
+            args.push(country_code.to_uppercase().into());
+        }
+        if let Some(process_name) = &filter.process_name {
+            sql.push_str(" AND LOWER(c.process_name) LIKE ?");
+            args.push(format!("%{}%", process_name.to_lowercase()).into());
+        }
+        if let Some(pid) = filter.pid {
+            sql.push_str(" AND c.pid = ?");
+            args.push((pid as i64).into());
+        }
+        if let Some(protocol) = filter.protocol {
+            sql.push_str(" AND c.protocol = ?");
+            args.push(protocol.as_str().to_string().into());
+        }
+        if let Some(port) = filter.port {
+            sql.push_str(" AND (c.remote_port = ? OR c.local_port = ?)");
+            args.push((port as i64).into());
+            args.push((port as i64).into());
+        }
+        let mut stmt = self.conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let rows = stmt.query_map(params_from_iter(args.iter()), |row| {
+            let protocol: String = row.get(0)?;
+            let remote_ip_addr: String = row.get(2)?;
+            Ok(HistoryConnection {
+                protocol: if protocol == "UDP" { TransportProtocol::UDP } else { TransportProtocol::TCP },
+                local_port: row.get(1)?,
+                remote_ip_addr: remote_ip_addr.parse().unwrap_or(IpAddr::from([0, 0, 0, 0])),
+                remote_port: row.get(3)?,
+                pid: row.get(4)?,
+                process_name: row.get(5)?,
+                unit: row.get(6)?,
+                host_name: row.get(7)?,
+                country_code: row.get(8)?,
+                country_name: row.get(9)?,
+                asn: row.get(10)?,
+                as_name: row.get(11)?,
+                traffic: TrafficInfo {
+                    packet_sent: row.get::<_, i64>(12)? as usize,
+                    packet_received: row.get::<_, i64>(13)? as usize,
+                    bytes_sent: row.get::<_, i64>(14)? as usize,
+                    bytes_received: row.get::<_, i64>(15)? as usize,
+                    estimated: row.get(16)?,
+                },
+            })
+        }).map_err(|e| e.to_string())?;
+        rows.collect::<rusqlite::Result<Vec<HistoryConnection>>>().map_err(|e| e.to_string())
+    }
+    /// Aggregate the recorded traffic matching the filter by the given group.
+    pub fn report(&self, group: ReportGroup, filter: &ReportFilter, sort: ReportSort, limit: Option<usize>) -> Result<HistoryReport, String> {
+        let connections = self.query_connections(filter)?;
+        Ok(build_report(&connections, group, sort, limit))
+    }
+}
+
+/// A recorded connection with the remote host and process resolved.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryConnection {
+    pub protocol: TransportProtocol,
+    pub local_port: u16,
+    pub remote_ip_addr: IpAddr,
+    pub remote_port: u16,
+    pub pid: Option<u32>,
+    pub process_name: Option<String>,
+    pub unit: Option<String>,
+    pub host_name: String,
+    pub country_code: String,
+    pub country_name: String,
+    pub asn: u32,
+    pub as_name: String,
+    pub traffic: TrafficInfo,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportGroup {
+    Host,
+    ASN,
+    Country,
+    Process,
+    /// Application protocol (service name).
+    Protocol,
+    /// Transport protocol and remote port.
+    Port,
+}
+
+impl ReportGroup {
+    pub fn from_name(s: &str) -> Option<ReportGroup> {
+        match s.to_lowercase().as_str() {
+            "host" => Some(ReportGroup::Host),
+            "asn" => Some(ReportGroup::ASN),
+            "country" => Some(ReportGroup::Country),
+            "process" => Some(ReportGroup::Process),
+            "protocol" => Some(ReportGroup::Protocol),
+            "port" => Some(ReportGroup::Port),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportSort {
+    Total,
+    Sent,
+    Received,
+    Packets,
+}
+
+impl ReportSort {
+    pub fn from_name(s: &str) -> Option<ReportSort> {
+        match s.to_lowercase().as_str() {
+            "total" => Some(ReportSort::Total),
+            "sent" => Some(ReportSort::Sent),
+            "received" => Some(ReportSort::Received),
+            "packets" => Some(ReportSort::Packets),
+            _ => None,
+        }
+    }
+    pub fn key(&self, traffic: &TrafficInfo) -> usize {
+        match self {
+            ReportSort::Total => traffic.total_bytes(),
+            ReportSort::Sent => traffic.bytes_sent,
+            ReportSort::Received => traffic.bytes_received,
+            ReportSort::Packets => traffic.total_packet(),
+        }
+    }
+}
+
+/// Filters of a report. Unset fields match everything.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ReportFilter {
+    pub start_time: Option<DateTime<Local>>,
+    pub end_time: Option<DateTime<Local>>,
+    /// Remote IP address.
+    pub ip_addr: Option<IpAddr>,
+    pub asn: Option<u32>,
+    pub country_code: Option<String>,
+    /// Case-insensitive process name substring.
+    pub process_name: Option<String>,
+    pub pid: Option<u32>,
+    pub protocol: Option<TransportProtocol>,
+    /// Local or remote port.
+    pub port: Option<u16>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AsnDisplayInfo {
+    pub asn: u32,
+    pub as_name: String,
+    pub traffic: TrafficInfo,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CountryDisplayInfo {
+    pub country_code: String,
+    pub country_name: String,
+    pub traffic: TrafficInfo,
+}
+
+/// Aggregated traffic of a report, typed by group.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum HistoryReport {
+    Hosts(Vec<HostDisplayInfo>),
+    ASNs(Vec<AsnDisplayInfo>),
+    Countries(Vec<CountryDisplayInfo>),
+    Processes(Vec<ProcessDisplayInfo>),
+    Services(Vec<ServiceDisplayInfo>),
+}
+
+fn add_traffic<K: std::hash::Hash + Eq, V>(map: &mut HashMap<K, V>, key: K, new_value: impl FnOnce() -> V, traffic: &TrafficInfo, get: impl FnOnce(&mut V) -> &mut TrafficInfo) {
+    let value = map.entry(key).or_insert_with(new_value);
+    get(value).add_traffic(traffic);
+}
+
+fn sort_and_limit<T>(mut items: Vec<T>, sort: ReportSort, limit: Option<usize>, traffic: impl Fn(&T) -> &TrafficInfo) -> Vec<T> {
+    items.sort_by_key(|item| std::cmp::Reverse(sort.key(traffic(item))));
+    if let Some(limit) = limit {
+        items.truncate(limit);
+    }
+    items
+}
+
+/// Aggregate recorded connections by the given group.
+pub fn build_report(connections: &[HistoryConnection], group: ReportGroup, sort: ReportSort, limit: Option<usize>) -> HistoryReport {
+    match group {
+        ReportGroup::Host => {
+            let mut map: HashMap<IpAddr, HostDisplayInfo> = HashMap::new();
+            for conn in connections {
+                add_traffic(&mut map, conn.remote_ip_addr, || HostDisplayInfo {
+                    ip_addr: conn.remote_ip_addr,
+                    host_name: conn.host_name.clone(),
+                    mac_addr: String::new(),
+                    mac_vendor: String::new(),
+                    mac_randomized: false,
+                    country_code: conn.country_code.clone(),
+                    country_name: conn.country_name.clone(),
+                    asn: conn.asn,
+                    as_name: conn.as_name.clone(),
+                    traffic: TrafficInfo::new(),
+                }, &conn.traffic, |host| &mut host.traffic);
+            }
+            HistoryReport::Hosts(sort_and_limit(map.into_values().collect(), sort, limit, |host| &host.traffic))
+        }
+        ReportGroup::ASN => {
+            let mut map: HashMap<u32, AsnDisplayInfo> = HashMap::new();
+            for conn in connections {
+                add_traffic(&mut map, conn.asn, || AsnDisplayInfo {
+                    asn: conn.asn,
+                    as_name: conn.as_name.clone(),
+                    traffic: TrafficInfo::new(),
+                }, &conn.traffic, |asn| &mut asn.traffic);
+            }
+            HistoryReport::ASNs(sort_and_limit(map.into_values().collect(), sort, limit, |asn| &asn.traffic))
+        }
+        ReportGroup::Country => {
+            let mut map: HashMap<String, CountryDisplayInfo> = HashMap::new();
+            for conn in connections {
+                add_traffic(&mut map, conn.country_code.clone(), || CountryDisplayInfo {
+                    country_code: conn.country_code.clone(),
+                    country_name: conn.country_name.clone(),
+                    traffic: TrafficInfo::new(),
+                }, &conn.traffic, |country| &mut country.traffic);
+            }
+            HistoryReport::Countries(sort_and_limit(map.into_values().collect(), sort, limit, |country| &country.traffic))
+        }
+        ReportGroup::Process => {
+            let mut map: HashMap<(u32, String), ProcessDisplayInfo> = HashMap::new();
+            for conn in connections {
+                let (pid, name) = match (conn.pid, &conn.process_name) {
+                    (Some(pid), Some(name)) => (pid, name.clone()),
+                    _ => continue,
+                };
+                add_traffic(&mut map, (pid, name.clone()), || ProcessDisplayInfo {
+                    pid,
+                    name,
+                    unit: conn.unit.clone(),
+                    traffic: TrafficInfo::new(),
+                }, &conn.traffic, |process| &mut process.traffic);
+            }
+            HistoryReport::Processes(sort_and_limit(map.into_values().collect(), sort, limit, |process| &process.traffic))
+        }
+        ReportGroup::Protocol | ReportGroup::Port => {
+            let service_db: ServiceDatabase = ServiceDatabase::new();
+            let mut map: HashMap<(String, u16), ServiceDisplayInfo> = HashMap::new();
+            for conn in connections {
+                let name = service_db.tcp_map.get(&conn.remote_port).cloned().unwrap_or(String::from("unknown"));
+                let protocol = conn.protocol.as_str().to_string();
+                // Services are keyed by name alone when grouping by protocol.
+                let key = match group {
+                    ReportGroup::Protocol => (name.clone(), 0),
+                    _ => (protocol.clone(), conn.remote_port),
+                };
+                add_traffic(&mut map, key, || ServiceDisplayInfo {
+                    port: if group == ReportGroup::Port { conn.remote_port } else { 0 },
+                    protocol,
+                    name,
+                    traffic: TrafficInfo::new(),
+                }, &conn.traffic, |service| &mut service.traffic);
+            }
+            HistoryReport::Services(sort_and_limit(map.into_values().collect(), sort, limit, |service| &service.traffic))
+        }
+    }
+}
+
+fn insert_snapshot_rows(tx: &Transaction, data: &NetStatData, start_time: DateTime<Local>, end_time: DateTime<Local>) -> rusqlite::Result<i64> {
+    tx.execute(
+        "INSERT INTO snapshots (start_time, end_time, if_name, packet_sent, packet_received, bytes_sent, bytes_received) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            start_time.timestamp(),
+            end_time.timestamp(),
+            data.if_name,
+            data.traffic.packet_sent as i64,
+            data.traffic.packet_received as i64,
+            data.traffic.bytes_sent as i64,
+            data.traffic.bytes_received as i64,
+        ],
+    )?;
+    let snapshot_id = tx.last_insert_rowid();
+    {
+        let mut stmt = tx.prepare("INSERT INTO host_traffic (snapshot_id, ip_addr, host_name, country_code, country_name, asn, as_name, packet_sent, packet_received, bytes_sent, bytes_received) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)")?;
+        for host in data.get_remote_hosts(None) {
+            stmt.execute(params![
+                snapshot_id,
+                host.ip_addr.to_string(),
+                host.host_name,
+                host.country_code,
+                host.country_name,
+                host.asn,
+                host.as_name,
+                host.traffic.packet_sent as i64,
+                host.traffic.packet_received as i64,
+                host.traffic.bytes_sent as i64,
+                host.traffic.bytes_received as i64,
+            ])?;
+        }
+    }
+    {
+        let mut stmt = tx.prepare("INSERT INTO connection_traffic (snapshot_id, interface_name, protocol, local_ip_addr, local_port, remote_ip_addr, remote_port, pid, process_name, packet_sent, packet_received, bytes_sent, bytes_received, estimated) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)")?;
+        for conn in data.get_connections(None) {
+            stmt.execute(params![
+                snapshot_id,
+                conn.interface_name,
+                conn.protocol.as_str(),
+                conn.local_ip_addr.map(|ip_addr| ip_addr.to_string()),
+                conn.local_port,
+                conn.remote_ip_addr.map(|ip_addr| ip_addr.to_string()).unwrap_or_default(),
+                conn.remote_port.unwrap_or(0),
+                conn.process.as_ref().map(|p| p.pid),
+                conn.process.as_ref().map(|p| p.name.clone()),
+                conn.traffic.packet_sent as i64,
+                conn.traffic.packet_received as i64,
+                conn.traffic.bytes_sent as i64,
+                conn.traffic.bytes_received as i64,
+                conn.traffic.estimated,
+            ])?;
+        }
+    }
+    {
+        let mut stmt = tx.prepare("INSERT INTO process_traffic (snapshot_id, pid, name, unit, packet_sent, packet_received, bytes_sent, bytes_received) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")?;
+        for process in data.get_processes(None) {
+            stmt.execute(params![
+                snapshot_id,
+                process.pid,
+                process.name,
+                process.unit,
+                process.traffic.packet_sent as i64,
+                process.traffic.packet_received as i64,
+                process.traffic.bytes_sent as i64,
+                process.traffic.bytes_received as i64,
+            ])?;
+        }
+    }
+    {
+        let mut stmt = tx.prepare("INSERT INTO app_protocol_traffic (snapshot_id, protocol, port, name, packet_sent, packet_received, bytes_sent, bytes_received) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")?;
+        for service in data.get_app_protocols(None) {
+            stmt.execute(params![
+                snapshot_id,
+                service.protocol,
+                service.port,
+                service.name,
+                service.traffic.packet_sent as i64,
+                service.traffic.packet_received as i64,
+                service.traffic.bytes_sent as i64,
+                service.traffic.bytes_received as i64,
+            ])?;
+        }
+    }
+    Ok(snapshot_id)
+}
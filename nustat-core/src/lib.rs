@@ -2,10 +2,23 @@ pub mod sys;
 pub mod net;
 pub mod socket;
 pub mod process;
+pub mod container;
+pub mod systemd;
 pub mod pcap;
 pub mod dns;
 pub mod ipinfo;
 pub mod db;
+#[cfg(feature = "history")]
+pub mod history;
+pub mod state;
+pub mod metrics;
+pub mod flow;
+pub mod stream;
+pub mod api;
+pub mod source;
+#[cfg(feature = "fleet")]
+pub mod fleet;
+pub mod alert;
 pub mod notification;
 pub mod github;
 pub mod config;
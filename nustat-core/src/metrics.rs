@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use crate::net::stat::{NetStatData, NetStatStrage};
+use crate::net::traffic::TrafficInfo;
+use crate::pcap::CaptureStats;
+use crate::db::service::ServiceDatabase;
+use crate::socket::ProtocolPort;
+use crate::thread_log;
+
+/// Default listen address of the metrics server. Loopback only.
+pub const DEFAULT_METRICS_LISTEN_ADDR: &str = "127.0.0.1:9595";
+
+/// Default maximum number of series per labeled family. The rest is aggregated into `other`.
+pub const DEFAULT_METRICS_SERIES_LIMIT: usize = 20;
+
+/// Label value of the series aggregating everything beyond the limit.
+pub const OTHER_LABEL: &str = "other";
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum number of remote hosts kept in `TrafficTotals`. Traffic of further hosts is only counted in `other_hosts`.
+const MAX_TOTAL_HOSTS: usize = 10000;
+
+/// Cumulative traffic of a remote host.
+#[derive(Debug, Clone)]
+pub struct HostTotals {
+    pub asn: u32,
+    pub as_name: String,
+    pub country_code: String,
+    pub traffic: TrafficInfo,
+}
+
+/// Cumulative traffic aggregates for the metrics exporter.
+/// Connections are folded into the aggregates, so memory does not grow with every 5-tuple seen.
+#[derive(Debug, Clone)]
+pub struct TrafficTotals {
+    pub traffic: TrafficInfo,
+    pub interfaces: HashMap<String, TrafficInfo>,
+    /// Up to `MAX_TOTAL_HOSTS` remote hosts
+    pub remote_hosts: HashMap<IpAddr, HostTotals>,
+    /// Traffic of the remote hosts beyond `MAX_TOTAL_HOSTS`
+    pub other_hosts: TrafficInfo,
+    /// (ASN, AS name) -> Traffic
+    pub asns: HashMap<(u32, String), TrafficInfo>,
+    /// Country code -> Traffic
+    pub countries: HashMap<String, TrafficInfo>,
+    /// Process name -> Traffic. Grouped by name so that restarts do not create new series.
+    pub processes: HashMap<String, TrafficInfo>,
+    /// Protocol and remote port -> Traffic. Service names are resolved when rendering.
+    pub app_protocols: HashMap<ProtocolPort, TrafficInfo>,
+}
+
+impl TrafficTotals {
+    pub fn new() -> Self {
+        TrafficTotals {
+            traffic: TrafficInfo::new(),
+            interfaces: HashMap::new(),
+            remote_hosts: HashMap::new(),
+            other_hosts: TrafficInfo::new(),
+            asns: HashMap::new(),
+            countries: HashMap::new(),
+            processes: HashMap::new(),
+            app_protocols: HashMap::new(),
+        }
+    }
+    /// Add the traffic of an interval.
+    pub fn add(&mut self, data: &NetStatData) {
+        self.traffic.add_traffic(&data.traffic);
+        for (conn, traffic) in &data.connection_map {
+            self.interfaces.entry(conn.interface_name.clone()).or_insert_with(TrafficInfo::new).add_traffic(traffic);
+            self.app_protocols.entry(ProtocolPort { protocol: conn.protocol, port: conn.remote_port }).or_insert_with(TrafficInfo::new).add_traffic(traffic);
+        }
+        for host in data.get_remote_hosts(None) {
+            self.asns.entry((host.asn, host.as_name.clone())).or_insert_with(TrafficInfo::new).add_traffic(&host.traffic);
+            self.countries.entry(host.country_code.clone()).or_insert_with(TrafficInfo::new).add_traffic(&host.traffic);
+            if self.remote_hosts.len() >= MAX_TOTAL_HOSTS && !self.remote_hosts.contains_key(&host.ip_addr) {
+                self.other_hosts.add_traffic(&host.traffic);
+                continue;
+            }
+            let entry = self.remote_hosts.entry(host.ip_addr).or_insert_with(|| HostTotals {
+                asn: host.asn,
+                as_name: host.as_name.clone(),
+                country_code: host.country_code.clone(),
+                traffic: TrafficInfo::new(),
+            });
+            entry.traffic.add_traffic(&host.traffic);
+        }
+        for process in data.get_processes(None) {
+            self.processes.entry(process.name).or_insert_with(TrafficInfo::new).add_traffic(&process.traffic);
+        }
+    }
+}
+
+impl Default for TrafficTotals {
+    fn default() -> Self {
+        TrafficTotals::new()
+    }
+}
+
+/// Escape a label value. (backslash, double quote and line feed)
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let labels: Vec<String> = labels.iter().map(|(name, value)| format!("{}=\"{}\"", name, escape_label(value))).collect();
+    format!("{{{}}}", labels.join(","))
+}
+
+/// Text exposition of a set of metric families.
+struct MetricWriter {
+    out: String,
+}
+
+impl MetricWriter {
+    fn new() -> Self {
+        MetricWriter { out: String::new() }
+    }
+    fn family(&mut self, name: &str, metric_type: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {} {}", name, help);
+        let _ = writeln!(self.out, "# TYPE {} {}", name, metric_type);
+    }
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: u64) {
+        let _ = writeln!(self.out, "{}{} {}", name, format_labels(labels), value);
+    }
+    /// Bytes or packets by direction. `labels` are prepended to the direction label.
+    fn traffic(&mut self, name: &str, labels: &[(&str, &str)], sent: usize, received: usize) {
+        let mut sent_labels = labels.to_vec();
+        sent_labels.push(("direction", "sent"));
+        self.sample(name, &sent_labels, sent as u64);
+        let mut received_labels = labels.to_vec();
+        received_labels.push(("direction", "received"));
+        self.sample(name, &received_labels, received as u64);
+    }
+}
+
+/// Keep the top `limit` entries by total bytes and aggregate the rest into one entry.
+fn limit_series<K: Clone>(mut entries: Vec<(K, TrafficInfo)>, limit: usize, other: K) -> Vec<(K, TrafficInfo)> {
+    entries.sort_by_key(|(_, traffic)| std::cmp::Reverse(traffic.total_bytes()));
+    if entries.len() <= limit {
+        return entries;
+    }
+    let rest = entries.split_off(limit);
+    let mut other_traffic = TrafficInfo::new();
+    for (_, traffic) in rest.iter() {
+        other_traffic.add_traffic(traffic);
+    }
+    entries.push((other, other_traffic));
+    entries
+}
+
+/// Write bytes and packets families for labeled entries.
+fn write_labeled_traffic(writer: &mut MetricWriter, prefix: &str, help: &str, label_names: &[&str], entries: &[(Vec<String>, TrafficInfo)]) {
+    let bytes_name = format!("{}_bytes_total", prefix);
+    writer.family(&bytes_name, "counter", &format!("Bytes by {}.", help));
+    for (values, traffic) in entries {
+        let labels: Vec<(&str, &str)> = label_names.iter().copied().zip(values.iter().map(|v| v.as_str())).collect();
+        writer.traffic(&bytes_name, &labels, traffic.bytes_sent, traffic.bytes_received);
+    }
+    let packets_name = format!("{}_packets_total", prefix);
+    writer.family(&packets_name, "counter", &format!("Packets by {}.", help));
+    for (values, traffic) in entries {
+        let labels: Vec<(&str, &str)> = label_names.iter().copied().zip(values.iter().map(|v| v.as_str())).collect();
+        writer.traffic(&packets_name, &labels, traffic.packet_sent, traffic.packet_received);
+    }
+}
+
+/// Render the metrics in the Prometheus text format.
+/// Labeled families are limited to `series_limit` series plus one `other` series.
+pub fn render_metrics(totals: &TrafficTotals, capture_stats: &HashMap<String, CaptureStats>, series_limit: usize) -> String {
+    let mut writer = MetricWriter::new();
+    let other = || vec![OTHER_LABEL.to_string()];
+
+    writer.family("nustat_bytes_total", "counter", "Total bytes captured.");
+    writer.traffic("nustat_bytes_total", &[], totals.traffic.bytes_sent, totals.traffic.bytes_received);
+    writer.family("nustat_packets_total", "counter", "Total packets captured.");
+    writer.traffic("nustat_packets_total", &[], totals.traffic.packet_sent, totals.traffic.packet_received);
+
+    // Interfaces are few, so they are not limited.
+    let mut interfaces: Vec<(Vec<String>, TrafficInfo)> = totals.interfaces.iter().map(|(name, traffic)| (vec![name.clone()], traffic.clone())).collect();
+    interfaces.sort_by(|a, b| a.0.cmp(&b.0));
+    write_labeled_traffic(&mut writer, "nustat_interface", "interface", &["interface"], &interfaces);
+
+    let remote_hosts: Vec<(Vec<String>, TrafficInfo)> = totals.remote_hosts.iter().map(|(ip_addr, host)| (vec![ip_addr.to_string()], host.traffic.clone())).collect();
+    let mut remote_hosts = limit_series(remote_hosts, series_limit, other());
+    if totals.other_hosts.total_packet() > 0 {
+        match remote_hosts.iter_mut().find(|(values, _)| values[0] == OTHER_LABEL) {
+            Some((_, traffic)) => traffic.add_traffic(&totals.other_hosts),
+            None => remote_hosts.push((other(), totals.other_hosts.clone())),
+        }
+    }
+    write_labeled_traffic(&mut writer, "nustat_remote_host", "remote host", &["remote_ip"], &remote_hosts);
+
+    let asns: Vec<(Vec<String>, TrafficInfo)> = totals.asns.iter().map(|((asn, as_name), traffic)| (vec![asn.to_string(), as_name.clone()], traffic.clone())).collect();
+    write_labeled_traffic(&mut writer, "nustat_asn", "autonomous system", &["asn", "as_name"], &limit_series(asns, series_limit, vec![OTHER_LABEL.to_string(), OTHER_LABEL.to_string()]));
+
+    let countries: Vec<(Vec<String>, TrafficInfo)> = totals.countries.iter().map(|(country_code, traffic)| (vec![country_code.clone()], traffic.clone())).collect();
+    write_labeled_traffic(&mut writer, "nustat_country", "country", &["country_code"], &limit_series(countries, series_limit, other()));
+
+    let processes: Vec<(Vec<String>, TrafficInfo)> = totals.processes.iter().map(|(name, traffic)| (vec![name.clone()], traffic.clone())).collect();
+    write_labeled_traffic(&mut writer, "nustat_process", "process", &["process"], &limit_series(processes, series_limit, other()));
+
+    let service_db = ServiceDatabase::new();
+    let services: Vec<(Vec<String>, TrafficInfo)> = totals.app_protocols.iter().map(|(protocol_port, traffic)| {
+        let name = service_db.tcp_map.get(&protocol_port.port).cloned().unwrap_or_else(|| String::from("unknown"));
+        (vec![protocol_port.protocol.as_str().to_string(), protocol_port.port.to_string(), name], traffic.clone())
+    }).collect();
+    write_labeled_traffic(&mut writer, "nustat_app_protocol", "application protocol", &["protocol", "port", "service"], &limit_series(services, series_limit, vec![OTHER_LABEL.to_string(); 3]));
+
+    let mut capture_stats: Vec<(&String, &CaptureStats)> = capture_stats.iter().collect();
+    capture_stats.sort_by(|a, b| a.0.cmp(b.0));
+    writer.family("nustat_capture_packets_total", "counter", "Frames read from the capture channel.");
+    for (if_name, stats) in capture_stats.iter() {
+        writer.sample("nustat_capture_packets_total", &[("interface", if_name)], stats.packets);
+    }
+    writer.family("nustat_capture_errors_total", "counter", "Receive errors of the capture channel.");
+    for (if_name, stats) in capture_stats.iter() {
+        writer.sample("nustat_capture_errors_total", &[("interface", if_name)], stats.errors);
+    }
+    writer.family("nustat_capture_filtered_total", "counter", "Frames discarded by the capture filter.");
+    for (if_name, stats) in capture_stats.iter() {
+        writer.sample("nustat_capture_filtered_total", &[("interface", if_name)], stats.filtered);
+    }
+    writer.out
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn handle_connection(mut stream: TcpStream, netstat_strage: &NetStatStrage, series_limit: usize) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the headers
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    match (method, path.split('?').next().unwrap_or_default()) {
+        ("GET", "/metrics") => {
+            let body = render_metrics(&netstat_strage.get_traffic_totals(), &netstat_strage.get_capture_stats(), series_limit);
+            write_response(&mut stream, "200 OK", CONTENT_TYPE, &body)
+        }
+        ("GET", _) => write_response(&mut stream, "404 Not Found", "text/plain", "Not Found\n"),
+        _ => write_response(&mut stream, "405 Method Not Allowed", "text/plain", "Method Not Allowed\n"),
+    }
+}
+
+/// Start the metrics server. Enables cumulative traffic totals on the strage so counters stay monotonic
+/// regardless of other consumers draining it.
+pub fn start_metrics_server(netstat_strage: Arc<NetStatStrage>, listen_addr: SocketAddr, series_limit: usize) -> Result<thread::JoinHandle<()>, String> {
+    let listener = TcpListener::bind(listen_addr).map_err(|e| format!("Failed to bind metrics server to {}: {}", listen_addr, e))?;
+    if !listen_addr.ip().is_loopback() {
+        thread_log!(warn, "Metrics server is listening on a non-loopback address: {}", listen_addr);
+    }
+    netstat_strage.enable_traffic_totals();
+    thread::Builder::new()
+        .name(String::from("metrics-server"))
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(e) = handle_connection(stream, &netstat_strage, series_limit) {
+                            thread_log!(error, "[metrics] connection error: {}", e);
+                        }
+                    }
+                    Err(e) => {
+                        thread_log!(error, "[metrics] accept error: {}", e);
+                    }
+                }
+            }
+        })
+        .map_err(|e| e.to_string())
+}
@@ -9,6 +9,10 @@ use super::traffic::TrafficInfo;
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RemoteHostInfo {
     pub mac_addr: String,
+    /// MAC address vendor (LAN peers and gateway only)
+    pub mac_vendor: String,
+    /// Locally administered (randomized) MAC address
+    pub mac_randomized: bool,
     pub ip_addr: IpAddr,
     pub hostname: String,
     pub country_code: String,
@@ -24,6 +28,8 @@ impl RemoteHostInfo {
     pub fn new(mac_addr: String, ip_addr: IpAddr) -> Self {
         RemoteHostInfo {
             mac_addr: mac_addr,
+            mac_vendor: String::new(),
+            mac_randomized: false,
             ip_addr: ip_addr,
             hostname: String::new(),
             country_code: String::new(),
@@ -42,6 +48,12 @@ impl RemoteHostInfo {
         if self.hostname.is_empty() {
             self.hostname = other.hostname.clone();
         }
+        if self.mac_vendor.is_empty() {
+            self.mac_vendor = other.mac_vendor.clone();
+        }
+        if !self.mac_randomized {
+            self.mac_randomized = other.mac_randomized;
+        }
         if self.country_code.is_empty() {
             self.country_code = other.country_code.clone();
         }
@@ -62,6 +74,9 @@ impl RemoteHostInfo {
 pub struct HostDisplayInfo {
     pub ip_addr: IpAddr,
     pub host_name: String,
+    pub mac_addr: String,
+    pub mac_vendor: String,
+    pub mac_randomized: bool,
     pub country_code: String,
     pub country_name: String,
     pub asn: u32,
@@ -7,3 +7,5 @@ pub mod packet;
 pub mod ip;
 pub mod service;
 pub mod http;
+pub mod neighbor;
+pub mod netns;
@@ -0,0 +1,435 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use default_net::mac::MacAddr;
+use serde::{Serialize, Deserialize};
+use xenet::packet::frame::Frame;
+use crate::sys;
+
+pub const DHCP_SERVER_PORT: u16 = 67;
+pub const DHCP_CLIENT_PORT: u16 = 68;
+pub const NBNS_PORT: u16 = 137;
+pub const MDNS_PORT: u16 = 5353;
+pub const LLMNR_PORT: u16 = 5355;
+
+const DHCP_MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+const DHCP_OPTION_OFFSET: usize = 240;
+const DHCP_OPT_PAD: u8 = 0;
+const DHCP_OPT_HOST_NAME: u8 = 12;
+const DHCP_OPT_REQUESTED_IP: u8 = 50;
+const DHCP_OPT_MESSAGE_TYPE: u8 = 53;
+const DHCP_OPT_VENDOR_CLASS: u8 = 60;
+const DHCP_OPT_END: u8 = 255;
+const DHCP_MSG_ACK: u8 = 5;
+
+const DNS_HEADER_LEN: usize = 12;
+const DNS_TYPE_A: u16 = 1;
+const DNS_TYPE_PTR: u16 = 12;
+const DNS_TYPE_AAAA: u16 = 28;
+const DNS_TYPE_NB: u16 = 32;
+const NETBIOS_ENCODED_NAME_LEN: usize = 32;
+
+/// Protocol a local neighbor was discovered by.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Hash, Eq, Clone, PartialOrd, Ord, Copy)]
+pub enum DiscoveryProtocol {
+    DHCP,
+    MDNS,
+    LLMNR,
+    NBNS,
+}
+
+impl DiscoveryProtocol {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DiscoveryProtocol::DHCP => "DHCP",
+            DiscoveryProtocol::MDNS => "mDNS",
+            DiscoveryProtocol::LLMNR => "LLMNR",
+            DiscoveryProtocol::NBNS => "NetBIOS",
+        }
+    }
+}
+
+/// Local neighbor learned passively from name service and DHCP traffic.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NeighborInfo {
+    pub mac_addr: String,
+    pub ip_addr: IpAddr,
+    pub hostname: String,
+    /// DHCP vendor class identifier (option 60)
+    pub vendor_class: String,
+    /// DNS-SD service types announced over mDNS
+    pub services: Vec<String>,
+    pub protocols: Vec<DiscoveryProtocol>,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+impl NeighborInfo {
+    pub fn new(mac_addr: String, ip_addr: IpAddr, protocol: DiscoveryProtocol) -> Self {
+        NeighborInfo {
+            mac_addr,
+            ip_addr,
+            hostname: String::new(),
+            vendor_class: String::new(),
+            services: Vec::new(),
+            protocols: vec![protocol],
+            first_seen: sys::get_sysdate(),
+            last_seen: sys::get_sysdate(),
+        }
+    }
+    pub fn merge(&mut self, other: &NeighborInfo) {
+        if !other.hostname.is_empty() {
+            self.hostname = other.hostname.clone();
+        }
+        if !other.vendor_class.is_empty() {
+            self.vendor_class = other.vendor_class.clone();
+        }
+        if self.mac_addr.is_empty() || self.mac_addr == MacAddr::zero().address() {
+            self.mac_addr = other.mac_addr.clone();
+        }
+        for service in &other.services {
+            if !self.services.contains(service) {
+                self.services.push(service.clone());
+            }
+        }
+        for protocol in &other.protocols {
+            if !self.protocols.contains(protocol) {
+                self.protocols.push(*protocol);
+            }
+        }
+        self.last_seen = other.last_seen.clone();
+    }
+}
+
+/// Parse DHCP, mDNS, LLMNR and NetBIOS name service packets in the frame.
+/// Returns the neighbors announced by the packet. (empty if not applicable)
+pub fn parse_frame(frame: &Frame) -> Vec<NeighborInfo> {
+    let udp = match &frame.transport {
+        Some(transport) => match &transport.udp {
+            Some(udp) => udp,
+            None => return vec![],
+        },
+        None => return vec![],
+    };
+    let src_ip: IpAddr = match &frame.ip {
+        Some(ip) => {
+            if let Some(ipv4) = &ip.ipv4 {
+                IpAddr::V4(ipv4.source)
+            } else if let Some(ipv6) = &ip.ipv6 {
+                IpAddr::V6(ipv6.source)
+            } else {
+                return vec![];
+            }
+        }
+        None => return vec![],
+    };
+    let src_mac: String = match &frame.datalink {
+        Some(datalink) => match &datalink.ethernet {
+            Some(ethernet) => ethernet.source.address(),
+            None => MacAddr::zero().address(),
+        },
+        None => MacAddr::zero().address(),
+    };
+    if udp.source == DHCP_CLIENT_PORT || udp.source == DHCP_SERVER_PORT {
+        match parse_dhcp(&frame.payload, src_ip) {
+            Some(neighbor) => vec![neighbor],
+            None => vec![],
+        }
+    } else if udp.source == MDNS_PORT || udp.destination == MDNS_PORT {
+        parse_name_service(&frame.payload, src_ip, src_mac, DiscoveryProtocol::MDNS)
+    } else if udp.source == LLMNR_PORT {
+        parse_name_service(&frame.payload, src_ip, src_mac, DiscoveryProtocol::LLMNR)
+    } else if udp.source == NBNS_PORT || udp.destination == NBNS_PORT {
+        parse_name_service(&frame.payload, src_ip, src_mac, DiscoveryProtocol::NBNS)
+    } else {
+        vec![]
+    }
+}
+
+/// Parse a DHCP (BOOTP) message.
+/// The client MAC address is taken from chaddr, the address from ciaddr, yiaddr (ACK) or option 50.
+pub fn parse_dhcp(payload: &[u8], src_ip: IpAddr) -> Option<NeighborInfo> {
+    if payload.len() < DHCP_OPTION_OFFSET || payload[236..240] != DHCP_MAGIC_COOKIE {
+        return None;
+    }
+    // Ethernet hardware address only
+    if payload[1] != 1 || payload[2] != 6 {
+        return None;
+    }
+    let chaddr = &payload[28..34];
+    let mac_addr = MacAddr::new(chaddr[0], chaddr[1], chaddr[2], chaddr[3], chaddr[4], chaddr[5]);
+    let ciaddr = Ipv4Addr::new(payload[12], payload[13], payload[14], payload[15]);
+    let yiaddr = Ipv4Addr::new(payload[16], payload[17], payload[18], payload[19]);
+    let mut message_type: u8 = 0;
+    let mut hostname = String::new();
+    let mut vendor_class = String::new();
+    let mut requested_ip: Option<Ipv4Addr> = None;
+    let mut offset = DHCP_OPTION_OFFSET;
+    while offset < payload.len() {
+        let code = payload[offset];
+        if code == DHCP_OPT_END {
+            break;
+        }
+        if code == DHCP_OPT_PAD {
+            offset += 1;
+            continue;
+        }
+        if offset + 1 >= payload.len() {
+            break;
+        }
+        let len = payload[offset + 1] as usize;
+        let start = offset + 2;
+        let end = start + len;
+        if end > payload.len() {
+            break;
+        }
+        let value = &payload[start..end];
+        match code {
+            DHCP_OPT_MESSAGE_TYPE if len == 1 => {
+                message_type = value[0];
+            }
+            DHCP_OPT_HOST_NAME => {
+                hostname = String::from_utf8_lossy(value).trim_end_matches('\0').to_string();
+            }
+            DHCP_OPT_VENDOR_CLASS => {
+                vendor_class = String::from_utf8_lossy(value).trim_end_matches('\0').to_string();
+            }
+            DHCP_OPT_REQUESTED_IP if len == 4 => {
+                requested_ip = Some(Ipv4Addr::new(value[0], value[1], value[2], value[3]));
+            }
+            _ => {}
+        }
+        offset = end;
+    }
+    let ip_addr: Ipv4Addr = if message_type == DHCP_MSG_ACK && !yiaddr.is_unspecified() {
+        yiaddr
+    } else if !ciaddr.is_unspecified() {
+        ciaddr
+    } else if let Some(requested_ip) = requested_ip {
+        requested_ip
+    } else {
+        match src_ip {
+            IpAddr::V4(ipv4) if !ipv4.is_unspecified() && payload[0] == 1 => ipv4,
+            _ => return None,
+        }
+    };
+    if hostname.is_empty() && vendor_class.is_empty() {
+        return None;
+    }
+    let mut neighbor = NeighborInfo::new(mac_addr.address(), IpAddr::V4(ip_addr), DiscoveryProtocol::DHCP);
+    neighbor.hostname = hostname;
+    neighbor.vendor_class = vendor_class;
+    Some(neighbor)
+}
+
+/// Parse an mDNS, LLMNR or NetBIOS name service message.
+/// All three share the DNS wire format. Only answers and additional records carry addresses.
+pub fn parse_name_service(payload: &[u8], src_ip: IpAddr, src_mac: String, protocol: DiscoveryProtocol) -> Vec<NeighborInfo> {
+    let mut neighbors: Vec<NeighborInfo> = vec![];
+    // mDNS and LLMNR queries may carry known answers of other hosts. Only trust responses.
+    // NetBIOS name registrations are requests, so they are accepted as well.
+    if payload.len() < DNS_HEADER_LEN {
+        return neighbors;
+    }
+    let is_response = payload[2] & 0x80 != 0;
+    if !is_response && protocol != DiscoveryProtocol::NBNS {
+        return neighbors;
+    }
+    let records = match parse_dns_records(payload) {
+        Some(records) => records,
+        None => return neighbors,
+    };
+    let mut services: Vec<String> = vec![];
+    for record in records {
+        match record.rdata {
+            RecordData::A(ipv4) => {
+                if let Some(name) = normalize_hostname(&record.name, protocol) {
+                    push_neighbor(&mut neighbors, src_ip, &src_mac, IpAddr::V4(ipv4), name, protocol);
+                }
+            }
+            RecordData::Aaaa(ipv6) => {
+                if let Some(name) = normalize_hostname(&record.name, protocol) {
+                    push_neighbor(&mut neighbors, src_ip, &src_mac, IpAddr::V6(ipv6), name, protocol);
+                }
+            }
+            RecordData::NetBios(ipv4) => {
+                if let Some(name) = normalize_hostname(&record.name, protocol) {
+                    push_neighbor(&mut neighbors, src_ip, &src_mac, IpAddr::V4(ipv4), name, protocol);
+                }
+            }
+            RecordData::Ptr(target) => {
+                // DNS-SD service instance: <Instance>._<service>._<proto>.local
+                if protocol == DiscoveryProtocol::MDNS && record.name.starts_with('_') && !record.name.starts_with("_services.") {
+                    let service = record.name.trim_end_matches('.').trim_end_matches(".local").to_string();
+                    if !services.contains(&service) && !target.is_empty() {
+                        services.push(service);
+                    }
+                }
+            }
+            RecordData::Other => {}
+        }
+    }
+    if !services.is_empty() {
+        match neighbors.iter_mut().find(|n| n.ip_addr == src_ip) {
+            Some(neighbor) => {
+                neighbor.services = services;
+            }
+            None => {
+                let mut neighbor = NeighborInfo::new(src_mac, src_ip, protocol);
+                neighbor.services = services;
+                neighbors.push(neighbor);
+            }
+        }
+    }
+    neighbors
+}
+
+fn push_neighbor(neighbors: &mut Vec<NeighborInfo>, src_ip: IpAddr, src_mac: &str, ip_addr: IpAddr, hostname: String, protocol: DiscoveryProtocol) {
+    if ip_addr.is_unspecified() || ip_addr.is_loopback() {
+        return;
+    }
+    if neighbors.iter().any(|n| n.ip_addr == ip_addr) {
+        return;
+    }
+    // The MAC address is only known for the sender's own address.
+    let mac_addr: String = if ip_addr == src_ip {
+        src_mac.to_string()
+    } else {
+        String::new()
+    };
+    let mut neighbor = NeighborInfo::new(mac_addr, ip_addr, protocol);
+    neighbor.hostname = hostname;
+    neighbors.push(neighbor);
+}
+
+fn normalize_hostname(name: &str, protocol: DiscoveryProtocol) -> Option<String> {
+    let name = name.trim_end_matches('.');
+    let name = match protocol {
+        DiscoveryProtocol::MDNS => {
+            if name.starts_with('_') {
+                return None;
+            }
+            name.trim_end_matches(".local")
+        }
+        _ => name,
+    };
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+enum RecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Ptr(String),
+    NetBios(Ipv4Addr),
+    Other,
+}
+
+struct ResourceRecord {
+    name: String,
+    rdata: RecordData,
+}
+
+/// Parse resource records (answer, authority and additional) from a DNS wire format message.
+fn parse_dns_records(payload: &[u8]) -> Option<Vec<ResourceRecord>> {
+    if payload.len() < DNS_HEADER_LEN {
+        return None;
+    }
+    let qdcount = u16::from_be_bytes([payload[4], payload[5]]) as usize;
+    let ancount = u16::from_be_bytes([payload[6], payload[7]]) as usize;
+    let nscount = u16::from_be_bytes([payload[8], payload[9]]) as usize;
+    let arcount = u16::from_be_bytes([payload[10], payload[11]]) as usize;
+    let mut offset = DNS_HEADER_LEN;
+    // Skip questions
+    for _ in 0..qdcount {
+        let (_name, next) = read_name(payload, offset)?;
+        offset = next + 4;
+    }
+    let mut records: Vec<ResourceRecord> = vec![];
+    for _ in 0..(ancount + nscount + arcount) {
+        let (name, next) = read_name(payload, offset)?;
+        if next + 10 > payload.len() {
+            return Some(records);
+        }
+        let rtype = u16::from_be_bytes([payload[next], payload[next + 1]]);
+        let rdlength = u16::from_be_bytes([payload[next + 8], payload[next + 9]]) as usize;
+        let rdata_start = next + 10;
+        let rdata_end = rdata_start + rdlength;
+        if rdata_end > payload.len() {
+            return Some(records);
+        }
+        let rdata = &payload[rdata_start..rdata_end];
+        let data = match rtype {
+            DNS_TYPE_A if rdlength == 4 => RecordData::A(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3])),
+            DNS_TYPE_AAAA if rdlength == 16 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(rdata);
+                RecordData::Aaaa(Ipv6Addr::from(octets))
+            }
+            DNS_TYPE_PTR => match read_name(payload, rdata_start) {
+                Some((target, _)) => RecordData::Ptr(target),
+                None => RecordData::Other,
+            },
+            // NB_FLAGS (2 bytes) followed by NB_ADDRESS (4 bytes)
+            DNS_TYPE_NB if rdlength >= 6 => RecordData::NetBios(Ipv4Addr::new(rdata[2], rdata[3], rdata[4], rdata[5])),
+            _ => RecordData::Other,
+        };
+        records.push(ResourceRecord { name, rdata: data });
+        offset = rdata_end;
+    }
+    Some(records)
+}
+
+/// Read a (possibly compressed) domain name. Returns the name and the offset after it.
+/// A single 32-byte label is decoded as a NetBIOS first-level encoded name.
+fn read_name(payload: &[u8], start: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = vec![];
+    let mut offset = start;
+    let mut end: Option<usize> = None;
+    let mut jumps = 0;
+    loop {
+        let len = *payload.get(offset)? as usize;
+        if len == 0 {
+            offset += 1;
+            break;
+        }
+        if len & 0xC0 == 0xC0 {
+            let ptr = ((len & 0x3F) << 8) | *payload.get(offset + 1)? as usize;
+            if end.is_none() {
+                end = Some(offset + 2);
+            }
+            jumps += 1;
+            if jumps > 16 {
+                return None;
+            }
+            offset = ptr;
+            continue;
+        }
+        let label = payload.get(offset + 1..offset + 1 + len)?;
+        if len == NETBIOS_ENCODED_NAME_LEN && labels.is_empty() {
+            if let Some(name) = decode_netbios_name(label) {
+                labels.push(name);
+                offset += 1 + len;
+                continue;
+            }
+        }
+        labels.push(String::from_utf8_lossy(label).to_string());
+        offset += 1 + len;
+    }
+    Some((labels.join("."), end.unwrap_or(offset)))
+}
+
+/// Decode a NetBIOS first-level encoded name (RFC 1001 section 14.1).
+/// The 16th byte (suffix) is dropped and the padding spaces are trimmed.
+fn decode_netbios_name(label: &[u8]) -> Option<String> {
+    let mut name: Vec<u8> = Vec::with_capacity(16);
+    for pair in label.chunks(2) {
+        if pair[0] < b'A' || pair[0] > b'P' || pair[1] < b'A' || pair[1] > b'P' {
+            return None;
+        }
+        name.push(((pair[0] - b'A') << 4) | (pair[1] - b'A'));
+    }
+    name.truncate(15);
+    Some(String::from_utf8_lossy(&name).trim_end().to_string())
+}
@@ -0,0 +1,168 @@
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+use std::thread;
+use crate::thread_log;
+use crate::net::interface;
+use crate::net::stat::NetStatStrage;
+use crate::pcap::{PacketCaptureOptions, PacketSampling};
+
+/// Named network namespaces created by `ip netns add`.
+pub const NETNS_RUN_DIR: &str = "/var/run/netns";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NetNamespace {
+    /// Name from /var/run/netns, or `pid:<pid>` for unnamed namespaces.
+    pub name: String,
+    /// Namespace inode number.
+    pub inode: u64,
+    /// Path to open for setns.
+    pub path: String,
+    /// True if this is the namespace nustat runs in.
+    pub is_host: bool,
+}
+
+impl NetNamespace {
+    /// Match a config selector: `*`, the namespace name, `pid:<pid>` or the inode number.
+    /// A `pid:<pid>` selector matches the namespace that process is in, not only the pid it was listed under.
+    pub fn matches(&self, selector: &str) -> bool {
+        if selector == "*" || selector == self.name || selector == self.inode.to_string() {
+            return true;
+        }
+        match selector.strip_prefix("pid:").and_then(|pid| pid.parse::<u32>().ok()) {
+            Some(pid) => pid_namespace_inode(pid) == Some(self.inode),
+            None => false,
+        }
+    }
+}
+
+/// Get the network namespace inode of the process from /proc/<pid>/ns/net.
+#[cfg(target_os = "linux")]
+pub fn pid_namespace_inode(pid: u32) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(format!("/proc/{}/ns/net", pid)).ok().map(|m| m.ino())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pid_namespace_inode(_pid: u32) -> Option<u64> {
+    None
+}
+
+/// Enumerate network namespaces from /var/run/netns and /proc/*/ns/net.
+/// Each namespace is listed once. Named namespaces take precedence.
+#[cfg(target_os = "linux")]
+pub fn list_namespaces() -> Vec<NetNamespace> {
+    use std::collections::HashSet;
+    use std::os::unix::fs::MetadataExt;
+    let host_inode: Option<u64> = std::fs::metadata("/proc/self/ns/net").ok().map(|m| m.ino());
+    let mut namespaces: Vec<NetNamespace> = Vec::new();
+    let mut seen: HashSet<u64> = HashSet::new();
+    if let Ok(entries) = std::fs::read_dir(NETNS_RUN_DIR) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if let Ok(metadata) = std::fs::metadata(&path) {
+                let inode = metadata.ino();
+                if !seen.insert(inode) {
+                    continue;
+                }
+                namespaces.push(NetNamespace {
+                    name: entry.file_name().to_string_lossy().to_string(),
+                    inode,
+                    path: path.to_string_lossy().to_string(),
+                    is_host: Some(inode) == host_inode,
+                });
+            }
+        }
+    }
+    if let Ok(entries) = std::fs::read_dir("/proc") {
+        for entry in entries.flatten() {
+            let pid: u32 = match entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+                Some(pid) => pid,
+                None => continue,
+            };
+            let path = format!("/proc/{}/ns/net", pid);
+            // The process may exit or deny access while scanning. Skip it.
+            let inode = match std::fs::metadata(&path) {
+                Ok(metadata) => metadata.ino(),
+                Err(_) => continue,
+            };
+            if !seen.insert(inode) {
+                continue;
+            }
+            namespaces.push(NetNamespace {
+                name: format!("pid:{}", pid),
+                inode,
+                path,
+                is_host: Some(inode) == host_inode,
+            });
+        }
+    }
+    namespaces
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn list_namespaces() -> Vec<NetNamespace> {
+    Vec::new()
+}
+
+/// Move the calling thread into the network namespace at `path`.
+#[cfg(target_os = "linux")]
+pub fn enter_namespace(path: &str) -> Result<(), String> {
+    use std::os::unix::io::AsRawFd;
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) => return Err(format!("Failed to open {}: {}", path, e)),
+    };
+    let ret = unsafe { libc::setns(file.as_raw_fd(), libc::CLONE_NEWNET) };
+    if ret < 0 {
+        return Err(format!("setns {} failed: {}", path, std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn enter_namespace(_path: &str) -> Result<(), String> {
+    Err(String::from("Network namespaces are only supported on Linux"))
+}
+
+/// Start packet capture inside the network namespace.
+/// A dedicated thread enters the namespace, registers its local IP map, and spawns one capture thread per usable interface.
+/// Capture threads inherit the namespace of the thread that spawns them, so the host threads are not affected.
+pub fn start_namespace_capture(namespace: NetNamespace, netstat_strage: Arc<NetStatStrage>, sampling: Option<PacketSampling>) -> Result<thread::JoinHandle<()>, String> {
+    let thread_name = format!("netns-thread-{}", namespace.name);
+    let handle = thread::Builder::new().name(thread_name).spawn(move || {
+        if let Err(e) = enter_namespace(&namespace.path) {
+            thread_log!(error, "[netns] {}", e);
+            return;
+        }
+        netstat_strage.set_netns_local_ip_map(namespace.inode, interface::get_local_ip_map());
+        let mut handles: Vec<thread::JoinHandle<()>> = Vec::new();
+        for iface in interface::get_usable_interfaces() {
+            let mut netstat_strage_pcap = Arc::clone(&netstat_strage);
+            let mut pcap_option = PacketCaptureOptions::from_interface(&iface);
+            pcap_option.netns = Some(namespace.inode);
+            pcap_option.sampling = sampling;
+            let pcap_thread = thread::Builder::new().name(format!("pcap-thread-{}-{}", namespace.inode, iface.name));
+            match pcap_thread.spawn(move || {
+                crate::pcap::start_background_capture(pcap_option, &mut netstat_strage_pcap, iface);
+            }) {
+                Ok(handle) => handles.push(handle),
+                Err(e) => thread_log!(error, "[netns] Failed to spawn capture thread: {:?}", e),
+            }
+        }
+        for handle in handles {
+            let _ = handle.join();
+        }
+    });
+    match handle {
+        Ok(handle) => Ok(handle),
+        Err(e) => Err(format!("Failed to spawn netns thread: {}", e)),
+    }
+}
+
+/// Select namespaces by config selectors. The host namespace is never selected since it is captured by default.
+pub fn select_namespaces(selectors: &[String]) -> Vec<NetNamespace> {
+    if selectors.is_empty() {
+        return Vec::new();
+    }
+    list_namespaces().into_iter().filter(|ns| !ns.is_host && selectors.iter().any(|s| ns.matches(s))).collect()
+}
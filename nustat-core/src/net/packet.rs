@@ -22,6 +22,16 @@ pub struct PacketFrame {
     pub packet_len: usize,
     /// Packet arrival time. RFC3339 format.
     pub timestamp: String,
+    /// Network namespace inode. None for the host namespace.
+    #[serde(default)]
+    pub netns: Option<u64>,
+    /// The packet was sampled 1-in-N. 1 if every packet is processed.
+    #[serde(default = "default_sampling_rate")]
+    pub sampling_rate: u32,
+}
+
+fn default_sampling_rate() -> u32 {
+    1
 }
 
 impl PacketFrame {
@@ -36,6 +46,8 @@ impl PacketFrame {
             //payload: Vec::new(),
             packet_len: 0,
             timestamp: String::new(),
+            netns: None,
+            sampling_rate: 1,
         }
     }
     pub fn from_xenet_frame(capture_no: usize, if_index: u32, if_name: String, frame: xenet::packet::frame::Frame) -> PacketFrame {
@@ -49,6 +61,8 @@ impl PacketFrame {
             //payload: frame.payload,
             packet_len: frame.packet_len,
             timestamp: sys::get_sysdate(),
+            netns: None,
+            sampling_rate: 1,
         }
     }
 }
\ No newline at end of file
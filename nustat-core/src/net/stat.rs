@@ -1,11 +1,23 @@
 use default_net::{mac::MacAddr, Interface};
 use serde::{Serialize, Deserialize};
-use std::{collections::HashMap, net::IpAddr, sync::{Arc, Mutex}};
+use std::{collections::{HashMap, HashSet}, net::IpAddr, sync::{mpsc::{SyncSender, TrySendError}, Arc, Mutex}, time::{Duration, Instant}};
 use crate::thread_log;
-use super::{host::{HostDisplayInfo, RemoteHostInfo}, packet::PacketFrame, service::ServiceDisplayInfo, traffic::{Direction, TrafficInfo}};
+use super::{host::{HostDisplayInfo, RemoteHostInfo}, neighbor::NeighborInfo, packet::PacketFrame, service::ServiceDisplayInfo, traffic::{Direction, TrafficInfo}};
 use super::interface;
-use crate::{db::ip::IpDatabase, notification::Notification, process::{ProcessDisplayInfo, ProcessInfo}, socket::{AddressFamily, LocalSocket, ProtocolPort, SocketConnection, SocketProcess, SocketTrafficInfo, TransportProtocol}};
+use super::ip;
+use crate::{db::ip::IpDatabase, notification::Notification, process::{ProcessDisplayInfo, ProcessInfo, ProcessTreeNode, RollupTarget, UserDisplayInfo}, socket::{self, AddressFamily, LocalSocket, ProtocolPort, SocketConnection, SocketEvent, SocketInfo, SocketProcess, SocketTrafficInfo, TcpMetrics, TransportProtocol}};
 use crate::db::service::ServiceDatabase;
+use crate::container::ContainerDisplayInfo;
+use crate::systemd::SystemdUnitDisplayInfo;
+use crate::db::oui::{self, OuiDatabase};
+use crate::pcap::CaptureStats;
+use crate::flow::sflow::PacketSample;
+use crate::metrics::TrafficTotals;
+
+/// Interval between new flow notifications of the same unattributed flow.
+const NEW_FLOW_NOTIFY_TTL: Duration = Duration::from_secs(10);
+/// Maximum number of flows remembered in `notified_flows`.
+const MAX_NOTIFIED_FLOWS: usize = 4096;
 
 #[derive(Debug, Clone)]
 pub struct NetStatStrage {
@@ -15,14 +27,44 @@ pub struct NetStatStrage {
     pub remote_hosts: Arc<Mutex<HashMap<IpAddr, RemoteHostInfo>>>,
     /// Socket Connection Traffic Map (SocketConnection -> TrafficInfo)
     pub connection_map: Arc<Mutex<HashMap<SocketConnection, TrafficInfo>>>,
-    /// Socket Process Map (LocalSocket -> SocketProcess)
+    /// Socket Process Map (LocalSocket -> SocketProcess). Listening and unconnected sockets.
     pub local_socket_map: Arc<Mutex<HashMap<LocalSocket, SocketProcess>>>,
+    /// Socket Process Map (SocketConnection -> SocketProcess). Connected sockets by 5-tuple.
+    pub connection_socket_map: Arc<Mutex<HashMap<SocketConnection, SocketProcess>>>,
     /// Reverse DNS Map (IpAddr -> Hostname)
     pub reverse_dns_map: Arc<Mutex<HashMap<IpAddr, String>>>,
     /// Local IP Map (IpAddr -> Interface Name)
     pub local_ip_map: Arc<Mutex<HashMap<IpAddr, String>>>,
+    /// Local IP Map per network namespace (Namespace inode -> IpAddr -> Interface Name)
+    pub netns_local_ip_map: Arc<Mutex<HashMap<u64, HashMap<IpAddr, String>>>>,
+    /// Local Neighbor Map (IpAddr -> NeighborInfo)
+    pub neighbor_map: Arc<Mutex<HashMap<IpAddr, NeighborInfo>>>,
+    /// TCP Metrics Map (SocketConnection -> TcpMetrics). Latest kernel snapshot.
+    pub tcp_metrics_map: Arc<Mutex<HashMap<SocketConnection, TcpMetrics>>>,
+    /// Listening TCP and unconnected UDP sockets of the last socket table scan.
+    pub listening_sockets: Arc<Mutex<Vec<SocketInfo>>>,
+    /// Channel to the socket updater. Set when event-driven socket updates are enabled.
+    pub socket_event_tx: Arc<Mutex<Option<SyncSender<SocketEvent>>>>,
+    /// Unattributed flows notified to the socket updater (SocketConnection -> Notified time)
+    pub notified_flows: Arc<Mutex<HashMap<SocketConnection, Instant>>>,
+    /// Channel to the sFlow exporter. Set when sFlow export is enabled.
+    pub packet_sample_tx: Arc<Mutex<Option<SyncSender<PacketSample>>>>,
+    /// Samples dropped because the sFlow exporter queue was full (Interface index -> Drops)
+    pub packet_sample_drops: Arc<Mutex<HashMap<u32, u32>>>,
+    /// Connection counters since the last `drain_flow_deltas`. Only kept once `enable_flow_deltas` is called.
+    pub flow_connection_map: Arc<Mutex<Option<HashMap<SocketConnection, TrafficInfo>>>>,
+    /// Cumulative data drained by `clone_data_and_reset`. Only kept once `enable_totals` is called.
+    pub totals: Arc<Mutex<Option<NetStatData>>>,
+    /// Cumulative traffic aggregates drained by `clone_data_and_reset`. Only kept once `enable_traffic_totals` is called.
+    pub traffic_totals: Arc<Mutex<Option<TrafficTotals>>>,
+    /// Capture counters per interface (Interface Name -> CaptureStats). Never reset.
+    pub capture_stats: Arc<Mutex<HashMap<String, CaptureStats>>>,
     /// IP Database for IP, ASN, Country, etc.
     pub ipdb: Arc<Mutex<IpDatabase>>,
+    /// OUI Database for MAC address vendor
+    pub ouidb: Arc<Mutex<OuiDatabase>>,
+    /// MAC addresses not found in the OUI database. Not looked up again until the database is reloaded.
+    pub oui_misses: Arc<Mutex<HashSet<String>>>,
 }
 
 impl NetStatStrage {
@@ -41,9 +83,24 @@ impl NetStatStrage {
             remote_hosts: Arc::new(Mutex::new(HashMap::new())),
             connection_map: Arc::new(Mutex::new(HashMap::new())),
             local_socket_map: Arc::new(Mutex::new(HashMap::new())),
+            connection_socket_map: Arc::new(Mutex::new(HashMap::new())),
             reverse_dns_map: Arc::new(Mutex::new(HashMap::new())),
             local_ip_map: Arc::new(Mutex::new(local_ip_map)),
+            netns_local_ip_map: Arc::new(Mutex::new(HashMap::new())),
+            neighbor_map: Arc::new(Mutex::new(HashMap::new())),
+            tcp_metrics_map: Arc::new(Mutex::new(HashMap::new())),
+            listening_sockets: Arc::new(Mutex::new(Vec::new())),
+            socket_event_tx: Arc::new(Mutex::new(None)),
+            notified_flows: Arc::new(Mutex::new(HashMap::new())),
+            packet_sample_tx: Arc::new(Mutex::new(None)),
+            packet_sample_drops: Arc::new(Mutex::new(HashMap::new())),
+            flow_connection_map: Arc::new(Mutex::new(None)),
+            totals: Arc::new(Mutex::new(None)),
+            traffic_totals: Arc::new(Mutex::new(None)),
+            capture_stats: Arc::new(Mutex::new(HashMap::new())),
             ipdb: Arc::new(Mutex::new(IpDatabase::new())),
+            ouidb: Arc::new(Mutex::new(OuiDatabase::new())),
+            oui_misses: Arc::new(Mutex::new(HashSet::new())),
         }
     }
     // Set interface
@@ -140,6 +197,71 @@ impl NetStatStrage {
             }
         }
     }
+    /// Get the neighbor_map (thread safe clone)
+    pub fn get_neighbor_map(&self) -> HashMap<IpAddr, NeighborInfo> {
+        match self.neighbor_map.lock() {
+            Ok(neighbor_map) => {
+                neighbor_map.clone()
+            }
+            Err(e) => {
+                thread_log!(error, "get_neighbor_map error: {:?}", e);
+                HashMap::new()
+            }
+        }
+    }
+    /// Register the local IP map of a network namespace
+    pub fn set_netns_local_ip_map(&self, netns: u64, ip_map: HashMap<IpAddr, String>) {
+        match self.netns_local_ip_map.lock() {
+            Ok(mut netns_local_ip_map) => {
+                netns_local_ip_map.insert(netns, ip_map);
+            }
+            Err(e) => {
+                thread_log!(error, "set_netns_local_ip_map error: {:?}", e);
+            }
+        }
+    }
+    pub fn get_tcp_metrics_map(&self) -> HashMap<SocketConnection, TcpMetrics> {
+        match self.tcp_metrics_map.lock() {
+            Ok(tcp_metrics_map) => {
+                tcp_metrics_map.clone()
+            }
+            Err(e) => {
+                thread_log!(error, "get_tcp_metrics_map error: {:?}", e);
+                HashMap::new()
+            }
+        }
+    }
+    /// Replace the tcp_metrics_map with the latest snapshot
+    pub fn set_tcp_metrics_map(&self, new_tcp_metrics_map: HashMap<SocketConnection, TcpMetrics>) {
+        match self.tcp_metrics_map.lock() {
+            Ok(mut tcp_metrics_map) => {
+                *tcp_metrics_map = new_tcp_metrics_map;
+            }
+            Err(e) => {
+                thread_log!(error, "set_tcp_metrics_map error: {:?}", e);
+            }
+        }
+    }
+    pub fn get_listening_sockets(&self) -> Vec<SocketInfo> {
+        match self.listening_sockets.lock() {
+            Ok(listening_sockets) => listening_sockets.clone(),
+            Err(e) => {
+                thread_log!(error, "get_listening_sockets error: {:?}", e);
+                Vec::new()
+            }
+        }
+    }
+    /// Replace the listening sockets with the latest snapshot
+    pub fn set_listening_sockets(&self, new_listening_sockets: Vec<SocketInfo>) {
+        match self.listening_sockets.lock() {
+            Ok(mut listening_sockets) => {
+                *listening_sockets = new_listening_sockets;
+            }
+            Err(e) => {
+                thread_log!(error, "set_listening_sockets error: {:?}", e);
+            }
+        }
+    }
     fn clear_trraffic(&self) {
         match self.traffic.lock() {
             Ok(mut traffic) => {
@@ -180,6 +302,28 @@ impl NetStatStrage {
             }
         }
     }
+    /// Get the connection_socket_map (thread safe clone)
+    pub fn get_connection_socket_map(&self) -> HashMap<SocketConnection, SocketProcess> {
+        match self.connection_socket_map.lock() {
+            Ok(connection_socket_map) => {
+                connection_socket_map.clone()
+            }
+            Err(e) => {
+                thread_log!(error, "get_connection_socket_map error: {:?}", e);
+                HashMap::new()
+            }
+        }
+    }
+    fn clear_connection_socket_map(&self) {
+        match self.connection_socket_map.lock() {
+            Ok(mut connection_socket_map) => {
+                connection_socket_map.clear();
+            }
+            Err(e) => {
+                thread_log!(error, "clear_connection_socket_map error: {:?}", e);
+            }
+        }
+    }
     fn clear_reverse_dns_map(&self) {
         match self.reverse_dns_map.lock() {
             Ok(mut reverse_dns_map) => {
@@ -190,18 +334,31 @@ impl NetStatStrage {
             }
         }
     }
+    fn clear_neighbor_map(&self) {
+        match self.neighbor_map.lock() {
+            Ok(mut neighbor_map) => {
+                neighbor_map.clear();
+            }
+            Err(e) => {
+                thread_log!(error, "clear_neighbor_map error: {:?}", e);
+            }
+        }
+    }
     pub fn reset(&self) {
         self.clear_trraffic();
         self.clear_remote_hosts();
         self.clear_connection_map();
         self.clear_local_socket_map();
+        self.clear_connection_socket_map();
         self.clear_reverse_dns_map();
+        self.clear_neighbor_map();
+        self.set_tcp_metrics_map(HashMap::new());
     }
+    /// Clear the traffic data. The socket maps are kept since the socket updater manages them.
     pub fn reset_data(&self) {
         self.clear_trraffic();
         self.clear_remote_hosts();
         self.clear_connection_map();
-        self.clear_local_socket_map();
     }
     pub fn clone_and_reset(&self) -> Self {
         let clone = self.clone();
@@ -209,6 +366,21 @@ impl NetStatStrage {
         clone
     }
     pub fn clone_data_and_reset(&self) -> NetStatData {
+        // Hold the totals until the drained data is merged, so readers see it either live or in the totals.
+        let mut totals = match self.totals.lock() {
+            Ok(totals) => Some(totals),
+            Err(e) => {
+                thread_log!(error, "clone_data_and_reset totals error: {:?}", e);
+                None
+            }
+        };
+        let mut traffic_totals = match self.traffic_totals.lock() {
+            Ok(traffic_totals) => Some(traffic_totals),
+            Err(e) => {
+                thread_log!(error, "clone_data_and_reset traffic_totals error: {:?}", e);
+                None
+            }
+        };
         let mut clone: NetStatData = NetStatData::new();
         clone.if_index = self.get_if_index();
         clone.if_name = self.get_if_name();
@@ -216,10 +388,145 @@ impl NetStatStrage {
         clone.remote_hosts = self.get_remote_hosts();
         clone.connection_map = self.get_connection_map();
         clone.local_socket_map = self.get_local_socket_map();
+        clone.connection_socket_map = self.get_connection_socket_map();
         clone.local_ip_map = self.get_local_ip_map();
+        clone.neighbor_map = self.get_neighbor_map();
+        clone.tcp_metrics_map = self.get_tcp_metrics_map();
         self.reset_data();
+        if let Some(traffic_totals) = traffic_totals.as_mut().and_then(|traffic_totals| traffic_totals.as_mut()) {
+            traffic_totals.add(&clone);
+        }
+        if let Some(totals) = totals.as_mut().and_then(|totals| totals.as_mut()) {
+            totals.merge(clone.clone());
+        }
         clone
     }
+    /// Keep cumulative totals across `clone_data_and_reset` calls, for consumers that need monotonic counters.
+    pub fn enable_totals(&self) {
+        match self.totals.lock() {
+            Ok(mut totals) => {
+                if totals.is_none() {
+                    *totals = Some(NetStatData::new());
+                }
+            }
+            Err(e) => {
+                thread_log!(error, "enable_totals error: {:?}", e);
+            }
+        }
+    }
+    /// Keep per-connection counters for `drain_flow_deltas`, independent of other consumers draining the strage.
+    pub fn enable_flow_deltas(&self) {
+        match self.flow_connection_map.lock() {
+            Ok(mut flow_connection_map) => {
+                if flow_connection_map.is_none() {
+                    *flow_connection_map = Some(HashMap::new());
+                }
+            }
+            Err(e) => {
+                thread_log!(error, "enable_flow_deltas error: {:?}", e);
+            }
+        }
+    }
+    /// Take the connection counters since the last call. Empty if flow deltas are not enabled.
+    pub fn drain_flow_deltas(&self) -> HashMap<SocketConnection, TrafficInfo> {
+        match self.flow_connection_map.lock() {
+            Ok(mut flow_connection_map) => flow_connection_map.as_mut().map(std::mem::take).unwrap_or_default(),
+            Err(e) => {
+                thread_log!(error, "drain_flow_deltas error: {:?}", e);
+                HashMap::new()
+            }
+        }
+    }
+    fn add_flow_delta(&self, conn: &SocketConnection, direction: Direction, packet_len: usize, sampling_rate: u32) {
+        if let Ok(mut flow_connection_map) = self.flow_connection_map.lock() {
+            if let Some(flow_connection_map) = flow_connection_map.as_mut() {
+                match flow_connection_map.get_mut(conn) {
+                    Some(traffic) => traffic.add_packet(direction, packet_len, sampling_rate),
+                    None => {
+                        let mut traffic = TrafficInfo::new();
+                        traffic.add_packet(direction, packet_len, sampling_rate);
+                        flow_connection_map.insert(conn.clone(), traffic);
+                    }
+                }
+            }
+        }
+    }
+    /// Cumulative data since `enable_totals`, including the data not drained yet.
+    /// Equivalent to `clone_data` if totals are not enabled.
+    pub fn get_totals(&self) -> NetStatData {
+        // The live data is cloned under the totals lock, so a concurrent drain is seen exactly once.
+        match self.totals.lock() {
+            Ok(totals) => {
+                let mut data = totals.clone().unwrap_or_else(NetStatData::new);
+                data.merge(self.clone_data());
+                data
+            }
+            Err(e) => {
+                thread_log!(error, "get_totals error: {:?}", e);
+                self.clone_data()
+            }
+        }
+    }
+    /// Keep cumulative traffic aggregates across `clone_data_and_reset` calls.
+    /// Unlike `enable_totals`, no per-connection data is kept, so memory is bounded by the aggregates.
+    pub fn enable_traffic_totals(&self) {
+        match self.traffic_totals.lock() {
+            Ok(mut traffic_totals) => {
+                if traffic_totals.is_none() {
+                    *traffic_totals = Some(TrafficTotals::new());
+                }
+            }
+            Err(e) => {
+                thread_log!(error, "enable_traffic_totals error: {:?}", e);
+            }
+        }
+    }
+    /// Cumulative traffic aggregates since `enable_traffic_totals`, including the data not drained yet.
+    pub fn get_traffic_totals(&self) -> TrafficTotals {
+        match self.traffic_totals.lock() {
+            Ok(traffic_totals) => {
+                let mut totals = traffic_totals.clone().unwrap_or_else(TrafficTotals::new);
+                totals.add(&self.clone_data());
+                totals
+            }
+            Err(e) => {
+                thread_log!(error, "get_traffic_totals error: {:?}", e);
+                let mut totals = TrafficTotals::new();
+                totals.add(&self.clone_data());
+                totals
+            }
+        }
+    }
+    /// Cumulative data of the drained intervals only. None if totals are not enabled.
+    pub fn get_drained_totals(&self) -> Option<NetStatData> {
+        match self.totals.lock() {
+            Ok(totals) => totals.clone(),
+            Err(e) => {
+                thread_log!(error, "get_drained_totals error: {:?}", e);
+                None
+            }
+        }
+    }
+    /// Add capture counters of the interface.
+    pub fn add_capture_stats(&self, if_name: &str, stats: &CaptureStats) {
+        match self.capture_stats.lock() {
+            Ok(mut capture_stats) => {
+                capture_stats.entry(if_name.to_string()).or_insert_with(CaptureStats::new).add(stats);
+            }
+            Err(e) => {
+                thread_log!(error, "add_capture_stats error: {:?}", e);
+            }
+        }
+    }
+    pub fn get_capture_stats(&self) -> HashMap<String, CaptureStats> {
+        match self.capture_stats.lock() {
+            Ok(capture_stats) => capture_stats.clone(),
+            Err(e) => {
+                thread_log!(error, "get_capture_stats error: {:?}", e);
+                HashMap::new()
+            }
+        }
+    }
     pub fn clone_data(&self) -> NetStatData {
         let mut clone: NetStatData = NetStatData::new();
         clone.if_index = self.get_if_index();
@@ -228,8 +535,60 @@ impl NetStatStrage {
         clone.remote_hosts = self.get_remote_hosts();
         clone.connection_map = self.get_connection_map();
         clone.local_socket_map = self.get_local_socket_map();
+        clone.connection_socket_map = self.get_connection_socket_map();
+        clone.local_ip_map = self.get_local_ip_map();
+        clone.neighbor_map = self.get_neighbor_map();
+        clone.tcp_metrics_map = self.get_tcp_metrics_map();
         clone
     }
+    /// Add the counters of a restored session. Owners already known to the socket updater are kept.
+    pub fn restore(&self, data: NetStatData) {
+        match self.traffic.lock() {
+            Ok(mut traffic) => {
+                traffic.add_traffic(&data.traffic);
+            }
+            Err(e) => {
+                thread_log!(error, "restore error: {:?}", e);
+            }
+        }
+        match self.remote_hosts.lock() {
+            Ok(mut remote_hosts) => {
+                for (ip, host) in data.remote_hosts {
+                    match remote_hosts.entry(ip) {
+                        std::collections::hash_map::Entry::Occupied(mut entry) => {
+                            entry.get_mut().merge(&host);
+                        },
+                        std::collections::hash_map::Entry::Vacant(entry) => {
+                            entry.insert(host);
+                        },
+                    }
+                }
+            }
+            Err(e) => {
+                thread_log!(error, "restore error: {:?}", e);
+            }
+        }
+        match self.connection_map.lock() {
+            Ok(mut connection_map) => {
+                for (conn, traffic) in data.connection_map {
+                    connection_map.entry(conn).or_insert_with(TrafficInfo::new).add_traffic(&traffic);
+                }
+            }
+            Err(e) => {
+                thread_log!(error, "restore error: {:?}", e);
+            }
+        }
+        match self.connection_socket_map.lock() {
+            Ok(mut connection_socket_map) => {
+                for (conn, socket_process) in data.connection_socket_map {
+                    connection_socket_map.entry(conn).or_insert(socket_process);
+                }
+            }
+            Err(e) => {
+                thread_log!(error, "restore error: {:?}", e);
+            }
+        }
+    }
     pub fn change_interface(&self, interface: &Interface) {
         //self.reset();
         self.set_interface(interface.clone());
@@ -263,13 +622,113 @@ impl NetStatStrage {
             }
         }
     }
-    pub fn update(&self, frame: PacketFrame) {
-        let local_ip_map_inner = match self.local_ip_map.lock() {
-            Ok(inner) => inner,
+    /// Load the OUI database. ~/.nustat/oui.bin overrides the bundled one.
+    pub fn load_ouidb(&self) {
+        match OuiDatabase::load_or_bundled() {
+            Ok(ouidb) => {
+                match self.ouidb.lock() {
+                    Ok(mut ouidb_mutex) => {
+                        *ouidb_mutex = ouidb;
+                    }
+                    Err(e) => {
+                        thread_log!(error, "load_ouidb error: {:?}", e);
+                    }
+                }
+                // Look up the misses again in the new database.
+                match self.oui_misses.lock() {
+                    Ok(mut oui_misses) => {
+                        oui_misses.clear();
+                    }
+                    Err(e) => {
+                        thread_log!(error, "load_ouidb error: {:?}", e);
+                    }
+                }
+            }
             Err(e) => {
-                thread_log!(error, "Failed to lock local_ips: {:?}", e);
+                thread_log!(error, "load_ouidb error: {:?}", e);
+            }
+        }
+    }
+    /// Check if the remote host is a LAN peer or the gateway of the default interface.
+    fn is_lan_peer(&self, local_ip_addr: IpAddr, remote_ip_addr: IpAddr) -> bool {
+        if ip::in_same_network(local_ip_addr, remote_ip_addr) {
+            return true;
+        }
+        match self.interface.try_lock() {
+            Ok(iface) => {
+                match &iface.gateway {
+                    Some(gateway) => gateway.ip_addr == remote_ip_addr,
+                    None => false,
+                }
+            }
+            Err(_) => false,
+        }
+    }
+    /// Update the neighbor inventory, and set the hostname of known LAN peers.
+    pub fn update_neighbors(&self, neighbors: Vec<NeighborInfo>) {
+        if neighbors.is_empty() {
+            return;
+        }
+        let mut named_hosts: Vec<(IpAddr, String)> = vec![];
+        match self.neighbor_map.lock() {
+            Ok(mut neighbor_map) => {
+                for neighbor in neighbors {
+                    let entry = neighbor_map.entry(neighbor.ip_addr).or_insert(neighbor.clone());
+                    entry.merge(&neighbor);
+                    if !entry.hostname.is_empty() {
+                        named_hosts.push((entry.ip_addr, entry.hostname.clone()));
+                    }
+                }
+            }
+            Err(e) => {
+                thread_log!(error, "update_neighbors error: {:?}", e);
                 return;
             }
+        }
+        // neighbor_map lock is released here. Do not hold both locks at once.
+        match self.remote_hosts.lock() {
+            Ok(mut remote_hosts) => {
+                for (ip_addr, hostname) in named_hosts {
+                    if let Some(remote_host) = remote_hosts.get_mut(&ip_addr) {
+                        if remote_host.hostname.is_empty() {
+                            remote_host.hostname = hostname;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                thread_log!(error, "update_neighbors error: {:?}", e);
+            }
+        }
+    }
+    pub fn update(&self, frame: PacketFrame) {
+        // Packets captured inside another network namespace use that namespace's local IP map.
+        let netns_local_ip_map_inner;
+        let host_local_ip_map_inner;
+        let local_ip_map_inner: &HashMap<IpAddr, String> = match frame.netns {
+            Some(netns) => {
+                netns_local_ip_map_inner = match self.netns_local_ip_map.lock() {
+                    Ok(inner) => inner,
+                    Err(e) => {
+                        thread_log!(error, "Failed to lock netns_local_ip_map: {:?}", e);
+                        return;
+                    }
+                };
+                match netns_local_ip_map_inner.get(&netns) {
+                    Some(ip_map) => ip_map,
+                    None => return,
+                }
+            }
+            None => {
+                host_local_ip_map_inner = match self.local_ip_map.lock() {
+                    Ok(inner) => inner,
+                    Err(e) => {
+                        thread_log!(error, "Failed to lock local_ips: {:?}", e);
+                        return;
+                    }
+                };
+                &host_local_ip_map_inner
+            }
         };
         // Lock traffic field
         let mut traffic_inner = match self.traffic.lock() {
@@ -335,16 +794,7 @@ impl NetStatStrage {
             return;
         };
         // Update TrafficInfo
-        match direction {
-            Direction::Egress => {
-                traffic_inner.packet_sent += 1;
-                traffic_inner.bytes_sent += frame.packet_len;
-            },
-            Direction::Ingress => {
-                traffic_inner.packet_received += 1;
-                traffic_inner.bytes_received += frame.packet_len;
-            },
-        }
+        traffic_inner.add_packet(direction, frame.packet_len, frame.sampling_rate);
         let mac_addr: String = match direction {
             Direction::Egress => {
                 if let Some(ethernet) = datalink_layer.ethernet {
@@ -466,16 +916,33 @@ impl NetStatStrage {
             mac_addr,
             remote_ip_addr,
         ));
-        match direction {
-            Direction::Egress => {
-                remote_host.traffic_info.packet_sent += 1;
-                remote_host.traffic_info.bytes_sent += frame.packet_len;
-            },
-            Direction::Ingress => {
-                remote_host.traffic_info.packet_received += 1;
-                remote_host.traffic_info.bytes_received += frame.packet_len;
-            },
+        // Resolve the MAC address vendor of LAN peers.
+        // Misses are remembered per MAC address and retried once the OUI database is (re)loaded in the background.
+        if remote_host.mac_vendor.is_empty() && !remote_host.mac_randomized && self.is_lan_peer(local_ip_addr, remote_ip_addr) {
+            if oui::is_locally_administered(&remote_host.mac_addr) {
+                remote_host.mac_randomized = true;
+            } else if let Ok(mut oui_misses) = self.oui_misses.try_lock() {
+                if !oui_misses.contains(&remote_host.mac_addr) {
+                    if let Ok(ouidb) = self.ouidb.try_lock() {
+                        match ouidb.get_vendor(&remote_host.mac_addr) {
+                            Some(vendor) => remote_host.mac_vendor = vendor,
+                            None => {
+                                oui_misses.insert(remote_host.mac_addr.clone());
+                            }
+                        }
+                    }
+                }
+            }
         }
+        // Name LAN peers from the neighbor inventory
+        if remote_host.hostname.is_empty() {
+            if let Ok(neighbor_map) = self.neighbor_map.try_lock() {
+                if let Some(neighbor) = neighbor_map.get(&remote_ip_addr) {
+                    remote_host.hostname = neighbor.hostname.clone();
+                }
+            }
+        }
+        remote_host.traffic_info.add_packet(direction, frame.packet_len, frame.sampling_rate);
         match remote_host.ip_addr {
             IpAddr::V4(ipv4) => {
                 if let Some(ipv4_info) = ipdb_inner.get_ipv4_info(ipv4) {
@@ -495,46 +962,41 @@ impl NetStatStrage {
             },
         }
         // Update SocketConnection if the packet is TCP or UDP.
+        let mut new_flow: Option<SocketConnection> = None;
         if let Some(transport) = frame.transport {
             if let Some(_tcp) = transport.tcp {
                 let socket_connection: SocketConnection = SocketConnection {
                     interface_name: interface_name.clone(),
+                    local_ip_addr: Some(local_ip_addr),
                     local_port: local_port,
                     remote_ip_addr: remote_ip_addr,
                     remote_port: remote_port,
                     protocol: TransportProtocol::TCP,
+                    netns: frame.netns,
                 };
-                let socket_traffic: &mut TrafficInfo = connections_inner.entry(socket_connection).or_insert(TrafficInfo::new());
-                match direction {
-                    Direction::Egress => {
-                        socket_traffic.packet_sent += 1;
-                        socket_traffic.bytes_sent += frame.packet_len;
-                    },
-                    Direction::Ingress => {
-                        socket_traffic.packet_received += 1;
-                        socket_traffic.bytes_received += frame.packet_len;
-                    },
+                if !connections_inner.contains_key(&socket_connection) {
+                    new_flow = Some(socket_connection.clone());
                 }
+                self.add_flow_delta(&socket_connection, direction, frame.packet_len, frame.sampling_rate);
+                let socket_traffic: &mut TrafficInfo = connections_inner.entry(socket_connection).or_insert(TrafficInfo::new());
+                socket_traffic.add_packet(direction, frame.packet_len, frame.sampling_rate);
             }
             if let Some(_udp) = transport.udp {
                 let socket_connection: SocketConnection = SocketConnection {
                     interface_name: interface_name,
+                    local_ip_addr: Some(local_ip_addr),
                     local_port: local_port,
                     remote_ip_addr: remote_ip_addr,
                     remote_port: remote_port,
                     protocol: TransportProtocol::UDP,
+                    netns: frame.netns,
                 };
-                let socket_traffic: &mut TrafficInfo = connections_inner.entry(socket_connection).or_insert(TrafficInfo::new());
-                match direction {
-                    Direction::Egress => {
-                        socket_traffic.packet_sent += 1;
-                        socket_traffic.bytes_sent += frame.packet_len;
-                    },
-                    Direction::Ingress => {
-                        socket_traffic.packet_received += 1;
-                        socket_traffic.bytes_received += frame.packet_len;
-                    },
+                if !connections_inner.contains_key(&socket_connection) {
+                    new_flow = Some(socket_connection.clone());
                 }
+                self.add_flow_delta(&socket_connection, direction, frame.packet_len, frame.sampling_rate);
+                let socket_traffic: &mut TrafficInfo = connections_inner.entry(socket_connection).or_insert(TrafficInfo::new());
+                socket_traffic.add_packet(direction, frame.packet_len, frame.sampling_rate);
             }
         }
         // Drop the locks
@@ -542,6 +1004,97 @@ impl NetStatStrage {
         drop(remote_hosts_inner);
         drop(connections_inner);
         drop(ipdb_inner);
+        if let Some(conn) = new_flow {
+            self.notify_new_flow(&conn);
+        }
+    }
+    /// Set the channel of the socket updater for new flow notifications.
+    pub fn set_socket_event_sender(&self, sender: Option<SyncSender<SocketEvent>>) {
+        match self.socket_event_tx.lock() {
+            Ok(mut socket_event_tx) => {
+                *socket_event_tx = sender;
+            }
+            Err(e) => {
+                thread_log!(error, "set_socket_event_sender error: {:?}", e);
+            }
+        }
+    }
+    /// Set the channel of the sFlow exporter for sampled packets.
+    pub fn set_packet_sample_sender(&self, sender: Option<SyncSender<PacketSample>>) {
+        match self.packet_sample_tx.lock() {
+            Ok(mut packet_sample_tx) => {
+                *packet_sample_tx = sender;
+            }
+            Err(e) => {
+                thread_log!(error, "set_packet_sample_sender error: {:?}", e);
+            }
+        }
+    }
+    /// Pass a sampled packet to the sFlow exporter. The sample is only built if an exporter is set,
+    /// and is dropped if the exporter queue is full.
+    /// The sample carries the number of samples of its interface dropped so far, as sFlow `drops`.
+    pub fn send_packet_sample<F: FnOnce() -> PacketSample>(&self, sample: F) {
+        if let Ok(packet_sample_tx) = self.packet_sample_tx.lock() {
+            if let Some(tx) = packet_sample_tx.as_ref() {
+                let mut sample = sample();
+                let mut packet_sample_drops = match self.packet_sample_drops.lock() {
+                    Ok(packet_sample_drops) => packet_sample_drops,
+                    Err(e) => {
+                        thread_log!(error, "Error happened {}", e);
+                        return;
+                    }
+                };
+                let drops = packet_sample_drops.entry(sample.if_index).or_insert(0);
+                sample.drops = *drops;
+                if let Err(TrySendError::Full(_)) = tx.try_send(sample) {
+                    *drops = drops.wrapping_add(1);
+                }
+            }
+        }
+    }
+    /// Ask the socket updater to rescan if the flow is not attributed to any socket yet.
+    /// Flows in other network namespaces are skipped since the host socket table does not contain them.
+    /// Each flow is notified once per `NEW_FLOW_NOTIFY_TTL`, so unattributable traffic (forwarded, scans, TIME_WAIT)
+    /// does not keep the updater rescanning.
+    fn notify_new_flow(&self, conn: &SocketConnection) {
+        if conn.netns.is_some() {
+            return;
+        }
+        let tx: SyncSender<SocketEvent> = match self.socket_event_tx.try_lock() {
+            Ok(socket_event_tx) => match socket_event_tx.as_ref() {
+                Some(tx) => tx.clone(),
+                None => return,
+            },
+            Err(_) => return,
+        };
+        let attributed: bool = match (self.connection_socket_map.try_lock(), self.local_socket_map.try_lock()) {
+            (Ok(connection_socket_map), Ok(local_socket_map)) => socket::lookup_socket_process(conn, &connection_socket_map, &local_socket_map).is_some(),
+            // The socket updater holds the lock. It is scanning right now.
+            _ => true,
+        };
+        if attributed {
+            return;
+        }
+        let mut notified_flows = match self.notified_flows.try_lock() {
+            Ok(notified_flows) => notified_flows,
+            Err(_) => return,
+        };
+        let now = Instant::now();
+        if let Some(notified_at) = notified_flows.get(conn) {
+            if now.duration_since(*notified_at) < NEW_FLOW_NOTIFY_TTL {
+                return;
+            }
+        }
+        if notified_flows.len() >= MAX_NOTIFIED_FLOWS {
+            notified_flows.retain(|_, notified_at| now.duration_since(*notified_at) < NEW_FLOW_NOTIFY_TTL);
+            if notified_flows.len() >= MAX_NOTIFIED_FLOWS {
+                // Too many new flows at once. The periodic scan picks them up.
+                return;
+            }
+        }
+        notified_flows.insert(conn.clone(), now);
+        // A full queue already has a rescan pending.
+        let _ = tx.try_send(SocketEvent::NewFlow(conn.clone()));
     }
 }
 
@@ -580,7 +1133,12 @@ pub struct NetStatData {
     pub remote_hosts: HashMap<IpAddr, RemoteHostInfo>,
     pub connection_map: HashMap<SocketConnection, TrafficInfo>,
     pub local_socket_map: HashMap<LocalSocket, SocketProcess>,
+    #[serde(default)]
+    pub connection_socket_map: HashMap<SocketConnection, SocketProcess>,
     pub local_ip_map: HashMap<IpAddr, String>,
+    pub neighbor_map: HashMap<IpAddr, NeighborInfo>,
+    #[serde(default)]
+    pub tcp_metrics_map: HashMap<SocketConnection, TcpMetrics>,
 }
 
 impl NetStatData {
@@ -592,7 +1150,10 @@ impl NetStatData {
             remote_hosts: HashMap::new(),
             connection_map: HashMap::new(),
             local_socket_map: HashMap::new(),
+            connection_socket_map: HashMap::new(),
             local_ip_map: HashMap::new(),
+            neighbor_map: HashMap::new(),
+            tcp_metrics_map: HashMap::new(),
         }
     }
     // merge using entry method to merge traffic info.
@@ -638,8 +1199,70 @@ impl NetStatData {
                 },
             }
         });
-        // Update local_ip_map
-        self.local_ip_map = other.local_ip_map;
+        // Update connection_socket_map. Sticky: a flow keeps its owner unless the owner was unknown.
+        other.connection_socket_map.into_iter().for_each(|(conn, socket_process)| {
+            match self.connection_socket_map.entry(conn) {
+                std::collections::hash_map::Entry::Occupied(mut entry) => {
+                    if entry.get().process.is_none() {
+                        entry.insert(socket_process);
+                    } else {
+                        entry.get_mut().status = socket_process.status;
+                    }
+                },
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    entry.insert(socket_process);
+                },
+            }
+        });
+        // Update local_ip_map. An empty map carries no addresses, so the known ones are kept.
+        if !other.local_ip_map.is_empty() {
+            self.local_ip_map = other.local_ip_map;
+        }
+        // Update neighbor_map
+        other.neighbor_map.iter().for_each(|(ip, neighbor)| {
+            match self.neighbor_map.entry(*ip) {
+                std::collections::hash_map::Entry::Occupied(mut entry) => {
+                    entry.get_mut().merge(neighbor);
+                },
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    entry.insert(neighbor.clone());
+                },
+            }
+        });
+        // Replace tcp_metrics_map with the latest inet_diag snapshot, so closed connections drop out
+        self.tcp_metrics_map = other.tcp_metrics_map;
+        // Name LAN peers from the neighbor inventory
+        for (ip, host) in self.remote_hosts.iter_mut() {
+            if host.hostname.is_empty() {
+                if let Some(neighbor) = self.neighbor_map.get(ip) {
+                    host.hostname = neighbor.hostname.clone();
+                }
+            }
+        }
+    }
+    /// Traffic since the `previous` snapshot, for consumers of cumulative data that need per-interval deltas.
+    /// Hosts and connections without new traffic are left out. The socket and neighbor maps are the current ones.
+    pub fn delta_since(&self, previous: &NetStatData) -> NetStatData {
+        let mut delta = self.clone();
+        delta.traffic = self.traffic.delta_since(&previous.traffic);
+        delta.remote_hosts.retain(|ip, host| {
+            if let Some(previous_host) = previous.remote_hosts.get(ip) {
+                host.traffic_info = host.traffic_info.delta_since(&previous_host.traffic_info);
+            }
+            host.traffic_info.total_packet() > 0 || host.traffic_info.total_bytes() > 0
+        });
+        delta.connection_map.retain(|conn, traffic| {
+            if let Some(previous_traffic) = previous.connection_map.get(conn) {
+                *traffic = traffic.delta_since(previous_traffic);
+            }
+            traffic.total_packet() > 0 || traffic.total_bytes() > 0
+        });
+        delta
+    }
+    pub fn get_neighbors(&self) -> Vec<NeighborInfo> {
+        let mut neighbors: Vec<NeighborInfo> = self.neighbor_map.values().cloned().collect();
+        neighbors.sort_by_key(|n| n.ip_addr);
+        neighbors
     }
     pub fn get_remote_hosts(&self, limit: Option<usize>) -> Vec<HostDisplayInfo> {
         let mut host_traffic_map: HashMap<IpAddr, usize> = HashMap::new();
@@ -665,6 +1288,9 @@ impl NetStatData {
                 let host = HostDisplayInfo {
                     ip_addr: host.ip_addr,
                     host_name: host.hostname.clone(),
+                    mac_addr: host.mac_addr.clone(),
+                    mac_vendor: host.mac_vendor.clone(),
+                    mac_randomized: host.mac_randomized,
                     country_code: host.country_code.clone(),
                     country_name: host.country_name.clone(),
                     asn: host.asn.clone(),
@@ -677,16 +1303,17 @@ impl NetStatData {
         remote_hosts
     }
 
-    pub fn get_processes(&self, limit: Option<usize>) -> Vec<ProcessDisplayInfo> {
+    /// Socket owning the connection. See `socket::lookup_socket_process`.
+    pub fn get_socket_process(&self, conn: &SocketConnection) -> Option<&SocketProcess> {
+        socket::lookup_socket_process(conn, &self.connection_socket_map, &self.local_socket_map)
+    }
+
+    /// Traffic per process (pid -> TrafficInfo) and the processes seen.
+    fn get_process_traffic_map(&self) -> (HashMap<u32, TrafficInfo>, HashMap<u32, ProcessInfo>) {
         let mut process_traffic_map: HashMap<u32, TrafficInfo> = HashMap::new();
         let mut process_map: HashMap<u32, ProcessInfo> = HashMap::new();
         self.connection_map.iter().for_each(|(conn, traffic_info)| {
-            let local_socket: LocalSocket = LocalSocket {
-                interface_name: conn.interface_name.clone(),
-                port: conn.local_port,
-                protocol: conn.protocol,
-            };
-            match self.local_socket_map.get(&local_socket) {
+            match self.get_socket_process(conn) {
                 Some(socket_process) => {
                     if let Some(process) = &socket_process.process {
                         match process_traffic_map.get(&process.pid) {
@@ -706,6 +1333,11 @@ impl NetStatData {
                 None => {}
             }
         });
+        (process_traffic_map, process_map)
+    }
+
+    pub fn get_processes(&self, limit: Option<usize>) -> Vec<ProcessDisplayInfo> {
+        let (process_traffic_map, process_map) = self.get_process_traffic_map();
         // Create process total traffic map from process_traffic_map
         let process_total_traffic_map: HashMap<u32, usize> = process_traffic_map.iter().map(|(pid, traffic)| (*pid, traffic.total_bytes())).collect();
         // Sort process_total_traffic_map by traffic
@@ -720,6 +1352,7 @@ impl NetStatData {
                     let process = ProcessDisplayInfo {
                         pid: process.pid,
                         name: process.name.clone(),
+                        unit: process.systemd_unit.as_ref().map(|u| u.unit.clone()),
                         traffic: traffic.clone(),
                     };
                     top_processes.push(process);
@@ -729,6 +1362,189 @@ impl NetStatData {
         top_processes
     }
 
+    /// Traffic rolled up to the chosen ancestor of each process.
+    pub fn get_processes_rollup(&self, target: &RollupTarget, limit: Option<usize>) -> Vec<ProcessDisplayInfo> {
+        let (process_traffic_map, process_map) = self.get_process_traffic_map();
+        let mut rollup_map: HashMap<u32, ProcessDisplayInfo> = HashMap::new();
+        for (pid, traffic) in process_traffic_map {
+            if let Some(process) = process_map.get(&pid) {
+                let ancestor = process.rollup_target(target);
+                // The unit of the ancestor itself. Unknown unless the ancestor also has traffic.
+                let unit = match process_map.get(&ancestor.pid) {
+                    Some(ancestor_process) => ancestor_process.systemd_unit.as_ref().map(|u| u.unit.clone()),
+                    None => None,
+                };
+                let entry = rollup_map.entry(ancestor.pid).or_insert_with(|| ProcessDisplayInfo {
+                    pid: ancestor.pid,
+                    name: ancestor.name.clone(),
+                    unit,
+                    traffic: TrafficInfo::new(),
+                });
+                entry.traffic.add_traffic(&traffic);
+            }
+        }
+        let mut processes: Vec<ProcessDisplayInfo> = rollup_map.into_values().collect();
+        processes.sort_by_key(|p| std::cmp::Reverse(p.traffic.total_bytes()));
+        // limit : if limit is None, return all processes.
+        processes.truncate(limit.unwrap_or(processes.len()));
+        processes
+    }
+
+    /// Process tree of the processes with traffic and their ancestors.
+    /// Children are sorted by cumulative traffic.
+    pub fn get_process_tree(&self) -> Vec<ProcessTreeNode> {
+        let (process_traffic_map, process_map) = self.get_process_traffic_map();
+        let mut name_map: HashMap<u32, String> = HashMap::new();
+        let mut parent_map: HashMap<u32, u32> = HashMap::new();
+        for process in process_map.values() {
+            name_map.insert(process.pid, process.name.clone());
+            let mut child: u32 = process.pid;
+            for ancestor in &process.ancestors {
+                name_map.entry(ancestor.pid).or_insert_with(|| ancestor.name.clone());
+                parent_map.insert(child, ancestor.pid);
+                child = ancestor.pid;
+            }
+        }
+        let mut children_map: HashMap<u32, Vec<u32>> = HashMap::new();
+        let mut roots: Vec<u32> = Vec::new();
+        for pid in name_map.keys() {
+            match parent_map.get(pid) {
+                Some(ppid) if name_map.contains_key(ppid) => children_map.entry(*ppid).or_default().push(*pid),
+                _ => roots.push(*pid),
+            }
+        }
+        fn build_node(pid: u32, name_map: &HashMap<u32, String>, children_map: &HashMap<u32, Vec<u32>>, process_traffic_map: &HashMap<u32, TrafficInfo>, depth: usize) -> ProcessTreeNode {
+            let traffic: TrafficInfo = process_traffic_map.get(&pid).cloned().unwrap_or(TrafficInfo::new());
+            let mut cumulative_traffic: TrafficInfo = traffic.clone();
+            let mut children: Vec<ProcessTreeNode> = Vec::new();
+            if depth < crate::process::MAX_ANCESTRY_DEPTH {
+                if let Some(child_pids) = children_map.get(&pid) {
+                    for child_pid in child_pids {
+                        let child = build_node(*child_pid, name_map, children_map, process_traffic_map, depth + 1);
+                        cumulative_traffic.add_traffic(&child.cumulative_traffic);
+                        children.push(child);
+                    }
+                }
+            }
+            children.sort_by_key(|c| std::cmp::Reverse(c.cumulative_traffic.total_bytes()));
+            ProcessTreeNode {
+                pid,
+                name: name_map.get(&pid).cloned().unwrap_or_default(),
+                traffic,
+                cumulative_traffic,
+                children,
+            }
+        }
+        let mut tree: Vec<ProcessTreeNode> = roots.into_iter().map(|pid| build_node(pid, &name_map, &children_map, &process_traffic_map, 0)).collect();
+        tree.sort_by_key(|n| std::cmp::Reverse(n.cumulative_traffic.total_bytes()));
+        tree
+    }
+
+    /// Traffic aggregated per process owner (user).
+    pub fn get_users(&self, limit: Option<usize>) -> Vec<UserDisplayInfo> {
+        let mut user_map: HashMap<String, UserDisplayInfo> = HashMap::new();
+        let mut user_pids: HashMap<String, Vec<u32>> = HashMap::new();
+        self.connection_map.iter().for_each(|(conn, traffic_info)| {
+            let process = match self.get_socket_process(conn) {
+                Some(socket_process) => match &socket_process.process {
+                    Some(process) => process,
+                    None => return,
+                },
+                None => return,
+            };
+            let user_info = match &process.user_info {
+                Some(user_info) => user_info,
+                None => return,
+            };
+            let entry = user_map.entry(user_info.user_id.clone()).or_insert_with(|| UserDisplayInfo {
+                user_id: user_info.user_id.clone(),
+                user_name: user_info.user_name.clone(),
+                process_count: 0,
+                traffic: TrafficInfo::new(),
+            });
+            entry.traffic.add_traffic(traffic_info);
+            let pids = user_pids.entry(user_info.user_id.clone()).or_default();
+            if !pids.contains(&process.pid) {
+                pids.push(process.pid);
+                entry.process_count += 1;
+            }
+        });
+        let mut users: Vec<UserDisplayInfo> = user_map.into_values().collect();
+        users.sort_by_key(|u| std::cmp::Reverse(u.traffic.total_bytes()));
+        // limit : if limit is None, return all users.
+        users.truncate(limit.unwrap_or(users.len()));
+        users
+    }
+
+    /// Traffic aggregated per container. Processes outside of containers are excluded.
+    pub fn get_containers(&self, limit: Option<usize>) -> Vec<ContainerDisplayInfo> {
+        let mut container_map: HashMap<String, ContainerDisplayInfo> = HashMap::new();
+        self.connection_map.iter().for_each(|(conn, traffic_info)| {
+            let process = match self.get_socket_process(conn) {
+                Some(socket_process) => match &socket_process.process {
+                    Some(process) => process,
+                    None => return,
+                },
+                None => return,
+            };
+            let container = match &process.container {
+                Some(container) => container,
+                None => return,
+            };
+            let entry = container_map.entry(container.container_id.clone()).or_insert_with(|| ContainerDisplayInfo {
+                container_id: container.container_id.clone(),
+                runtime: container.runtime,
+                pod_name: container.pod_name.clone(),
+                pod_namespace: container.pod_namespace.clone(),
+                netns: process.netns,
+                process_names: Vec::new(),
+                traffic: TrafficInfo::new(),
+            });
+            entry.traffic.add_traffic(traffic_info);
+            if !entry.process_names.contains(&process.name) {
+                entry.process_names.push(process.name.clone());
+            }
+        });
+        let mut containers: Vec<ContainerDisplayInfo> = container_map.into_values().collect();
+        containers.sort_by_key(|c| std::cmp::Reverse(c.traffic.total_bytes()));
+        // limit : if limit is None, return all containers.
+        containers.truncate(limit.unwrap_or(containers.len()));
+        containers
+    }
+
+    /// Traffic aggregated per systemd unit. Linux only.
+    pub fn get_systemd_units(&self, limit: Option<usize>) -> Vec<SystemdUnitDisplayInfo> {
+        let mut unit_map: HashMap<String, SystemdUnitDisplayInfo> = HashMap::new();
+        self.connection_map.iter().for_each(|(conn, traffic_info)| {
+            let process = match self.get_socket_process(conn) {
+                Some(socket_process) => match &socket_process.process {
+                    Some(process) => process,
+                    None => return,
+                },
+                None => return,
+            };
+            let systemd_unit = match &process.systemd_unit {
+                Some(systemd_unit) => systemd_unit,
+                None => return,
+            };
+            let entry = unit_map.entry(systemd_unit.unit.clone()).or_insert_with(|| SystemdUnitDisplayInfo {
+                unit: systemd_unit.unit.clone(),
+                slice: systemd_unit.slice.clone(),
+                process_names: Vec::new(),
+                traffic: TrafficInfo::new(),
+            });
+            entry.traffic.add_traffic(traffic_info);
+            if !entry.process_names.contains(&process.name) {
+                entry.process_names.push(process.name.clone());
+            }
+        });
+        let mut units: Vec<SystemdUnitDisplayInfo> = unit_map.into_values().collect();
+        units.sort_by_key(|u| std::cmp::Reverse(u.traffic.total_bytes()));
+        // limit : if limit is None, return all units.
+        units.truncate(limit.unwrap_or(units.len()));
+        units
+    }
+
     pub fn get_connections(&self, limit: Option<usize>) -> Vec<SocketTrafficInfo> {
         let connection_total_traffic_map: HashMap<SocketConnection, usize> = self.connection_map.iter().map(|(conn, traffic)| (conn.clone(), traffic.total_bytes())).collect();
         let mut connection_total_traffic_vec: Vec<(&SocketConnection, &usize)> = connection_total_traffic_map.iter().collect();
@@ -737,11 +1553,7 @@ impl NetStatData {
         // limit : if limit is None, return all connections.
         for (conn, _) in connection_total_traffic_vec.iter().take(limit.unwrap_or(connection_total_traffic_vec.len())) {
             // Get process info from local_socket_map
-            let process: Option<ProcessInfo> = match self.local_socket_map.get(&LocalSocket {
-                interface_name: conn.interface_name.clone(),
-                port: conn.local_port,
-                protocol: conn.protocol,
-            }) {
+            let process: Option<ProcessInfo> = match self.get_socket_process(conn) {
                 Some(socket_process) => {
                     socket_process.process.clone()
                 },
@@ -750,6 +1562,7 @@ impl NetStatData {
             if let Some(traffic) = self.connection_map.get(conn) {
                 let socket_traffic_info = SocketTrafficInfo {
                     interface_name: conn.interface_name.clone(),
+                    local_ip_addr: conn.local_ip_addr,
                     local_port: conn.local_port,
                     remote_ip_addr: Some(conn.remote_ip_addr),
                     remote_port: Some(conn.remote_port),
@@ -760,6 +1573,8 @@ impl NetStatData {
                     },
                     traffic: traffic.clone(),
                     process: process,
+                    tcp_metrics: self.tcp_metrics_map.get(conn).cloned(),
+                    netns: conn.netns,
                 };
                 top_connections.push(socket_traffic_info);
             }
@@ -805,6 +1620,7 @@ impl NetStatData {
         top_app_protocols
     }
 
+    /// Overview of the data. Notifications are left empty; they are filled by the owner of the alert engine.
     pub fn get_overview(&self) -> Overview {
         let mut overview = Overview::new();
         overview.if_index = self.if_index;
@@ -12,6 +12,9 @@ pub struct TrafficInfo {
     pub packet_received: usize,
     pub bytes_sent: usize,
     pub bytes_received: usize,
+    /// Counters are scaled up from sampled packets and are estimates.
+    #[serde(default)]
+    pub estimated: bool,
 }
 
 impl TrafficInfo {
@@ -21,6 +24,7 @@ impl TrafficInfo {
             packet_received: 0,
             bytes_sent: 0,
             bytes_received: 0,
+            estimated: false,
         }
     }
     pub fn add_traffic(&mut self, traffic: &TrafficInfo) {
@@ -28,6 +32,34 @@ impl TrafficInfo {
         self.packet_received += traffic.packet_received;
         self.bytes_sent += traffic.bytes_sent;
         self.bytes_received += traffic.bytes_received;
+        self.estimated |= traffic.estimated;
+    }
+    /// Traffic counted since the `previous` snapshot of the same cumulative counters.
+    pub fn delta_since(&self, previous: &TrafficInfo) -> TrafficInfo {
+        TrafficInfo {
+            packet_sent: self.packet_sent.saturating_sub(previous.packet_sent),
+            packet_received: self.packet_received.saturating_sub(previous.packet_received),
+            bytes_sent: self.bytes_sent.saturating_sub(previous.bytes_sent),
+            bytes_received: self.bytes_received.saturating_sub(previous.bytes_received),
+            estimated: self.estimated,
+        }
+    }
+    /// Count a packet. A packet sampled 1-in-N counts as N packets of the same length.
+    pub fn add_packet(&mut self, direction: Direction, packet_len: usize, sampling_rate: u32) {
+        let scale = sampling_rate.max(1) as usize;
+        match direction {
+            Direction::Egress => {
+                self.packet_sent += scale;
+                self.bytes_sent += packet_len * scale;
+            },
+            Direction::Ingress => {
+                self.packet_received += scale;
+                self.bytes_received += packet_len * scale;
+            },
+        }
+        if scale > 1 {
+            self.estimated = true;
+        }
     }
     pub fn total_packet(&self) -> usize {
         self.packet_sent + self.packet_received
@@ -5,6 +5,8 @@ pub enum NotificationType {
     Traffic,
     RemoteHost,
     Protocol,
+    Connection,
+    Listener,
 }
 
 impl NotificationType {
@@ -13,6 +15,26 @@ impl NotificationType {
             NotificationType::Traffic => "Traffic".to_string(),
             NotificationType::RemoteHost => "Remote Host".to_string(),
             NotificationType::Protocol => "Protocol".to_string(),
+            NotificationType::Connection => "Connection".to_string(),
+            NotificationType::Listener => "Listener".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn name(&self) -> String {
+        match self {
+            Severity::Info => "Info".to_string(),
+            Severity::Warning => "Warning".to_string(),
+            Severity::Critical => "Critical".to_string(),
         }
     }
 }
@@ -23,4 +45,15 @@ pub struct Notification {
     pub body: String,
     pub notification_type: NotificationType,
     pub timestamp: String,
+    #[serde(default)]
+    pub severity: Severity,
+    /// Name of the alert rule that raised it.
+    #[serde(default)]
+    pub rule: String,
+    /// What the rule fired on, e.g. the remote host. Repeats of the same rule and key update one notification.
+    #[serde(default)]
+    pub key: String,
+    /// Times the rule fired for the key.
+    #[serde(default)]
+    pub count: usize,
 }
@@ -11,9 +11,11 @@ use xenet::packet::frame::Frame;
 use xenet::packet::frame::ParseOption;
 use crate::thread_log;
 use crate::net::interface;
+use crate::net::neighbor;
 use crate::net::stat::NetStatStrage;
 use crate::sys;
 use crate::net::packet::PacketFrame;
+use crate::flow::sflow::PacketSample;
 
 /// Packet capture message
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -69,6 +71,12 @@ pub struct PacketCaptureOptions {
     pub tunnel: bool,
     /// Loopback interface
     pub loopback: bool,
+    /// Network namespace inode. None for the host namespace.
+    #[serde(default)]
+    pub netns: Option<u64>,
+    /// Process only 1-in-N packets. Counters are scaled up and marked as estimates.
+    #[serde(default)]
+    pub sampling: Option<PacketSampling>,
 }
 
 impl PacketCaptureOptions {
@@ -89,6 +97,8 @@ impl PacketCaptureOptions {
             receive_undefined: true,
             tunnel: iface.is_tun(),
             loopback: iface.is_loopback(),
+            netns: None,
+            sampling: None,
         };
         Ok(options)
     }
@@ -109,6 +119,8 @@ impl PacketCaptureOptions {
             receive_undefined: true,
             tunnel: iface.is_tun(),
             loopback: iface.is_loopback(),
+            netns: None,
+            sampling: None,
         };
         Some(options)
     }
@@ -129,6 +141,8 @@ impl PacketCaptureOptions {
             receive_undefined: true,
             tunnel: iface.is_tun(),
             loopback: iface.is_loopback(),
+            netns: None,
+            sampling: None,
         };
         options
     }
@@ -148,11 +162,104 @@ impl PacketCaptureOptions {
             receive_undefined: true,
             tunnel: iface.is_tun(),
             loopback: iface.is_loopback(),
+            netns: None,
+            sampling: None,
         };
         options
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SamplingMode {
+    /// Every Nth packet.
+    #[default]
+    Deterministic,
+    /// 1-in-N on average, with a random skip between samples. Avoids aliasing with periodic traffic.
+    Random,
+}
+
+/// Packet sampling setting.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketSampling {
+    /// Sample 1 in `rate` packets.
+    pub rate: u32,
+    pub mode: SamplingMode,
+}
+
+impl PacketSampling {
+    /// None if `rate` is 0 or 1. (every packet)
+    pub fn new(rate: u32, mode: SamplingMode) -> Option<PacketSampling> {
+        if rate <= 1 {
+            return None;
+        }
+        Some(PacketSampling { rate, mode })
+    }
+}
+
+/// Decides which packets are processed.
+pub struct PacketSampler {
+    rate: u32,
+    mode: SamplingMode,
+    /// Packets until the next sample.
+    skip: u32,
+    /// Packets seen so far. (sFlow sample pool)
+    pool: u32,
+    rng: u64,
+}
+
+impl PacketSampler {
+    pub fn new(sampling: Option<PacketSampling>) -> Self {
+        let (rate, mode) = match sampling {
+            Some(sampling) => (sampling.rate.max(1), sampling.mode),
+            None => (1, SamplingMode::Deterministic),
+        };
+        let seed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        let mut sampler = PacketSampler {
+            rate,
+            mode,
+            skip: 0,
+            pool: 0,
+            // xorshift state must not be zero
+            rng: seed | 1,
+        };
+        sampler.skip = sampler.next_skip();
+        sampler
+    }
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+    pub fn pool(&self) -> u32 {
+        self.pool
+    }
+    fn next_random(&mut self) -> u64 {
+        // xorshift64*
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+    fn next_skip(&mut self) -> u32 {
+        match self.mode {
+            SamplingMode::Deterministic => self.rate,
+            // Uniform in 1..=2N-1, so the mean is N
+            SamplingMode::Random => 1 + (self.next_random() % (2 * self.rate as u64 - 1)) as u32,
+        }
+    }
+    /// Count a packet and return true if it is sampled.
+    pub fn sample(&mut self) -> bool {
+        self.pool = self.pool.wrapping_add(1);
+        if self.rate <= 1 {
+            return true;
+        }
+        self.skip -= 1;
+        if self.skip > 0 {
+            return false;
+        }
+        self.skip = self.next_skip();
+        true
+    }
+}
+
 /// Start packet capture
 pub fn start_capture(
     capture_options: PacketCaptureOptions,
@@ -228,6 +335,31 @@ pub fn start_capture(
     report
 }
 
+/// Capture counters of an interface.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CaptureStats {
+    /// Frames read from the datalink channel.
+    pub packets: u64,
+    /// Receive errors. The datalink channel does not expose kernel drop counters.
+    pub errors: u64,
+    /// Frames discarded by the capture filter.
+    pub filtered: u64,
+}
+
+impl CaptureStats {
+    pub fn new() -> Self {
+        CaptureStats::default()
+    }
+    pub fn add(&mut self, other: &CaptureStats) {
+        self.packets += other.packets;
+        self.errors += other.errors;
+        self.filtered += other.filtered;
+    }
+}
+
+/// How often the capture loop flushes its counters to the strage.
+const CAPTURE_STATS_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
 pub fn start_background_capture(capture_options: PacketCaptureOptions, netstat_strage: &mut Arc<NetStatStrage>, interface: Interface) {
     let config = xenet::datalink::Config {
         write_buffer_size: 4096,
@@ -251,9 +383,17 @@ pub fn start_background_capture(capture_options: PacketCaptureOptions, netstat_s
         },
     };
     let start_time = Instant::now();
+    let mut stats = CaptureStats::new();
+    let mut last_flush = Instant::now();
+    let mut sampler = PacketSampler::new(capture_options.sampling);
     loop {
         match rx.next() {
+            // Skipped packets are not parsed at all.
+            Ok(_) if !sampler.sample() => {
+                stats.packets += 1;
+            }
             Ok(packet) => {
+                stats.packets += 1;
                 let mut parse_option: ParseOption = ParseOption::default();
                 if interface.is_tun() || (cfg!(any(target_os = "macos", target_os = "ios")) && interface.is_loopback()) {
                     let payload_offset;
@@ -265,16 +405,33 @@ pub fn start_background_capture(capture_options: PacketCaptureOptions, netstat_s
                     parse_option.from_ip_packet = true;
                     parse_option.offset = payload_offset;
                 }
+                netstat_strage.send_packet_sample(|| PacketSample::from_packet(packet, &parse_option, interface.index, sampler.rate(), sampler.pool(), 0));
                 let frame: Frame = Frame::from_bytes(&packet, parse_option);
                 if filter_packet(&frame, &capture_options) {
-                    let packet_frame = PacketFrame::from_xenet_frame(0,interface.index, interface.name.clone(), frame);
+                    // Passive local host discovery (DHCP, mDNS, LLMNR, NetBIOS)
+                    netstat_strage.update_neighbors(neighbor::parse_frame(&frame));
+                    let mut packet_frame = PacketFrame::from_xenet_frame(0,interface.index, interface.name.clone(), frame);
+                    packet_frame.netns = capture_options.netns;
+                    packet_frame.sampling_rate = sampler.rate();
                     /* if netstat_strage.interface_changed(interface.index) {
                         netstat_strage.change_interface(&interface);
                     } */
                     netstat_strage.update(packet_frame);
+                } else {
+                    stats.filtered += 1;
                 }
             }
-            Err(_) => {}
+            Err(e) => {
+                // Read timeouts are expected when the link is idle.
+                if e.kind() != std::io::ErrorKind::TimedOut {
+                    stats.errors += 1;
+                }
+            }
+        }
+        if last_flush.elapsed() >= CAPTURE_STATS_FLUSH_INTERVAL {
+            netstat_strage.add_capture_stats(&interface.name, &stats);
+            stats = CaptureStats::new();
+            last_flush = Instant::now();
         }
         if Instant::now().duration_since(start_time) > capture_options.capture_timeout {
             break;
@@ -1,10 +1,12 @@
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
-use sysinfo::{PidExt, ProcessExt, SystemExt, ProcessRefreshKind};
-//use sysinfo::UserExt;
-use chrono::{DateTime, TimeZone, NaiveDateTime, Local};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, PidExt, ProcessExt, SystemExt, ProcessRefreshKind, UserExt};
+use chrono::{DateTime, Local};
 
 use crate::net::traffic::TrafficInfo;
+use crate::container::{self, ContainerInfo};
+use crate::systemd::{self, SystemdUnitInfo};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UserInfo {
@@ -14,6 +16,56 @@ pub struct UserInfo {
     pub groups: Vec<String>,
 }
 
+/// Maximum depth of the ancestry chain. Guards against loops caused by pid reuse.
+pub(crate) const MAX_ANCESTRY_DEPTH: usize = 64;
+
+/// Minimum interval between user list refreshes on unknown uid.
+const USER_TABLE_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Cached uid -> UserInfo table.
+/// The system user list is reloaded only when an unknown uid is looked up, at most once per minute.
+pub struct UserTable {
+    system: sysinfo::System,
+    user_map: HashMap<String, UserInfo>,
+    last_refresh: Option<Instant>,
+}
+
+impl UserTable {
+    pub fn new() -> Self {
+        UserTable {
+            system: sysinfo::System::new(),
+            user_map: HashMap::new(),
+            last_refresh: None,
+        }
+    }
+    pub fn refresh(&mut self) {
+        self.system.refresh_users_list();
+        self.user_map = self.system.users().iter().map(|user| {
+            let user_info = UserInfo {
+                user_id: user.id().to_string(),
+                group_id: user.group_id().to_string(),
+                user_name: user.name().to_string(),
+                groups: user.groups().to_owned(),
+            };
+            (user_info.user_id.clone(), user_info)
+        }).collect();
+        self.last_refresh = Some(Instant::now());
+    }
+    pub fn get(&mut self, user_id: &sysinfo::Uid) -> Option<UserInfo> {
+        let user_id = user_id.to_string();
+        if !self.user_map.contains_key(&user_id) {
+            let refresh_due = match self.last_refresh {
+                Some(last_refresh) => last_refresh.elapsed() >= USER_TABLE_REFRESH_INTERVAL,
+                None => true,
+            };
+            if refresh_due {
+                self.refresh();
+            }
+        }
+        self.user_map.get(&user_id).cloned()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ProcessInfo {
     pub pid: u32,
@@ -21,9 +73,101 @@ pub struct ProcessInfo {
     pub exe_path: String,
     pub cmd: Vec<String>,
     pub status: String,
-    //pub user_info: Option<UserInfo>,
+    #[serde(default)]
+    pub user_info: Option<UserInfo>,
     pub start_time: DateTime<Local>,
     pub elapsed_time: u64,
+    /// Parent process ID.
+    #[serde(default)]
+    pub ppid: Option<u32>,
+    /// Ancestry chain from the parent up to the root process.
+    #[serde(default)]
+    pub ancestors: Vec<ProcessAncestor>,
+    /// cgroup path. Linux only.
+    #[serde(default)]
+    pub cgroup_path: Option<String>,
+    /// Network namespace inode. Linux only.
+    #[serde(default)]
+    pub netns: Option<u64>,
+    /// Container the process belongs to, if any.
+    #[serde(default)]
+    pub container: Option<ContainerInfo>,
+    /// systemd unit and slice of the process. Linux only.
+    #[serde(default)]
+    pub systemd_unit: Option<SystemdUnitInfo>,
+}
+
+impl ProcessInfo {
+    /// Resolve cgroup, container, systemd unit and network namespace of the process.
+    pub fn resolve_container(&mut self) {
+        // Both the container and the systemd unit are parsed from a single read of /proc/<pid>/cgroup.
+        let cgroup = container::read_cgroup(self.pid);
+        self.cgroup_path = cgroup.as_deref().and_then(container::select_cgroup_path);
+        self.netns = container::get_netns_inode(self.pid);
+        self.container = match &self.cgroup_path {
+            Some(cgroup_path) => container::parse_cgroup_path(cgroup_path),
+            None => None,
+        };
+        self.systemd_unit = match cgroup.as_deref().and_then(systemd::select_cgroup_path) {
+            Some(cgroup_path) => systemd::parse_cgroup_path(&cgroup_path),
+            None => None,
+        };
+    }
+    /// Resolve the process to roll its traffic up to. Falls back to the process itself.
+    pub fn rollup_target(&self, target: &RollupTarget) -> ProcessAncestor {
+        let mut chain: Vec<ProcessAncestor> = vec![ProcessAncestor { pid: self.pid, name: self.name.clone() }];
+        chain.extend(self.ancestors.iter().cloned());
+        let index: usize = match target {
+            RollupTarget::Pid(pid) => chain.iter().position(|p| p.pid == *pid).unwrap_or(0),
+            RollupTarget::Name(name) => chain.iter().position(|p| p.name == *name).unwrap_or(0),
+            RollupTarget::TopLevel => {
+                // The child of the init process (pid 1). If the chain does not reach init, its topmost known process.
+                if chain.len() >= 2 && chain[chain.len() - 1].pid == 1 {
+                    chain.len() - 2
+                } else {
+                    chain.len() - 1
+                }
+            }
+            RollupTarget::Depth(depth) => {
+                if *depth < chain.len() {
+                    chain.len() - 1 - depth
+                } else {
+                    0
+                }
+            }
+        };
+        chain.swap_remove(index)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProcessAncestor {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// Ancestor to roll process traffic up to.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum RollupTarget {
+    /// The process itself or the ancestor with this pid.
+    Pid(u32),
+    /// The nearest process with this name. (e.g. `bash`, `sshd`, `Google Chrome`)
+    Name(String),
+    /// The ancestor right below init. Typically a systemd service, a login session or an app bundle.
+    TopLevel,
+    /// The ancestor at this depth from the root process. 0 is the root.
+    Depth(usize),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProcessTreeNode {
+    pub pid: u32,
+    pub name: String,
+    /// Traffic of this process only.
+    pub traffic: TrafficInfo,
+    /// Traffic of this process and all descendants.
+    pub cumulative_traffic: TrafficInfo,
+    pub children: Vec<ProcessTreeNode>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -32,48 +176,257 @@ pub struct ProcessTrafficInfo {
     pub traffic: TrafficInfo,
 }
 
+/// How long exited processes are kept for attribution of late packets.
+pub const PROCESS_EXIT_RETENTION: Duration = Duration::from_secs(30);
+
+/// Maximum number of pending process events.
+const MAX_PROCESS_EVENTS: usize = 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessEventKind {
+    Started,
+    Exited,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProcessEvent {
+    pub kind: ProcessEventKind,
+    pub pid: u32,
+    pub name: String,
+    pub time: DateTime<Local>,
+}
+
+struct ProcessEntry {
+    info: ProcessInfo,
+    /// Start time reported by the OS. Detects pid reuse.
+    start_time: u64,
+}
+
+/// Long-lived process table.
+/// Only the requested and cached processes are refreshed on each call, and their ProcessInfo is built once per process lifetime.
+/// Exited processes are kept for `retention` so that late packets can still be attributed.
+pub struct ProcessCache {
+    system: sysinfo::System,
+    user_table: UserTable,
+    process_map: HashMap<u32, ProcessEntry>,
+    exited_map: HashMap<u32, (ProcessInfo, Instant)>,
+    events: Vec<ProcessEvent>,
+    retention: Duration,
+}
+
+impl ProcessCache {
+    pub fn new() -> Self {
+        ProcessCache::with_retention(PROCESS_EXIT_RETENTION)
+    }
+    pub fn with_retention(retention: Duration) -> Self {
+        ProcessCache {
+            system: sysinfo::System::new(),
+            user_table: UserTable::new(),
+            process_map: HashMap::new(),
+            exited_map: HashMap::new(),
+            events: Vec::new(),
+            retention,
+        }
+    }
+    /// Refresh all processes on the system.
+    pub fn refresh_all(&mut self) -> HashMap<u32, ProcessInfo> {
+        self.system.refresh_processes_specifics(ProcessRefreshKind::new().with_user());
+        let pids: HashSet<u32> = self.system.processes().keys().map(|pid| pid.as_u32()).collect();
+        let now = Instant::now();
+        let gone: Vec<u32> = self.process_map.keys().filter(|pid| !pids.contains(pid)).copied().collect();
+        for pid in gone {
+            self.mark_exited(pid, now);
+        }
+        self.update(&pids, false)
+    }
+    /// Refresh the given processes and return their ProcessInfo.
+    /// Processes that exited recently are returned from the exited list.
+    pub fn refresh_pids(&mut self, pids: &HashSet<u32>) -> HashMap<u32, ProcessInfo> {
+        self.update(pids, true)
+    }
+    fn update(&mut self, pids: &HashSet<u32>, refresh: bool) -> HashMap<u32, ProcessInfo> {
+        let now = Instant::now();
+        let mut process_map: HashMap<u32, ProcessInfo> = HashMap::new();
+        let mut refreshed: HashSet<u32> = HashSet::new();
+        for pid in pids {
+            let sys_pid = Pid::from_u32(*pid);
+            let alive = if refresh {
+                self.system.refresh_process_specifics(sys_pid, ProcessRefreshKind::new().with_user())
+            } else {
+                self.system.process(sys_pid).is_some()
+            };
+            if !alive {
+                self.mark_exited(*pid, now);
+                if let Some((info, _)) = self.exited_map.get(pid) {
+                    process_map.insert(*pid, info.clone());
+                }
+                continue;
+            }
+            refreshed.insert(*pid);
+            let (start_time, status, run_time) = match self.system.process(sys_pid) {
+                Some(proc) => (proc.start_time(), proc.status().to_string(), proc.run_time()),
+                None => continue,
+            };
+            if let Some(entry) = self.process_map.get_mut(pid) {
+                if entry.start_time == start_time {
+                    entry.info.status = status;
+                    entry.info.elapsed_time = run_time;
+                    process_map.insert(*pid, entry.info.clone());
+                    continue;
+                }
+                // The pid was reused by a new process.
+                self.mark_exited(*pid, now);
+            }
+            if refresh {
+                refresh_ancestors(&mut self.system, sys_pid, &mut refreshed);
+            }
+            if let Some(info) = new_process_info(&self.system, &mut self.user_table, sys_pid) {
+                self.push_event(ProcessEventKind::Started, &info);
+                self.exited_map.remove(pid);
+                self.process_map.insert(*pid, ProcessEntry { info: info.clone(), start_time });
+                process_map.insert(*pid, info);
+            }
+        }
+        if refresh {
+            self.check_unrequested(pids, now);
+        }
+        self.expire(now);
+        process_map
+    }
+    /// Get a live or recently exited process.
+    pub fn get(&self, pid: u32) -> Option<&ProcessInfo> {
+        match self.process_map.get(&pid) {
+            Some(entry) => Some(&entry.info),
+            None => self.exited_map.get(&pid).map(|(info, _)| info),
+        }
+    }
+    /// True if the process exited within the retention period.
+    pub fn is_exited(&self, pid: u32) -> bool {
+        self.exited_map.contains_key(&pid)
+    }
+    /// Take the process start/exit events since the last call.
+    pub fn drain_events(&mut self) -> Vec<ProcessEvent> {
+        std::mem::take(&mut self.events)
+    }
+    /// Re-check cached processes missing from `pids`, which exited along with their sockets or reused their pid.
+    fn check_unrequested(&mut self, pids: &HashSet<u32>, now: Instant) {
+        let unrequested: Vec<u32> = self.process_map.keys().filter(|pid| !pids.contains(pid)).copied().collect();
+        for pid in unrequested {
+            let sys_pid = Pid::from_u32(pid);
+            let alive = self.system.refresh_process_specifics(sys_pid, ProcessRefreshKind::new())
+                && self.system.process(sys_pid).map(|proc| proc.start_time()) == self.process_map.get(&pid).map(|entry| entry.start_time);
+            if !alive {
+                self.mark_exited(pid, now);
+            }
+        }
+    }
+    fn mark_exited(&mut self, pid: u32, now: Instant) {
+        if let Some(entry) = self.process_map.remove(&pid) {
+            self.push_event(ProcessEventKind::Exited, &entry.info);
+            self.exited_map.insert(pid, (entry.info, now));
+        }
+    }
+    fn push_event(&mut self, kind: ProcessEventKind, info: &ProcessInfo) {
+        if self.events.len() >= MAX_PROCESS_EVENTS {
+            self.events.remove(0);
+        }
+        self.events.push(ProcessEvent {
+            kind,
+            pid: info.pid,
+            name: info.name.clone(),
+            time: Local::now(),
+        });
+    }
+    /// Drop exited processes past the retention period.
+    fn expire(&mut self, now: Instant) {
+        let retention = self.retention;
+        self.exited_map.retain(|_, (_, exited_at)| now.duration_since(*exited_at) < retention);
+    }
+}
+
 pub fn get_process_map() -> HashMap<u32, ProcessInfo> {
-    let mut process_map: HashMap<u32, ProcessInfo> = HashMap::new();
-    //let system: sysinfo::System = sysinfo::System::new_with_specifics(sysinfo::RefreshKind::new().with_processes(ProcessRefreshKind::everything()).with_users_list());
-    let system: sysinfo::System = sysinfo::System::new_with_specifics(sysinfo::RefreshKind::new().with_processes(ProcessRefreshKind::new()));
-    for (pid, proc) in system.processes() {
-        /* let user_info: Option<UserInfo> = 
-        if let Some(user_id) = proc.user_id() {
-            let user = system.get_user_by_id(user_id);
-            if let Some(user) = user {
-                Some(UserInfo { 
-                    user_id: user.id().to_string(), 
-                    user_name: user.name().to_string(), 
-                    group_id: user.group_id().to_string(), 
-                    groups: user.groups().to_owned(), 
-                })
-            }else{
-                None
+    let mut process_cache: ProcessCache = ProcessCache::new();
+    process_cache.refresh_all()
+}
+
+/// Build ProcessInfo from the process in `system`.
+/// Non-UTF8 paths are converted lossily and an invalid start time falls back to now.
+fn new_process_info(system: &sysinfo::System, user_table: &mut UserTable, pid: Pid) -> Option<ProcessInfo> {
+    let proc = system.process(pid)?;
+    let start_time: DateTime<Local> = match DateTime::from_timestamp(proc.start_time() as i64, 0) {
+        Some(start_time) => start_time.with_timezone(&Local),
+        None => Local::now(),
+    };
+    let mut process_info: ProcessInfo = ProcessInfo {
+        pid: pid.as_u32(),
+        name: proc.name().to_string(),
+        exe_path: proc.exe().to_string_lossy().to_string(),
+        cmd: proc.cmd().to_owned(),
+        status: proc.status().to_string(),
+        user_info: match proc.user_id() {
+            Some(user_id) => user_table.get(user_id),
+            None => None,
+        },
+        start_time,
+        elapsed_time: proc.run_time(),
+        ppid: proc.parent().map(|ppid| ppid.as_u32()),
+        ancestors: get_ancestors(system, proc.parent()),
+        cgroup_path: None,
+        netns: None,
+        container: None,
+        systemd_unit: None,
+    };
+    process_info.resolve_container();
+    Some(process_info)
+}
+
+/// Refresh the ancestors of the process. Each ancestor is refreshed once per `refreshed` set.
+fn refresh_ancestors(system: &mut sysinfo::System, pid: Pid, refreshed: &mut HashSet<u32>) {
+    let mut next: Option<Pid> = system.process(pid).and_then(|proc| proc.parent());
+    let mut depth: usize = 0;
+    while let Some(ppid) = next {
+        if depth >= MAX_ANCESTRY_DEPTH || !refreshed.insert(ppid.as_u32()) {
+            break;
+        }
+        system.refresh_process_specifics(ppid, ProcessRefreshKind::new());
+        next = system.process(ppid).and_then(|proc| proc.parent());
+        depth += 1;
+    }
+}
+
+/// Walk the parent links in `system` starting from `ppid`.
+fn get_ancestors(system: &sysinfo::System, ppid: Option<Pid>) -> Vec<ProcessAncestor> {
+    let mut ancestors: Vec<ProcessAncestor> = Vec::new();
+    let mut next: Option<Pid> = ppid;
+    while let Some(pid) = next {
+        if ancestors.len() >= MAX_ANCESTRY_DEPTH || ancestors.iter().any(|a| a.pid == pid.as_u32()) {
+            break;
+        }
+        match system.process(pid) {
+            Some(proc) => {
+                ancestors.push(ProcessAncestor { pid: pid.as_u32(), name: proc.name().to_string() });
+                next = proc.parent();
             }
-        }else {
-            None
-        }; */
-        //let _start_time: DateTime<Utc> = Utc.timestamp_opt(proc.start_time() as i64, 0).unwrap();
-        let naive_start_time: NaiveDateTime = NaiveDateTime::from_timestamp_opt(proc.start_time() as i64, 0).unwrap();
-        let local_start_time: DateTime<Local> = Local.from_utc_datetime(&naive_start_time);
-        let process_info: ProcessInfo = ProcessInfo { 
-            pid: pid.as_u32(), 
-            name: proc.name().to_string(), 
-            exe_path: proc.exe().to_str().unwrap().to_string(),
-            cmd: proc.cmd().to_owned(), 
-            status: proc.status().to_string(), 
-            //start_time: local_start_time.to_rfc3339(),
-            start_time: local_start_time,
-            elapsed_time: proc.run_time(), 
-        };
-        process_map.insert(pid.as_u32(), process_info);
+            None => break,
+        }
     }
-    process_map
+    ancestors
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserDisplayInfo {
+    pub user_id: String,
+    pub user_name: String,
+    pub process_count: usize,
+    pub traffic: TrafficInfo,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ProcessDisplayInfo {
     pub pid: u32,
     pub name: String,
+    /// systemd unit of the process. Linux only.
+    #[serde(default)]
+    pub unit: Option<String>,
     pub traffic: TrafficInfo,
 }
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use serde::{Serialize, Deserialize};
+use crate::net::traffic::TrafficInfo;
+use crate::process::ProcessInfo;
+use crate::net::stat::NetStatStrage;
+use super::{AddressFamily, SocketConnection, SocketInfo, SocketStatus, TransportProtocol};
+
+/// Which addresses a listening socket accepts traffic on.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BindScope {
+    /// Bound to a loopback address. Only reachable from this host.
+    Loopback,
+    /// Bound to the address of a specific interface.
+    Interface,
+    /// Bound to the wildcard address (0.0.0.0 or ::). Reachable on every interface.
+    All,
+}
+
+impl BindScope {
+    pub fn from_ip_addr(ip_addr: &IpAddr) -> BindScope {
+        if ip_addr.is_loopback() {
+            BindScope::Loopback
+        } else if ip_addr.is_unspecified() {
+            BindScope::All
+        } else {
+            BindScope::Interface
+        }
+    }
+    pub fn as_str(&self) -> &str {
+        match self {
+            BindScope::Loopback => "Loopback",
+            BindScope::Interface => "Interface",
+            BindScope::All => "All",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListeningPortInfo {
+    pub local_ip_addr: IpAddr,
+    pub local_port: u16,
+    pub protocol: TransportProtocol,
+    pub ip_version: AddressFamily,
+    pub bind_scope: BindScope,
+    /// Interface of the bound address. None for wildcard binds.
+    pub interface_name: Option<String>,
+    pub process: Option<ProcessInfo>,
+    /// Traffic observed on the port.
+    pub traffic: TrafficInfo,
+}
+
+impl ListeningPortInfo {
+    /// True if the port is reachable from other hosts.
+    pub fn is_exposed(&self) -> bool {
+        self.bind_scope != BindScope::Loopback
+    }
+}
+
+/// True for listening TCP and unconnected UDP sockets.
+pub fn is_listening(si: &SocketInfo) -> bool {
+    match si.protocol {
+        TransportProtocol::TCP => si.status == SocketStatus::Listen,
+        // Unconnected UDP sockets receive from any peer.
+        TransportProtocol::UDP => si.remote_ip_addr.is_none_or(|ip_addr| ip_addr.is_unspecified()),
+        TransportProtocol::RAW => false,
+    }
+}
+
+/// Listening TCP and bound UDP sockets with the owning process and the traffic observed on each port.
+/// Built from the last scan of the socket updater, so no socket table is read here.
+pub fn listening_ports(netstat_strage: &NetStatStrage, connection_map: &HashMap<SocketConnection, TrafficInfo>) -> Vec<ListeningPortInfo> {
+    build_listening_ports(&netstat_strage.get_listening_sockets(), connection_map, &netstat_strage.get_local_ip_map())
+}
+
+/// Build the listening port inventory from a socket snapshot.
+/// Exposed ports come first, then ports are sorted by protocol and port number.
+pub fn build_listening_ports(sockets_info: &[SocketInfo], connection_map: &HashMap<SocketConnection, TrafficInfo>, local_ip_map: &HashMap<IpAddr, String>) -> Vec<ListeningPortInfo> {
+    let mut listeners: Vec<ListeningPortInfo> = Vec::new();
+    for si in sockets_info {
+        if !is_listening(si) {
+            continue;
+        }
+        let bind_scope = BindScope::from_ip_addr(&si.local_ip_addr);
+        let interface_name: Option<String> = match bind_scope {
+            BindScope::All => None,
+            _ => local_ip_map.get(&si.local_ip_addr).cloned(),
+        };
+        let mut traffic: TrafficInfo = TrafficInfo::new();
+        for (conn, conn_traffic) in connection_map {
+            if conn.netns.is_some() || conn.protocol != si.protocol || conn.local_port != si.local_port {
+                continue;
+            }
+            let matched = match bind_scope {
+                // A dual-stack `::` socket also receives IPv4, unless `0.0.0.0` is bound to the port as well.
+                BindScope::All => match (si.local_ip_addr, conn.remote_ip_addr) {
+                    (IpAddr::V4(_), remote_ip_addr) => remote_ip_addr.is_ipv4(),
+                    (IpAddr::V6(_), IpAddr::V6(_)) => true,
+                    (IpAddr::V6(_), IpAddr::V4(_)) => !sockets_info.iter().any(|other| {
+                        other.protocol == si.protocol
+                            && other.local_port == si.local_port
+                            && other.local_ip_addr == IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED)
+                            && is_listening(other)
+                    }),
+                },
+                _ => match conn.local_ip_addr {
+                    Some(ip_addr) => ip_addr == si.local_ip_addr,
+                    None => interface_name.as_ref() == Some(&conn.interface_name),
+                },
+            };
+            if matched {
+                traffic.add_traffic(conn_traffic);
+            }
+        }
+        listeners.push(ListeningPortInfo {
+            local_ip_addr: si.local_ip_addr,
+            local_port: si.local_port,
+            protocol: si.protocol,
+            ip_version: si.ip_version.clone(),
+            bind_scope,
+            interface_name,
+            process: si.process.clone(),
+            traffic,
+        });
+    }
+    listeners.sort_by_key(|l| (!l.is_exposed(), l.protocol, l.local_port, l.local_ip_addr));
+    listeners
+}
@@ -0,0 +1,949 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+use xenet::packet::tcp::TcpFlags;
+use std::collections::{HashMap, HashSet};
+use netstat2::{AddressFamilyFlags, ProtocolFlags, ProtocolSocketInfo};
+use crate::thread_log;
+use crate::net::stat::NetStatStrage;
+use crate::net::traffic::TrafficInfo;
+use crate::process::{ProcessCache, ProcessEvent, ProcessEventKind, ProcessInfo, PROCESS_EXIT_RETENTION};
+
+pub mod listener;
+#[cfg(target_os = "linux")]
+pub mod netlink;
+#[cfg(target_os = "linux")]
+pub mod procfs;
+
+/// How long a vanished connection stays attributed to its process.
+pub const SOCKET_STICKY_RETENTION: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Hash, Eq, Clone, PartialOrd, Ord)]
+pub struct SocketConnection {
+    pub interface_name: String,
+    /// Local IP address. Completes the 5-tuple together with the ports and remote address.
+    #[serde(default)]
+    pub local_ip_addr: Option<IpAddr>,
+    pub local_port: u16,
+    pub remote_ip_addr: IpAddr,
+    pub remote_port: u16,
+    pub protocol: TransportProtocol,
+    /// Network namespace inode. None for the host namespace.
+    #[serde(default)]
+    pub netns: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub enum SocketStatus {
+    Closed,
+    Listen,
+    SynSent,
+    SynReceived,
+    Established,
+    FinWait1,
+    FinWait2,
+    CloseWait,
+    Closing,
+    LastAck,
+    TimeWait,
+    DeleteTcb,
+    Unknown,
+}
+
+impl SocketStatus {
+    pub fn from_netstat2_state(state: netstat2::TcpState) -> Self {
+        match state {
+            netstat2::TcpState::Closed => SocketStatus::Closed,
+            netstat2::TcpState::Listen => SocketStatus::Listen,
+            netstat2::TcpState::SynSent => SocketStatus::SynSent,
+            netstat2::TcpState::SynReceived => SocketStatus::SynReceived,
+            netstat2::TcpState::Established => SocketStatus::Established,
+            netstat2::TcpState::FinWait1 => SocketStatus::FinWait1,
+            netstat2::TcpState::FinWait2 => SocketStatus::FinWait2,
+            netstat2::TcpState::CloseWait => SocketStatus::CloseWait,
+            netstat2::TcpState::Closing => SocketStatus::Closing,
+            netstat2::TcpState::LastAck => SocketStatus::LastAck,
+            netstat2::TcpState::TimeWait => SocketStatus::TimeWait,
+            netstat2::TcpState::DeleteTcb => SocketStatus::DeleteTcb,
+            _ => SocketStatus::Unknown,
+        }
+    }
+    /// Convert the Linux kernel TCP state (`include/net/tcp_states.h`).
+    pub fn from_linux_tcp_state(state: u8) -> Self {
+        match state {
+            1 => SocketStatus::Established,
+            2 => SocketStatus::SynSent,
+            3 => SocketStatus::SynReceived,
+            4 => SocketStatus::FinWait1,
+            5 => SocketStatus::FinWait2,
+            6 => SocketStatus::TimeWait,
+            7 => SocketStatus::Closed,
+            8 => SocketStatus::CloseWait,
+            9 => SocketStatus::LastAck,
+            10 => SocketStatus::Listen,
+            11 => SocketStatus::Closing,
+            _ => SocketStatus::Unknown,
+        }
+    }
+    pub fn from_xenet_tcp_flags(flags: u8) -> Self {        
+        // match is cause unreachable pattern. so use if-else.
+        if flags == TcpFlags::SYN {
+            SocketStatus::SynSent
+        } else if flags == TcpFlags::SYN | TcpFlags::ACK {
+            SocketStatus::SynReceived
+        } else if flags == TcpFlags::ACK {
+            SocketStatus::Established
+        } else if flags == TcpFlags::FIN | TcpFlags::ACK {
+            SocketStatus::Closing
+        } else if flags == TcpFlags::FIN {
+            SocketStatus::FinWait1
+        } else {
+            SocketStatus::Unknown
+        }
+    }
+}
+
+impl std::fmt::Display for SocketStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                SocketStatus::Closed => "CLOSED",
+                SocketStatus::Listen => "LISTEN",
+                SocketStatus::SynSent => "SYN_SENT",
+                SocketStatus::SynReceived => "SYN_RCVD",
+                SocketStatus::Established => "ESTABLISHED",
+                SocketStatus::FinWait1 => "FIN_WAIT_1",
+                SocketStatus::FinWait2 => "FIN_WAIT_2",
+                SocketStatus::CloseWait => "CLOSE_WAIT",
+                SocketStatus::Closing => "CLOSING",
+                SocketStatus::LastAck => "LAST_ACK",
+                SocketStatus::TimeWait => "TIME_WAIT",
+                SocketStatus::DeleteTcb => "DELETE_TCB",
+                SocketStatus::Unknown => "UNKNOWN",
+            }
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SocketProcess {
+    pub socket_addr: SocketAddr,
+    pub protocol: TransportProtocol,
+    pub status: SocketStatus,
+    pub process: Option<ProcessInfo>,
+}
+
+impl SocketProcess {
+    pub fn new() -> Self {
+        SocketProcess {
+            socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            protocol: TransportProtocol::TCP,
+            status: SocketStatus::Unknown,
+            process: None,
+        }
+    }
+    pub fn merge(&mut self, other: &SocketProcess) {
+        self.socket_addr = other.socket_addr;
+        self.protocol = other.protocol;
+        self.status = other.status;
+        self.process = other.process.clone();
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SocketInfo {
+    pub local_ip_addr: IpAddr,
+    pub local_port: u16,
+    pub remote_ip_addr: Option<IpAddr>,
+    pub remote_port: Option<u16>,
+    pub protocol: TransportProtocol,
+    pub status: SocketStatus,
+    pub ip_version: AddressFamily,
+    pub process: Option<ProcessInfo>,
+    /// Kernel TCP metrics. Only available for TCP sockets on Linux.
+    #[serde(default)]
+    pub tcp_metrics: Option<TcpMetrics>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SocketTrafficInfo {
+    pub interface_name: String,
+    #[serde(default)]
+    pub local_ip_addr: Option<IpAddr>,
+    pub local_port: u16,
+    pub remote_ip_addr: Option<IpAddr>,
+    pub remote_port: Option<u16>,
+    pub protocol: TransportProtocol,
+    pub ip_version: AddressFamily,
+    pub process: Option<ProcessInfo>,
+    pub traffic: TrafficInfo,
+    #[serde(default)]
+    pub tcp_metrics: Option<TcpMetrics>,
+    /// Network namespace inode. None for the host namespace.
+    #[serde(default)]
+    pub netns: Option<u64>,
+}
+
+/// Per-socket TCP metrics reported by the kernel (Linux `struct tcp_info`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TcpMetrics {
+    /// Smoothed round trip time in microseconds.
+    pub rtt_us: u32,
+    /// Round trip time variance in microseconds.
+    pub rttvar_us: u32,
+    /// Minimum observed round trip time in microseconds.
+    pub min_rtt_us: u32,
+    /// Retransmission timeout in microseconds.
+    pub rto_us: u32,
+    /// Congestion window in segments.
+    pub snd_cwnd: u32,
+    pub snd_ssthresh: u32,
+    pub snd_mss: u32,
+    /// Retransmits of the current unacknowledged segment.
+    pub retransmits: u8,
+    pub total_retrans: u32,
+    pub lost: u32,
+    pub unacked: u32,
+    pub bytes_acked: u64,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_retrans: u64,
+    /// Pacing rate in bytes per second.
+    pub pacing_rate: u64,
+    /// Delivery rate in bytes per second.
+    pub delivery_rate: u64,
+}
+
+impl TcpMetrics {
+    /// Minimum length of `struct tcp_info` (up to `tcpi_total_retrans`).
+    const MIN_TCP_INFO_LEN: usize = 104;
+    /// Parse the raw `struct tcp_info`.
+    /// Fields newer than the running kernel are left as 0.
+    pub fn from_tcp_info(buf: &[u8]) -> Option<TcpMetrics> {
+        if buf.len() < Self::MIN_TCP_INFO_LEN {
+            return None;
+        }
+        let u32_at = |offset: usize| -> u32 {
+            match buf.get(offset..offset + 4) {
+                Some(b) => u32::from_ne_bytes([b[0], b[1], b[2], b[3]]),
+                None => 0,
+            }
+        };
+        let u64_at = |offset: usize| -> u64 {
+            match buf.get(offset..offset + 8) {
+                Some(b) => u64::from_ne_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]),
+                None => 0,
+            }
+        };
+        Some(TcpMetrics {
+            retransmits: buf[2],
+            rto_us: u32_at(8),
+            snd_mss: u32_at(16),
+            unacked: u32_at(24),
+            lost: u32_at(32),
+            rtt_us: u32_at(68),
+            rttvar_us: u32_at(72),
+            snd_ssthresh: u32_at(76),
+            snd_cwnd: u32_at(80),
+            total_retrans: u32_at(100),
+            pacing_rate: u64_at(104),
+            bytes_acked: u64_at(120),
+            bytes_received: u64_at(128),
+            min_rtt_us: u32_at(148),
+            delivery_rate: u64_at(160),
+            bytes_sent: u64_at(200),
+            bytes_retrans: u64_at(208),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum AddressFamily {
+    IPv4,
+    IPv6
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Hash, Eq, PartialOrd, Ord, Copy)]
+pub enum TransportProtocol {
+    TCP,
+    UDP,
+    RAW,
+}
+
+impl TransportProtocol {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TransportProtocol::TCP => "TCP",
+            TransportProtocol::UDP => "UDP",
+            TransportProtocol::RAW => "RAW",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum UnixSocketType {
+    Stream,
+    Dgram,
+    SeqPacket,
+    Unknown,
+}
+
+impl UnixSocketType {
+    pub fn from_sock_type(sock_type: u8) -> Self {
+        match sock_type {
+            1 => UnixSocketType::Stream,
+            2 => UnixSocketType::Dgram,
+            5 => UnixSocketType::SeqPacket,
+            _ => UnixSocketType::Unknown,
+        }
+    }
+    pub fn as_str(&self) -> &str {
+        match self {
+            UnixSocketType::Stream => "STREAM",
+            UnixSocketType::Dgram => "DGRAM",
+            UnixSocketType::SeqPacket => "SEQPACKET",
+            UnixSocketType::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnixSocketInfo {
+    /// Bound path. Abstract socket names start with `@`.
+    pub path: Option<String>,
+    pub socket_type: UnixSocketType,
+    pub status: SocketStatus,
+    pub inode: u32,
+    pub peer_inode: Option<u32>,
+    pub process: Option<ProcessInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Hash, Eq, Clone, PartialOrd, Ord, Copy)]
+pub struct ProtocolSocketAddress {
+    pub socket: SocketAddr,
+    pub protocol: TransportProtocol,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Hash, Eq, Clone, PartialOrd, Ord)]
+pub struct LocalSocket {
+    pub interface_name: String,
+    /// Bound local IP address. None if bound to any address.
+    #[serde(default)]
+    pub local_ip_addr: Option<IpAddr>,
+    pub port: u16,
+    pub protocol: TransportProtocol,
+}
+
+impl LocalSocket {
+    pub fn new(interface_name: String, local_ip_addr: Option<IpAddr>, port: u16, protocol: TransportProtocol) -> Self {
+        LocalSocket {
+            interface_name,
+            local_ip_addr,
+            port,
+            protocol,
+        }
+    }
+    pub fn to_key_string(&self) -> String {
+        match self.local_ip_addr {
+            Some(ip_addr) => format!("{}-{}-{}-{}", self.interface_name, ip_addr, self.port, self.protocol.as_str()),
+            None => format!("{}-{}-{}", self.interface_name, self.port, self.protocol.as_str()),
+        }
+    }
+}
+
+/// Find the socket owning the connection.
+/// Looks up the full 5-tuple first, then the socket bound to the local address, then the socket bound to any address.
+/// The fallbacks attribute accepted connections to their listening socket.
+/// Connections in other network namespaces are only matched by 5-tuple.
+pub fn lookup_socket_process<'a>(conn: &SocketConnection, connection_socket_map: &'a HashMap<SocketConnection, SocketProcess>, local_socket_map: &'a HashMap<LocalSocket, SocketProcess>) -> Option<&'a SocketProcess> {
+    if let Some(socket_process) = connection_socket_map.get(conn) {
+        return Some(socket_process);
+    }
+    if conn.netns.is_some() {
+        return None;
+    }
+    let mut local_socket = LocalSocket::new(conn.interface_name.clone(), conn.local_ip_addr, conn.local_port, conn.protocol);
+    if local_socket.local_ip_addr.is_some() {
+        if let Some(socket_process) = local_socket_map.get(&local_socket) {
+            return Some(socket_process);
+        }
+    }
+    local_socket.local_ip_addr = None;
+    local_socket_map.get(&local_socket)
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Hash, Eq, Clone, PartialOrd, Ord, Copy)]
+pub struct ProtocolPort {
+    pub port: u16,
+    pub protocol: TransportProtocol,
+}
+
+impl ProtocolPort {
+    pub fn new(port: u16, protocol: TransportProtocol) -> Self {
+        ProtocolPort {
+            port: port,
+            protocol: protocol,
+        }
+    }
+    pub fn to_key_string(&self) -> String {
+        format!("{}-{}", self.port, self.protocol.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SocketInfoOption {
+    pub address_family: Vec<AddressFamily>,
+    pub transport_protocol: Vec<TransportProtocol>
+}
+
+impl Default for SocketInfoOption {
+    fn default() -> SocketInfoOption {
+        SocketInfoOption {
+            address_family: vec![AddressFamily::IPv4, AddressFamily::IPv6],
+            transport_protocol: vec![TransportProtocol::TCP, TransportProtocol::UDP],
+        }
+    }
+}
+
+impl SocketInfoOption {
+    pub fn new(address_family: Vec<AddressFamily>, transport_protocol: Vec<TransportProtocol>) -> SocketInfoOption {
+        SocketInfoOption {
+            address_family: address_family,
+            transport_protocol: transport_protocol,
+        }
+    }
+    pub fn get_address_family_flags(&self) -> AddressFamilyFlags {
+        let mut flags: AddressFamilyFlags = AddressFamilyFlags::empty();
+        for af in &self.address_family {
+            match af {
+                AddressFamily::IPv4 => {
+                    flags |= AddressFamilyFlags::IPV4;
+                }
+                AddressFamily::IPv6 => {
+                    flags |= AddressFamilyFlags::IPV6;
+                }
+            }
+        }
+        flags
+    }
+    pub fn get_protocol_flags(&self) -> ProtocolFlags {
+        let mut flags: ProtocolFlags = ProtocolFlags::empty();
+        for tp in &self.transport_protocol {
+            match tp {
+                TransportProtocol::TCP => {
+                    flags |= ProtocolFlags::TCP;
+                }
+                TransportProtocol::UDP => {
+                    flags |= ProtocolFlags::UDP;
+                }
+                // Not supported by netstat2
+                TransportProtocol::RAW => {}
+            }
+        }
+        flags
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum SocketBackendType {
+    /// Cross-platform backend using netstat2.
+    Netstat2,
+    /// Linux NETLINK_SOCK_DIAG backend.
+    Netlink,
+}
+
+impl Default for SocketBackendType {
+    fn default() -> SocketBackendType {
+        if cfg!(target_os = "linux") {
+            SocketBackendType::Netlink
+        } else {
+            SocketBackendType::Netstat2
+        }
+    }
+}
+
+/// Source of socket information.
+/// Backends are long-lived so that process lookups can be cached between calls.
+pub trait SocketBackend: Send {
+    fn name(&self) -> &str;
+    fn get_sockets_info(&mut self, opt: &SocketInfoOption) -> Result<Vec<SocketInfo>, String>;
+    fn get_unix_sockets_info(&mut self) -> Result<Vec<UnixSocketInfo>, String> {
+        Err(format!("Unix sockets are not supported by the {} backend", self.name()))
+    }
+    /// Take the process start/exit events observed since the last call.
+    fn drain_process_events(&mut self) -> Vec<ProcessEvent> {
+        Vec::new()
+    }
+}
+
+/// Create the backend of the given type.
+/// Falls back to netstat2 if the type is not available on this platform.
+pub fn new_backend(backend_type: SocketBackendType) -> Box<dyn SocketBackend> {
+    match backend_type {
+        #[cfg(target_os = "linux")]
+        SocketBackendType::Netlink => Box::new(NetlinkBackend::new()),
+        _ => Box::new(Netstat2Backend::new()),
+    }
+}
+
+pub fn default_backend() -> Box<dyn SocketBackend> {
+    new_backend(SocketBackendType::default())
+}
+
+pub struct Netstat2Backend {
+    process_cache: ProcessCache,
+}
+
+impl Netstat2Backend {
+    pub fn new() -> Self {
+        Netstat2Backend {
+            process_cache: ProcessCache::new(),
+        }
+    }
+}
+
+impl SocketBackend for Netstat2Backend {
+    fn name(&self) -> &str {
+        "netstat2"
+    }
+    fn get_sockets_info(&mut self, opt: &SocketInfoOption) -> Result<Vec<SocketInfo>, String> {
+        let af_flags: AddressFamilyFlags = opt.get_address_family_flags();
+        let proto_flags: ProtocolFlags = opt.get_protocol_flags();
+        let sockets: Vec<netstat2::SocketInfo> = match netstat2::get_sockets_info(af_flags, proto_flags) {
+            Ok(sockets) => sockets,
+            Err(e) => return Err(format!("netstat2 error: {}", e)),
+        };
+        let pids: HashSet<u32> = sockets.iter().filter_map(|si| si.associated_pids.first().copied()).collect();
+        let process_map: HashMap<u32, ProcessInfo> = self.process_cache.refresh_pids(&pids);
+        let mut sockets_info: Vec<SocketInfo> = Vec::new();
+
+        for si in sockets {
+            let process: Option<ProcessInfo> = if let Some(pid) = si.associated_pids.first() {
+                process_map.get(pid).map(|pi| pi.to_owned())
+            } else {
+                None
+            };
+            match si.protocol_socket_info {
+                ProtocolSocketInfo::Tcp(tcp_si) => {
+                    if tcp_si.local_port == 0 {
+                        continue;
+                    }
+                    let socket_info = SocketInfo {
+                        local_ip_addr: tcp_si.local_addr,
+                        local_port: tcp_si.local_port,
+                        remote_ip_addr: Some(tcp_si.remote_addr),
+                        remote_port: Some(tcp_si.remote_port),
+                        protocol: TransportProtocol::TCP,
+                        status: SocketStatus::from_netstat2_state(tcp_si.state),
+                        ip_version: if tcp_si.local_addr.is_ipv4() {AddressFamily::IPv4} else {AddressFamily::IPv6},
+                        process,
+                        tcp_metrics: None,
+                    };
+                    sockets_info.push(socket_info);
+                },
+                ProtocolSocketInfo::Udp(udp_si) => {
+                    if udp_si.local_port == 0 {
+                        continue;
+                    }
+                    let socket_info = SocketInfo {
+                        local_ip_addr: udp_si.local_addr,
+                        local_port: udp_si.local_port,
+                        remote_ip_addr: None,
+                        remote_port: None,
+                        protocol: TransportProtocol::UDP,
+                        status: SocketStatus::Unknown,
+                        ip_version: if udp_si.local_addr.is_ipv4() {AddressFamily::IPv4} else {AddressFamily::IPv6},
+                        process,
+                        tcp_metrics: None,
+                    };
+                    sockets_info.push(socket_info);
+                },
+            }
+        }
+        Ok(sockets_info)
+    }
+    fn drain_process_events(&mut self) -> Vec<ProcessEvent> {
+        self.process_cache.drain_events()
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub struct NetlinkBackend {
+    socket: Option<netlink::NetlinkSocket>,
+    inode_cache: procfs::InodeProcessCache,
+    process_cache: ProcessCache,
+}
+
+#[cfg(target_os = "linux")]
+impl NetlinkBackend {
+    pub fn new() -> Self {
+        NetlinkBackend {
+            socket: None,
+            inode_cache: procfs::InodeProcessCache::new(),
+            process_cache: ProcessCache::new(),
+        }
+    }
+    /// Run `f` with the netlink socket, opening it if needed.
+    /// The socket is dropped on error so that the next call starts with a fresh one.
+    fn with_socket<T>(&mut self, f: impl FnOnce(&mut netlink::NetlinkSocket) -> Result<T, String>) -> Result<T, String> {
+        if self.socket.is_none() {
+            self.socket = Some(netlink::NetlinkSocket::open()?);
+        }
+        let result = match self.socket.as_mut() {
+            Some(socket) => f(socket),
+            None => Err(String::from("Netlink socket is not available")),
+        };
+        if result.is_err() {
+            self.socket = None;
+        }
+        result
+    }
+    fn get_process_map(&mut self, inodes: &HashSet<u64>) -> HashMap<u64, ProcessInfo> {
+        let inode_pid_map: HashMap<u64, u32> = self.inode_cache.resolve(inodes);
+        let pids: HashSet<u32> = inode_pid_map.values().copied().collect();
+        let process_map: HashMap<u32, ProcessInfo> = self.process_cache.refresh_pids(&pids);
+        let mut inode_process_map: HashMap<u64, ProcessInfo> = HashMap::new();
+        for (inode, pid) in inode_pid_map {
+            if let Some(process) = process_map.get(&pid) {
+                inode_process_map.insert(inode, process.clone());
+            }
+        }
+        inode_process_map
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl SocketBackend for NetlinkBackend {
+    fn name(&self) -> &str {
+        "netlink"
+    }
+    fn get_sockets_info(&mut self, opt: &SocketInfoOption) -> Result<Vec<SocketInfo>, String> {
+        let mut diag_msgs: Vec<(TransportProtocol, netlink::InetDiagMsg)> = Vec::new();
+        for af in &opt.address_family {
+            let family: u8 = match af {
+                AddressFamily::IPv4 => libc::AF_INET as u8,
+                AddressFamily::IPv6 => libc::AF_INET6 as u8,
+            };
+            for tp in &opt.transport_protocol {
+                let (protocol, ext): (u8, u8) = match tp {
+                    TransportProtocol::TCP => (libc::IPPROTO_TCP as u8, netlink::ext_flag(netlink::INET_DIAG_INFO)),
+                    TransportProtocol::UDP => (libc::IPPROTO_UDP as u8, 0),
+                    TransportProtocol::RAW => (libc::IPPROTO_RAW as u8, 0),
+                };
+                let result = self.with_socket(|socket| netlink::dump_inet_sockets(socket, family, protocol, ext));
+                match result {
+                    Ok(msgs) => {
+                        for msg in msgs {
+                            diag_msgs.push((*tp, msg));
+                        }
+                    }
+                    // raw_diag is an optional kernel module. Missing support is not fatal.
+                    Err(e) if *tp == TransportProtocol::RAW => {
+                        thread_log!(debug, "raw socket dump is not available: {}", e);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        let inodes: HashSet<u64> = diag_msgs.iter().map(|(_, msg)| msg.inode as u64).collect();
+        let process_map: HashMap<u64, ProcessInfo> = self.get_process_map(&inodes);
+        let mut sockets_info: Vec<SocketInfo> = Vec::new();
+        for (protocol, msg) in diag_msgs {
+            if protocol != TransportProtocol::RAW && msg.src_port == 0 {
+                continue;
+            }
+            let (remote_ip_addr, remote_port) = match protocol {
+                TransportProtocol::TCP => (Some(msg.dst_addr), Some(msg.dst_port)),
+                TransportProtocol::UDP if msg.dst_port != 0 => (Some(msg.dst_addr), Some(msg.dst_port)),
+                _ => (None, None),
+            };
+            let socket_info = SocketInfo {
+                local_ip_addr: msg.src_addr,
+                // For raw sockets, the kernel reports the IP protocol number as the source port.
+                local_port: msg.src_port,
+                remote_ip_addr,
+                remote_port,
+                protocol,
+                status: if protocol == TransportProtocol::TCP { SocketStatus::from_linux_tcp_state(msg.state) } else { SocketStatus::Unknown },
+                ip_version: if msg.family == libc::AF_INET as u8 {AddressFamily::IPv4} else {AddressFamily::IPv6},
+                process: process_map.get(&(msg.inode as u64)).cloned(),
+                tcp_metrics: if protocol == TransportProtocol::TCP {
+                    msg.get_attr(netlink::INET_DIAG_INFO).and_then(TcpMetrics::from_tcp_info)
+                } else {
+                    None
+                },
+            };
+            sockets_info.push(socket_info);
+        }
+        Ok(sockets_info)
+    }
+    fn get_unix_sockets_info(&mut self) -> Result<Vec<UnixSocketInfo>, String> {
+        let diag_msgs: Vec<netlink::UnixDiagMsg> = self.with_socket(netlink::dump_unix_sockets)?;
+        let inodes: HashSet<u64> = diag_msgs.iter().map(|msg| msg.inode as u64).collect();
+        let process_map: HashMap<u64, ProcessInfo> = self.get_process_map(&inodes);
+        let mut sockets_info: Vec<UnixSocketInfo> = Vec::new();
+        for msg in diag_msgs {
+            sockets_info.push(UnixSocketInfo {
+                path: msg.path(),
+                socket_type: UnixSocketType::from_sock_type(msg.socket_type),
+                status: SocketStatus::from_linux_tcp_state(msg.state),
+                inode: msg.inode,
+                peer_inode: msg.peer_inode(),
+                process: process_map.get(&(msg.inode as u64)).cloned(),
+            });
+        }
+        Ok(sockets_info)
+    }
+    fn drain_process_events(&mut self) -> Vec<ProcessEvent> {
+        self.process_cache.drain_events()
+    }
+}
+
+/// Get sockets info with the default backend for this platform.
+pub fn get_sockets_info(opt: SocketInfoOption) -> Result<Vec<SocketInfo>, String> {
+    let mut backend = default_backend();
+    backend.get_sockets_info(&opt)
+}
+
+/// Get unix domain sockets info with the default backend for this platform.
+pub fn get_unix_sockets_info() -> Result<Vec<UnixSocketInfo>, String> {
+    let mut backend = default_backend();
+    backend.get_unix_sockets_info()
+}
+
+/// Event that triggers a socket table rescan before the polling interval elapses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SocketEvent {
+    ProcessFork(u32),
+    ProcessExec(u32),
+    ProcessExit(u32),
+    /// Packet capture saw a flow that is not attributed to any socket yet.
+    NewFlow(SocketConnection),
+}
+
+/// Options for the socket table updater.
+#[derive(Debug, Clone)]
+pub struct SocketUpdateOption {
+    /// Interval between full socket table scans.
+    pub interval: Duration,
+    /// Rescan on process and new flow events. Polling remains as a fallback.
+    pub event_driven: bool,
+}
+
+impl SocketUpdateOption {
+    pub fn new() -> Self {
+        SocketUpdateOption {
+            interval: DEFAULT_SOCKET_SCAN_INTERVAL,
+            event_driven: true,
+        }
+    }
+}
+
+/// Default interval between full socket table scans.
+pub const DEFAULT_SOCKET_SCAN_INTERVAL: Duration = Duration::from_secs(10);
+/// Minimum interval between event-triggered scans. Bursts of events are coalesced into one scan.
+const MIN_SOCKET_SCAN_INTERVAL: Duration = Duration::from_millis(100);
+/// Maximum number of pending socket events. Events beyond it are dropped since they are coalesced anyway.
+const SOCKET_EVENT_QUEUE_SIZE: usize = 1024;
+
+/// Forward proc connector events (fork/exec/exit) to `tx`.
+/// The inet_diag multicast groups only report socket destruction, so process events are used to catch new sockets.
+#[cfg(target_os = "linux")]
+pub fn start_proc_event_listener(tx: SyncSender<SocketEvent>) -> Result<std::thread::JoinHandle<()>, String> {
+    let mut socket = netlink::NetlinkSocket::open_proc_connector()?;
+    let handle = std::thread::Builder::new().name(String::from("proc-event-thread")).spawn(move || {
+        loop {
+            let payloads = match socket.recv_messages() {
+                Ok(payloads) => payloads,
+                Err(e) => {
+                    thread_log!(error, "[proc_event] {}", e);
+                    return;
+                }
+            };
+            for payload in payloads {
+                let event = match netlink::ProcEvent::from_bytes(&payload) {
+                    Some(netlink::ProcEvent::Fork { child_pid, .. }) => SocketEvent::ProcessFork(child_pid),
+                    Some(netlink::ProcEvent::Exec { pid }) => SocketEvent::ProcessExec(pid),
+                    Some(netlink::ProcEvent::Exit { pid }) => SocketEvent::ProcessExit(pid),
+                    None => continue,
+                };
+                match tx.try_send(event) {
+                    // A full queue already has a rescan pending.
+                    Ok(_) | Err(TrySendError::Full(_)) => {}
+                    // The receiver is gone. Stop listening.
+                    Err(TrySendError::Disconnected(_)) => return,
+                }
+            }
+        }
+    });
+    match handle {
+        Ok(handle) => Ok(handle),
+        Err(e) => Err(format!("Failed to spawn proc event thread: {}", e)),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn start_proc_event_listener(_tx: SyncSender<SocketEvent>) -> Result<std::thread::JoinHandle<()>, String> {
+    Err(String::from("Process events are only supported on Linux"))
+}
+
+pub fn start_socket_info_update(netstat_strage: &mut Arc<NetStatStrage>) {
+    start_socket_info_update_with_backend(netstat_strage, default_backend(), SocketUpdateOption::new());
+}
+
+/// Keep the socket maps of `netstat_strage` up to date.
+/// The socket table is scanned every `option.interval`, and immediately on process and new flow events if `option.event_driven` is set.
+pub fn start_socket_info_update_with_backend(netstat_strage: &mut Arc<NetStatStrage>, mut backend: Box<dyn SocketBackend>, option: SocketUpdateOption) {
+    let (tx, rx): (SyncSender<SocketEvent>, Receiver<SocketEvent>) = sync_channel(SOCKET_EVENT_QUEUE_SIZE);
+    let interval: Duration = option.interval.max(MIN_SOCKET_SCAN_INTERVAL);
+    if option.event_driven {
+        netstat_strage.set_socket_event_sender(Some(tx.clone()));
+        match start_proc_event_listener(tx.clone()) {
+            Ok(_) => thread_log!(info, "[socket_info_update] Listening for process events"),
+            Err(e) => thread_log!(info, "[socket_info_update] Process events are not available: {}. Using new flow events and polling.", e),
+        }
+    }
+    let mut local_ip_map: HashMap<IpAddr, String> = HashMap::new();
+    let opt = SocketInfoOption::default();
+    // pid -> exit time of recently exited processes
+    let mut exited_map: HashMap<u32, Instant> = HashMap::new();
+    // 5-tuple -> time the connection vanished from the socket table
+    let mut vanished_map: HashMap<SocketConnection, Instant> = HashMap::new();
+    loop {
+        if local_ip_map.is_empty() {
+            local_ip_map = netstat_strage.get_local_ip_map();
+        }
+        let sockets_info = match backend.get_sockets_info(&opt) {
+            Ok(sockets_info) => sockets_info,
+            Err(e) => {
+                thread_log!(error, "[socket_info_update] {} backend error: {}", backend.name(), e);
+                std::thread::sleep(interval);
+                continue;
+            }
+        };
+        for event in backend.drain_process_events() {
+            thread_log!(debug, "[socket_info_update] process {:?}: {} ({})", event.kind, event.name, event.pid);
+            match event.kind {
+                ProcessEventKind::Exited => {
+                    exited_map.insert(event.pid, Instant::now());
+                }
+                ProcessEventKind::Started => {
+                    exited_map.remove(&event.pid);
+                }
+            }
+        }
+        exited_map.retain(|_, exited_at| exited_at.elapsed() < PROCESS_EXIT_RETENTION);
+        apply_socket_snapshot(netstat_strage, &sockets_info, &local_ip_map, &exited_map, &mut vanished_map);
+        let scanned_at = Instant::now();
+        // Wait for the next event or the polling interval.
+        match rx.recv_timeout(interval) {
+            Ok(event) => {
+                thread_log!(debug, "[socket_info_update] rescan on {:?}", event);
+                let elapsed = scanned_at.elapsed();
+                if elapsed < MIN_SOCKET_SCAN_INTERVAL {
+                    std::thread::sleep(MIN_SOCKET_SCAN_INTERVAL - elapsed);
+                }
+                // Coalesce the events received meanwhile
+                while rx.try_recv().is_ok() {}
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => std::thread::sleep(interval),
+        }
+    }
+}
+
+/// Interface names and bound address of the socket.
+/// Sockets bound to any address are registered on every interface.
+fn socket_interfaces(si: &SocketInfo, local_ip_map: &HashMap<IpAddr, String>) -> (Vec<String>, Option<IpAddr>) {
+    if si.local_ip_addr.is_unspecified() {
+        let mut interface_names: Vec<String> = local_ip_map.values().cloned().collect();
+        interface_names.sort();
+        interface_names.dedup();
+        (interface_names, None)
+    } else {
+        match local_ip_map.get(&si.local_ip_addr) {
+            Some(interface_name) => (vec![interface_name.to_owned()], Some(si.local_ip_addr)),
+            None => (vec![], None),
+        }
+    }
+}
+
+/// Update the socket maps of `netstat_strage` from a socket snapshot.
+/// Connected sockets are keyed by 5-tuple and stay attributed for `SOCKET_STICKY_RETENTION` after they vanish.
+/// Listening and unconnected sockets are keyed by local address.
+fn apply_socket_snapshot(netstat_strage: &Arc<NetStatStrage>, sockets_info: &[SocketInfo], local_ip_map: &HashMap<IpAddr, String>, exited_map: &HashMap<u32, Instant>, vanished_map: &mut HashMap<SocketConnection, Instant>) {
+    let mut local_sockets: HashMap<LocalSocket, SocketProcess> = HashMap::new();
+    let mut connections: HashMap<SocketConnection, SocketProcess> = HashMap::new();
+    let mut tcp_metrics_map: HashMap<SocketConnection, TcpMetrics> = HashMap::new();
+    for si in sockets_info {
+        let socket_process = SocketProcess {
+            socket_addr: SocketAddr::new(si.local_ip_addr, si.local_port),
+            protocol: si.protocol,
+            status: si.status,
+            process: si.process.clone(),
+        };
+        let (interface_names, local_ip_addr) = socket_interfaces(si, local_ip_map);
+        for interface_name in interface_names {
+            match (si.remote_ip_addr, si.remote_port) {
+                (Some(remote_ip_addr), Some(remote_port)) if remote_port != 0 && !remote_ip_addr.is_unspecified() => {
+                    let conn = SocketConnection {
+                        interface_name,
+                        local_ip_addr,
+                        local_port: si.local_port,
+                        remote_ip_addr,
+                        remote_port,
+                        protocol: si.protocol,
+                        netns: None,
+                    };
+                    if let Some(tcp_metrics) = &si.tcp_metrics {
+                        tcp_metrics_map.insert(conn.clone(), tcp_metrics.clone());
+                    }
+                    connections.insert(conn, socket_process.clone());
+                }
+                _ => {
+                    let local_socket = LocalSocket::new(interface_name, local_ip_addr, si.local_port, si.protocol);
+                    local_sockets.insert(local_socket, socket_process.clone());
+                }
+            }
+        }
+    }
+    netstat_strage.set_tcp_metrics_map(tcp_metrics_map);
+    netstat_strage.set_listening_sockets(sockets_info.iter().filter(|si| listener::is_listening(si)).cloned().collect());
+    // Update the 5-tuple map. Vanished connections are kept for a while so that late packets stay attributed.
+    match netstat_strage.connection_socket_map.lock() {
+        Ok(mut connection_socket_inner) => {
+            for conn in connection_socket_inner.keys() {
+                if !connections.contains_key(conn) {
+                    vanished_map.entry(conn.clone()).or_insert_with(Instant::now);
+                }
+            }
+            vanished_map.retain(|conn, vanished_at| !connections.contains_key(conn) && vanished_at.elapsed() < SOCKET_STICKY_RETENTION);
+            connection_socket_inner.retain(|conn, _| connections.contains_key(conn) || vanished_map.contains_key(conn));
+            connection_socket_inner.extend(connections);
+        }
+        Err(e) => {
+            thread_log!(error, "[socket_info_update] lock error: {}", e);
+        }
+    }
+    // Lock the local_socket_map
+    let mut local_socket_inner = match netstat_strage.local_socket_map.lock() {
+        Ok(local_socket_inner) => local_socket_inner,
+        Err(e) => {
+            thread_log!(error, "[socket_info_update] lock error: {}", e);
+            return;
+        }
+    };
+    // Remove old socket info. Sockets of recently exited processes are kept for late packets.
+    local_socket_inner.retain(|local_socket, socket_process| {
+        let recently_exited = match &socket_process.process {
+            Some(process) => exited_map.contains_key(&process.pid),
+            None => false,
+        };
+        local_sockets.contains_key(local_socket) || recently_exited
+    });
+    local_socket_inner.extend(local_sockets);
+}
@@ -0,0 +1,395 @@
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::io::RawFd;
+
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+const NLMSG_HDR_LEN: usize = 16;
+const INET_DIAG_REQ_V2_LEN: usize = 56;
+const INET_DIAG_MSG_LEN: usize = 72;
+const UNIX_DIAG_REQ_LEN: usize = 24;
+const UNIX_DIAG_MSG_LEN: usize = 16;
+const RTA_HDR_LEN: usize = 4;
+const RECV_BUF_SIZE: usize = 32 * 1024;
+
+/// All TCP states.
+pub const ALL_STATES: u32 = 0xffffffff;
+/// inet_diag extension for `struct tcp_info`.
+pub const INET_DIAG_INFO: u16 = 2;
+pub const UDIAG_SHOW_NAME: u32 = 0x00000001;
+pub const UDIAG_SHOW_PEER: u32 = 0x00000004;
+pub const UNIX_DIAG_NAME: u16 = 0;
+pub const UNIX_DIAG_PEER: u16 = 2;
+/// Proc connector multicast group and value (`CN_IDX_PROC`, `CN_VAL_PROC`).
+pub const CN_IDX_PROC: u32 = 1;
+pub const CN_VAL_PROC: u32 = 1;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const CN_MSG_LEN: usize = 20;
+const PROC_EVENT_FORK: u32 = 0x00000001;
+const PROC_EVENT_EXEC: u32 = 0x00000002;
+const PROC_EVENT_EXIT: u32 = 0x80000000;
+/// Offset of the event data in `struct proc_event` (what, cpu, timestamp_ns).
+const PROC_EVENT_DATA_OFFSET: usize = 16;
+
+/// Netlink socket for NETLINK_SOCK_DIAG requests and NETLINK_CONNECTOR events.
+pub struct NetlinkSocket {
+    fd: RawFd,
+    seq: u32,
+}
+
+impl NetlinkSocket {
+    pub fn open() -> Result<Self, String> {
+        NetlinkSocket::open_with(libc::NETLINK_SOCK_DIAG, 0)
+    }
+    /// Open a netlink socket of the protocol and join the multicast groups.
+    pub fn open_with(protocol: libc::c_int, groups: u32) -> Result<Self, String> {
+        let fd = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, protocol) };
+        if fd < 0 {
+            return Err(format!("Failed to open netlink socket (protocol {}): {}", protocol, io::Error::last_os_error()));
+        }
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = groups;
+        let ret = unsafe {
+            libc::bind(fd, &addr as *const libc::sockaddr_nl as *const libc::sockaddr, mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t)
+        };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(format!("Failed to bind netlink socket: {}", err));
+        }
+        Ok(NetlinkSocket { fd, seq: 0 })
+    }
+    /// Send a dump request and collect the payload of every reply message.
+    pub fn dump(&mut self, request: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+        self.seq = self.seq.wrapping_add(1);
+        let seq = self.seq;
+        let mut msg: Vec<u8> = Vec::with_capacity(NLMSG_HDR_LEN + request.len());
+        msg.extend_from_slice(&((NLMSG_HDR_LEN + request.len()) as u32).to_ne_bytes());
+        msg.extend_from_slice(&SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+        msg.extend_from_slice(&((libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16).to_ne_bytes());
+        msg.extend_from_slice(&seq.to_ne_bytes());
+        msg.extend_from_slice(&0u32.to_ne_bytes());
+        msg.extend_from_slice(request);
+        let sent = unsafe { libc::send(self.fd, msg.as_ptr() as *const libc::c_void, msg.len(), 0) };
+        if sent < 0 {
+            return Err(format!("Failed to send sock_diag request: {}", io::Error::last_os_error()));
+        }
+        let mut payloads: Vec<Vec<u8>> = Vec::new();
+        let mut buf = vec![0u8; RECV_BUF_SIZE];
+        loop {
+            let received = unsafe { libc::recv(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+            if received < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(format!("Failed to receive sock_diag reply: {}", err));
+            }
+            let mut offset: usize = 0;
+            let received = received as usize;
+            while offset + NLMSG_HDR_LEN <= received {
+                let msg_len = read_u32(&buf, offset) as usize;
+                let msg_type = read_u16(&buf, offset + 4);
+                let msg_seq = read_u32(&buf, offset + 8);
+                if msg_len < NLMSG_HDR_LEN || offset + msg_len > received {
+                    return Err(String::from("Malformed netlink message"));
+                }
+                if msg_seq == seq {
+                    match msg_type as i32 {
+                        libc::NLMSG_DONE => return Ok(payloads),
+                        libc::NLMSG_ERROR => {
+                            let errno = if msg_len >= NLMSG_HDR_LEN + 4 {
+                                -(read_u32(&buf, offset + NLMSG_HDR_LEN) as i32)
+                            } else {
+                                0
+                            };
+                            if errno == 0 {
+                                return Ok(payloads);
+                            }
+                            return Err(format!("sock_diag error: {}", io::Error::from_raw_os_error(errno)));
+                        }
+                        _ => payloads.push(buf[offset + NLMSG_HDR_LEN..offset + msg_len].to_vec()),
+                    }
+                }
+                offset += align(msg_len);
+            }
+        }
+    }
+}
+
+impl NetlinkSocket {
+    /// Open a proc connector socket and subscribe to process events. Requires CAP_NET_ADMIN.
+    pub fn open_proc_connector() -> Result<Self, String> {
+        let socket = NetlinkSocket::open_with(libc::NETLINK_CONNECTOR, CN_IDX_PROC)?;
+        let mut msg: Vec<u8> = Vec::with_capacity(NLMSG_HDR_LEN + CN_MSG_LEN + 4);
+        msg.extend_from_slice(&((NLMSG_HDR_LEN + CN_MSG_LEN + 4) as u32).to_ne_bytes());
+        msg.extend_from_slice(&(libc::NLMSG_DONE as u16).to_ne_bytes());
+        msg.extend_from_slice(&0u16.to_ne_bytes());
+        msg.extend_from_slice(&0u32.to_ne_bytes());
+        msg.extend_from_slice(&std::process::id().to_ne_bytes());
+        // struct cn_msg
+        msg.extend_from_slice(&CN_IDX_PROC.to_ne_bytes());
+        msg.extend_from_slice(&CN_VAL_PROC.to_ne_bytes());
+        msg.extend_from_slice(&0u32.to_ne_bytes());
+        msg.extend_from_slice(&0u32.to_ne_bytes());
+        msg.extend_from_slice(&4u16.to_ne_bytes());
+        msg.extend_from_slice(&0u16.to_ne_bytes());
+        msg.extend_from_slice(&PROC_CN_MCAST_LISTEN.to_ne_bytes());
+        let sent = unsafe { libc::send(socket.fd, msg.as_ptr() as *const libc::c_void, msg.len(), 0) };
+        if sent < 0 {
+            return Err(format!("Failed to subscribe to proc connector: {}", io::Error::last_os_error()));
+        }
+        Ok(socket)
+    }
+    /// Block until the next datagram and return the payload of each message in it.
+    pub fn recv_messages(&mut self) -> Result<Vec<Vec<u8>>, String> {
+        let mut buf = vec![0u8; RECV_BUF_SIZE];
+        let received = loop {
+            let received = unsafe { libc::recv(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+            if received >= 0 {
+                break received as usize;
+            }
+            let err = io::Error::last_os_error();
+            // ENOBUFS: events were dropped under load. Keep listening.
+            if err.kind() != io::ErrorKind::Interrupted && err.raw_os_error() != Some(libc::ENOBUFS) {
+                return Err(format!("Failed to receive netlink message: {}", err));
+            }
+        };
+        let mut payloads: Vec<Vec<u8>> = Vec::new();
+        let mut offset: usize = 0;
+        while offset + NLMSG_HDR_LEN <= received {
+            let msg_len = read_u32(&buf, offset) as usize;
+            if msg_len < NLMSG_HDR_LEN || offset + msg_len > received {
+                break;
+            }
+            payloads.push(buf[offset + NLMSG_HDR_LEN..offset + msg_len].to_vec());
+            offset += align(msg_len);
+        }
+        Ok(payloads)
+    }
+}
+
+/// Process event reported by the proc connector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcEvent {
+    Fork { parent_pid: u32, child_pid: u32 },
+    Exec { pid: u32 },
+    Exit { pid: u32 },
+}
+
+impl ProcEvent {
+    /// Parse a proc connector message payload (`struct cn_msg` followed by `struct proc_event`).
+    /// Thread events are ignored. Only thread group leaders (processes) are reported.
+    pub fn from_bytes(payload: &[u8]) -> Option<ProcEvent> {
+        if payload.len() < CN_MSG_LEN || read_u32(payload, 0) != CN_IDX_PROC {
+            return None;
+        }
+        let event = &payload[CN_MSG_LEN..];
+        if event.len() < PROC_EVENT_DATA_OFFSET + 16 {
+            return None;
+        }
+        let data = PROC_EVENT_DATA_OFFSET;
+        match read_u32(event, 0) {
+            PROC_EVENT_FORK => {
+                // parent_pid, parent_tgid, child_pid, child_tgid
+                let child_pid = read_u32(event, data + 8);
+                let child_tgid = read_u32(event, data + 12);
+                if child_pid != child_tgid {
+                    return None;
+                }
+                Some(ProcEvent::Fork { parent_pid: read_u32(event, data + 4), child_pid: child_tgid })
+            }
+            PROC_EVENT_EXEC => Some(ProcEvent::Exec { pid: read_u32(event, data + 4) }),
+            PROC_EVENT_EXIT => {
+                let pid = read_u32(event, data);
+                let tgid = read_u32(event, data + 4);
+                if pid != tgid {
+                    return None;
+                }
+                Some(ProcEvent::Exit { pid: tgid })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Drop for NetlinkSocket {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+/// Parsed `struct inet_diag_msg` with its attributes.
+#[derive(Debug, Clone)]
+pub struct InetDiagMsg {
+    pub family: u8,
+    pub state: u8,
+    pub timer: u8,
+    pub retrans: u8,
+    pub src_addr: IpAddr,
+    pub src_port: u16,
+    pub dst_addr: IpAddr,
+    pub dst_port: u16,
+    pub if_index: u32,
+    pub expires: u32,
+    pub rqueue: u32,
+    pub wqueue: u32,
+    pub uid: u32,
+    pub inode: u32,
+    pub attrs: Vec<(u16, Vec<u8>)>,
+}
+
+impl InetDiagMsg {
+    pub fn from_bytes(payload: &[u8]) -> Option<InetDiagMsg> {
+        if payload.len() < INET_DIAG_MSG_LEN {
+            return None;
+        }
+        let family = payload[0];
+        Some(InetDiagMsg {
+            family,
+            state: payload[1],
+            timer: payload[2],
+            retrans: payload[3],
+            src_port: u16::from_be_bytes([payload[4], payload[5]]),
+            dst_port: u16::from_be_bytes([payload[6], payload[7]]),
+            src_addr: read_ip_addr(family, &payload[8..24])?,
+            dst_addr: read_ip_addr(family, &payload[24..40])?,
+            if_index: read_u32(payload, 40),
+            expires: read_u32(payload, 52),
+            rqueue: read_u32(payload, 56),
+            wqueue: read_u32(payload, 60),
+            uid: read_u32(payload, 64),
+            inode: read_u32(payload, 68),
+            attrs: parse_attrs(&payload[INET_DIAG_MSG_LEN..]),
+        })
+    }
+    pub fn get_attr(&self, attr_type: u16) -> Option<&[u8]> {
+        self.attrs.iter().find(|(t, _)| *t == attr_type).map(|(_, v)| v.as_slice())
+    }
+}
+
+/// Parsed `struct unix_diag_msg` with its attributes.
+#[derive(Debug, Clone)]
+pub struct UnixDiagMsg {
+    pub socket_type: u8,
+    pub state: u8,
+    pub inode: u32,
+    pub attrs: Vec<(u16, Vec<u8>)>,
+}
+
+impl UnixDiagMsg {
+    pub fn from_bytes(payload: &[u8]) -> Option<UnixDiagMsg> {
+        if payload.len() < UNIX_DIAG_MSG_LEN {
+            return None;
+        }
+        Some(UnixDiagMsg {
+            socket_type: payload[1],
+            state: payload[2],
+            inode: read_u32(payload, 4),
+            attrs: parse_attrs(&payload[UNIX_DIAG_MSG_LEN..]),
+        })
+    }
+    pub fn get_attr(&self, attr_type: u16) -> Option<&[u8]> {
+        self.attrs.iter().find(|(t, _)| *t == attr_type).map(|(_, v)| v.as_slice())
+    }
+    /// Bound path. Abstract names are returned with a leading `@`.
+    pub fn path(&self) -> Option<String> {
+        let name = self.get_attr(UNIX_DIAG_NAME)?;
+        if name.is_empty() {
+            return None;
+        }
+        if name[0] == 0 {
+            return Some(format!("@{}", String::from_utf8_lossy(&name[1..])));
+        }
+        let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        Some(String::from_utf8_lossy(&name[..end]).to_string())
+    }
+    pub fn peer_inode(&self) -> Option<u32> {
+        let peer = self.get_attr(UNIX_DIAG_PEER)?;
+        if peer.len() < 4 {
+            return None;
+        }
+        Some(read_u32(peer, 0))
+    }
+}
+
+/// Build `struct inet_diag_req_v2`.
+/// For raw sockets, `raw_protocol` is written to the pad field (`sdiag_raw_protocol`).
+pub fn inet_diag_request(family: u8, protocol: u8, ext: u8, raw_protocol: u8, states: u32) -> Vec<u8> {
+    let mut req = vec![0u8; INET_DIAG_REQ_V2_LEN];
+    req[0] = family;
+    req[1] = protocol;
+    req[2] = ext;
+    req[3] = raw_protocol;
+    req[4..8].copy_from_slice(&states.to_ne_bytes());
+    req
+}
+
+/// Build `struct unix_diag_req`.
+pub fn unix_diag_request(show: u32) -> Vec<u8> {
+    let mut req = vec![0u8; UNIX_DIAG_REQ_LEN];
+    req[0] = libc::AF_UNIX as u8;
+    req[4..8].copy_from_slice(&ALL_STATES.to_ne_bytes());
+    req[12..16].copy_from_slice(&show.to_ne_bytes());
+    req
+}
+
+/// Dump inet sockets of the given family and protocol.
+pub fn dump_inet_sockets(socket: &mut NetlinkSocket, family: u8, protocol: u8, ext: u8) -> Result<Vec<InetDiagMsg>, String> {
+    let raw_protocol = if protocol == libc::IPPROTO_RAW as u8 { protocol } else { 0 };
+    let request = inet_diag_request(family, protocol, ext, raw_protocol, ALL_STATES);
+    let payloads = socket.dump(&request)?;
+    Ok(payloads.iter().filter_map(|payload| InetDiagMsg::from_bytes(payload)).collect())
+}
+
+/// Dump unix domain sockets.
+pub fn dump_unix_sockets(socket: &mut NetlinkSocket) -> Result<Vec<UnixDiagMsg>, String> {
+    let payloads = socket.dump(&unix_diag_request(UDIAG_SHOW_NAME | UDIAG_SHOW_PEER))?;
+    Ok(payloads.iter().filter_map(|payload| UnixDiagMsg::from_bytes(payload)).collect())
+}
+
+/// Convert an inet_diag attribute type to the `idiag_ext` request bit.
+pub fn ext_flag(attr_type: u16) -> u8 {
+    1 << (attr_type - 1)
+}
+
+/// Parse a sequence of `struct rtattr`.
+pub fn parse_attrs(buf: &[u8]) -> Vec<(u16, Vec<u8>)> {
+    let mut attrs: Vec<(u16, Vec<u8>)> = Vec::new();
+    let mut offset: usize = 0;
+    while offset + RTA_HDR_LEN <= buf.len() {
+        let attr_len = read_u16(buf, offset) as usize;
+        let attr_type = read_u16(buf, offset + 2);
+        if attr_len < RTA_HDR_LEN || offset + attr_len > buf.len() {
+            break;
+        }
+        attrs.push((attr_type, buf[offset + RTA_HDR_LEN..offset + attr_len].to_vec()));
+        offset += align(attr_len);
+    }
+    attrs
+}
+
+fn read_ip_addr(family: u8, buf: &[u8]) -> Option<IpAddr> {
+    if family == libc::AF_INET as u8 {
+        Some(IpAddr::V4(Ipv4Addr::new(buf[0], buf[1], buf[2], buf[3])))
+    } else if family == libc::AF_INET6 as u8 {
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&buf[..16]);
+        Some(IpAddr::V6(Ipv6Addr::from(octets)))
+    } else {
+        None
+    }
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_ne_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]])
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
@@ -0,0 +1,79 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::time::{Duration, Instant};
+
+const PROC_DIR: &str = "/proc";
+const SOCKET_LINK_PREFIX: &str = "socket:[";
+/// Minimum interval between full /proc/*/fd scans.
+const RESCAN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Socket inode to PID map built from /proc/*/fd.
+/// The map is kept across calls and only rescanned when an unknown inode is requested.
+#[derive(Debug, Clone)]
+pub struct InodeProcessCache {
+    pub inode_map: HashMap<u64, u32>,
+    last_scan: Option<Instant>,
+}
+
+impl InodeProcessCache {
+    pub fn new() -> Self {
+        InodeProcessCache {
+            inode_map: HashMap::new(),
+            last_scan: None,
+        }
+    }
+    /// Rebuild the inode map by scanning /proc/*/fd.
+    pub fn rescan(&mut self) -> Result<(), String> {
+        let proc_entries = match fs::read_dir(PROC_DIR) {
+            Ok(entries) => entries,
+            Err(e) => return Err(format!("Failed to read {}: {}", PROC_DIR, e)),
+        };
+        let mut inode_map: HashMap<u64, u32> = HashMap::new();
+        for entry in proc_entries.flatten() {
+            let pid: u32 = match entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+                Some(pid) => pid,
+                None => continue,
+            };
+            // The process may exit or deny access while scanning. Skip it.
+            let fd_entries = match fs::read_dir(entry.path().join("fd")) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for fd_entry in fd_entries.flatten() {
+                if let Ok(link) = fs::read_link(fd_entry.path()) {
+                    if let Some(inode) = parse_socket_link(&link.to_string_lossy()) {
+                        inode_map.entry(inode).or_insert(pid);
+                    }
+                }
+            }
+        }
+        self.inode_map = inode_map;
+        self.last_scan = Some(Instant::now());
+        Ok(())
+    }
+    /// Resolve socket inodes to PIDs. Rescans /proc at most once per call when some inode is unknown.
+    pub fn resolve(&mut self, inodes: &HashSet<u64>) -> HashMap<u64, u32> {
+        let missing = inodes.iter().any(|inode| *inode != 0 && !self.inode_map.contains_key(inode));
+        let rescan_due = match self.last_scan {
+            Some(last_scan) => last_scan.elapsed() >= RESCAN_INTERVAL,
+            None => true,
+        };
+        if missing && rescan_due {
+            if let Err(e) = self.rescan() {
+                crate::thread_log!(error, "InodeProcessCache rescan error: {}", e);
+            }
+        }
+        let mut resolved: HashMap<u64, u32> = HashMap::new();
+        for inode in inodes {
+            if let Some(pid) = self.inode_map.get(inode) {
+                resolved.insert(*inode, *pid);
+            }
+        }
+        resolved
+    }
+}
+
+/// Parse a fd link target like `socket:[12345]`.
+fn parse_socket_link(link: &str) -> Option<u64> {
+    link.strip_prefix(SOCKET_LINK_PREFIX)?.strip_suffix(']')?.parse().ok()
+}
@@ -0,0 +1,126 @@
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use chrono::{DateTime, Local};
+use crate::api::client::ApiClient;
+use crate::api::{ApiData, ApiEndpoint};
+use crate::net::stat::{NetStatData, NetStatStrage};
+use crate::socket::listener::ListeningPortInfo;
+
+/// Where a frontend gets its traffic data from.
+pub trait DataSource: Send {
+    /// Description shown in the UI.
+    fn name(&self) -> String;
+    /// Start of the counters kept by the source. None if the frontend keeps its own.
+    fn since(&self) -> Option<DateTime<Local>>;
+    /// Data since the previous call, or None if nothing arrived.
+    /// The first call on a remote source returns the counters so far.
+    fn next_delta(&mut self) -> Result<Option<NetStatData>, String>;
+    /// Listening sockets of the monitored host. `data` is the frontend's merged data.
+    fn listeners(&mut self, data: &NetStatData) -> Result<Vec<ListeningPortInfo>, String>;
+}
+
+/// Capture threads in this process.
+pub struct LocalSource {
+    netstat_strage: Arc<NetStatStrage>,
+}
+
+impl LocalSource {
+    pub fn new(netstat_strage: Arc<NetStatStrage>) -> LocalSource {
+        LocalSource { netstat_strage }
+    }
+}
+
+impl DataSource for LocalSource {
+    fn name(&self) -> String {
+        String::from("local")
+    }
+    fn since(&self) -> Option<DateTime<Local>> {
+        None
+    }
+    fn next_delta(&mut self) -> Result<Option<NetStatData>, String> {
+        Ok(Some(self.netstat_strage.clone_data_and_reset()))
+    }
+    fn listeners(&mut self, data: &NetStatData) -> Result<Vec<ListeningPortInfo>, String> {
+        Ok(crate::socket::listener::listening_ports(&self.netstat_strage, &data.connection_map))
+    }
+}
+
+/// A nustat daemon, attached over its event stream. Needs no capture privileges.
+pub struct RemoteSource {
+    client: ApiClient,
+    since: DateTime<Local>,
+    receiver: Receiver<Result<ApiData, String>>,
+    /// Set once the event stream is lost. The daemon is not reattached since its counters would be counted twice.
+    error: Option<String>,
+}
+
+impl RemoteSource {
+    pub fn connect(endpoint: ApiEndpoint) -> Result<RemoteSource, String> {
+        let client = ApiClient::connect(endpoint)?;
+        let mut events = client.events()?;
+        // The stream starts with the counters up to the last drain, then the deltas continue from there.
+        let snapshot: ApiData = loop {
+            let event = events.next_event()?;
+            if event.event == "snapshot" {
+                break serde_json::from_str(&event.data).map_err(|e| format!("Invalid snapshot event: {}", e))?;
+            }
+        };
+        let since = snapshot.start;
+        let (sender, receiver) = mpsc::channel();
+        let _ = sender.send(Ok(snapshot));
+        thread::Builder::new()
+            .name(String::from("api-source"))
+            .spawn(move || loop {
+                let delta = events.next_delta();
+                let closed = delta.is_err();
+                if sender.send(delta).is_err() || closed {
+                    return;
+                }
+            })
+            .map_err(|e| e.to_string())?;
+        Ok(RemoteSource {
+            client,
+            since,
+            receiver,
+            error: None,
+        })
+    }
+}
+
+impl DataSource for RemoteSource {
+    fn name(&self) -> String {
+        self.client.endpoint().to_string()
+    }
+    fn since(&self) -> Option<DateTime<Local>> {
+        Some(self.since)
+    }
+    fn next_delta(&mut self) -> Result<Option<NetStatData>, String> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        let mut data: Option<NetStatData> = None;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Ok(delta)) => match data.as_mut() {
+                    Some(data) => data.merge(delta.into_data()),
+                    None => data = Some(delta.into_data()),
+                },
+                Ok(Err(e)) => {
+                    self.error = Some(format!("Disconnected from {}: {}", self.client.endpoint(), e));
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.error = Some(format!("Disconnected from {}", self.client.endpoint()));
+                    break;
+                }
+            }
+        }
+        // Deltas received before the error are still returned. The error is reported on the next call.
+        Ok(data)
+    }
+    fn listeners(&mut self, _data: &NetStatData) -> Result<Vec<ListeningPortInfo>, String> {
+        self.client.listeners()
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local};
+use serde::{Serialize, Deserialize};
+use crate::net::stat::NetStatData;
+use crate::sys;
+use crate::thread_log;
+
+/// File name of the session state in the config directory. (`~/.nustat/state`)
+pub const STATE_FILE_NAME: &str = "state";
+
+/// Magic bytes at the head of the state file.
+const STATE_MAGIC: [u8; 4] = *b"NSST";
+
+/// Version of the encoded `SessionState`. Bump when `NetStatData` changes in a way bincode cannot read.
+/// v2: `TrafficInfo::estimated`
+pub const STATE_SCHEMA_VERSION: u32 = 2;
+
+/// Counters saved across restarts.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionState {
+    /// When counting started. Kept across restores.
+    pub since: DateTime<Local>,
+    pub saved_at: DateTime<Local>,
+    pub data: NetStatData,
+}
+
+impl SessionState {
+    pub fn new(since: DateTime<Local>, data: NetStatData) -> Self {
+        SessionState {
+            since,
+            saved_at: Local::now(),
+            data,
+        }
+    }
+}
+
+/// Encode the state as magic bytes, the schema version (little-endian u32) and the bincode payload.
+pub fn encode_state(state: &SessionState) -> Result<Vec<u8>, String> {
+    let payload = bincode::serialize(state).map_err(|e| e.to_string())?;
+    let mut buf: Vec<u8> = Vec::with_capacity(8 + payload.len());
+    buf.extend_from_slice(&STATE_MAGIC);
+    buf.extend_from_slice(&STATE_SCHEMA_VERSION.to_le_bytes());
+    buf.extend_from_slice(&payload);
+    Ok(buf)
+}
+
+/// Decode the state, migrating older schema versions.
+pub fn decode_state(buf: &[u8]) -> Result<SessionState, String> {
+    if buf.len() < 8 || buf[0..4] != STATE_MAGIC {
+        return Err(String::from("Not a nustat state file"));
+    }
+    let version = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
+    let payload = &buf[8..];
+    match version {
+        STATE_SCHEMA_VERSION => bincode::deserialize(payload).map_err(|e| e.to_string()),
+        // Older versions are migrated here as the schema evolves.
+        // v1 lacks `TrafficInfo::estimated` and is discarded, since bincode cannot fill in defaults.
+        _ => Err(format!("Unsupported state version: {} (supported: {})", version, STATE_SCHEMA_VERSION)),
+    }
+}
+
+/// Saves and restores the session state at a fixed path.
+#[derive(Debug, Clone)]
+pub struct StateStore {
+    path: PathBuf,
+    since: DateTime<Local>,
+}
+
+impl StateStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        StateStore {
+            path: path.as_ref().to_path_buf(),
+            since: Local::now(),
+        }
+    }
+    /// Store at `~/.nustat/state`.
+    pub fn open_default() -> Option<Self> {
+        sys::get_user_file_path(STATE_FILE_NAME).map(StateStore::new)
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// Start of the counters. Now, or the restored `since` after `restore`.
+    pub fn since(&self) -> DateTime<Local> {
+        self.since
+    }
+    /// Load the saved state. Files that cannot be decoded are removed and None is returned.
+    pub fn restore(&mut self) -> Option<NetStatData> {
+        let buf = match std::fs::read(&self.path) {
+            Ok(buf) => buf,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    thread_log!(error, "[state] read error: {}", e);
+                }
+                return None;
+            }
+        };
+        match decode_state(&buf) {
+            Ok(state) => {
+                self.since = state.since;
+                Some(restorable_data(state.data))
+            }
+            Err(e) => {
+                thread_log!(warn, "[state] discarding {}: {}", self.path.display(), e);
+                if let Err(e) = std::fs::remove_file(&self.path) {
+                    thread_log!(error, "[state] remove error: {}", e);
+                }
+                None
+            }
+        }
+    }
+    /// Write the state atomically (write to a temporary file, then rename).
+    pub fn save(&self, data: &NetStatData) -> Result<(), String> {
+        let buf = encode_state(&SessionState::new(self.since, data.clone()))?;
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, buf).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp_path, &self.path).map_err(|e| e.to_string())
+    }
+    /// Remove the saved state and restart the counters.
+    pub fn clear(&mut self) -> Result<(), String> {
+        self.since = Local::now();
+        match std::fs::remove_file(&self.path) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// Drop the live socket state. It is rebuilt by the socket scanner and would be stale after a restart.
+fn restorable_data(mut data: NetStatData) -> NetStatData {
+    data.local_socket_map = HashMap::new();
+    data.connection_socket_map = HashMap::new();
+    data.tcp_metrics_map = HashMap::new();
+    data
+}
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::PathBuf;
+use chrono::{DateTime, Local};
+use serde::{Serialize, Deserialize};
+use crate::net::stat::NetStatData;
+use crate::net::traffic::TrafficInfo;
+
+/// Version of the record types, measurements, tags and fields.
+/// Names are only added within a version. Renames and removals bump it.
+/// Version 2: `local_port`, `remote_ip_addr` and `remote_port` of `nustat_connection` are fields instead of tags,
+/// so the number of series stays bounded by the interfaces, local addresses and processes.
+pub const STREAM_SCHEMA_VERSION: u32 = 2;
+
+/// Maximum size of a UDP datagram. Lines are packed into datagrams up to this size.
+pub const MAX_DATAGRAM_SIZE: usize = 1400;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StreamFormat {
+    /// One JSON object per line
+    #[default]
+    JSONLines,
+    /// InfluxDB line protocol
+    Influx,
+}
+
+impl StreamFormat {
+    pub fn from_name(name: &str) -> Option<StreamFormat> {
+        match name.to_lowercase().as_str() {
+            "jsonl" | "json-lines" | "jsonlines" | "ndjson" => Some(StreamFormat::JSONLines),
+            "influx" | "line-protocol" | "lp" => Some(StreamFormat::Influx),
+            _ => None,
+        }
+    }
+}
+
+/// Destination of the stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamTarget {
+    Stdout,
+    File(PathBuf),
+    UDP(String),
+    Unix(PathBuf),
+}
+
+impl StreamTarget {
+    /// `-` for stdout, `udp://host:port`, `unix:///path/to.sock`, `file:///path` or a plain file path.
+    pub fn parse(target: &str) -> Result<StreamTarget, String> {
+        if target.is_empty() || target == "-" || target == "stdout" {
+            Ok(StreamTarget::Stdout)
+        } else if let Some(addr) = target.strip_prefix("udp://") {
+            Ok(StreamTarget::UDP(addr.to_string()))
+        } else if let Some(path) = target.strip_prefix("unix://") {
+            if path.is_empty() {
+                return Err(format!("Missing socket path: {}", target));
+            }
+            Ok(StreamTarget::Unix(PathBuf::from(path)))
+        } else if let Some(path) = target.strip_prefix("file://") {
+            Ok(StreamTarget::File(PathBuf::from(path)))
+        } else if target.contains("://") {
+            Err(format!("Unsupported stream target: {}", target))
+        } else {
+            Ok(StreamTarget::File(PathBuf::from(target)))
+        }
+    }
+}
+
+enum StreamSink {
+    Stdout(std::io::Stdout),
+    File(BufWriter<File>),
+    Datagram(UdpSocket, SocketAddr),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixStream),
+}
+
+/// Traffic counters of a record.
+#[derive(Serialize, Debug, Clone)]
+pub struct StreamTraffic {
+    pub packets_sent: usize,
+    pub packets_received: usize,
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+    pub estimated: bool,
+}
+
+impl StreamTraffic {
+    fn from_traffic(traffic: &TrafficInfo) -> Self {
+        StreamTraffic {
+            packets_sent: traffic.packet_sent,
+            packets_received: traffic.packet_received,
+            bytes_sent: traffic.bytes_sent,
+            bytes_received: traffic.bytes_received,
+            estimated: traffic.estimated,
+        }
+    }
+}
+
+/// Entity of a record. Serialized as the `type` field.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEntity {
+    Total,
+    Host {
+        ip_addr: String,
+        host_name: String,
+        country_code: String,
+        asn: u32,
+        as_name: String,
+    },
+    Connection {
+        interface: String,
+        protocol: String,
+        local_ip_addr: String,
+        local_port: u16,
+        remote_ip_addr: String,
+        remote_port: u16,
+        pid: Option<u32>,
+        process: Option<String>,
+    },
+    Process {
+        pid: u32,
+        process: String,
+    },
+}
+
+/// Traffic of one entity in one interval.
+#[derive(Serialize, Debug, Clone)]
+pub struct StreamRecord {
+    pub schema: u32,
+    /// Start and end of the interval
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    #[serde(flatten)]
+    pub entity: StreamEntity,
+    #[serde(flatten)]
+    pub traffic: StreamTraffic,
+}
+
+/// Records of the traffic delta of one interval: the total, then per host, connection and process.
+pub fn stream_records(data: &NetStatData, start: DateTime<Local>, end: DateTime<Local>) -> Vec<StreamRecord> {
+    let record = |entity: StreamEntity, traffic: &TrafficInfo| StreamRecord {
+        schema: STREAM_SCHEMA_VERSION,
+        start,
+        end,
+        entity,
+        traffic: StreamTraffic::from_traffic(traffic),
+    };
+    let mut records: Vec<StreamRecord> = vec![record(StreamEntity::Total, &data.traffic)];
+    for host in data.get_remote_hosts(None) {
+        records.push(record(StreamEntity::Host {
+            ip_addr: host.ip_addr.to_string(),
+            host_name: host.host_name,
+            country_code: host.country_code,
+            asn: host.asn,
+            as_name: host.as_name,
+        }, &host.traffic));
+    }
+    for conn in data.get_connections(None) {
+        records.push(record(StreamEntity::Connection {
+            interface: conn.interface_name,
+            protocol: conn.protocol.as_str().to_string(),
+            local_ip_addr: conn.local_ip_addr.map(|ip_addr| ip_addr.to_string()).unwrap_or_default(),
+            local_port: conn.local_port,
+            remote_ip_addr: conn.remote_ip_addr.map(|ip_addr| ip_addr.to_string()).unwrap_or_default(),
+            remote_port: conn.remote_port.unwrap_or(0),
+            pid: conn.process.as_ref().map(|p| p.pid),
+            process: conn.process.map(|p| p.name),
+        }, &conn.traffic));
+    }
+    for process in data.get_processes(None) {
+        records.push(record(StreamEntity::Process {
+            pid: process.pid,
+            process: process.name,
+        }, &process.traffic));
+    }
+    records
+}
+
+/// Escape a measurement name. (comma and space)
+fn escape_measurement(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(',', "\\,").replace(' ', "\\ ")
+}
+
+/// Escape a tag key or value. (comma, equals sign and space)
+fn escape_tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ").replace('\n', "\\n")
+}
+
+/// Escape a string field value. (double quote and backslash)
+fn escape_field_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Format a record in the line protocol.
+/// Measurements are `nustat_total`, `nustat_host`, `nustat_connection` and `nustat_process`,
+/// tagged with `schema`. Empty tags are omitted since the line protocol does not allow them.
+pub fn format_influx_line(record: &StreamRecord) -> String {
+    let (series, fields) = influx_series_fields(record);
+    format!("{} {} {}", series, fields, record.end.timestamp_nanos_opt().unwrap_or_default())
+}
+
+/// Measurement with tags, and the fields of the record.
+fn influx_series_fields(record: &StreamRecord) -> (String, String) {
+    let mut tags: Vec<(&str, String)> = vec![("schema", record.schema.to_string())];
+    let mut fields: Vec<(&str, String)> = Vec::new();
+    let measurement = match &record.entity {
+        StreamEntity::Total => "nustat_total",
+        StreamEntity::Host { ip_addr, host_name, country_code, asn, as_name } => {
+            tags.push(("ip_addr", ip_addr.clone()));
+            tags.push(("host_name", host_name.clone()));
+            tags.push(("country_code", country_code.clone()));
+            tags.push(("asn", asn.to_string()));
+            tags.push(("as_name", as_name.clone()));
+            "nustat_host"
+        }
+        StreamEntity::Connection { interface, protocol, local_ip_addr, local_port, remote_ip_addr, remote_port, pid, process } => {
+            tags.push(("interface", interface.clone()));
+            tags.push(("protocol", protocol.clone()));
+            tags.push(("local_ip_addr", local_ip_addr.clone()));
+            tags.push(("process", process.clone().unwrap_or_default()));
+            fields.push(("local_port", format!("{}i", local_port)));
+            if !remote_ip_addr.is_empty() {
+                fields.push(("remote_ip_addr", escape_field_string(remote_ip_addr)));
+            }
+            fields.push(("remote_port", format!("{}i", remote_port)));
+            if let Some(pid) = pid {
+                fields.push(("pid", format!("{}i", pid)));
+            }
+            "nustat_connection"
+        }
+        StreamEntity::Process { pid, process } => {
+            tags.push(("pid", pid.to_string()));
+            tags.push(("process", process.clone()));
+            "nustat_process"
+        }
+    };
+    let traffic = &record.traffic;
+    fields.push(("packets_sent", format!("{}i", traffic.packets_sent)));
+    fields.push(("packets_received", format!("{}i", traffic.packets_received)));
+    fields.push(("bytes_sent", format!("{}i", traffic.bytes_sent)));
+    fields.push(("bytes_received", format!("{}i", traffic.bytes_received)));
+    fields.push(("estimated", traffic.estimated.to_string()));
+    fields.push(("duration_ms", format!("{}i", (record.end - record.start).num_milliseconds())));
+    let tags: Vec<String> = tags.into_iter().filter(|(_, value)| !value.is_empty()).map(|(key, value)| format!("{}={}", key, escape_tag(&value))).collect();
+    let fields: Vec<String> = fields.into_iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+    (format!("{},{}", escape_measurement(measurement), tags.join(",")), fields.join(","))
+}
+
+/// Format the records of an interval.
+/// In the line protocol, connections sharing a series (e.g. of the same process) are a nanosecond apart,
+/// since points with the same series and timestamp overwrite each other.
+pub fn format_lines(format: StreamFormat, records: &[StreamRecord]) -> Vec<String> {
+    match format {
+        StreamFormat::JSONLines => records.iter().filter_map(|record| serde_json::to_string(record).ok()).collect(),
+        StreamFormat::Influx => {
+            let mut series_count: HashMap<String, i64> = HashMap::new();
+            records.iter().map(|record| {
+                let (series, fields) = influx_series_fields(record);
+                let count = series_count.entry(series.clone()).or_insert(0);
+                let timestamp = record.end.timestamp_nanos_opt().unwrap_or_default() + *count;
+                *count += 1;
+                format!("{} {} {}", series, fields, timestamp)
+            }).collect()
+        }
+    }
+}
+
+/// Writes the traffic delta of every interval to the target.
+pub struct StreamWriter {
+    format: StreamFormat,
+    sink: StreamSink,
+}
+
+impl StreamWriter {
+    pub fn open(format: StreamFormat, target: &StreamTarget) -> Result<StreamWriter, String> {
+        let sink = match target {
+            StreamTarget::Stdout => StreamSink::Stdout(std::io::stdout()),
+            StreamTarget::File(path) => {
+                let file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+                StreamSink::File(BufWriter::new(file))
+            }
+            StreamTarget::UDP(addr) => {
+                let addr = addr.to_socket_addrs().map_err(|e| format!("Invalid UDP address {}: {}", addr, e))?.next().ok_or(format!("Could not resolve {}", addr))?;
+                let bind_addr: SocketAddr = if addr.is_ipv4() { "0.0.0.0:0".parse().unwrap() } else { "[::]:0".parse().unwrap() };
+                let socket = UdpSocket::bind(bind_addr).map_err(|e| e.to_string())?;
+                StreamSink::Datagram(socket, addr)
+            }
+            #[cfg(unix)]
+            StreamTarget::Unix(path) => {
+                let stream = std::os::unix::net::UnixStream::connect(path).map_err(|e| format!("Failed to connect to {}: {}", path.display(), e))?;
+                StreamSink::Unix(stream)
+            }
+            #[cfg(not(unix))]
+            StreamTarget::Unix(_) => return Err(String::from("Unix sockets are not supported on this platform")),
+        };
+        Ok(StreamWriter { format, sink })
+    }
+    pub fn format(&self) -> StreamFormat {
+        self.format
+    }
+    /// Write the records of one interval.
+    pub fn write(&mut self, data: &NetStatData, start: DateTime<Local>, end: DateTime<Local>) -> Result<(), String> {
+        let lines = format_lines(self.format, &stream_records(data, start, end));
+        self.write_lines(&lines).map_err(|e| e.to_string())
+    }
+    fn write_lines(&mut self, lines: &[String]) -> std::io::Result<()> {
+        fn write_all<W: Write>(writer: &mut W, lines: &[String]) -> std::io::Result<()> {
+            for line in lines {
+                writer.write_all(line.as_bytes())?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()
+        }
+        match &mut self.sink {
+            StreamSink::Stdout(stdout) => write_all(&mut stdout.lock(), lines),
+            StreamSink::File(file) => write_all(file, lines),
+            #[cfg(unix)]
+            StreamSink::Unix(stream) => write_all(stream, lines),
+            StreamSink::Datagram(socket, addr) => {
+                // Whole lines only. A line longer than the limit is sent alone.
+                let mut datagram: Vec<u8> = Vec::new();
+                for line in lines {
+                    if !datagram.is_empty() && datagram.len() + line.len() + 1 > MAX_DATAGRAM_SIZE {
+                        socket.send_to(&datagram, *addr)?;
+                        datagram.clear();
+                    }
+                    datagram.extend_from_slice(line.as_bytes());
+                    datagram.push(b'\n');
+                }
+                if !datagram.is_empty() {
+                    socket.send_to(&datagram, *addr)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -22,6 +22,32 @@ pub fn get_sysdate() -> String {
     now.to_rfc3339()
 }
 
+/// Whether the process runs with root privileges.
+#[cfg(unix)]
+pub fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn is_root() -> bool {
+    false
+}
+
+/// Group ID of a group name or numeric GID.
+#[cfg(unix)]
+pub fn get_group_id(group: &str) -> Option<u32> {
+    if let Ok(gid) = group.parse::<u32>() {
+        return Some(gid);
+    }
+    let name = std::ffi::CString::new(group).ok()?;
+    let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+    if entry.is_null() {
+        None
+    } else {
+        Some(unsafe { (*entry).gr_gid })
+    }
+}
+
 pub fn get_config_dir_path() -> Option<PathBuf> {
     match home::home_dir() {
         Some(mut path) => {
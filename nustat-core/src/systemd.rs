@@ -0,0 +1,81 @@
+use serde::{Serialize, Deserialize};
+use crate::net::traffic::TrafficInfo;
+
+/// Unit types that can own processes.
+const PROCESS_UNIT_SUFFIXES: [&str; 5] = [".service", ".scope", ".socket", ".mount", ".swap"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SystemdUnitInfo {
+    /// Innermost unit owning the process. (e.g. `nginx.service`, `session-3.scope`)
+    pub unit: String,
+    /// Slice the unit belongs to. (e.g. `system.slice`)
+    pub slice: Option<String>,
+    /// True if the unit is managed by a per-user systemd instance (`user@<uid>.service`).
+    pub user_unit: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SystemdUnitDisplayInfo {
+    pub unit: String,
+    pub slice: Option<String>,
+    pub process_names: Vec<String>,
+    pub traffic: TrafficInfo,
+}
+
+/// Parse the systemd unit and slice from a cgroup path.
+/// e.g. `/system.slice/nginx.service` or `/user.slice/user-1000.slice/user@1000.service/app.slice/foo.service`
+pub fn parse_cgroup_path(cgroup_path: &str) -> Option<SystemdUnitInfo> {
+    let mut unit: Option<&str> = None;
+    let mut unit_slice: Option<&str> = None;
+    let mut slice: Option<&str> = None;
+    let mut user_manager: bool = false;
+    for segment in cgroup_path.split('/') {
+        if segment.ends_with(".slice") {
+            slice = Some(segment);
+        } else if PROCESS_UNIT_SUFFIXES.iter().any(|suffix| segment.ends_with(suffix)) {
+            if let Some(prev) = unit {
+                if prev.starts_with("user@") && prev.ends_with(".service") {
+                    user_manager = true;
+                }
+            }
+            unit = Some(segment);
+            unit_slice = slice;
+        }
+    }
+    let unit = unit?;
+    Some(SystemdUnitInfo {
+        unit: unit.to_string(),
+        slice: unit_slice.map(|slice| slice.to_string()),
+        user_unit: user_manager,
+    })
+}
+
+/// Select the systemd cgroup path from the content of /proc/<pid>/cgroup.
+/// Uses the cgroup v2 unified hierarchy, or the `name=systemd` hierarchy on cgroup v1.
+pub fn select_cgroup_path(content: &str) -> Option<String> {
+    let mut unified: Option<String> = None;
+    for line in content.lines() {
+        // hierarchy-ID:controller-list:cgroup-path
+        let mut fields = line.splitn(3, ':');
+        let _id = fields.next()?;
+        let controllers = fields.next()?;
+        let path = fields.next()?;
+        if controllers == "name=systemd" {
+            return Some(path.to_string());
+        }
+        if controllers.is_empty() {
+            unified = Some(path.to_string());
+        }
+    }
+    unified
+}
+
+/// Read the systemd cgroup path of the process from /proc/<pid>/cgroup.
+pub fn get_cgroup_path(pid: u32) -> Option<String> {
+    select_cgroup_path(&crate::container::read_cgroup(pid)?)
+}
+
+/// Resolve the systemd unit of the process. Linux only.
+pub fn get_unit(pid: u32) -> Option<SystemdUnitInfo> {
+    parse_cgroup_path(&get_cgroup_path(pid)?)
+}
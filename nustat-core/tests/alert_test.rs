@@ -0,0 +1,236 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Local, TimeZone};
+use nustat_core::alert::{AlertCondition, AlertEngine, AlertOption, AlertRule};
+use nustat_core::api::client::ApiClient;
+use nustat_core::api::server::{start_api_server, ApiServerOption};
+use nustat_core::api::ApiEndpoint;
+use nustat_core::config::AppConfig;
+use nustat_core::net::host::RemoteHostInfo;
+use nustat_core::net::stat::{NetStatData, NetStatStrage};
+use nustat_core::net::traffic::{Direction, TrafficInfo};
+use nustat_core::notification::Severity;
+use nustat_core::process::ProcessInfo;
+use nustat_core::socket::listener::{BindScope, ListeningPortInfo};
+use nustat_core::socket::{AddressFamily, SocketConnection, SocketProcess, SocketStatus, TransportProtocol};
+
+extern crate nustat_core;
+
+fn at(secs: i64) -> DateTime<Local> {
+    Local.timestamp_opt(1_700_000_000 + secs, 0).unwrap()
+}
+
+/// `bytes` sent by curl to `remote_ip`:443, located in `country_code`.
+fn test_data(remote_ip: Ipv4Addr, country_code: &str, bytes: usize) -> NetStatData {
+    let conn = SocketConnection {
+        interface_name: String::from("eth0"),
+        local_ip_addr: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))),
+        local_port: 50000,
+        remote_ip_addr: IpAddr::V4(remote_ip),
+        remote_port: 443,
+        protocol: TransportProtocol::TCP,
+        netns: None,
+    };
+    let traffic = TrafficInfo { packet_sent: 1, packet_received: 1, bytes_sent: bytes, bytes_received: 10, estimated: false };
+    let mut data = NetStatData::new();
+    let mut host = RemoteHostInfo::new(String::new(), conn.remote_ip_addr);
+    host.country_code = country_code.to_string();
+    host.traffic_info = traffic.clone();
+    data.remote_hosts.insert(host.ip_addr, host);
+    data.connection_socket_map.insert(conn.clone(), SocketProcess {
+        socket_addr: SocketAddr::new(conn.local_ip_addr.unwrap(), conn.local_port),
+        protocol: TransportProtocol::TCP,
+        status: SocketStatus::Established,
+        process: Some(ProcessInfo {
+            pid: 4242,
+            name: String::from("curl"),
+            exe_path: String::new(),
+            cmd: vec![],
+            status: String::new(),
+            user_info: None,
+            start_time: Local::now(),
+            elapsed_time: 0,
+            ppid: None,
+            ancestors: vec![],
+            cgroup_path: None,
+            netns: None,
+            container: None,
+            systemd_unit: None,
+        }),
+    });
+    data.connection_map.insert(conn, traffic.clone());
+    data.traffic = traffic;
+    data
+}
+
+fn listener(port: u16, bind_scope: BindScope) -> ListeningPortInfo {
+    ListeningPortInfo {
+        local_ip_addr: IpAddr::V4(if bind_scope == BindScope::Loopback { Ipv4Addr::LOCALHOST } else { Ipv4Addr::UNSPECIFIED }),
+        local_port: port,
+        protocol: TransportProtocol::TCP,
+        ip_version: AddressFamily::IPv4,
+        bind_scope,
+        interface_name: None,
+        process: None,
+        traffic: TrafficInfo::new(),
+    }
+}
+
+fn rule(name: &str, cooldown: u64, condition: AlertCondition) -> AlertRule {
+    AlertRule { name: name.to_string(), severity: Severity::Warning, cooldown, condition }
+}
+
+#[test]
+fn test_alert_config() {
+    let json = r#"{
+        "rules": [
+            {"name": "upload", "severity": "Critical", "condition": {"type": "host_bytes", "direction": "Egress", "threshold": 1000000}},
+            {"name": "new country", "severity": "Info", "cooldown": 0, "condition": {"type": "new_country", "ignore": ["JP"]}},
+            {"name": "ssh", "condition": {"type": "process_port", "process": "curl", "port": 22}},
+            {"name": "listener", "condition": {"type": "new_listener", "exposed_only": true}}
+        ]
+    }"#;
+    let config: nustat_core::config::AlertConfig = serde_json::from_str(json).unwrap();
+    assert_eq!(config.rules.len(), 4);
+    assert_eq!(config.max_notifications, 100);
+    assert_eq!(config.rules[0].severity, Severity::Critical);
+    assert_eq!(config.rules[0].cooldown, 300);
+    assert!(matches!(config.rules[0].condition, AlertCondition::HostBytes { host: None, direction: Some(Direction::Egress), threshold: 1000000, window: 60 }));
+    assert_eq!(config.rules[2].severity, Severity::Warning);
+    // Configs without the section get no rules.
+    let config = AppConfig::new();
+    assert!(config.alert.rules.is_empty());
+    assert!(!AlertEngine::new(config.alert.to_alert_option()).has_rules());
+}
+
+#[test]
+fn test_alert_host_bytes() {
+    let mut engine = AlertEngine::new(AlertOption::new(vec![rule("upload", 120, AlertCondition::HostBytes {
+        host: None,
+        direction: Some(Direction::Egress),
+        threshold: 1000,
+        window: 60,
+    })]));
+    let remote = Ipv4Addr::new(203, 0, 113, 1);
+    assert_eq!(engine.evaluate(&test_data(remote, "", 600), None, at(0)), 0);
+    // 1200 bytes within 60s
+    assert_eq!(engine.evaluate(&test_data(remote, "", 600), None, at(30)), 1);
+    assert_eq!(engine.notifications()[0].key, "203.0.113.1");
+    assert_eq!(engine.notifications()[0].body, "1200 bytes sent to 203.0.113.1 in 60s");
+    // Still above the threshold, but within the cooldown.
+    assert_eq!(engine.evaluate(&test_data(remote, "", 600), None, at(50)), 0);
+    // The first 600 bytes left the window.
+    assert_eq!(engine.evaluate(&test_data(remote, "", 100), None, at(150)), 0);
+    assert_eq!(engine.evaluate(&test_data(remote, "", 1001), None, at(160)), 1);
+    // The repeat updates the notification.
+    assert_eq!(engine.notifications().len(), 1);
+    assert_eq!(engine.notifications()[0].count, 2);
+    assert_eq!(engine.notifications()[0].timestamp, at(160).to_rfc3339());
+}
+
+#[test]
+fn test_alert_cumulative_deltas() {
+    // Consumers of cumulative data evaluate the difference between snapshots.
+    let mut engine = AlertEngine::new(AlertOption::new(vec![rule("upload", 0, AlertCondition::HostBytes {
+        host: None,
+        direction: Some(Direction::Egress),
+        threshold: 1000,
+        window: 60,
+    })]));
+    let remote = Ipv4Addr::new(203, 0, 113, 1);
+    let previous = test_data(remote, "", 600);
+    let current = test_data(remote, "", 900);
+    let delta = current.delta_since(&previous);
+    assert_eq!(delta.traffic.bytes_sent, 300);
+    assert_eq!(delta.remote_hosts[&IpAddr::V4(remote)].traffic_info.bytes_sent, 300);
+    assert_eq!(delta.connection_map.values().next().unwrap().bytes_sent, 300);
+    assert_eq!(engine.evaluate(&previous, None, at(0)), 0);
+    assert_eq!(engine.evaluate(&delta, None, at(1)), 0);
+    // Idle hosts and connections drop out of the delta.
+    let idle = current.delta_since(&current);
+    assert!(idle.remote_hosts.is_empty());
+    assert!(idle.connection_map.is_empty());
+    assert_eq!(engine.evaluate(&test_data(remote, "", 1000).delta_since(&previous), None, at(2)), 1);
+}
+
+#[test]
+fn test_alert_new_country() {
+    let mut engine = AlertEngine::new(AlertOption::new(vec![rule("new country", 0, AlertCondition::NewCountry { ignore: vec![String::from("jp")] })]));
+    // The first evaluation is the baseline.
+    assert_eq!(engine.evaluate(&test_data(Ipv4Addr::new(203, 0, 113, 1), "US", 1), None, at(0)), 0);
+    assert_eq!(engine.evaluate(&test_data(Ipv4Addr::new(203, 0, 113, 2), "US", 1), None, at(1)), 0);
+    assert_eq!(engine.evaluate(&test_data(Ipv4Addr::new(203, 0, 113, 3), "JP", 1), None, at(2)), 0);
+    assert_eq!(engine.evaluate(&test_data(Ipv4Addr::new(203, 0, 113, 4), "DE", 1), None, at(3)), 1);
+    assert_eq!(engine.evaluate(&test_data(Ipv4Addr::new(203, 0, 113, 5), "DE", 1), None, at(4)), 0);
+    assert_eq!(engine.notifications()[0].body, "First traffic with DE from 203.0.113.4");
+
+    // Countries of a restored session replace the baseline.
+    let mut engine = AlertEngine::new(AlertOption::new(vec![rule("new country", 0, AlertCondition::NewCountry { ignore: vec![] })]));
+    engine.learn(&test_data(Ipv4Addr::new(203, 0, 113, 1), "US", 1));
+    assert_eq!(engine.evaluate(&test_data(Ipv4Addr::new(203, 0, 113, 1), "US", 1), None, at(0)), 0);
+    assert_eq!(engine.evaluate(&test_data(Ipv4Addr::new(203, 0, 113, 4), "DE", 1), None, at(1)), 1);
+}
+
+#[test]
+fn test_alert_process_port() {
+    let mut engine = AlertEngine::new(AlertOption::new(vec![
+        rule("curl https", 60, AlertCondition::ProcessPort { process: Some(String::from("curl")), port: 443 }),
+        rule("wget https", 60, AlertCondition::ProcessPort { process: Some(String::from("wget")), port: 443 }),
+        rule("any ssh", 60, AlertCondition::ProcessPort { process: None, port: 22 }),
+    ]));
+    assert_eq!(engine.evaluate(&test_data(Ipv4Addr::new(203, 0, 113, 1), "", 1), None, at(0)), 1);
+    let notification = &engine.notifications()[0];
+    assert_eq!(notification.rule, "curl https");
+    assert_eq!(notification.body, "curl (4242) connected to 203.0.113.1 port 443 (TCP)");
+    // Deduplicated by process and remote socket
+    assert_eq!(engine.evaluate(&test_data(Ipv4Addr::new(203, 0, 113, 1), "", 1), None, at(61)), 1);
+    assert_eq!(engine.evaluate(&test_data(Ipv4Addr::new(203, 0, 113, 2), "", 1), None, at(62)), 1);
+    assert_eq!(engine.notifications().len(), 2);
+    assert_eq!(engine.notifications()[1].count, 2);
+}
+
+#[test]
+fn test_alert_new_listener() {
+    let mut option = AlertOption::new(vec![rule("listener", 0, AlertCondition::NewListener { ignore: vec![8080], exposed_only: true })]);
+    option.max_notifications = 1;
+    let mut engine = AlertEngine::new(option);
+    assert!(engine.needs_listeners());
+    let data = NetStatData::new();
+    let baseline = vec![listener(22, BindScope::All)];
+    assert_eq!(engine.evaluate(&data, Some(&baseline), at(0)), 0);
+    let listeners = vec![listener(22, BindScope::All), listener(8080, BindScope::All), listener(5432, BindScope::Loopback), listener(443, BindScope::All)];
+    assert_eq!(engine.evaluate(&data, Some(&listeners), at(1)), 1);
+    assert_eq!(engine.notifications()[0].body, "TCP port 443 opened on 0.0.0.0");
+    // Reopened ports alert again.
+    assert_eq!(engine.evaluate(&data, Some(&baseline), at(2)), 0);
+    let listeners = vec![listener(22, BindScope::All), listener(443, BindScope::All), listener(3000, BindScope::All)];
+    assert_eq!(engine.evaluate(&data, Some(&listeners), at(3)), 2);
+    // Only the newest notifications are kept.
+    assert_eq!(engine.notifications().len(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_alert_api_overview() {
+    let path = std::env::temp_dir().join(format!("nustat-alert-test-{}.sock", std::process::id()));
+    let strage = Arc::new(NetStatStrage::new());
+    let mut option = ApiServerOption::new(ApiEndpoint::Unix(path.clone()));
+    option.tick_rate = Duration::from_millis(50);
+    option.alert = AlertOption::new(vec![rule("any traffic", 300, AlertCondition::HostBytes { host: None, direction: None, threshold: 0, window: 60 })]);
+    start_api_server(Arc::clone(&strage), option).unwrap();
+    strage.restore(test_data(Ipv4Addr::new(203, 0, 113, 1), "", 100));
+    let client = ApiClient::connect(ApiEndpoint::Unix(path.clone())).unwrap();
+    let mut overview = client.overview().unwrap();
+    for _ in 0..50 {
+        if !overview.notificatons.is_empty() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+        overview = client.overview().unwrap();
+    }
+    assert_eq!(overview.notificatons.len(), 1);
+    assert_eq!(overview.notificatons[0].title, "any traffic");
+    assert_eq!(overview.notificatons[0].body, "110 bytes exchanged with 203.0.113.1 in 60s");
+    let _ = std::fs::remove_file(&path);
+}
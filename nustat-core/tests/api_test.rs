@@ -0,0 +1,244 @@
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Local;
+use nustat_core::api::client::ApiClient;
+use nustat_core::api::server::{start_api_server, ApiServerOption};
+use nustat_core::api::{ApiData, ApiEndpoint, API_VERSION};
+use nustat_core::net::host::RemoteHostInfo;
+use nustat_core::net::stat::{NetStatData, NetStatStrage};
+use nustat_core::net::traffic::TrafficInfo;
+use nustat_core::socket::{SocketConnection, TransportProtocol};
+use nustat_core::source::{DataSource, LocalSource, RemoteSource};
+
+extern crate nustat_core;
+
+fn test_data() -> NetStatData {
+    let mut data = NetStatData::new();
+    data.if_name = String::from("eth0");
+    data.traffic = TrafficInfo { packet_sent: 1, packet_received: 2, bytes_sent: 100, bytes_received: 200, estimated: false };
+    data.connection_map.insert(SocketConnection {
+        interface_name: String::from("eth0"),
+        local_ip_addr: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))),
+        local_port: 50000,
+        remote_ip_addr: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)),
+        remote_port: 443,
+        protocol: TransportProtocol::TCP,
+        netns: None,
+    }, data.traffic.clone());
+    let mut host = RemoteHostInfo::new(String::new(), IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)));
+    host.traffic_info = data.traffic.clone();
+    data.remote_hosts.insert(host.ip_addr, host);
+    data
+}
+
+#[test]
+fn test_api_endpoint_parse() {
+    let addr: SocketAddr = "127.0.0.1:7391".parse().unwrap();
+    assert_eq!(ApiEndpoint::parse("127.0.0.1:7391").unwrap(), ApiEndpoint::TCP(addr));
+    assert_eq!(ApiEndpoint::parse("http://127.0.0.1:7391/").unwrap(), ApiEndpoint::TCP(addr));
+    assert_eq!(ApiEndpoint::parse("unix:///run/nustatd.sock").unwrap(), ApiEndpoint::Unix(PathBuf::from("/run/nustatd.sock")));
+    assert_eq!(ApiEndpoint::parse("unix:///run/nustatd.sock").unwrap().to_string(), "unix:///run/nustatd.sock");
+    assert!(ApiEndpoint::parse("unix://").is_err());
+    assert!(ApiEndpoint::parse("localhost").is_err());
+}
+
+#[test]
+fn test_api_data_roundtrip() {
+    let now = Local::now();
+    let json = serde_json::to_string(&ApiData::from_data(&test_data(), now, now)).unwrap();
+    let data = serde_json::from_str::<ApiData>(&json).unwrap().into_data();
+    assert_eq!(data.if_name, "eth0");
+    assert_eq!(data.traffic.bytes_received, 200);
+    assert_eq!(data.connection_map.keys().collect::<Vec<_>>(), test_data().connection_map.keys().collect::<Vec<_>>());
+    assert_eq!(data.connection_map.values().next().unwrap().bytes_sent, 100);
+}
+
+#[test]
+fn test_api_server_rejects_non_loopback() {
+    let option = ApiServerOption::new(ApiEndpoint::TCP("0.0.0.0:0".parse().unwrap()));
+    assert!(start_api_server(Arc::new(NetStatStrage::new()), option).is_err());
+}
+
+#[test]
+fn test_api_server_checks_host() {
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let mut option = ApiServerOption::new(ApiEndpoint::TCP(addr));
+    option.tick_rate = Duration::from_millis(100);
+    start_api_server(Arc::new(NetStatStrage::new()), option).unwrap();
+    let request = |host: Option<&str>| {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        match host {
+            Some(host) => write!(stream, "GET /v{}/version HTTP/1.1\r\nHost: {}\r\n\r\n", API_VERSION, host).unwrap(),
+            None => write!(stream, "GET /v{}/version HTTP/1.0\r\n\r\n", API_VERSION).unwrap(),
+        }
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    for host in ["localhost", "127.0.0.1", &addr.to_string(), "LOCALHOST:7391", "[::1]:7391"] {
+        assert!(request(Some(host)).starts_with("HTTP/1.1 200 OK"), "{}", host);
+    }
+    // DNS rebinding: a page on attacker.example resolved to 127.0.0.1
+    for host in ["attacker.example", "attacker.example:7391", "localhost.attacker.example", "[::1]x"] {
+        assert!(request(Some(host)).starts_with("HTTP/1.1 403"), "{}", host);
+    }
+    assert!(request(None).starts_with("HTTP/1.1 403"));
+    assert_eq!(ApiClient::connect(ApiEndpoint::TCP(addr)).unwrap().get::<serde_json::Value>("version").unwrap()["api_version"], API_VERSION);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_api_server_unix() {
+    let path = std::env::temp_dir().join(format!("nustat-api-test-{}.sock", std::process::id()));
+    let endpoint = ApiEndpoint::Unix(path.clone());
+    let strage = Arc::new(NetStatStrage::new());
+    let mut option = ApiServerOption::new(endpoint.clone());
+    option.tick_rate = Duration::from_millis(100);
+    start_api_server(Arc::clone(&strage), option.clone()).unwrap();
+    // A second daemon on the same socket is refused.
+    assert!(start_api_server(Arc::new(NetStatStrage::new()), option).is_err());
+
+    // Deltas are pushed every tick.
+    let client = ApiClient::connect(endpoint.clone()).unwrap();
+    let mut events = client.events().unwrap();
+    let hello = events.next_event().unwrap();
+    assert_eq!(hello.event, "hello");
+    strage.restore(test_data());
+    let mut received = 0;
+    for _ in 0..20 {
+        received += events.next_delta().unwrap().traffic.bytes_sent;
+        if received > 0 {
+            break;
+        }
+    }
+    assert_eq!(received, 100);
+
+    // The cumulative endpoints keep counting after the drain.
+    let overview = client.overview().unwrap();
+    assert_eq!(overview.traffic.bytes_sent, 100);
+    let connections = client.connections(Some(10)).unwrap();
+    assert_eq!(connections.len(), 1);
+    assert_eq!(connections[0].remote_port, Some(443));
+    assert_eq!(client.remote_hosts(None).unwrap().len(), 1);
+    assert_eq!(client.data().unwrap().into_data().connection_map.len(), 1);
+    assert!(client.get::<serde_json::Value>("unknown").unwrap_err().contains("404"));
+
+    // Raw HTTP over the socket, as curl --unix-socket would send.
+    let mut stream = std::os::unix::net::UnixStream::connect(&path).unwrap();
+    write!(stream, "GET /v{}/version HTTP/1.1\r\nHost: localhost\r\n\r\n", API_VERSION).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains(&format!("\"api_version\":{}", API_VERSION)));
+
+    let mut stream = std::os::unix::net::UnixStream::connect(&path).unwrap();
+    write!(stream, "POST /v{}/version HTTP/1.1\r\n\r\n", API_VERSION).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 405"));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_local_source() {
+    let strage = Arc::new(NetStatStrage::new());
+    strage.restore(test_data());
+    let mut source = LocalSource::new(Arc::clone(&strage));
+    assert!(source.since().is_none());
+    assert_eq!(source.next_delta().unwrap().unwrap().traffic.bytes_sent, 100);
+    assert_eq!(source.next_delta().unwrap().unwrap().traffic.bytes_sent, 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_remote_source() {
+    let path = std::env::temp_dir().join(format!("nustat-source-test-{}.sock", std::process::id()));
+    let strage = Arc::new(NetStatStrage::new());
+    let mut option = ApiServerOption::new(ApiEndpoint::Unix(path.clone()));
+    option.tick_rate = Duration::from_millis(50);
+    start_api_server(Arc::clone(&strage), option).unwrap();
+    // Traffic before the client attaches arrives in the snapshot.
+    strage.restore(test_data());
+    std::thread::sleep(Duration::from_millis(200));
+
+    let mut source = RemoteSource::connect(ApiEndpoint::Unix(path.clone())).unwrap();
+    assert!(source.since().is_some());
+    assert_eq!(source.name(), format!("unix://{}", path.display()));
+    strage.restore(test_data());
+    let mut data = NetStatData::new();
+    for _ in 0..100 {
+        if let Some(delta) = source.next_delta().unwrap() {
+            data.merge(delta);
+        }
+        if data.traffic.bytes_sent >= 200 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    // Snapshot and deltas never overlap.
+    std::thread::sleep(Duration::from_millis(200));
+    if let Some(delta) = source.next_delta().unwrap() {
+        data.merge(delta);
+    }
+    assert_eq!(data.traffic.bytes_sent, 200);
+    assert_eq!(data.connection_map.values().next().unwrap().bytes_sent, 200);
+    assert_eq!(data.if_name, "eth0");
+    let _ = std::fs::remove_file(&path);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_api_local_ip_map() {
+    let path = std::env::temp_dir().join(format!("nustat-local-ip-test-{}.sock", std::process::id()));
+    let strage = Arc::new(NetStatStrage::new());
+    let local_ip_addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let interface_name = strage.get_local_ip_map().get(&local_ip_addr).cloned().expect("loopback address not found");
+    let mut option = ApiServerOption::new(ApiEndpoint::Unix(path.clone()));
+    option.tick_rate = Duration::from_millis(50);
+    start_api_server(Arc::clone(&strage), option).unwrap();
+    let listener = std::net::TcpListener::bind((local_ip_addr, 0)).unwrap();
+    let local_port = listener.local_addr().unwrap().port();
+    // Listeners come from the scan of the socket updater.
+    let mut strage_socket = Arc::clone(&strage);
+    std::thread::spawn(move || nustat_core::socket::start_socket_info_update(&mut strage_socket));
+    // Let a drain and a socket scan happen, so the totals hold the map.
+    std::thread::sleep(Duration::from_millis(500));
+
+    let client = ApiClient::connect(ApiEndpoint::Unix(path.clone())).unwrap();
+    let data = client.data().unwrap().into_data();
+    assert_eq!(data.local_ip_map.get(&local_ip_addr), Some(&interface_name));
+    let listeners = client.listeners().unwrap();
+    let port = listeners.iter().find(|l| l.local_port == local_port && l.local_ip_addr == local_ip_addr).expect("listener not found");
+    assert_eq!(port.interface_name.as_ref(), Some(&interface_name));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_api_socket_dir_and_group() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    assert_eq!(nustat_core::sys::get_group_id("root"), Some(0));
+    assert_eq!(nustat_core::sys::get_group_id("1234"), Some(1234));
+    let mut config = nustat_core::config::ApiConfig::new();
+    config.socket_group = String::from("nustat-no-such-group");
+    assert!(config.to_server_option(Duration::from_millis(100)).is_err());
+    if nustat_core::sys::is_root() {
+        assert_eq!(ApiEndpoint::default_server_endpoint(), Some(ApiEndpoint::Unix(PathBuf::from(nustat_core::api::DEFAULT_API_SYSTEM_SOCKET_PATH))));
+    }
+
+    // The socket directory is created and the socket handed to the group.
+    let dir = std::env::temp_dir().join(format!("nustat-api-dir-test-{}", std::process::id()));
+    let path = dir.join("nustatd.sock");
+    let gid = std::fs::metadata(std::env::temp_dir()).unwrap().gid();
+    let mut option = ApiServerOption::new(ApiEndpoint::Unix(path.clone()));
+    option.socket_group = Some(gid);
+    start_api_server(Arc::new(NetStatStrage::new()), option).unwrap();
+    assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o755);
+    let metadata = std::fs::metadata(&path).unwrap();
+    assert_eq!(metadata.gid(), gid);
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o660);
+    let _ = std::fs::remove_dir_all(&dir);
+}
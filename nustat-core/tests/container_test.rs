@@ -0,0 +1,39 @@
+use nustat_core::container::{self, ContainerRuntime};
+
+extern crate nustat_core;
+
+const ID: &str = "3f1e2d4c5b6a79880716253443526170f9e8d7c6b5a4938271605f4e3d2c1b0a";
+
+#[test]
+fn test_parse_docker_cgroup() {
+    let info = container::parse_cgroup_path(&format!("/system.slice/docker-{}.scope", ID)).unwrap();
+    assert_eq!(info.runtime, ContainerRuntime::Docker);
+    assert_eq!(info.container_id, ID);
+    let info = container::parse_cgroup_path(&format!("/docker/{}", ID)).unwrap();
+    assert_eq!(info.runtime, ContainerRuntime::Docker);
+}
+
+#[test]
+fn test_parse_kubernetes_cgroup() {
+    let path = format!("/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod0a1b2c3d_4e5f_6071_8293_a4b5c6d7e8f9.slice/cri-containerd-{}.scope", ID);
+    let info = container::parse_cgroup_path(&path).unwrap();
+    assert_eq!(info.runtime, ContainerRuntime::Containerd);
+    assert_eq!(info.container_id, ID);
+    assert_eq!(info.pod_uid.as_deref(), Some("0a1b2c3d-4e5f-6071-8293-a4b5c6d7e8f9"));
+    let path = format!("/kubepods.slice/kubepods-pod0a1b2c3d_4e5f_6071_8293_a4b5c6d7e8f9.slice/crio-{}.scope", ID);
+    assert_eq!(container::parse_cgroup_path(&path).unwrap().runtime, ContainerRuntime::CRIO);
+}
+
+#[test]
+fn test_parse_podman_cgroup() {
+    let path = format!("/user.slice/user-1000.slice/user@1000.service/user.slice/libpod-{}.scope/container", ID);
+    let info = container::parse_cgroup_path(&path).unwrap();
+    assert_eq!(info.runtime, ContainerRuntime::Podman);
+    assert_eq!(info.container_id, ID);
+}
+
+#[test]
+fn test_parse_host_cgroup() {
+    assert!(container::parse_cgroup_path("/user.slice/user-1000.slice/session-2.scope").is_none());
+    assert!(container::parse_cgroup_path("/").is_none());
+}
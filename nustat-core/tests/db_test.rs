@@ -0,0 +1,13 @@
+use nustat_core::db::oui::{self, OuiDatabase};
+
+extern crate nustat_core;
+
+#[test]
+fn test_oui_vendor() {
+    let ouidb = OuiDatabase::load_from_crate().unwrap();
+    assert_eq!(ouidb.get_vendor("00:00:00:12:34:56"), Some("XEROX CORPORATION".to_string()));
+    // Locally administered (randomized) address
+    assert!(oui::is_locally_administered("da:a1:19:00:00:01"));
+    assert_eq!(ouidb.get_vendor("da:a1:19:00:00:01"), None);
+    assert!(!oui::is_locally_administered("00:00:00:12:34:56"));
+}
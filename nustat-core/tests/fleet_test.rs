@@ -0,0 +1,208 @@
+#![cfg(feature = "fleet")]
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Local;
+use nustat_core::fleet::agent::{FleetAgent, FleetAgentOption};
+use nustat_core::fleet::server::{start_fleet_api, start_fleet_server, FleetServerOption};
+use nustat_core::fleet::{FleetHost, FleetStore};
+use nustat_core::net::host::RemoteHostInfo;
+use nustat_core::net::stat::NetStatData;
+use nustat_core::net::traffic::TrafficInfo;
+use nustat_core::process::ProcessInfo;
+use nustat_core::socket::{SocketConnection, SocketProcess, SocketStatus, TransportProtocol};
+
+extern crate nustat_core;
+
+const PSK: &[u8] = b"fleet-test-pre-shared-key";
+
+/// One connection of `process` to 203.0.113.1 (AS64500) with `bytes` sent.
+fn test_data(process: &str, bytes: usize) -> NetStatData {
+    let conn = SocketConnection {
+        interface_name: String::from("eth0"),
+        local_ip_addr: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))),
+        local_port: 50000,
+        remote_ip_addr: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)),
+        remote_port: 443,
+        protocol: TransportProtocol::TCP,
+        netns: None,
+    };
+    let traffic = TrafficInfo { packet_sent: 1, packet_received: 0, bytes_sent: bytes, bytes_received: 0, estimated: false };
+    let mut data = NetStatData::new();
+    let mut host = RemoteHostInfo::new(String::new(), conn.remote_ip_addr);
+    host.asn = 64500;
+    host.as_name = String::from("Example Net");
+    host.traffic_info = traffic.clone();
+    data.remote_hosts.insert(conn.remote_ip_addr, host);
+    data.connection_socket_map.insert(conn.clone(), SocketProcess {
+        socket_addr: SocketAddr::new(conn.local_ip_addr.unwrap(), conn.local_port),
+        protocol: TransportProtocol::TCP,
+        status: SocketStatus::Established,
+        process: Some(ProcessInfo {
+            pid: 4242,
+            name: process.to_string(),
+            exe_path: String::new(),
+            cmd: vec![],
+            status: String::new(),
+            user_info: None,
+            start_time: Local::now(),
+            elapsed_time: 0,
+            ppid: None,
+            ancestors: vec![],
+            cgroup_path: None,
+            netns: None,
+            container: None,
+            systemd_unit: None,
+        }),
+    });
+    data.connection_map.insert(conn, traffic.clone());
+    data.traffic = traffic;
+    data
+}
+
+fn free_port() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+}
+
+fn agent(server: SocketAddr, agent_id: &str, psk: &[u8]) -> FleetAgent {
+    let mut option = FleetAgentOption::new(server.to_string(), psk.to_vec());
+    option.agent_id = agent_id.to_string();
+    option.hostname = agent_id.to_string();
+    FleetAgent::new(option).unwrap()
+}
+
+fn http_get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_fleet_store_merge() {
+    let store = FleetStore::new();
+    store.connect("web1", "web1", "192.0.2.1:40000", 1);
+    store.connect("web2", "web2", "192.0.2.2:40000", 1);
+    assert!(store.apply("web1", 1, test_data("nginx", 100)));
+    assert!(store.apply("web1", 2, test_data("nginx", 50)));
+    // A resend of a merged delta is dropped.
+    assert!(!store.apply("web1", 2, test_data("nginx", 50)));
+    assert!(store.apply("web2", 1, test_data("curl", 30)));
+    // Deltas of unknown agents are dropped.
+    assert!(!store.apply("db1", 1, test_data("postgres", 1)));
+
+    assert_eq!(store.agent_data("web1").unwrap().traffic.bytes_sent, 150);
+    let agents = store.agents();
+    assert_eq!(agents.iter().map(|a| a.agent_id.as_str()).collect::<Vec<_>>(), vec!["web1", "web2"]);
+    assert_eq!(agents[0].deltas, 2);
+
+    let hosts = store.top_hosts(None);
+    assert_eq!(hosts.len(), 1);
+    assert_eq!(hosts[0].agents, 2);
+    assert_eq!(hosts[0].traffic.bytes_sent, 180);
+    assert_eq!(hosts[0].asn, 64500);
+    let asns = store.top_asns(Some(10));
+    assert_eq!(asns.len(), 1);
+    assert_eq!((asns[0].hosts, asns[0].agents, asns[0].traffic.bytes_sent), (1, 2, 180));
+    let processes = store.top_processes(None);
+    assert_eq!(processes.iter().map(|p| (p.name.as_str(), p.traffic.bytes_sent)).collect::<Vec<_>>(), vec![("nginx", 150), ("curl", 30)]);
+    assert_eq!(store.top_processes(Some(1)).len(), 1);
+
+    // A restarted agent numbers its deltas from 1 again.
+    store.connect("web2", "web2", "192.0.2.2:40001", 2);
+    assert!(store.apply("web2", 1, test_data("curl", 10)));
+    assert_eq!(store.agent_data("web2").unwrap().traffic.bytes_sent, 40);
+}
+
+#[test]
+fn test_fleet_server_rejects_short_psk() {
+    let option = FleetServerOption::new(free_port(), b"short".to_vec());
+    assert!(start_fleet_server(Arc::new(FleetStore::new()), option).is_err());
+    assert!(FleetAgent::new(FleetAgentOption::new(String::from("127.0.0.1:7392"), b"short".to_vec())).is_err());
+    // The API is loopback only.
+    assert!(start_fleet_api(Arc::new(FleetStore::new()), "0.0.0.0:0".parse().unwrap()).is_err());
+}
+
+#[test]
+fn test_fleet_config_defaults() {
+    let config = nustat_core::config::FleetConfig::new();
+    assert_eq!(config.listen_addr, nustat_core::fleet::DEFAULT_FLEET_LISTEN_ADDR);
+    assert_eq!(config.api_addr, nustat_core::fleet::DEFAULT_FLEET_API_ADDR);
+    config.psk().unwrap_err();
+}
+
+#[test]
+fn test_fleet_server_caps_handshake_frames() {
+    use nustat_core::fleet::MAX_HANDSHAKE_FRAME_SIZE;
+    let server_addr = free_port();
+    start_fleet_server(Arc::new(FleetStore::new()), FleetServerOption::new(server_addr, PSK.to_vec())).unwrap();
+    let mut stream = TcpStream::connect(server_addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    // Challenge: magic, length, payload and an empty MAC
+    let mut header = [0u8; 8];
+    stream.read_exact(&mut header).unwrap();
+    let len = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let mut rest = vec![0u8; len + 32];
+    stream.read_exact(&mut rest).unwrap();
+    // An unauthenticated Hello larger than the cap is dropped without waiting for the payload.
+    let mut frame = b"NSFL".to_vec();
+    frame.extend_from_slice(&((MAX_HANDSHAKE_FRAME_SIZE + 1) as u32).to_be_bytes());
+    stream.write_all(&frame).unwrap();
+    let mut buf = [0u8; 1];
+    assert_eq!(stream.read(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn test_fleet_loopback() {
+    let store = Arc::new(FleetStore::new());
+    let server_addr = free_port();
+    start_fleet_server(Arc::clone(&store), FleetServerOption::new(server_addr, PSK.to_vec())).unwrap();
+    let api_addr = free_port();
+    start_fleet_api(Arc::clone(&store), api_addr).unwrap();
+
+    let now = Local::now();
+    let mut web1 = agent(server_addr, "web1", PSK);
+    let mut web2 = agent(server_addr, "web2", PSK);
+    web1.push(test_data("nginx", 100), now, now).unwrap();
+    web1.push(test_data("nginx", 50), now, now).unwrap();
+    web2.push(test_data("curl", 30), now, now).unwrap();
+    assert!(web1.is_connected() && !web1.has_pending());
+    assert!(!web2.has_pending());
+
+    // Acknowledged deltas are merged already.
+    assert_eq!(store.agent_data("web1").unwrap().traffic.bytes_sent, 150);
+    assert_eq!(store.agent_data("web2").unwrap().traffic.bytes_sent, 30);
+    assert_eq!(store.agents()[0].deltas, 2);
+
+    // An agent with another key is rejected and keeps its data.
+    let mut intruder = agent(server_addr, "intruder", b"another-pre-shared-key");
+    assert!(intruder.push(test_data("nc", 1000), now, now).is_err());
+    assert!(intruder.has_pending() && !intruder.is_connected());
+    assert!(store.agent_data("intruder").is_none());
+
+    let response = http_get(api_addr, "/v1/fleet/hosts?limit=5");
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    let body = response.split_once("\r\n\r\n").unwrap().1;
+    let hosts: Vec<FleetHost> = serde_json::from_str(body).unwrap();
+    assert_eq!(hosts.len(), 1);
+    assert_eq!((hosts[0].agents, hosts[0].traffic.bytes_sent), (2, 180));
+    for path in ["/v1/fleet/agents", "/v1/fleet/asns", "/v1/fleet/processes"] {
+        assert!(http_get(api_addr, path).starts_with("HTTP/1.1 200"), "{}", path);
+    }
+    assert!(http_get(api_addr, "/v1/fleet/unknown").starts_with("HTTP/1.1 404"));
+
+    // Agents are marked disconnected when they go away.
+    drop(web1);
+    for _ in 0..50 {
+        if !store.agents()[0].connected {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert!(!store.agents()[0].connected);
+    assert!(store.agents()[1].connected);
+}
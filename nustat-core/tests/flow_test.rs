@@ -0,0 +1,241 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use chrono::{Local, TimeZone};
+use nustat_core::flow::collector::FlowCollector;
+use nustat_core::flow::{new_encoder, FlowCache, FlowDirection, FlowEndReason, FlowExportProtocol, FlowRecord, DEFAULT_ENTERPRISE_NUMBER, MAX_MESSAGE_SIZE};
+use nustat_core::net::host::RemoteHostInfo;
+use nustat_core::net::stat::NetStatData;
+use nustat_core::net::traffic::TrafficInfo;
+use nustat_core::process::ProcessInfo;
+use nustat_core::socket::{SocketConnection, SocketProcess, SocketStatus, TransportProtocol};
+
+extern crate nustat_core;
+
+fn test_conn() -> SocketConnection {
+    SocketConnection {
+        interface_name: String::from("eth0"),
+        local_ip_addr: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))),
+        local_port: 50000,
+        remote_ip_addr: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)),
+        remote_port: 443,
+        protocol: TransportProtocol::TCP,
+        netns: None,
+    }
+}
+
+fn test_data(traffic: TrafficInfo) -> NetStatData {
+    let conn = test_conn();
+    let mut data = NetStatData::new();
+    let mut host = RemoteHostInfo::new(String::new(), conn.remote_ip_addr);
+    host.hostname = String::from("example.com");
+    data.remote_hosts.insert(conn.remote_ip_addr, host);
+    data.connection_socket_map.insert(conn.clone(), SocketProcess {
+        socket_addr: SocketAddr::new(conn.local_ip_addr.unwrap(), conn.local_port),
+        protocol: TransportProtocol::TCP,
+        status: SocketStatus::Established,
+        process: Some(ProcessInfo {
+            pid: 4242,
+            name: String::from("curl"),
+            exe_path: String::new(),
+            cmd: vec![],
+            status: String::new(),
+            user_info: None,
+            start_time: Local::now(),
+            elapsed_time: 0,
+            ppid: None,
+            ancestors: vec![],
+            cgroup_path: None,
+            netns: None,
+            container: None,
+            systemd_unit: None,
+        }),
+    });
+    data.connection_map.insert(conn, traffic);
+    data
+}
+
+fn test_record(ipv6: bool) -> FlowRecord {
+    let (src_ip_addr, dst_ip_addr) = if ipv6 {
+        (IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)), IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2)))
+    } else {
+        (IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)), IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)))
+    };
+    FlowRecord {
+        src_ip_addr,
+        dst_ip_addr,
+        src_port: 50000,
+        dst_port: 443,
+        protocol: TransportProtocol::TCP,
+        direction: FlowDirection::Egress,
+        end_reason: FlowEndReason::ActiveTimeout,
+        octets: 1500,
+        packets: 3,
+        start_time: Local.timestamp_millis_opt(1_700_000_000_123).unwrap(),
+        end_time: Local.timestamp_millis_opt(1_700_000_060_456).unwrap(),
+        pid: Some(4242),
+        process_name: Some(String::from("curl")),
+        domain: Some(String::from("example.com")),
+    }
+}
+
+#[test]
+fn test_flow_cache_timeouts() {
+    let mut cache = FlowCache::new(Duration::from_secs(60), Duration::from_secs(15));
+    let t0 = Local::now();
+    let traffic = TrafficInfo { packet_sent: 2, packet_received: 3, bytes_sent: 200, bytes_received: 3000, estimated: false };
+    assert!(cache.update(&test_data(traffic), t0).is_empty());
+    assert_eq!(cache.active_flows(), 1);
+
+    // Active timeout: both directions are exported and the flow stays active.
+    let traffic = TrafficInfo { packet_sent: 1, packet_received: 1, bytes_sent: 100, bytes_received: 1000, estimated: false };
+    assert!(cache.update(&test_data(traffic.clone()), t0 + chrono::Duration::seconds(50)).is_empty());
+    // Intervals without packets of the flow
+    assert!(cache.update(&NetStatData::new(), t0 + chrono::Duration::seconds(55)).is_empty());
+    let records = cache.update(&test_data(traffic), t0 + chrono::Duration::seconds(60));
+    assert_eq!(records.len(), 2);
+    let egress = records.iter().find(|r| r.direction == FlowDirection::Egress).unwrap();
+    assert_eq!(egress.end_reason, FlowEndReason::ActiveTimeout);
+    assert_eq!((egress.octets, egress.packets), (400, 4));
+    assert_eq!(egress.src_port, 50000);
+    assert_eq!(egress.dst_ip_addr, test_conn().remote_ip_addr);
+    assert_eq!(egress.pid, Some(4242));
+    assert_eq!(egress.process_name.as_deref(), Some("curl"));
+    assert_eq!(egress.domain.as_deref(), Some("example.com"));
+    let ingress = records.iter().find(|r| r.direction == FlowDirection::Ingress).unwrap();
+    assert_eq!((ingress.octets, ingress.packets), (5000, 5));
+    assert_eq!(ingress.src_port, 443);
+
+    // Idle timeout: only the counters since the last export, ending at the last packet.
+    // The socket is gone by then, but the flow keeps its process.
+    let last_seen = t0 + chrono::Duration::seconds(70);
+    let traffic = TrafficInfo { packet_sent: 1, packet_received: 0, bytes_sent: 100, bytes_received: 0, estimated: false };
+    assert!(cache.update(&test_data(traffic), last_seen).is_empty());
+    let records = cache.update(&NetStatData::new(), last_seen + chrono::Duration::seconds(15));
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].end_reason, FlowEndReason::IdleTimeout);
+    assert_eq!((records[0].octets, records[0].packets), (100, 1));
+    assert_eq!(records[0].end_time, last_seen);
+    assert_eq!(records[0].pid, Some(4242));
+    // The exported flow is dropped from the cache.
+    assert_eq!(cache.active_flows(), 0);
+
+    // New packets start a new flow.
+    let traffic = TrafficInfo { packet_sent: 1, packet_received: 0, bytes_sent: 100, bytes_received: 0, estimated: false };
+    assert!(cache.update(&test_data(traffic), last_seen + chrono::Duration::seconds(30)).is_empty());
+    let records = cache.flush();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].end_reason, FlowEndReason::ForcedEnd);
+    assert_eq!(records[0].start_time, last_seen + chrono::Duration::seconds(30));
+    assert_eq!(cache.active_flows(), 0);
+}
+
+#[test]
+fn test_flow_deltas() {
+    use nustat_core::net::stat::NetStatStrage;
+    let strage = NetStatStrage::new();
+    let conn = test_conn();
+    // Nothing is kept until enabled.
+    assert!(strage.drain_flow_deltas().is_empty());
+    strage.enable_flow_deltas();
+    strage.flow_connection_map.lock().unwrap().as_mut().unwrap().insert(conn.clone(), TrafficInfo { packet_sent: 1, packet_received: 0, bytes_sent: 60, bytes_received: 0, estimated: false });
+    // Draining the strage for other consumers leaves the flow deltas alone.
+    strage.clone_data_and_reset();
+    let deltas = strage.drain_flow_deltas();
+    assert_eq!(deltas[&conn].bytes_sent, 60);
+    assert!(strage.drain_flow_deltas().is_empty());
+}
+
+#[test]
+fn test_ipfix_encode_decode() {
+    let mut encoder = new_encoder(FlowExportProtocol::IPFIX, 7, DEFAULT_ENTERPRISE_NUMBER);
+    let records = vec![test_record(false), test_record(true)];
+    let messages = encoder.encode(&records, true, Local::now());
+    assert_eq!(messages.len(), 1);
+    let message = &messages[0];
+    assert_eq!(&message[0..2], &10u16.to_be_bytes());
+    assert_eq!(&message[2..4], &(message.len() as u16).to_be_bytes());
+    assert_eq!(&message[12..16], &7u32.to_be_bytes());
+
+    let mut collector = FlowCollector::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    assert_eq!(collector.decode(message).unwrap(), records);
+
+    // Data without templates is decoded with the cached templates, and the sequence counts data records.
+    let messages = encoder.encode(&records, false, Local::now());
+    assert_eq!(&messages[0][8..12], &2u32.to_be_bytes());
+    assert_eq!(collector.decode(&messages[0]).unwrap(), records);
+
+    // Unknown templates are skipped.
+    let mut collector = FlowCollector::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    assert!(collector.decode(&messages[0]).unwrap().is_empty());
+}
+
+#[test]
+fn test_netflow_v9_encode_decode() {
+    let mut encoder = new_encoder(FlowExportProtocol::NetFlowV9, 7, DEFAULT_ENTERPRISE_NUMBER);
+    let now = Local::now();
+    let mut records = vec![test_record(false), test_record(true)];
+    for record in records.iter_mut() {
+        record.start_time = now;
+        record.end_time = now + chrono::Duration::seconds(1);
+    }
+    let packets = encoder.encode(&records, true, now + chrono::Duration::seconds(1));
+    assert_eq!(packets.len(), 1);
+    let packet = &packets[0];
+    assert_eq!(&packet[0..2], &9u16.to_be_bytes());
+    // 2 template records and 2 data records
+    assert_eq!(&packet[2..4], &4u16.to_be_bytes());
+
+    let mut collector = FlowCollector::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let decoded = collector.decode(packet).unwrap();
+    assert_eq!(decoded.len(), 2);
+    for (decoded, record) in decoded.iter().zip(records.iter()) {
+        assert_eq!(decoded.src_ip_addr, record.src_ip_addr);
+        assert_eq!(decoded.dst_ip_addr, record.dst_ip_addr);
+        assert_eq!(decoded.octets, record.octets);
+        assert_eq!(decoded.end_reason, record.end_reason);
+        assert_eq!(decoded.pid, record.pid);
+        assert_eq!(decoded.process_name, record.process_name);
+        assert_eq!(decoded.domain, record.domain);
+        // Export time has a resolution of seconds.
+        assert!((decoded.end_time - record.end_time).num_milliseconds().abs() < 1000);
+        assert_eq!(decoded.end_time - decoded.start_time, record.end_time - record.start_time);
+    }
+}
+
+#[test]
+fn test_flow_message_size() {
+    for protocol in [FlowExportProtocol::IPFIX, FlowExportProtocol::NetFlowV9] {
+        let mut encoder = new_encoder(protocol, 0, DEFAULT_ENTERPRISE_NUMBER);
+        let now = Local::now();
+        let records: Vec<FlowRecord> = (0..100).map(|i| {
+            let mut record = test_record(i % 3 == 0);
+            record.src_port = i;
+            record.start_time = now;
+            record.end_time = now;
+            record
+        }).collect();
+        let messages = encoder.encode(&records, true, now);
+        assert!(messages.len() > 1);
+        assert!(messages.iter().all(|message| message.len() <= MAX_MESSAGE_SIZE));
+        let mut collector = FlowCollector::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let mut decoded: Vec<FlowRecord> = Vec::new();
+        for message in messages.iter() {
+            decoded.extend(collector.decode(message).unwrap());
+        }
+        assert_eq!(decoded.iter().map(|r| r.src_port).collect::<Vec<u16>>(), (0..100).collect::<Vec<u16>>());
+    }
+}
+
+#[test]
+fn test_flow_export_udp() {
+    let mut collector = FlowCollector::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    collector.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.connect(collector.local_addr().unwrap()).unwrap();
+    let mut encoder = new_encoder(FlowExportProtocol::IPFIX, 0, DEFAULT_ENTERPRISE_NUMBER);
+    let records = vec![test_record(false)];
+    for message in encoder.encode(&records, true, Local::now()) {
+        socket.send(&message).unwrap();
+    }
+    assert_eq!(collector.recv().unwrap(), records);
+}
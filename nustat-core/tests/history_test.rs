@@ -0,0 +1,129 @@
+#![cfg(feature = "history")]
+
+use std::net::{IpAddr, Ipv4Addr};
+use chrono::{Duration, Local};
+use nustat_core::history::{HistoryDb, HISTORY_SCHEMA_VERSION};
+use nustat_core::net::stat::NetStatData;
+use nustat_core::net::traffic::TrafficInfo;
+use nustat_core::socket::{SocketConnection, TransportProtocol};
+
+extern crate nustat_core;
+
+#[test]
+fn test_history_insert_snapshot() {
+    let mut db = HistoryDb::open_in_memory().unwrap();
+    let version: i32 = db.connection().query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+    assert_eq!(version, HISTORY_SCHEMA_VERSION);
+
+    let mut data = NetStatData::new();
+    data.if_name = String::from("eth0");
+    let mut traffic = TrafficInfo::new();
+    traffic.packet_sent = 2;
+    traffic.bytes_sent = 120;
+    traffic.packet_received = 3;
+    traffic.bytes_received = 4500;
+    data.traffic = traffic.clone();
+    data.connection_map.insert(SocketConnection {
+        interface_name: String::from("eth0"),
+        local_ip_addr: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))),
+        local_port: 50000,
+        remote_ip_addr: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)),
+        remote_port: 443,
+        protocol: TransportProtocol::TCP,
+        netns: None,
+    }, traffic);
+
+    let end_time = Local::now();
+    let start_time = end_time - Duration::seconds(10);
+    let snapshot_id = db.insert_snapshot(&data, start_time, end_time).unwrap();
+    db.insert_snapshot(&NetStatData::new(), end_time, end_time + Duration::seconds(10)).unwrap();
+    assert_eq!(db.snapshot_count().unwrap(), 2);
+
+    let (bytes_received, duration): (i64, i64) = db.connection()
+        .query_row("SELECT bytes_received, end_time - start_time FROM snapshots WHERE id = ?1", [snapshot_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap();
+    assert_eq!(bytes_received, 4500);
+    assert_eq!(duration, 10);
+
+    let (remote, port, bytes_sent): (String, u16, i64) = db.connection()
+        .query_row("SELECT remote_ip_addr, remote_port, bytes_sent FROM connection_traffic WHERE snapshot_id = ?1", [snapshot_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap();
+    assert_eq!(remote, "203.0.113.1");
+    assert_eq!(port, 443);
+    assert_eq!(bytes_sent, 120);
+
+    let services: i64 = db.connection()
+        .query_row("SELECT COUNT(*) FROM app_protocol_traffic WHERE snapshot_id = ?1 AND port = 443", [snapshot_id], |row| row.get(0))
+        .unwrap();
+    assert_eq!(services, 1);
+}
+
+#[test]
+fn test_history_report() {
+    use nustat_core::history::{build_report, HistoryConnection, HistoryReport, ReportFilter, ReportGroup, ReportSort};
+    let connection = |remote: [u8; 4], port: u16, pid: u32, country: &str, asn: u32, bytes: usize| HistoryConnection {
+        protocol: TransportProtocol::TCP,
+        local_port: 50000,
+        remote_ip_addr: IpAddr::from(remote),
+        remote_port: port,
+        pid: Some(pid),
+        process_name: Some(format!("proc{}", pid)),
+        unit: None,
+        host_name: String::new(),
+        country_code: country.to_string(),
+        country_name: String::new(),
+        asn,
+        as_name: format!("AS{}", asn),
+        traffic: TrafficInfo { packet_sent: 1, packet_received: 1, bytes_sent: bytes, bytes_received: bytes, estimated: false },
+    };
+    let connections = vec![
+        connection([203, 0, 113, 1], 443, 100, "US", 64500, 100),
+        connection([203, 0, 113, 1], 443, 100, "US", 64500, 100),
+        connection([198, 51, 100, 1], 22, 200, "JP", 64501, 1000),
+        connection([192, 0, 2, 1], 443, 300, "US", 64502, 10),
+    ];
+    match build_report(&connections, ReportGroup::Country, ReportSort::Total, None) {
+        HistoryReport::Countries(countries) => {
+            assert_eq!(countries.len(), 2);
+            assert_eq!(countries[0].country_code, "JP");
+            assert_eq!(countries[1].traffic.bytes_sent, 210);
+        }
+        other => panic!("unexpected report: {:?}", other),
+    }
+    match build_report(&connections, ReportGroup::Host, ReportSort::Packets, Some(1)) {
+        HistoryReport::Hosts(hosts) => {
+            assert_eq!(hosts.len(), 1);
+            assert_eq!(hosts[0].ip_addr, IpAddr::from([203, 0, 113, 1]));
+        }
+        other => panic!("unexpected report: {:?}", other),
+    }
+    match build_report(&connections, ReportGroup::Port, ReportSort::Total, None) {
+        HistoryReport::Services(services) => {
+            assert_eq!(services.len(), 2);
+            assert_eq!(services[0].port, 22);
+        }
+        other => panic!("unexpected report: {:?}", other),
+    }
+
+    // Time range and port filters are applied by the query.
+    let mut db = HistoryDb::open_in_memory().unwrap();
+    let mut data = NetStatData::new();
+    data.connection_map.insert(SocketConnection {
+        interface_name: String::from("eth0"),
+        local_ip_addr: None,
+        local_port: 50000,
+        remote_ip_addr: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)),
+        remote_port: 443,
+        protocol: TransportProtocol::TCP,
+        netns: None,
+    }, TrafficInfo { packet_sent: 1, packet_received: 1, bytes_sent: 10, bytes_received: 20, estimated: false });
+    let now = Local::now();
+    db.insert_snapshot(&data, now - Duration::hours(2), now - Duration::hours(1)).unwrap();
+    db.insert_snapshot(&data, now - Duration::minutes(10), now).unwrap();
+    let mut filter = ReportFilter::default();
+    assert_eq!(db.query_connections(&filter).unwrap().len(), 2);
+    filter.start_time = Some(now - Duration::minutes(30));
+    assert_eq!(db.query_connections(&filter).unwrap().len(), 1);
+    filter.port = Some(22);
+    assert_eq!(db.query_connections(&filter).unwrap().len(), 0);
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, TcpStream};
+use std::sync::Arc;
+use nustat_core::metrics::{render_metrics, start_metrics_server, TrafficTotals};
+use nustat_core::net::stat::{NetStatData, NetStatStrage};
+use nustat_core::net::traffic::TrafficInfo;
+use nustat_core::pcap::CaptureStats;
+use nustat_core::socket::{SocketConnection, TransportProtocol};
+
+extern crate nustat_core;
+
+fn connection(remote: [u8; 4], remote_port: u16) -> SocketConnection {
+    SocketConnection {
+        interface_name: String::from("eth0"),
+        local_ip_addr: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))),
+        local_port: 50000,
+        remote_ip_addr: IpAddr::from(remote),
+        remote_port,
+        protocol: TransportProtocol::TCP,
+        netns: None,
+    }
+}
+
+#[test]
+fn test_render_metrics() {
+    let mut data = NetStatData::new();
+    data.traffic = TrafficInfo { packet_sent: 3, packet_received: 4, bytes_sent: 300, bytes_received: 400, estimated: false };
+    data.connection_map.insert(connection([203, 0, 113, 1], 443), TrafficInfo { packet_sent: 1, packet_received: 1, bytes_sent: 100, bytes_received: 100, estimated: false });
+    data.connection_map.insert(connection([203, 0, 113, 2], 22), TrafficInfo { packet_sent: 2, packet_received: 3, bytes_sent: 200, bytes_received: 300, estimated: false });
+    let mut capture_stats: HashMap<String, CaptureStats> = HashMap::new();
+    capture_stats.insert(String::from("eth0"), CaptureStats { packets: 10, errors: 1, filtered: 2 });
+
+    let mut totals = TrafficTotals::new();
+    totals.add(&data);
+    let metrics = render_metrics(&totals, &capture_stats, 1);
+    assert!(metrics.contains("# TYPE nustat_bytes_total counter\n"));
+    assert!(metrics.contains("nustat_bytes_total{direction=\"sent\"} 300\n"));
+    assert!(metrics.contains("nustat_interface_bytes_total{interface=\"eth0\",direction=\"received\"} 400\n"));
+    // Series beyond the limit are aggregated into `other`.
+    assert!(metrics.contains("nustat_app_protocol_bytes_total{protocol=\"TCP\",port=\"22\",service=\"ssh\",direction=\"sent\"} 200\n"));
+    assert!(metrics.contains("nustat_app_protocol_bytes_total{protocol=\"other\",port=\"other\",service=\"other\",direction=\"sent\"} 100\n"));
+    assert!(metrics.contains("nustat_capture_errors_total{interface=\"eth0\"} 1\n"));
+}
+
+#[test]
+fn test_metrics_server() {
+    let strage = Arc::new(NetStatStrage::new());
+    let listen_addr = "127.0.0.1:19595".parse().unwrap();
+    start_metrics_server(Arc::clone(&strage), listen_addr, 10).unwrap();
+    // Counters survive the data being drained by another consumer.
+    *strage.traffic.lock().unwrap() = TrafficInfo { packet_sent: 1, packet_received: 0, bytes_sent: 60, bytes_received: 0, estimated: false };
+    strage.clone_data_and_reset();
+    *strage.traffic.lock().unwrap() = TrafficInfo { packet_sent: 1, packet_received: 0, bytes_sent: 40, bytes_received: 0, estimated: false };
+
+    let mut stream = TcpStream::connect(listen_addr).unwrap();
+    stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("nustat_bytes_total{direction=\"sent\"} 100\n"));
+
+    let mut stream = TcpStream::connect(listen_addr).unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 404"));
+}
+
+#[test]
+fn test_totals_local_ip_map() {
+    let strage = NetStatStrage::new();
+    strage.enable_totals();
+    *strage.local_ip_map.lock().unwrap() = HashMap::from([(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)), String::from("eth0"))]);
+    strage.clone_data_and_reset();
+    assert_eq!(strage.get_drained_totals().unwrap().local_ip_map.len(), 1);
+    // Totals include the undrained data without losing the local addresses.
+    assert_eq!(strage.get_totals().local_ip_map.get(&IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))).map(String::as_str), Some("eth0"));
+    assert_eq!(strage.clone_data().local_ip_map.len(), 1);
+    // Merging data without addresses keeps them.
+    let mut totals = strage.get_totals();
+    totals.merge(NetStatData::new());
+    assert_eq!(totals.local_ip_map.len(), 1);
+}
+
+#[test]
+fn test_traffic_totals() {
+    let strage = NetStatStrage::new();
+    strage.enable_traffic_totals();
+    for port in [50000, 50001] {
+        strage.connection_map.lock().unwrap().insert(SocketConnection { local_port: port, ..connection([203, 0, 113, 1], 443) }, TrafficInfo { packet_sent: 1, packet_received: 0, bytes_sent: 100, bytes_received: 0, estimated: false });
+        strage.clone_data_and_reset();
+    }
+    // Connections are folded into the aggregates.
+    let totals = strage.get_traffic_totals();
+    assert_eq!(totals.interfaces["eth0"].bytes_sent, 200);
+    assert_eq!(totals.app_protocols.len(), 1);
+    assert!(strage.get_drained_totals().is_none());
+}
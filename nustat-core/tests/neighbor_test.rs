@@ -0,0 +1,86 @@
+use std::net::{IpAddr, Ipv4Addr};
+use nustat_core::net::neighbor::{self, DiscoveryProtocol};
+
+extern crate nustat_core;
+
+fn dhcp_request(hostname: &str, vendor_class: &str) -> Vec<u8> {
+    let mut payload = vec![0u8; 240];
+    // op: BOOTREQUEST, htype: Ethernet, hlen: 6
+    payload[0] = 1;
+    payload[1] = 1;
+    payload[2] = 6;
+    payload[28..34].copy_from_slice(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+    payload[236..240].copy_from_slice(&[99, 130, 83, 99]);
+    // DHCP message type: REQUEST
+    payload.extend_from_slice(&[53, 1, 3]);
+    // Requested IP address
+    payload.extend_from_slice(&[50, 4, 192, 168, 1, 23]);
+    payload.push(12);
+    payload.push(hostname.len() as u8);
+    payload.extend_from_slice(hostname.as_bytes());
+    payload.push(60);
+    payload.push(vendor_class.len() as u8);
+    payload.extend_from_slice(vendor_class.as_bytes());
+    payload.push(255);
+    payload
+}
+
+fn push_dns_name(buf: &mut Vec<u8>, name: &str) {
+    for label in name.split('.') {
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+}
+
+#[test]
+fn test_parse_dhcp_request() {
+    let payload = dhcp_request("printer-2f", "MSFT 5.0");
+    let neighbor = neighbor::parse_dhcp(&payload, IpAddr::V4(Ipv4Addr::UNSPECIFIED)).unwrap();
+    assert_eq!(neighbor.ip_addr, IpAddr::V4(Ipv4Addr::new(192, 168, 1, 23)));
+    assert_eq!(neighbor.mac_addr, "00:11:22:33:44:55");
+    assert_eq!(neighbor.hostname, "printer-2f");
+    assert_eq!(neighbor.vendor_class, "MSFT 5.0");
+    assert_eq!(neighbor.protocols, vec![DiscoveryProtocol::DHCP]);
+}
+
+#[test]
+fn test_parse_mdns_response() {
+    let src_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 40));
+    let mut payload: Vec<u8> = vec![0x00, 0x00, 0x84, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00];
+    // A record: macbook.local -> 192.168.1.40
+    push_dns_name(&mut payload, "macbook.local");
+    payload.extend_from_slice(&[0x00, 0x01, 0x80, 0x01, 0x00, 0x00, 0x00, 0x78, 0x00, 0x04, 192, 168, 1, 40]);
+    // PTR record: _airplay._tcp.local -> pointer to the first name
+    push_dns_name(&mut payload, "_airplay._tcp.local");
+    payload.extend_from_slice(&[0x00, 0x0c, 0x00, 0x01, 0x00, 0x00, 0x11, 0x94, 0x00, 0x02, 0xc0, 0x0c]);
+    let neighbors = neighbor::parse_name_service(&payload, src_ip, "aa:bb:cc:dd:ee:ff".to_string(), DiscoveryProtocol::MDNS);
+    assert_eq!(neighbors.len(), 1);
+    assert_eq!(neighbors[0].ip_addr, src_ip);
+    assert_eq!(neighbors[0].hostname, "macbook");
+    assert_eq!(neighbors[0].mac_addr, "aa:bb:cc:dd:ee:ff");
+    assert_eq!(neighbors[0].services, vec!["_airplay._tcp".to_string()]);
+}
+
+#[test]
+fn test_parse_netbios_registration() {
+    let src_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 50));
+    // Registration request: QDCOUNT 1, ARCOUNT 1
+    let mut payload: Vec<u8> = vec![0x12, 0x34, 0x29, 0x10, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+    let mut name = [b' '; 16];
+    name[..7].copy_from_slice(b"DESKTOP");
+    name[15] = 0x00;
+    payload.push(32);
+    for b in name {
+        payload.push(b'A' + (b >> 4));
+        payload.push(b'A' + (b & 0x0f));
+    }
+    payload.push(0);
+    payload.extend_from_slice(&[0x00, 0x20, 0x00, 0x01]);
+    // Additional record: pointer to question name, NB, IN, TTL, NB_FLAGS + NB_ADDRESS
+    payload.extend_from_slice(&[0xc0, 0x0c, 0x00, 0x20, 0x00, 0x01, 0x00, 0x04, 0x93, 0xe0, 0x00, 0x06, 0x00, 0x00, 192, 168, 1, 50]);
+    let neighbors = neighbor::parse_name_service(&payload, src_ip, "00:aa:00:bb:00:cc".to_string(), DiscoveryProtocol::NBNS);
+    assert_eq!(neighbors.len(), 1);
+    assert_eq!(neighbors[0].hostname, "DESKTOP");
+    assert_eq!(neighbors[0].ip_addr, src_ip);
+}
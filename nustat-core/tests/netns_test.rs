@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use nustat_core::net::packet::PacketFrame;
+use nustat_core::net::stat::NetStatStrage;
+use xenet::packet::frame::{Frame, ParseOption};
+
+extern crate nustat_core;
+
+fn udp_packet(src: [u8; 4], dst: [u8; 4], src_port: u16, dst_port: u16) -> Vec<u8> {
+    let mut packet: Vec<u8> = Vec::new();
+    // Ethernet
+    packet.extend_from_slice(&[0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00]);
+    // IPv4: version/IHL, TOS, total length, id, flags, TTL, protocol UDP, checksum
+    packet.extend_from_slice(&[0x45, 0x00, 0x00, 0x20, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00]);
+    packet.extend_from_slice(&src);
+    packet.extend_from_slice(&dst);
+    // UDP with 4 bytes payload
+    packet.extend_from_slice(&src_port.to_be_bytes());
+    packet.extend_from_slice(&dst_port.to_be_bytes());
+    packet.extend_from_slice(&[0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef]);
+    packet
+}
+
+#[test]
+fn test_update_tags_netns() {
+    let netstat_strage = NetStatStrage::new();
+    let mut ip_map: HashMap<IpAddr, String> = HashMap::new();
+    ip_map.insert(IpAddr::V4(Ipv4Addr::new(10, 200, 0, 2)), "eth0".to_string());
+    netstat_strage.set_netns_local_ip_map(4026532999, ip_map);
+    let packet = udp_packet([10, 200, 0, 2], [10, 200, 0, 1], 40000, 53);
+    let frame = Frame::from_bytes(&packet, ParseOption::default());
+    let mut packet_frame = PacketFrame::from_xenet_frame(0, 0, "veth0".to_string(), frame);
+    packet_frame.netns = Some(4026532999);
+    netstat_strage.update(packet_frame);
+    let connection_map = netstat_strage.get_connection_map();
+    assert_eq!(connection_map.len(), 1);
+    let (conn, traffic) = connection_map.iter().next().unwrap();
+    assert_eq!(conn.netns, Some(4026532999));
+    assert_eq!(conn.interface_name, "eth0");
+    assert_eq!(conn.local_port, 40000);
+    assert_eq!(traffic.packet_sent, 1);
+}
+
+#[test]
+fn test_update_ignores_unknown_netns() {
+    let netstat_strage = NetStatStrage::new();
+    let packet = udp_packet([10, 200, 0, 2], [10, 200, 0, 1], 40000, 53);
+    let frame = Frame::from_bytes(&packet, ParseOption::default());
+    let mut packet_frame = PacketFrame::from_xenet_frame(0, 0, "veth0".to_string(), frame);
+    packet_frame.netns = Some(1);
+    netstat_strage.update(packet_frame);
+    assert!(netstat_strage.get_connection_map().is_empty());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_list_namespaces() {
+    let namespaces = nustat_core::net::netns::list_namespaces();
+    assert!(namespaces.iter().any(|ns| ns.is_host));
+    let selected = nustat_core::net::netns::select_namespaces(&["*".to_string()]);
+    assert!(selected.iter().all(|ns| !ns.is_host));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_namespace_matches_any_pid() {
+    let namespaces = nustat_core::net::netns::list_namespaces();
+    let host = namespaces.iter().find(|ns| ns.is_host).unwrap();
+    // The child shares the host namespace but is not the pid it was listed under.
+    let mut child = std::process::Command::new("sleep").arg("5").spawn().unwrap();
+    let selector = format!("pid:{}", child.id());
+    assert_ne!(host.name, selector);
+    assert!(host.matches(&selector));
+    assert!(namespaces.iter().filter(|ns| !ns.is_host).all(|ns| !ns.matches(&selector)));
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(!host.matches("pid:not-a-pid"));
+}
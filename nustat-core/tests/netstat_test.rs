@@ -2,15 +2,116 @@ use nustat_core::socket::SocketInfoOption;
 
 extern crate nustat_core;
 
+/// Ethernet/IPv4/UDP packet with 4 bytes of payload.
+fn udp_packet(src: [u8; 4], dst: [u8; 4], src_port: u16, dst_port: u16) -> Vec<u8> {
+    let mut packet: Vec<u8> = Vec::new();
+    packet.extend_from_slice(&[0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00]);
+    packet.extend_from_slice(&[0x45, 0x00, 0x00, 0x20, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00]);
+    packet.extend_from_slice(&src);
+    packet.extend_from_slice(&dst);
+    packet.extend_from_slice(&src_port.to_be_bytes());
+    packet.extend_from_slice(&dst_port.to_be_bytes());
+    packet.extend_from_slice(&[0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef]);
+    packet
+}
+
 #[test]
 fn show_netstat() {
-    let netstat = nustat_core::socket::get_sockets_info(SocketInfoOption::default());
+    let netstat = nustat_core::socket::get_sockets_info(SocketInfoOption::default()).unwrap();
     for ns in netstat.iter() {
         println!("{:?}", ns);
     }
     assert!(netstat.len() > 0);
 }
 
+#[test]
+fn test_socket_backends() {
+    use nustat_core::socket::SocketBackendType;
+    for backend_type in [SocketBackendType::Netstat2, SocketBackendType::Netlink] {
+        let mut backend = nustat_core::socket::new_backend(backend_type);
+        let sockets = backend.get_sockets_info(&SocketInfoOption::default()).unwrap();
+        println!("{}: {} sockets", backend.name(), sockets.len());
+    }
+}
+
+#[test]
+fn test_tcp_metrics_from_tcp_info() {
+    use nustat_core::socket::TcpMetrics;
+    let mut buf = vec![0u8; 232];
+    buf[2] = 1;
+    buf[68..72].copy_from_slice(&1500u32.to_ne_bytes());
+    buf[80..84].copy_from_slice(&10u32.to_ne_bytes());
+    buf[100..104].copy_from_slice(&3u32.to_ne_bytes());
+    buf[120..128].copy_from_slice(&4096u64.to_ne_bytes());
+    buf[160..168].copy_from_slice(&125000u64.to_ne_bytes());
+    let metrics = TcpMetrics::from_tcp_info(&buf).unwrap();
+    assert_eq!(metrics.retransmits, 1);
+    assert_eq!(metrics.rtt_us, 1500);
+    assert_eq!(metrics.snd_cwnd, 10);
+    assert_eq!(metrics.total_retrans, 3);
+    assert_eq!(metrics.bytes_acked, 4096);
+    assert_eq!(metrics.delivery_rate, 125000);
+    // Older kernels report a shorter struct
+    let metrics = TcpMetrics::from_tcp_info(&buf[..104]).unwrap();
+    assert_eq!(metrics.bytes_acked, 0);
+    assert!(TcpMetrics::from_tcp_info(&buf[..64]).is_none());
+}
+
+#[test]
+fn test_merge_replaces_tcp_metrics() {
+    use std::net::{IpAddr, Ipv4Addr};
+    use nustat_core::net::stat::NetStatData;
+    use nustat_core::socket::{SocketConnection, TcpMetrics, TransportProtocol};
+    let conn = |remote_port: u16| SocketConnection {
+        interface_name: String::from("eth0"),
+        local_ip_addr: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))),
+        local_port: 50000,
+        remote_ip_addr: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)),
+        remote_port,
+        protocol: TransportProtocol::TCP,
+        netns: None,
+    };
+    let mut buf = vec![0u8; 104];
+    buf[68..72].copy_from_slice(&1500u32.to_ne_bytes());
+    let metrics = TcpMetrics::from_tcp_info(&buf).unwrap();
+    let mut data = NetStatData::new();
+    let mut snapshot = NetStatData::new();
+    snapshot.tcp_metrics_map.insert(conn(443), metrics.clone());
+    snapshot.tcp_metrics_map.insert(conn(22), metrics.clone());
+    data.merge(snapshot);
+    assert_eq!(data.tcp_metrics_map.len(), 2);
+    // The connection to port 22 was closed.
+    let mut snapshot = NetStatData::new();
+    snapshot.tcp_metrics_map.insert(conn(443), metrics);
+    data.merge(snapshot);
+    assert_eq!(data.tcp_metrics_map.len(), 1);
+    assert_eq!(data.tcp_metrics_map[&conn(443)].rtt_us, 1500);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_netlink_tcp_metrics() {
+    use nustat_core::socket::{SocketBackendType, SocketStatus};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let stream = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let local_port = stream.local_addr().unwrap().port();
+    let mut backend = nustat_core::socket::new_backend(SocketBackendType::Netlink);
+    let sockets = backend.get_sockets_info(&SocketInfoOption::default()).unwrap();
+    let socket = sockets.iter().find(|s| s.local_port == local_port && s.status == SocketStatus::Established).unwrap();
+    assert!(socket.tcp_metrics.is_some());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_get_unix_sockets_info() {
+    let listener = std::os::unix::net::UnixListener::bind(std::env::temp_dir().join(format!("nustat-test-{}.sock", std::process::id()))).unwrap();
+    let path = listener.local_addr().unwrap().as_pathname().unwrap().to_string_lossy().to_string();
+    let sockets = nustat_core::socket::get_unix_sockets_info().unwrap();
+    let _ = std::fs::remove_file(&path);
+    let socket = sockets.iter().find(|s| s.path.as_deref() == Some(path.as_str())).unwrap();
+    assert_eq!(socket.process.as_ref().map(|p| p.pid), Some(std::process::id()));
+}
+
 #[test]
 fn test_get_os_type() {
     let os_type = nustat_core::sys::get_os_type();
@@ -88,3 +189,419 @@ fn test_pcap() {
         }
     } */
 }
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_update_resolves_vendor_after_ouidb_load() {
+    use std::collections::HashMap;
+    use std::net::{IpAddr, Ipv4Addr};
+    use nustat_core::net::packet::PacketFrame;
+    use nustat_core::net::stat::NetStatStrage;
+    use xenet::packet::frame::{Frame, ParseOption};
+    let netstat_strage = NetStatStrage::new();
+    let mut ip_map: HashMap<IpAddr, String> = HashMap::new();
+    ip_map.insert(IpAddr::V4(Ipv4Addr::new(10, 200, 0, 2)), "eth0".to_string());
+    netstat_strage.set_netns_local_ip_map(4026532999, ip_map);
+    let update = || {
+        let mut packet = udp_packet([10, 200, 0, 2], [10, 200, 0, 1], 40000, 53);
+        // Universally administered destination MAC (XEROX)
+        packet[0..6].copy_from_slice(&[0x00, 0x00, 0x00, 0x12, 0x34, 0x56]);
+        let frame = Frame::from_bytes(&packet, ParseOption::default());
+        let mut packet_frame = PacketFrame::from_xenet_frame(0, 0, "veth0".to_string(), frame);
+        packet_frame.netns = Some(4026532999);
+        netstat_strage.update(packet_frame);
+    };
+    // The peer is first seen before the OUI database is loaded. The miss is remembered.
+    update();
+    let remote_ip_addr = IpAddr::V4(Ipv4Addr::new(10, 200, 0, 1));
+    let remote_host = netstat_strage.get_remote_hosts()[&remote_ip_addr].clone();
+    assert_eq!(remote_host.mac_vendor, "");
+    assert!(netstat_strage.oui_misses.lock().unwrap().contains(&remote_host.mac_addr));
+    // Loading the database retries the misses.
+    netstat_strage.load_ouidb();
+    assert!(netstat_strage.oui_misses.lock().unwrap().is_empty());
+    update();
+    assert_eq!(netstat_strage.get_remote_hosts()[&remote_ip_addr].mac_vendor, "XEROX CORPORATION");
+}
+
+#[test]
+fn test_process_user_info() {
+    use std::collections::HashSet;
+    use std::os::unix::fs::MetadataExt;
+    use nustat_core::process::ProcessCache;
+    let uid = std::fs::metadata("/proc/self").unwrap().uid();
+    let mut process_cache = ProcessCache::new();
+    let pids: HashSet<u32> = [std::process::id()].into_iter().collect();
+    let process_map = process_cache.refresh_pids(&pids);
+    let process = process_map.get(&std::process::id()).unwrap();
+    let user_info = process.user_info.as_ref().unwrap();
+    assert_eq!(user_info.user_id, uid.to_string());
+    assert!(!user_info.user_name.is_empty());
+}
+
+#[test]
+fn test_process_rollup_target() {
+    use nustat_core::process::{ProcessAncestor, ProcessInfo, RollupTarget};
+    let process = ProcessInfo {
+        pid: 300,
+        name: String::from("curl"),
+        exe_path: String::new(),
+        cmd: vec![],
+        status: String::new(),
+        user_info: None,
+        start_time: chrono::Local::now(),
+        elapsed_time: 0,
+        ppid: Some(200),
+        ancestors: vec![
+            ProcessAncestor { pid: 200, name: String::from("bash") },
+            ProcessAncestor { pid: 100, name: String::from("sshd") },
+            ProcessAncestor { pid: 1, name: String::from("systemd") },
+        ],
+        cgroup_path: None,
+        netns: None,
+        container: None,
+        systemd_unit: None,
+    };
+    assert_eq!(process.rollup_target(&RollupTarget::TopLevel).pid, 100);
+    assert_eq!(process.rollup_target(&RollupTarget::Name(String::from("bash"))).pid, 200);
+    assert_eq!(process.rollup_target(&RollupTarget::Pid(999)).pid, 300);
+    assert_eq!(process.rollup_target(&RollupTarget::Depth(0)).pid, 1);
+    assert_eq!(process.rollup_target(&RollupTarget::Depth(2)).pid, 200);
+    assert_eq!(process.rollup_target(&RollupTarget::Depth(10)).pid, 300);
+}
+
+#[test]
+fn test_systemd_units_and_rollup() {
+    use std::net::{IpAddr, Ipv4Addr};
+    use nustat_core::net::stat::NetStatData;
+    use nustat_core::net::traffic::TrafficInfo;
+    use nustat_core::process::{ProcessAncestor, ProcessInfo, RollupTarget};
+    use nustat_core::socket::{SocketConnection, SocketProcess, TransportProtocol};
+    use nustat_core::systemd::SystemdUnitInfo;
+    let process = |pid: u32, name: &str, ancestors: Vec<ProcessAncestor>, unit: Option<&str>| ProcessInfo {
+        pid,
+        name: String::from(name),
+        exe_path: String::new(),
+        cmd: vec![],
+        status: String::new(),
+        user_info: None,
+        start_time: chrono::Local::now(),
+        elapsed_time: 0,
+        ppid: ancestors.first().map(|a| a.pid),
+        ancestors,
+        cgroup_path: None,
+        netns: None,
+        container: None,
+        systemd_unit: unit.map(|unit| SystemdUnitInfo { unit: String::from(unit), slice: None, user_unit: false }),
+    };
+    let mut data = NetStatData::new();
+    let mut add = |local_port: u16, bytes: usize, process: ProcessInfo| {
+        let conn = SocketConnection {
+            interface_name: String::from("eth0"),
+            local_ip_addr: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))),
+            local_port,
+            remote_ip_addr: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)),
+            remote_port: 443,
+            protocol: TransportProtocol::TCP,
+            netns: None,
+        };
+        let mut socket_process = SocketProcess::new();
+        socket_process.process = Some(process);
+        data.connection_map.insert(conn.clone(), TrafficInfo { packet_sent: 1, packet_received: 0, bytes_sent: bytes, bytes_received: 0, estimated: false });
+        data.connection_socket_map.insert(conn, socket_process);
+    };
+    let sshd = ProcessAncestor { pid: 100, name: String::from("sshd") };
+    let init = ProcessAncestor { pid: 1, name: String::from("systemd") };
+    add(50000, 100, process(300, "curl", vec![ProcessAncestor { pid: 200, name: String::from("bash") }, sshd.clone(), init.clone()], Some("session-3.scope")));
+    add(50001, 10, process(100, "sshd", vec![init.clone()], Some("sshd.service")));
+    add(50002, 50, process(400, "worker", vec![ProcessAncestor { pid: 500, name: String::from("runner") }, init], Some("runner.service")));
+    add(50003, 5, process(600, "orphan", vec![], None));
+
+    let units = data.get_systemd_units(None);
+    assert_eq!(units.iter().map(|u| u.unit.as_str()).collect::<Vec<&str>>(), vec!["session-3.scope", "runner.service", "sshd.service"]);
+    assert_eq!(units[0].process_names, vec![String::from("curl")]);
+    assert_eq!(units[0].traffic.bytes_sent, 100);
+    assert_eq!(data.get_systemd_units(Some(1)).len(), 1);
+
+    // Rolled up rows carry the unit of the ancestor itself, not of the child.
+    let rollup = data.get_processes_rollup(&RollupTarget::TopLevel, None);
+    let sshd_row = rollup.iter().find(|p| p.pid == 100).unwrap();
+    assert_eq!(sshd_row.traffic.bytes_sent, 110);
+    assert_eq!(sshd_row.unit.as_deref(), Some("sshd.service"));
+    let runner_row = rollup.iter().find(|p| p.pid == 500).unwrap();
+    assert_eq!(runner_row.unit, None);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_process_ancestors() {
+    use std::collections::HashSet;
+    use nustat_core::process::ProcessCache;
+    let mut process_cache = ProcessCache::new();
+    let pids: HashSet<u32> = [std::process::id()].into_iter().collect();
+    let process_map = process_cache.refresh_pids(&pids);
+    let process = process_map.get(&std::process::id()).unwrap();
+    assert_eq!(process.ppid, process.ancestors.first().map(|a| a.pid));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_process_cache() {
+    use std::collections::HashSet;
+    use nustat_core::process::{ProcessCache, ProcessEventKind};
+    let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+    let pid = child.id();
+    let pids: HashSet<u32> = [pid].into_iter().collect();
+    let mut process_cache = ProcessCache::new();
+    let process_map = process_cache.refresh_pids(&pids);
+    assert_eq!(process_map.get(&pid).unwrap().name, "sleep");
+    // A second refresh reuses the cached entry without a new start event.
+    process_cache.refresh_pids(&pids);
+    let events = process_cache.drain_events();
+    assert_eq!(events.iter().filter(|e| e.pid == pid && e.kind == ProcessEventKind::Started).count(), 1);
+    child.kill().unwrap();
+    child.wait().unwrap();
+    // The exited process is still resolvable for late packets.
+    let process_map = process_cache.refresh_pids(&pids);
+    assert!(process_map.contains_key(&pid));
+    assert!(process_cache.is_exited(pid));
+    let events = process_cache.drain_events();
+    assert!(events.iter().any(|e| e.pid == pid && e.kind == ProcessEventKind::Exited));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_process_cache_unrequested_exit() {
+    use std::collections::HashSet;
+    use nustat_core::process::{ProcessCache, ProcessEventKind};
+    let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+    let pid = child.id();
+    let mut process_cache = ProcessCache::with_retention(std::time::Duration::from_millis(200));
+    process_cache.refresh_pids(&[pid].into_iter().collect());
+    // Live processes are kept past the retention period even if not requested.
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    let self_pids: HashSet<u32> = [std::process::id()].into_iter().collect();
+    process_cache.refresh_pids(&self_pids);
+    assert!(process_cache.get(pid).is_some());
+    assert!(!process_cache.is_exited(pid));
+    child.kill().unwrap();
+    child.wait().unwrap();
+    // The process exited along with its sockets, so its pid is not requested again.
+    process_cache.refresh_pids(&self_pids);
+    assert!(process_cache.is_exited(pid));
+    let events = process_cache.drain_events();
+    assert_eq!(events.iter().filter(|e| e.pid == pid && e.kind == ProcessEventKind::Started).count(), 1);
+    assert!(events.iter().any(|e| e.pid == pid && e.kind == ProcessEventKind::Exited));
+}
+
+#[test]
+fn test_lookup_socket_process() {
+    use std::collections::HashMap;
+    use std::net::{IpAddr, Ipv4Addr};
+    use nustat_core::socket::{self, LocalSocket, SocketConnection, SocketProcess, TransportProtocol};
+    let local_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10));
+    let socket_process = |port: u16| {
+        let mut sp = SocketProcess::new();
+        sp.socket_addr = std::net::SocketAddr::new(local_ip, port);
+        sp
+    };
+    let conn = SocketConnection {
+        interface_name: String::from("eth0"),
+        local_ip_addr: Some(local_ip),
+        local_port: 22,
+        remote_ip_addr: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)),
+        remote_port: 50000,
+        protocol: TransportProtocol::TCP,
+        netns: None,
+    };
+    let mut connection_socket_map: HashMap<SocketConnection, SocketProcess> = HashMap::new();
+    let mut local_socket_map: HashMap<LocalSocket, SocketProcess> = HashMap::new();
+    assert!(socket::lookup_socket_process(&conn, &connection_socket_map, &local_socket_map).is_none());
+    // Accepted connection falls back to the listener bound to any address
+    local_socket_map.insert(LocalSocket::new(String::from("eth0"), None, 22, TransportProtocol::TCP), socket_process(1));
+    assert_eq!(socket::lookup_socket_process(&conn, &connection_socket_map, &local_socket_map).unwrap().socket_addr.port(), 1);
+    // A listener bound to the local address takes precedence
+    local_socket_map.insert(LocalSocket::new(String::from("eth0"), Some(local_ip), 22, TransportProtocol::TCP), socket_process(2));
+    assert_eq!(socket::lookup_socket_process(&conn, &connection_socket_map, &local_socket_map).unwrap().socket_addr.port(), 2);
+    // The 5-tuple match takes precedence
+    connection_socket_map.insert(conn.clone(), socket_process(3));
+    assert_eq!(socket::lookup_socket_process(&conn, &connection_socket_map, &local_socket_map).unwrap().socket_addr.port(), 3);
+    // Connections in other namespaces do not fall back to host listeners
+    let mut netns_conn = conn.clone();
+    netns_conn.netns = Some(4026532000);
+    assert!(socket::lookup_socket_process(&netns_conn, &HashMap::new(), &local_socket_map).is_none());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_parse_proc_event() {
+    use nustat_core::socket::netlink::{ProcEvent, CN_IDX_PROC, CN_VAL_PROC};
+    let message = |what: u32, data: [u32; 4]| {
+        let mut payload: Vec<u8> = Vec::new();
+        // struct cn_msg
+        payload.extend_from_slice(&CN_IDX_PROC.to_ne_bytes());
+        payload.extend_from_slice(&CN_VAL_PROC.to_ne_bytes());
+        payload.extend_from_slice(&[0u8; 12]);
+        // struct proc_event: what, cpu, timestamp_ns, event_data
+        payload.extend_from_slice(&what.to_ne_bytes());
+        payload.extend_from_slice(&0u32.to_ne_bytes());
+        payload.extend_from_slice(&0u64.to_ne_bytes());
+        for v in data {
+            payload.extend_from_slice(&v.to_ne_bytes());
+        }
+        payload
+    };
+    assert_eq!(ProcEvent::from_bytes(&message(1, [100, 100, 200, 200])), Some(ProcEvent::Fork { parent_pid: 100, child_pid: 200 }));
+    // New thread, not a process
+    assert_eq!(ProcEvent::from_bytes(&message(1, [100, 100, 201, 200])), None);
+    assert_eq!(ProcEvent::from_bytes(&message(2, [200, 200, 0, 0])), Some(ProcEvent::Exec { pid: 200 }));
+    assert_eq!(ProcEvent::from_bytes(&message(0x80000000, [200, 200, 0, 0])), Some(ProcEvent::Exit { pid: 200 }));
+    assert_eq!(ProcEvent::from_bytes(&message(0x80000000, [201, 200, 0, 0])), None);
+    assert_eq!(ProcEvent::from_bytes(&[0u8; 8]), None);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_proc_event_listener() {
+    use std::sync::mpsc::sync_channel;
+    use nustat_core::socket::{self, SocketEvent};
+    let (tx, rx) = sync_channel(1024);
+    // The proc connector requires CAP_NET_ADMIN.
+    if socket::start_proc_event_listener(tx).is_err() {
+        return;
+    }
+    let mut child = std::process::Command::new("true").spawn().unwrap();
+    let pid = child.id();
+    child.wait().unwrap();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    let mut exited = false;
+    while let Ok(event) = rx.recv_timeout(deadline.saturating_duration_since(std::time::Instant::now())) {
+        if event == SocketEvent::ProcessExit(pid) {
+            exited = true;
+            break;
+        }
+    }
+    assert!(exited);
+}
+
+#[test]
+fn test_new_flow_notified_once() {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::mpsc::sync_channel;
+    use nustat_core::net::packet::PacketFrame;
+    use nustat_core::net::stat::NetStatStrage;
+    use nustat_core::socket::SocketEvent;
+    use xenet::packet::frame::{Frame, ParseOption};
+    let netstat_strage = NetStatStrage::new();
+    netstat_strage.local_ip_map.lock().unwrap().insert(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)), String::from("eth0"));
+    let (tx, rx) = sync_channel(1);
+    netstat_strage.set_socket_event_sender(Some(tx));
+    let update = |src_port: u16| {
+        let packet = udp_packet([192, 0, 2, 10], [198, 51, 100, 1], src_port, 53);
+        netstat_strage.update(PacketFrame::from_xenet_frame(0, 0, String::from("eth0"), Frame::from_bytes(&packet, ParseOption::default())));
+    };
+    update(40000);
+    assert!(matches!(rx.try_recv(), Ok(SocketEvent::NewFlow(conn)) if conn.local_port == 40000));
+    // The connection map is drained every tick, but the unattributed flow is not notified again.
+    netstat_strage.clone_data_and_reset();
+    update(40000);
+    assert!(rx.try_recv().is_err());
+    // A full queue does not block the capture.
+    update(40001);
+    update(40002);
+    assert!(matches!(rx.try_recv(), Ok(SocketEvent::NewFlow(conn)) if conn.local_port == 40001));
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn test_build_listening_ports() {
+    use std::collections::HashMap;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use nustat_core::net::traffic::TrafficInfo;
+    use nustat_core::socket::{AddressFamily, SocketConnection, SocketInfo, SocketStatus, TransportProtocol};
+    use nustat_core::socket::listener::{self, BindScope};
+    let lan_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10));
+    let socket_info = |ip: IpAddr, port: u16, protocol: TransportProtocol, status: SocketStatus, remote: Option<IpAddr>| SocketInfo {
+        local_ip_addr: ip,
+        local_port: port,
+        remote_ip_addr: remote,
+        remote_port: remote.map(|_| 40000),
+        protocol,
+        status,
+        ip_version: AddressFamily::IPv4,
+        process: None,
+        tcp_metrics: None,
+    };
+    let sockets_info = vec![
+        socket_info(IpAddr::V4(Ipv4Addr::LOCALHOST), 5432, TransportProtocol::TCP, SocketStatus::Listen, None),
+        socket_info(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 22, TransportProtocol::TCP, SocketStatus::Listen, None),
+        socket_info(lan_ip, 53, TransportProtocol::UDP, SocketStatus::Unknown, None),
+        // Established and connected sockets are not listeners
+        socket_info(lan_ip, 22, TransportProtocol::TCP, SocketStatus::Established, Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)))),
+        socket_info(lan_ip, 40001, TransportProtocol::UDP, SocketStatus::Unknown, Some(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)))),
+    ];
+    let mut local_ip_map: HashMap<IpAddr, String> = HashMap::new();
+    local_ip_map.insert(lan_ip, String::from("eth0"));
+    let mut connection_map: HashMap<SocketConnection, TrafficInfo> = HashMap::new();
+    let mut traffic = TrafficInfo::new();
+    traffic.bytes_received = 100;
+    connection_map.insert(SocketConnection {
+        interface_name: String::from("eth0"),
+        local_ip_addr: Some(lan_ip),
+        local_port: 22,
+        remote_ip_addr: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)),
+        remote_port: 40000,
+        protocol: TransportProtocol::TCP,
+        netns: None,
+    }, traffic);
+    let listeners = listener::build_listening_ports(&sockets_info, &connection_map, &local_ip_map);
+    assert_eq!(listeners.len(), 3);
+    // Exposed ports first
+    assert_eq!(listeners[0].local_port, 22);
+    assert_eq!(listeners[0].bind_scope, BindScope::All);
+    assert_eq!(listeners[0].traffic.bytes_received, 100);
+    assert_eq!(listeners[1].local_port, 53);
+    assert_eq!(listeners[1].bind_scope, BindScope::Interface);
+    assert_eq!(listeners[1].interface_name.as_deref(), Some("eth0"));
+    assert_eq!(listeners[2].bind_scope, BindScope::Loopback);
+    assert!(!listeners[2].is_exposed());
+
+    // IPv4 traffic of a dual-stack port is counted once, on 0.0.0.0 if bound, else on ::.
+    let mut sockets_info = vec![
+        socket_info(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 22, TransportProtocol::TCP, SocketStatus::Listen, None),
+        socket_info(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 22, TransportProtocol::TCP, SocketStatus::Listen, None),
+    ];
+    sockets_info[1].ip_version = AddressFamily::IPv6;
+    let listeners = listener::build_listening_ports(&sockets_info, &connection_map, &local_ip_map);
+    assert_eq!(listeners.iter().map(|l| l.traffic.bytes_received).sum::<usize>(), 100);
+    assert_eq!(listeners.iter().find(|l| l.local_ip_addr.is_ipv4()).unwrap().traffic.bytes_received, 100);
+    let listeners = listener::build_listening_ports(&sockets_info[1..], &connection_map, &local_ip_map);
+    assert_eq!(listeners[0].traffic.bytes_received, 100);
+}
+
+#[test]
+fn test_listening_ports() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use nustat_core::net::stat::NetStatStrage;
+    use nustat_core::socket::{self, SocketUpdateOption};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let mut netstat_strage = Arc::new(NetStatStrage::new());
+    let strage = Arc::clone(&netstat_strage);
+    std::thread::spawn(move || {
+        socket::start_socket_info_update_with_backend(&mut netstat_strage, socket::default_backend(), SocketUpdateOption::new());
+    });
+    // Listeners come from the scan of the socket updater.
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    let entry = loop {
+        let listeners = socket::listener::listening_ports(&strage, &HashMap::new());
+        if let Some(entry) = listeners.into_iter().find(|l| l.local_port == port) {
+            break entry;
+        }
+        assert!(std::time::Instant::now() < deadline);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    };
+    assert_eq!(entry.bind_scope, socket::listener::BindScope::Loopback);
+    assert_eq!(entry.process.as_ref().map(|p| p.pid), Some(std::process::id()));
+}
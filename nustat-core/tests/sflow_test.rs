@@ -0,0 +1,114 @@
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::sync::Arc;
+use std::time::Duration;
+use nustat_core::flow::sflow::{decode_datagram, start_sflow_export, HeaderProtocol, PacketSample, SflowEncoder, SflowExportOption, SAMPLED_HEADER_LEN};
+use nustat_core::flow::MAX_MESSAGE_SIZE;
+use nustat_core::net::stat::NetStatStrage;
+use nustat_core::net::traffic::{Direction, TrafficInfo};
+use nustat_core::pcap::{PacketSampler, PacketSampling, SamplingMode};
+use xenet::packet::frame::ParseOption;
+
+extern crate nustat_core;
+
+fn test_sample(if_index: u32) -> PacketSample {
+    let packet: Vec<u8> = (0..200).map(|i| i as u8).collect();
+    PacketSample::from_packet(&packet, &ParseOption::default(), if_index, 100, 12345, 2)
+}
+
+#[test]
+fn test_packet_sampler() {
+    assert!(PacketSampling::new(1, SamplingMode::Deterministic).is_none());
+
+    let mut sampler = PacketSampler::new(PacketSampling::new(10, SamplingMode::Deterministic));
+    let sampled: Vec<usize> = (1..=30).filter(|_| sampler.sample()).collect();
+    assert_eq!(sampled, vec![10, 20, 30]);
+    assert_eq!(sampler.pool(), 30);
+
+    // Random sampling keeps 1 in N on average.
+    let mut sampler = PacketSampler::new(PacketSampling::new(10, SamplingMode::Random));
+    let sampled = (0..100_000).filter(|_| sampler.sample()).count();
+    assert!((9_000..11_000).contains(&sampled), "sampled {}", sampled);
+
+    let mut sampler = PacketSampler::new(None);
+    assert!((0..10).all(|_| sampler.sample()));
+}
+
+#[test]
+fn test_traffic_sampled_estimate() {
+    let mut traffic = TrafficInfo::new();
+    traffic.add_packet(Direction::Egress, 100, 1);
+    assert!(!traffic.estimated);
+    traffic.add_packet(Direction::Ingress, 60, 10);
+    assert_eq!((traffic.packet_sent, traffic.bytes_sent), (1, 100));
+    assert_eq!((traffic.packet_received, traffic.bytes_received), (10, 600));
+    assert!(traffic.estimated);
+
+    let mut total = TrafficInfo::new();
+    total.add_traffic(&traffic);
+    assert!(total.estimated);
+}
+
+#[test]
+fn test_sflow_encode_decode() {
+    let sample = test_sample(3);
+    assert_eq!(sample.header_protocol, HeaderProtocol::Ethernet);
+    assert_eq!(sample.frame_length, 200);
+    assert_eq!(sample.header.len(), SAMPLED_HEADER_LEN);
+
+    let agent_addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let mut encoder = SflowEncoder::new(agent_addr, 7);
+    let datagrams = encoder.encode(&[sample.clone(), test_sample(4), test_sample(3)]);
+    assert_eq!(datagrams.len(), 1);
+    let datagram = decode_datagram(&datagrams[0]).unwrap();
+    assert_eq!(datagram.agent_addr, agent_addr);
+    assert_eq!(datagram.sub_agent_id, 7);
+    assert_eq!(datagram.sequence, 1);
+    assert_eq!(datagram.samples.len(), 3);
+    assert_eq!(datagram.samples[0], (1, sample));
+    // Sample sequence numbers are per data source.
+    assert_eq!(datagram.samples[1].0, 1);
+    assert_eq!(datagram.samples[2].0, 2);
+    assert_eq!(datagram.samples[1].1.sampling_rate, 100);
+    assert_eq!(datagram.samples[1].1.sample_pool, 12345);
+
+    // Large batches are split into datagrams below the size limit.
+    let samples: Vec<PacketSample> = (0..50).map(|_| test_sample(1)).collect();
+    let datagrams = encoder.encode(&samples);
+    assert!(datagrams.len() > 1);
+    assert!(datagrams.iter().all(|datagram| datagram.len() <= MAX_MESSAGE_SIZE));
+    let decoded: usize = datagrams.iter().map(|datagram| decode_datagram(datagram).unwrap().samples.len()).sum();
+    assert_eq!(decoded, 50);
+}
+
+#[test]
+fn test_sflow_export_udp() {
+    let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+    collector.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let strage = Arc::new(NetStatStrage::new());
+    start_sflow_export(Arc::clone(&strage), SflowExportOption::new(collector.local_addr().unwrap())).unwrap();
+    strage.send_packet_sample(|| test_sample(2));
+    let mut buf = [0u8; 65535];
+    let len = collector.recv(&mut buf).unwrap();
+    let datagram = decode_datagram(&buf[..len]).unwrap();
+    assert_eq!(datagram.agent_addr, IpAddr::V4(Ipv4Addr::LOCALHOST));
+    assert_eq!(datagram.samples.len(), 1);
+    assert_eq!(datagram.samples[0].1.if_index, 2);
+}
+
+#[test]
+fn test_sflow_drops_queue_full() {
+    let strage = NetStatStrage::new();
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    strage.set_packet_sample_sender(Some(tx));
+    // The queue holds one sample, so the next two are dropped.
+    for _ in 0..3 {
+        strage.send_packet_sample(|| test_sample(2));
+    }
+    assert_eq!(rx.try_recv().unwrap().drops, 0);
+    strage.send_packet_sample(|| test_sample(2));
+    strage.send_packet_sample(|| test_sample(3));
+    assert_eq!(rx.try_recv().unwrap().drops, 2);
+    // Drops are counted per interface.
+    strage.send_packet_sample(|| test_sample(3));
+    assert_eq!(strage.packet_sample_drops.lock().unwrap().get(&3), Some(&1));
+}
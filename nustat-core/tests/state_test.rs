@@ -0,0 +1,71 @@
+use std::net::{IpAddr, Ipv4Addr};
+use chrono::{Duration, Local};
+use nustat_core::net::stat::{NetStatData, NetStatStrage};
+use nustat_core::net::traffic::TrafficInfo;
+use nustat_core::socket::{SocketConnection, SocketProcess, TransportProtocol};
+use nustat_core::state::{decode_state, encode_state, SessionState, StateStore};
+
+extern crate nustat_core;
+
+fn test_data() -> NetStatData {
+    let mut data = NetStatData::new();
+    data.if_name = String::from("eth0");
+    data.traffic = TrafficInfo { packet_sent: 1, packet_received: 2, bytes_sent: 100, bytes_received: 200, estimated: false };
+    let conn = SocketConnection {
+        interface_name: String::from("eth0"),
+        local_ip_addr: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))),
+        local_port: 50000,
+        remote_ip_addr: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)),
+        remote_port: 443,
+        protocol: TransportProtocol::TCP,
+        netns: None,
+    };
+    data.connection_map.insert(conn.clone(), data.traffic.clone());
+    data.connection_socket_map.insert(conn, SocketProcess::new());
+    data
+}
+
+#[test]
+fn test_state_encode_decode() {
+    let since = Local::now() - Duration::hours(1);
+    let buf = encode_state(&SessionState::new(since, test_data())).unwrap();
+    assert_eq!(&buf[0..4], b"NSST");
+    let state = decode_state(&buf).unwrap();
+    assert_eq!(state.since, since);
+    assert_eq!(state.data.traffic.bytes_received, 200);
+    assert_eq!(state.data.connection_map.len(), 1);
+
+    // Unknown versions and foreign files are rejected.
+    let mut future = buf.clone();
+    future[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(decode_state(&future).is_err());
+    assert!(decode_state(b"{}").is_err());
+}
+
+#[test]
+fn test_state_store() {
+    let path = std::env::temp_dir().join(format!("nustat-state-test-{}", std::process::id()));
+    let store = StateStore::new(&path);
+    let since = store.since();
+    store.save(&test_data()).unwrap();
+
+    // A new session resumes the counters and the start time.
+    let mut store = StateStore::new(&path);
+    let data = store.restore().unwrap();
+    assert_eq!(store.since(), since);
+    // Socket state is rebuilt by the scanner and not restored.
+    assert!(data.connection_socket_map.is_empty());
+    assert!(data.local_socket_map.is_empty());
+    let strage = NetStatStrage::new();
+    strage.restore(data.clone());
+    strage.restore(data);
+    let restored = strage.clone_data();
+    assert_eq!(restored.traffic.bytes_sent, 200);
+    assert_eq!(restored.connection_map.values().next().unwrap().bytes_sent, 200);
+
+    // Undecodable state is discarded.
+    std::fs::write(&path, b"NSST\x00\x00\x00\x00garbage").unwrap();
+    let mut store = StateStore::new(&path);
+    assert!(store.restore().is_none());
+    assert!(!path.exists());
+}
@@ -0,0 +1,149 @@
+use std::io::{BufRead, BufReader};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::PathBuf;
+use std::time::Duration;
+use chrono::{Local, TimeZone};
+use nustat_core::net::host::RemoteHostInfo;
+use nustat_core::net::stat::NetStatData;
+use nustat_core::net::traffic::TrafficInfo;
+use nustat_core::process::ProcessInfo;
+use nustat_core::socket::{SocketConnection, SocketProcess, SocketStatus, TransportProtocol};
+use nustat_core::stream::{format_influx_line, format_lines, stream_records, StreamFormat, StreamTarget, StreamWriter, STREAM_SCHEMA_VERSION};
+
+extern crate nustat_core;
+
+fn test_data() -> NetStatData {
+    let conn = SocketConnection {
+        interface_name: String::from("eth0"),
+        local_ip_addr: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))),
+        local_port: 50000,
+        remote_ip_addr: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)),
+        remote_port: 443,
+        protocol: TransportProtocol::TCP,
+        netns: None,
+    };
+    let traffic = TrafficInfo { packet_sent: 2, packet_received: 3, bytes_sent: 200, bytes_received: 3000, estimated: false };
+    let mut data = NetStatData::new();
+    let mut host = RemoteHostInfo::new(String::new(), conn.remote_ip_addr);
+    host.hostname = String::from("example.com");
+    host.as_name = String::from("Example Net, Inc.");
+    host.traffic_info = traffic.clone();
+    data.remote_hosts.insert(conn.remote_ip_addr, host);
+    data.connection_socket_map.insert(conn.clone(), SocketProcess {
+        socket_addr: SocketAddr::new(conn.local_ip_addr.unwrap(), conn.local_port),
+        protocol: TransportProtocol::TCP,
+        status: SocketStatus::Established,
+        process: Some(ProcessInfo {
+            pid: 4242,
+            name: String::from("curl"),
+            exe_path: String::new(),
+            cmd: vec![],
+            status: String::new(),
+            user_info: None,
+            start_time: Local::now(),
+            elapsed_time: 0,
+            ppid: None,
+            ancestors: vec![],
+            cgroup_path: None,
+            netns: None,
+            container: None,
+            systemd_unit: None,
+        }),
+    });
+    data.connection_map.insert(conn, traffic.clone());
+    data.traffic = traffic;
+    data
+}
+
+fn test_interval() -> (chrono::DateTime<Local>, chrono::DateTime<Local>) {
+    (Local.timestamp_millis_opt(1_700_000_000_000).unwrap(), Local.timestamp_millis_opt(1_700_000_001_000).unwrap())
+}
+
+#[test]
+fn test_stream_target_parse() {
+    assert_eq!(StreamTarget::parse("-").unwrap(), StreamTarget::Stdout);
+    assert_eq!(StreamTarget::parse("").unwrap(), StreamTarget::Stdout);
+    assert_eq!(StreamTarget::parse("udp://127.0.0.1:8089").unwrap(), StreamTarget::UDP(String::from("127.0.0.1:8089")));
+    assert_eq!(StreamTarget::parse("unix:///run/nustat.sock").unwrap(), StreamTarget::Unix(PathBuf::from("/run/nustat.sock")));
+    assert_eq!(StreamTarget::parse("file:///tmp/out.jsonl").unwrap(), StreamTarget::File(PathBuf::from("/tmp/out.jsonl")));
+    assert_eq!(StreamTarget::parse("out.jsonl").unwrap(), StreamTarget::File(PathBuf::from("out.jsonl")));
+    assert!(StreamTarget::parse("tcp://127.0.0.1:8089").is_err());
+    assert!(StreamTarget::parse("unix://").is_err());
+    assert_eq!(StreamFormat::from_name("ndjson"), Some(StreamFormat::JSONLines));
+    assert_eq!(StreamFormat::from_name("Influx"), Some(StreamFormat::Influx));
+    assert_eq!(StreamFormat::from_name("csv"), None);
+}
+
+#[test]
+fn test_stream_json_lines() {
+    let (start, end) = test_interval();
+    let records = stream_records(&test_data(), start, end);
+    let types: Vec<String> = format_lines(StreamFormat::JSONLines, &records).iter().map(|line| {
+        let value: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(value["schema"], STREAM_SCHEMA_VERSION);
+        assert_eq!(value["start"].as_str().map(|s| chrono::DateTime::parse_from_rfc3339(s).unwrap().timestamp_millis()), Some(1_700_000_000_000));
+        if value["type"] == "connection" {
+            assert_eq!(value["remote_ip_addr"], "203.0.113.1");
+            assert_eq!(value["remote_port"], 443);
+            assert_eq!(value["pid"], 4242);
+            assert_eq!(value["bytes_received"], 3000);
+            assert_eq!(value["estimated"], false);
+        }
+        value["type"].as_str().unwrap().to_string()
+    }).collect();
+    assert_eq!(types, vec!["total", "host", "connection", "process"]);
+}
+
+#[test]
+fn test_stream_influx_line() {
+    let (start, end) = test_interval();
+    let records = stream_records(&test_data(), start, end);
+    assert_eq!(
+        format_influx_line(&records[0]),
+        "nustat_total,schema=2 packets_sent=2i,packets_received=3i,bytes_sent=200i,bytes_received=3000i,estimated=false,duration_ms=1000i 1700000001000000000"
+    );
+    // Spaces and commas in tags are escaped, empty tags are omitted.
+    let host = format_influx_line(&records[1]);
+    assert!(host.starts_with("nustat_host,schema=2,ip_addr=203.0.113.1,host_name=example.com,asn=0,as_name=Example\\ Net\\,\\ Inc. "), "{}", host);
+    assert!(!host.contains("country_code"));
+    let connection = format_influx_line(&records[2]);
+    assert!(connection.starts_with("nustat_connection,schema=2,interface=eth0,protocol=TCP,local_ip_addr=192.168.1.10,process=curl "), "{}", connection);
+    // Ports and the remote address are fields, so they do not create a series per connection.
+    assert!(connection.contains(" local_port=50000i,remote_ip_addr=\"203.0.113.1\",remote_port=443i,pid=4242i,"), "{}", connection);
+
+    // Connections in the same series do not overwrite each other.
+    let mut data = test_data();
+    let mut conn = data.connection_map.keys().next().unwrap().clone();
+    let socket_process = data.connection_socket_map.values().next().unwrap().clone();
+    conn.local_port = 50001;
+    data.connection_map.insert(conn.clone(), TrafficInfo { packet_sent: 1, packet_received: 1, bytes_sent: 10, bytes_received: 10, estimated: false });
+    data.connection_socket_map.insert(conn, socket_process);
+    let lines = format_lines(StreamFormat::Influx, &stream_records(&data, start, end));
+    let timestamps: Vec<&str> = lines.iter().filter(|line| line.starts_with("nustat_connection,")).map(|line| line.rsplit(' ').next().unwrap()).collect();
+    assert_eq!(timestamps, vec!["1700000001000000000", "1700000001000000001"]);
+}
+
+#[test]
+fn test_stream_writer_file_udp() {
+    let (start, end) = test_interval();
+    let path = std::env::temp_dir().join(format!("nustat-stream-test-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut writer = StreamWriter::open(StreamFormat::JSONLines, &StreamTarget::File(path.clone())).unwrap();
+    writer.write(&test_data(), start, end).unwrap();
+    writer.write(&NetStatData::new(), start, end).unwrap();
+    let lines: Vec<String> = BufReader::new(std::fs::File::open(&path).unwrap()).lines().map(|line| line.unwrap()).collect();
+    // An empty interval still writes the total.
+    assert_eq!(lines.len(), 5);
+    std::fs::remove_file(&path).unwrap();
+
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let target = StreamTarget::UDP(receiver.local_addr().unwrap().to_string());
+    let mut writer = StreamWriter::open(StreamFormat::Influx, &target).unwrap();
+    writer.write(&test_data(), start, end).unwrap();
+    let mut buf = [0u8; 65535];
+    let len = receiver.recv(&mut buf).unwrap();
+    let datagram = String::from_utf8_lossy(&buf[..len]).to_string();
+    assert_eq!(datagram.lines().count(), 4);
+    assert!(datagram.ends_with('\n'));
+}
@@ -0,0 +1,42 @@
+use nustat_core::systemd;
+
+extern crate nustat_core;
+
+#[test]
+fn test_parse_system_service() {
+    let info = systemd::parse_cgroup_path("/system.slice/nginx.service").unwrap();
+    assert_eq!(info.unit, "nginx.service");
+    assert_eq!(info.slice.as_deref(), Some("system.slice"));
+    assert!(!info.user_unit);
+}
+
+#[test]
+fn test_parse_user_unit() {
+    let info = systemd::parse_cgroup_path("/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox.scope").unwrap();
+    assert_eq!(info.unit, "app-firefox.scope");
+    assert_eq!(info.slice.as_deref(), Some("app.slice"));
+    assert!(info.user_unit);
+    let info = systemd::parse_cgroup_path("/user.slice/user-1000.slice/session-3.scope").unwrap();
+    assert_eq!(info.unit, "session-3.scope");
+    assert_eq!(info.slice.as_deref(), Some("user-1000.slice"));
+    assert!(!info.user_unit);
+}
+
+#[test]
+fn test_parse_non_unit_cgroup() {
+    assert!(systemd::parse_cgroup_path("/").is_none());
+    assert!(systemd::parse_cgroup_path("/docker/3f1e2d4c5b6a").is_none());
+    let info = systemd::parse_cgroup_path("/system.slice/containerd.service/kubepods-burstable.slice").unwrap();
+    assert_eq!(info.unit, "containerd.service");
+}
+
+#[test]
+fn test_select_cgroup_path() {
+    // cgroup v1 with a container in the cpu hierarchy
+    let id = "3f1e2d4c5b6a7980a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718";
+    let content = format!("4:cpu,cpuacct:/docker/{}\n1:name=systemd:/system.slice/sshd.service\n0::/\n", id);
+    assert_eq!(systemd::select_cgroup_path(&content).as_deref(), Some("/system.slice/sshd.service"));
+    assert_eq!(nustat_core::container::select_cgroup_path(&content), Some(format!("/docker/{}", id)));
+    // cgroup v2 unified hierarchy
+    assert_eq!(systemd::select_cgroup_path("0::/system.slice/nginx.service\n").as_deref(), Some("/system.slice/nginx.service"));
+}
@@ -0,0 +1,2 @@
+pub const OUI_BIN_NAME: &str = "oui.bin";
+pub const OUI_BIN: &[u8] = include_bytes!("../resources/oui.bin");
@@ -0,0 +1,31 @@
+pub mod db;
+
+use std::path::PathBuf;
+
+use serde::{Serialize, Deserialize};
+
+const USER_CONFIG_DIR_NAME: &str = ".nustat";
+const CONTENT_BASE_URL: &str = "https://raw.githubusercontent.com/shellrow/nustat";
+
+/// IEEE MA-L assignment. `oui` is the 24-bit prefix of the MAC address.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Oui {
+    pub oui: u32,
+    pub vendor_name: String,
+}
+
+impl Oui {
+    pub fn bin_file_path() -> Option<PathBuf> {
+        match home::home_dir() {
+            Some(mut path) => {
+                path.push(USER_CONFIG_DIR_NAME);
+                path.push(db::OUI_BIN_NAME);
+                Some(path)
+            }
+            None => None,
+        }
+    }
+    pub fn get_github_url(commit_hash: &str) -> String {
+        format!("{}/{}/nustat-db/nustat-db-oui/resources/{}", CONTENT_BASE_URL, commit_hash, db::OUI_BIN_NAME)
+    }
+}
@@ -2,12 +2,15 @@ use std::collections::HashMap;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use nustat_core::alert::AlertEngine;
 use nustat_core::net::host::RemoteHostInfo;
+use nustat_core::net::neighbor::NeighborInfo;
 use nustat_core::net::stat::NetStatStrage;
 use nustat_core::net::traffic::TrafficInfo;
 use nustat_core::process::{ProcessInfo, ProcessTrafficInfo};
 use tauri::{Manager, State};
-use nustat_core::socket::{LocalSocket, SocketInfo, SocketInfoOption};
+use nustat_core::socket::{self, SocketInfo, SocketInfoOption};
+use nustat_core::socket::listener::ListeningPortInfo;
 use nustat_core::pcap::CaptureReport;
 use nustat_core::net::packet::PacketFrame;
 use nustat_core::net::stat::Overview;
@@ -68,10 +71,15 @@ pub async fn start_packet_capture(app_handle: tauri::AppHandle) -> CaptureReport
 }
 
 #[tauri::command]
-pub fn get_netstat(opt: SocketInfoOption) -> Vec<SocketInfo> {
+pub fn get_netstat(opt: SocketInfoOption) -> Result<Vec<SocketInfo>, String> {
     nustat_core::socket::get_sockets_info(opt)
 }
 
+#[tauri::command]
+pub fn get_listening_ports(netstat: State<'_, Arc<NetStatStrage>>) -> Result<Vec<ListeningPortInfo>, String> {
+    Ok(nustat_core::socket::listener::listening_ports(&netstat, &netstat.get_connection_map()))
+}
+
 #[tauri::command]
 pub fn get_remote_hosts(netstat: State<'_, Arc<NetStatStrage>>) -> Vec<RemoteHostInfo> {
     let mut hosts: Vec<RemoteHostInfo> = Vec::new();
@@ -81,6 +89,15 @@ pub fn get_remote_hosts(netstat: State<'_, Arc<NetStatStrage>>) -> Vec<RemoteHos
     hosts
 }
 
+#[tauri::command]
+pub fn get_neighbors(netstat: State<'_, Arc<NetStatStrage>>) -> Vec<NeighborInfo> {
+    let mut neighbors: Vec<NeighborInfo> = Vec::new();
+    for (_ip_addr, neighbor) in netstat.get_neighbor_map() {
+        neighbors.push(neighbor);
+    }
+    neighbors
+}
+
 #[tauri::command]
 pub fn get_process_info(netstat: State<'_, Arc<NetStatStrage>>) -> Vec<ProcessTrafficInfo> {
     let mut processes: Vec<ProcessTrafficInfo> = Vec::new();
@@ -88,13 +105,9 @@ pub fn get_process_info(netstat: State<'_, Arc<NetStatStrage>>) -> Vec<ProcessTr
     let mut process_map: HashMap<u32, ProcessInfo> = HashMap::new();
     let connection_map = netstat.get_connection_map();
     let local_socket_map = netstat.get_local_socket_map();
+    let connection_socket_map = netstat.get_connection_socket_map();
     connection_map.iter().for_each(|(conn, traffic_info)| {
-        let local_socket: LocalSocket = LocalSocket {
-            interface_name: conn.interface_name.clone(),
-            port: conn.local_port,
-            protocol: conn.protocol,
-        };
-        match local_socket_map.get(&local_socket) {
+        match socket::lookup_socket_process(conn, &connection_socket_map, &local_socket_map) {
             Some(socket_process) => {
                 if let Some(process) = &socket_process.process {
                     match process_traffic_map.get(&process.pid) {
@@ -134,7 +147,12 @@ pub fn get_process_info(netstat: State<'_, Arc<NetStatStrage>>) -> Vec<ProcessTr
 }
 
 #[tauri::command]
-pub fn get_overview(netstat: State<'_, Arc<NetStatStrage>>) -> Overview {
+pub fn get_overview(netstat: State<'_, Arc<NetStatStrage>>, alert_engine: State<'_, Arc<Mutex<AlertEngine>>>) -> Overview {
     let netstat_data = netstat.clone_data();
-    netstat_data.get_overview()
+    let mut overview = netstat_data.get_overview();
+    match alert_engine.lock() {
+        Ok(alert_engine) => overview.notificatons = alert_engine.notifications().to_vec(),
+        Err(e) => eprintln!("Error: {:?}", e),
+    }
+    overview
 }
@@ -5,5 +5,7 @@ use nustat_core::net::traffic::TrafficInfo;
 pub struct ProcessDisplayInfo {
     pub pid: u32,
     pub name: String,
+    #[serde(default)]
+    pub unit: Option<String>,
     pub traffic: TrafficInfo,
 }
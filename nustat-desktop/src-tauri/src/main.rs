@@ -8,7 +8,9 @@ mod components;
 
 use std::sync::Arc;
 use nustat_core::net::stat::NetStatStrage;
-use commands::{get_overview, get_remote_hosts, get_netstat, get_process_info, start_packet_capture};
+use nustat_core::state::StateStore;
+use tauri::Manager;
+use commands::{get_overview, get_remote_hosts, get_neighbors, get_netstat, get_process_info, get_listening_ports, start_packet_capture};
 
 fn main() {
     //let netstat_strage: Arc<Mutex<NetStatStrage>> = Arc::new(Mutex::new(NetStatStrage::new()));
@@ -18,8 +20,10 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             get_overview,
             get_remote_hosts,
+            get_neighbors,
             get_netstat,
             get_process_info,
+            get_listening_ports,
             start_packet_capture,
             ])
         .setup(|app| {
@@ -30,6 +34,14 @@ fn main() {
             })
         .on_window_event(|event| match event.event() {
             tauri::WindowEvent::Destroyed => {
+                // Save the session state on exit
+                let window = event.window();
+                if let Some(store) = window.try_state::<StateStore>() {
+                    let netstat_strage = window.state::<Arc<NetStatStrage>>();
+                    if let Err(e) = store.save(&netstat_strage.clone_data()) {
+                        eprintln!("Error: {}", e);
+                    }
+                }
                 sys::cleanup();
             },
             _ => {}
@@ -1,6 +1,11 @@
 use std::thread;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::Local;
+use nustat_core::alert::AlertEngine;
+use nustat_core::config::AppConfig;
 use nustat_core::pcap;
+use nustat_core::state::StateStore;
 use tauri::Manager;
 
 pub fn start_background_task(handle: &tauri::AppHandle) {
@@ -14,12 +19,18 @@ pub fn start_background_task(handle: &tauri::AppHandle) {
     let mut netstat_strage_dns = Arc::clone(&netstat_strage);
     // For IP Info update
     //let mut netstat_strage_ipinfo = Arc::clone(&netstat_strage);
+    let config = AppConfig::load();
+    let packet_sampling = config.network.packet_sampling();
+    let socket_backend = nustat_core::socket::new_backend(config.network.socket_backend);
+    let socket_update_option = config.network.socket_update_option();
     thread::spawn(move || {
         netstat_strage_pcap.load_ipdb_from_crate();
+        netstat_strage_pcap.load_ouidb();
         println!("[start] background_capture");
         match default_net::get_default_interface() {
             Ok(iface) => {
-                let pcap_option = nustat_core::pcap::PacketCaptureOptions::from_interface(&iface);
+                let mut pcap_option = nustat_core::pcap::PacketCaptureOptions::from_interface(&iface);
+                pcap_option.sampling = packet_sampling;
                 pcap::start_background_capture(pcap_option, &mut netstat_strage_pcap, iface);
             }
             Err(e) => {
@@ -29,12 +40,110 @@ pub fn start_background_task(handle: &tauri::AppHandle) {
     });
     thread::spawn(move || {
         println!("[start] socket_info_update");
-        nustat_core::socket::start_socket_info_update(&mut netstat_strage_socket);
+        nustat_core::socket::start_socket_info_update_with_backend(&mut netstat_strage_socket, socket_backend, socket_update_option);
     });
     thread::spawn(move || {
         println!("[start] dns_map_update");
         nustat_core::dns::start_dns_map_update(&mut netstat_strage_dns);
     });
+    // Session state
+    if config.state.enabled {
+        if let Some(mut store) = StateStore::open_default() {
+            if let Some(data) = store.restore() {
+                netstat_strage.restore(data);
+            }
+            println!("[start] state_save since {}", store.since());
+            let netstat_strage_state = Arc::clone(&netstat_strage);
+            let save_interval = Duration::from_secs(config.state.save_interval.max(1));
+            handle.manage(store.clone());
+            thread::spawn(move || {
+                loop {
+                    thread::sleep(save_interval);
+                    if let Err(e) = store.save(&netstat_strage_state.clone_data()) {
+                        eprintln!("Error: {}", e);
+                    }
+                }
+            });
+        }
+    }
+    // Alert rules. The desktop keeps cumulative data, so the rules are evaluated on the difference between snapshots.
+    let alert_engine = Arc::new(Mutex::new(AlertEngine::new(config.alert.to_alert_option())));
+    handle.manage(Arc::clone(&alert_engine));
+    if !config.alert.rules.is_empty() {
+        let netstat_strage_alert = Arc::clone(&netstat_strage);
+        let tick_rate = Duration::from_millis(config.display.tick_rate.max(1));
+        thread::spawn(move || {
+            println!("[start] alert_evaluation");
+            let mut previous = netstat_strage_alert.clone_data();
+            // Data collected before the engine started (e.g. a restored session) is taken as known.
+            if !previous.remote_hosts.is_empty() {
+                if let Ok(mut alert_engine) = alert_engine.lock() {
+                    alert_engine.learn(&previous);
+                }
+            }
+            loop {
+                thread::sleep(tick_rate);
+                let current = netstat_strage_alert.clone_data();
+                let delta = current.delta_since(&previous);
+                match alert_engine.lock() {
+                    Ok(mut alert_engine) => {
+                        let listeners = if alert_engine.needs_listeners() {
+                            Some(nustat_core::socket::listener::listening_ports(&netstat_strage_alert, &delta.connection_map))
+                        } else {
+                            None
+                        };
+                        alert_engine.evaluate(&delta, listeners.as_deref(), Local::now());
+                    }
+                    Err(e) => {
+                        eprintln!("Error: {:?}", e);
+                    }
+                }
+                previous = current;
+            }
+        });
+    }
+    // Prometheus metrics
+    if config.metrics.enabled {
+        match config.metrics.listen_addr.parse() {
+            Ok(listen_addr) => {
+                println!("[start] metrics_server {}", listen_addr);
+                if let Err(e) = nustat_core::metrics::start_metrics_server(Arc::clone(&netstat_strage), listen_addr, config.metrics.series_limit) {
+                    eprintln!("Error: {}", e);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+            }
+        }
+    }
+    // sFlow export
+    if config.sflow_export.enabled {
+        match config.sflow_export.to_export_option() {
+            Ok(option) => {
+                println!("[start] sflow_export {}", option.collector);
+                if let Err(e) = nustat_core::flow::sflow::start_sflow_export(Arc::clone(&netstat_strage), option) {
+                    eprintln!("Error: {}", e);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+            }
+        }
+    }
+    // Flow export
+    if config.flow_export.enabled {
+        match config.flow_export.to_export_option() {
+            Ok(option) => {
+                println!("[start] flow_export {:?} {}", option.protocol, option.collector);
+                if let Err(e) = nustat_core::flow::start_flow_export(Arc::clone(&netstat_strage), option) {
+                    eprintln!("Error: {}", e);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+            }
+        }
+    }
     /* thread::spawn(move || {
         println!("[start] ipinfo_update");
         nustat_core::ipinfo::start_ipinfo_update(&mut netstat_strage_ipinfo);
@@ -171,6 +171,31 @@ export interface ProcessInfo {
     user_info: UserInfo | null,
     start_time: string,
     elapsed_time: number,
+    ppid: number | null,
+    ancestors: ProcessAncestor[],
+    cgroup_path: string | null,
+    netns: number | null,
+    container: ContainerInfo | null,
+    systemd_unit: SystemdUnitInfo | null,
+}
+
+export interface SystemdUnitInfo {
+    unit: string,
+    slice: string | null,
+    user_unit: boolean,
+}
+
+export interface ProcessAncestor {
+    pid: number,
+    name: string,
+}
+
+export interface ContainerInfo {
+    runtime: string,
+    container_id: string,
+    pod_uid: string | null,
+    pod_name: string | null,
+    pod_namespace: string | null,
 }
 
 export interface SocketInfo {
@@ -182,6 +207,27 @@ export interface SocketInfo {
     status: string,
     ip_version: string,
     process: ProcessInfo | null,
+    tcp_metrics: TcpMetrics | null,
+}
+
+export interface TcpMetrics {
+    rtt_us: number,
+    rttvar_us: number,
+    min_rtt_us: number,
+    rto_us: number,
+    snd_cwnd: number,
+    snd_ssthresh: number,
+    snd_mss: number,
+    retransmits: number,
+    total_retrans: number,
+    lost: number,
+    unacked: number,
+    bytes_acked: number,
+    bytes_received: number,
+    bytes_sent: number,
+    bytes_retrans: number,
+    pacing_rate: number,
+    delivery_rate: number,
 }
 
 export interface TrafficInfo {
@@ -193,7 +239,8 @@ export interface TrafficInfo {
 
 export enum TransportProtocol {
     TCP,
-    UDP
+    UDP,
+    RAW
 }
 
 export interface PortInfo {
@@ -205,6 +252,8 @@ export interface RemoteHostInfo {
     if_index: number,
     if_name: string,
     mac_addr: string,
+    mac_vendor: string,
+    mac_randomized: boolean,
     ip_addr: string,
     hostname: string,
     country_code: string,
@@ -217,6 +266,17 @@ export interface RemoteHostInfo {
     updated_at: string,
 }
 
+export interface NeighborInfo {
+    mac_addr: string,
+    ip_addr: string,
+    hostname: string,
+    vendor_class: string,
+    services: string[],
+    protocols: string[],
+    first_seen: string,
+    last_seen: string,
+}
+
 export interface ProcessTrafficInfo {
     process: ProcessInfo,
     traffic: TrafficInfo,
@@ -225,12 +285,16 @@ export interface ProcessTrafficInfo {
 export interface ProcessDisplayInfo {
     pid: number,
     name: string,
+    unit: string | null,
     traffic: TrafficInfo,
 }
 
 export interface HostDisplayInfo {
     ip_addr: string,
     host_name: string,
+    mac_addr: string,
+    mac_vendor: string,
+    mac_randomized: boolean,
     country_code: string,
     country_name: string,
     asn: number,
@@ -268,3 +332,14 @@ export interface Overview {
     top_app_protocols: ServiceDisplayInfo[],
     notifications: Notification[],
 }
+
+export interface ListeningPortInfo {
+    local_ip_addr: string,
+    local_port: number,
+    protocol: string,
+    ip_version: string,
+    bind_scope: string,
+    interface_name: string | null,
+    process: ProcessInfo | null,
+    traffic: TrafficInfo,
+}
@@ -1,4 +1,11 @@
-use nustat_core::{config::AppConfig, net::{host::HostDisplayInfo, service::ServiceDisplayInfo, stat::NetStatData}, process::ProcessDisplayInfo, socket::SocketTrafficInfo};
+use std::collections::HashSet;
+use nustat_core::{config::AppConfig, container::ContainerDisplayInfo, net::{host::HostDisplayInfo, service::ServiceDisplayInfo, stat::NetStatData}, process::{ProcessDisplayInfo, ProcessTreeNode}, socket::{listener::ListeningPortInfo, SocketTrafficInfo}, systemd::SystemdUnitDisplayInfo};
+use nustat_core::alert::AlertEngine;
+use nustat_core::state::StateStore;
+use nustat_core::stream::StreamWriter;
+use nustat_core::source::DataSource;
+use nustat_core::thread_log;
+use chrono::{DateTime, Local};
 use ratatui::widgets::TableState;
 
 pub struct TabsState<'a> {
@@ -23,6 +30,14 @@ impl<'a> TabsState<'a> {
     }
 }
 
+/// A visible row of the process tree.
+pub struct ProcessTreeRow {
+    pub depth: usize,
+    pub has_children: bool,
+    pub collapsed: bool,
+    pub node: ProcessTreeNode,
+}
+
 pub struct App<'a> {
     pub title: &'a str,
     pub should_pause: bool,
@@ -33,26 +48,83 @@ pub struct App<'a> {
     pub remote_hosts: Vec<HostDisplayInfo>,
     pub processes: Vec<ProcessDisplayInfo>,
     pub connections: Vec<SocketTrafficInfo>,
+    pub containers: Vec<ContainerDisplayInfo>,
+    pub systemd_units: Vec<SystemdUnitDisplayInfo>,
+    pub process_tree: Vec<ProcessTreeNode>,
+    pub process_rows: Vec<ProcessTreeRow>,
+    pub collapsed: HashSet<u32>,
+    pub listeners: Vec<ListeningPortInfo>,
     pub app_protocols: Vec<ServiceDisplayInfo>,
     pub enhanced_graphics: bool,
     pub config: AppConfig,
+    /// Start of the counters. Restored from the session state if enabled.
+    pub since: DateTime<Local>,
+    pub state_store: Option<StateStore>,
+    /// Writer of the per-tick traffic delta, if streaming is enabled.
+    pub stream: Option<StreamWriter>,
+    /// End of the previous tick.
+    pub last_tick: DateTime<Local>,
+    /// Where the traffic data comes from.
+    pub source: Box<dyn DataSource>,
+    /// Last error of the source, shown in the title.
+    pub source_error: Option<String>,
+    /// Alert rules evaluated on every tick. Their notifications are shown in the Overview tab.
+    pub alerts: AlertEngine,
 }
 
 impl<'a> App<'a> {
-    pub fn new(title:&'a str, enhanced_graphics: bool, config: AppConfig) -> App<'a> {
+    pub fn new(title:&'a str, enhanced_graphics: bool, config: AppConfig, source: Box<dyn DataSource>) -> App<'a> {
+        // A source keeping its own counters (a daemon) replaces the session state.
+        let mut state_store: Option<StateStore> = if config.state.enabled && source.since().is_none() { StateStore::open_default() } else { None };
+        let restored: Option<NetStatData> = state_store.as_mut().and_then(|store| store.restore());
+        let mut alerts = AlertEngine::new(config.alert.to_alert_option());
+        // Countries of the restored session are not new.
+        if let Some(restored) = &restored {
+            alerts.learn(restored);
+        }
+        let netstat_data: NetStatData = restored.unwrap_or_else(NetStatData::new);
+        let since: DateTime<Local> = match (&state_store, source.since()) {
+            (_, Some(since)) => since,
+            (Some(store), None) => store.since(),
+            (None, None) => Local::now(),
+        };
+        let stream: Option<StreamWriter> = if config.stream.enabled {
+            match config.stream.target().and_then(|target| StreamWriter::open(config.stream.format, &target)) {
+                Ok(writer) => Some(writer),
+                Err(e) => {
+                    thread_log!(error, "stream open error: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
         App {
             title,
             should_pause: false,
             should_quit: false,
-            tabs: TabsState::new(vec!["Overview", "RemoteAddresses", "Connections"]),
+            tabs: TabsState::new(vec!["Overview", "RemoteAddresses", "Connections", "Containers", "Processes", "Listeners"]),
             talbe_state: TableState::default(),
-            netstat_data: NetStatData::new(),
+            netstat_data,
             remote_hosts: vec![],
             processes: vec![],
             connections: vec![],
+            containers: vec![],
+            systemd_units: vec![],
+            process_tree: vec![],
+            process_rows: vec![],
+            collapsed: HashSet::new(),
+            listeners: vec![],
             app_protocols: vec![],
             enhanced_graphics: enhanced_graphics,
             config: config,
+            since,
+            state_store,
+            stream,
+            last_tick: Local::now(),
+            source,
+            source_error: None,
+            alerts,
         }
     }
 
@@ -64,8 +136,14 @@ impl<'a> App<'a> {
         let row_count = match self.tabs.index {
             1 => self.remote_hosts.len(),
             2 => self.connections.len(),
+            3 => self.containers.len(),
+            4 => self.process_rows.len(),
+            5 => self.listeners.len(),
             _ => 0,
         };
+        if row_count == 0 {
+            return;
+        }
         let i = match self.talbe_state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -87,8 +165,14 @@ impl<'a> App<'a> {
         let row_count = match self.tabs.index {
             1 => self.remote_hosts.len(),
             2 => self.connections.len(),
+            3 => self.containers.len(),
+            4 => self.process_rows.len(),
+            5 => self.listeners.len(),
             _ => 0,
         };
+        if row_count == 0 {
+            return;
+        }
         let i = match self.talbe_state.selected() {
             Some(i) => {
                 if i >= row_count - 1 {
@@ -112,6 +196,43 @@ impl<'a> App<'a> {
         self.tabs.previous();
     }
 
+    pub fn on_enter(&mut self) {
+        // Expand or collapse the selected process
+        if self.tabs.index != 4 {
+            return;
+        }
+        if let Some(row) = self.talbe_state.selected().and_then(|i| self.process_rows.get(i)) {
+            if !row.has_children {
+                return;
+            }
+            let pid = row.node.pid;
+            if !self.collapsed.remove(&pid) {
+                self.collapsed.insert(pid);
+            }
+            self.update_process_rows();
+        }
+    }
+
+    fn update_process_rows(&mut self) {
+        fn flatten(nodes: &[ProcessTreeNode], depth: usize, collapsed: &HashSet<u32>, rows: &mut Vec<ProcessTreeRow>) {
+            for node in nodes {
+                let is_collapsed = collapsed.contains(&node.pid);
+                rows.push(ProcessTreeRow {
+                    depth,
+                    has_children: !node.children.is_empty(),
+                    collapsed: is_collapsed,
+                    node: node.clone(),
+                });
+                if !is_collapsed {
+                    flatten(&node.children, depth + 1, collapsed, rows);
+                }
+            }
+        }
+        let mut rows: Vec<ProcessTreeRow> = Vec::new();
+        flatten(&self.process_tree, 0, &self.collapsed, &mut rows);
+        self.process_rows = rows;
+    }
+
     pub fn on_key(&mut self, c: char) {
         match c {
             'q' => {
@@ -129,11 +250,64 @@ impl<'a> App<'a> {
         }
     }
 
-    pub fn on_tick(&mut self, netstat_data: NetStatData) {
+    /// Save the counters if the session state is enabled.
+    pub fn save_state(&self) {
+        if let Some(store) = &self.state_store {
+            if let Err(e) = store.save(&self.netstat_data) {
+                thread_log!(error, "save_state error: {}", e);
+            }
+        }
+    }
+
+    pub fn on_tick(&mut self) {
+        let netstat_data: NetStatData = match self.source.next_delta() {
+            Ok(Some(netstat_data)) => netstat_data,
+            Ok(None) => return,
+            Err(e) => {
+                if self.source_error.is_none() {
+                    thread_log!(error, "data source error: {}", e);
+                }
+                self.source_error = Some(e);
+                return;
+            }
+        };
+        let now = Local::now();
+        if let Some(stream) = &mut self.stream {
+            if let Err(e) = stream.write(&netstat_data, self.last_tick, now) {
+                thread_log!(error, "stream write error: {}", e);
+            }
+        }
+        self.last_tick = now;
+        // Alert rules see the delta of this tick.
+        let listeners: Option<Vec<ListeningPortInfo>> = if self.alerts.needs_listeners() {
+            match self.source.listeners(&self.netstat_data) {
+                Ok(listeners) => Some(listeners),
+                Err(e) => {
+                    thread_log!(error, "listening_ports error: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        if self.alerts.has_rules() {
+            self.alerts.evaluate(&netstat_data, listeners.as_deref(), now);
+        }
         // Update the state of the application
         self.netstat_data.merge(netstat_data);
         self.remote_hosts = self.netstat_data.get_remote_hosts(None);
         //self.top_processes = app.netstat_data.get_top_processes();
         self.connections = self.netstat_data.get_connections(None);
+        self.containers = self.netstat_data.get_containers(None);
+        self.systemd_units = self.netstat_data.get_systemd_units(None);
+        self.process_tree = self.netstat_data.get_process_tree();
+        self.update_process_rows();
+        // Listeners are only rebuilt while the Listeners tab is shown.
+        if self.tabs.index == 5 {
+            match self.source.listeners(&self.netstat_data) {
+                Ok(listeners) => self.listeners = listeners,
+                Err(e) => thread_log!(error, "listening_ports error: {}", e),
+            }
+        }
     }
 }
\ No newline at end of file
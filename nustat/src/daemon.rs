@@ -0,0 +1,42 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use clap::ArgMatches;
+use nustat_core::api::ApiEndpoint;
+use nustat_core::config::AppConfig;
+use nustat_core::net::stat::NetStatStrage;
+use nustat_core::thread_log;
+
+/// How often the stop flag is checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Entry point of `nustat daemon` (nustatd mode).
+/// Serves the data of the capture threads over the API until SIGINT/SIGTERM is received.
+pub fn run(args: &ArgMatches, config: &AppConfig, netstat_strage: &Arc<NetStatStrage>) -> Result<(), Box<dyn Error>> {
+    let mut option = config.api.to_server_option(Duration::from_millis(config.display.tick_rate))?;
+    option.alert = config.alert.to_alert_option();
+    if let Some(listen) = args.get_one::<String>("listen") {
+        option.endpoint = ApiEndpoint::parse(listen)?;
+    }
+    let endpoint = option.endpoint.clone();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&stop))?;
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&stop))?;
+
+    nustat_core::api::server::start_api_server(Arc::clone(netstat_strage), option)?;
+    thread_log!(info, "[daemon] API listening on {}", endpoint);
+    println!("nustatd listening on {}", endpoint);
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(POLL_INTERVAL);
+    }
+    if let ApiEndpoint::Unix(path) = &endpoint {
+        if let Err(e) = std::fs::remove_file(path) {
+            thread_log!(warn, "[daemon] failed to remove {}: {}", path.display(), e);
+        }
+    }
+    println!("nustatd stopped");
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::Local;
+use clap::ArgMatches;
+use nustat_core::config::AppConfig;
+use nustat_core::fleet::agent::FleetAgent;
+use nustat_core::fleet::FleetStore;
+use nustat_core::net::stat::NetStatStrage;
+use nustat_core::thread_log;
+
+/// How often the stop flag and deadline are checked between deltas.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+fn register_stop_flag() -> Result<Arc<AtomicBool>, Box<dyn Error>> {
+    let stop = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&stop))?;
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&stop))?;
+    Ok(stop)
+}
+
+/// Entry point of `nustat agent`.
+/// Sends the traffic delta of every interval to the aggregation server until SIGINT/SIGTERM is received.
+pub fn run_agent(args: &ArgMatches, config: &mut AppConfig, netstat_strage: &Arc<NetStatStrage>) -> Result<(), Box<dyn Error>> {
+    if let Some(server) = args.get_one::<String>("server") {
+        config.fleet.server = server.clone();
+    }
+    if let Some(agent_id) = args.get_one::<String>("agent_id") {
+        config.fleet.agent_id = agent_id.clone();
+    }
+    if let Some(psk_file) = args.get_one::<String>("psk_file") {
+        config.fleet.psk_file = Some(psk_file.clone());
+    }
+    let interval = match args.get_one::<String>("interval") {
+        Some(interval) => crate::sys::parse_duration(interval)?,
+        None => Duration::from_secs(config.fleet.interval),
+    };
+    if interval.is_zero() {
+        return Err("Interval must be greater than zero".into());
+    }
+    let option = config.fleet.to_agent_option()?;
+    println!("Sending to {} as {} every {}s", option.server, option.agent_id, interval.as_secs_f64());
+    let mut agent = FleetAgent::new(option)?;
+    let stop = register_stop_flag()?;
+
+    // Discard traffic captured before the agent started.
+    netstat_strage.reset_data();
+    let mut interval_start = Local::now();
+    let mut next_delta = Instant::now() + interval;
+    let mut failing = false;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let finished = stop.load(Ordering::Relaxed);
+        if Instant::now() < next_delta && !finished {
+            continue;
+        }
+        let interval_end = Local::now();
+        let data = netstat_strage.clone_data_and_reset();
+        match agent.push(data, interval_start, interval_end) {
+            Ok(_) => {
+                if failing && agent.is_connected() {
+                    println!("Connected to the aggregation server");
+                    failing = false;
+                }
+            }
+            Err(e) => {
+                thread_log!(warn, "[agent] {}", e);
+                // Report once per outage. The data is kept and sent after a reconnect.
+                if !failing {
+                    eprintln!("Error: {}", e);
+                    failing = true;
+                }
+            }
+        }
+        interval_start = interval_end;
+        next_delta += interval;
+        if finished {
+            break;
+        }
+    }
+    if agent.has_pending() {
+        eprintln!("Stopped with unsent traffic");
+    }
+    Ok(())
+}
+
+/// Entry point of `nustat aggregate`.
+/// Accepts agents and serves the fleet-wide views until SIGINT/SIGTERM is received. Needs no capture.
+pub fn run_aggregate(args: &ArgMatches, config: &mut AppConfig) -> Result<(), Box<dyn Error>> {
+    if let Some(listen_addr) = args.get_one::<String>("listen") {
+        config.fleet.listen_addr = listen_addr.clone();
+    }
+    if let Some(api_addr) = args.get_one::<String>("api") {
+        config.fleet.api_addr = api_addr.clone();
+    }
+    if let Some(psk_file) = args.get_one::<String>("psk_file") {
+        config.fleet.psk_file = Some(psk_file.clone());
+    }
+    let option = config.fleet.to_server_option()?;
+    let api_addr = config.fleet.api_addr.parse().map_err(|e| format!("Invalid fleet API address {}: {}", config.fleet.api_addr, e))?;
+    let store = Arc::new(FleetStore::new());
+    nustat_core::fleet::server::start_fleet_server(Arc::clone(&store), option.clone())?;
+    nustat_core::fleet::server::start_fleet_api(Arc::clone(&store), api_addr)?;
+    println!("Accepting agents on {}, fleet API on http://{}/v1/fleet/", option.listen_addr, api_addr);
+    let stop = register_stop_flag()?;
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(POLL_INTERVAL);
+    }
+    println!("Stopped with {} agents", store.agents().len());
+    Ok(())
+}
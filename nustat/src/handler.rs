@@ -1,5 +1,6 @@
 use inquire::Confirm;
 use nustat_core::db::ip::{AS_BIN_NAME, COUNTRY_BIN_NAME, IPV4_INFO_BIN_NAME, IPV6_INFO_BIN_NAME};
+use nustat_core::db::oui::OUI_BIN_NAME;
 use nustat_core::net::http::DownloadProgress;
 use indicatif::ProgressBar;
 
@@ -148,6 +149,32 @@ pub fn check_db_files() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
         bar.finish();
+
+        // Download the latest OUI file
+        let oui_db_url = nustat_core::db::oui::Oui::get_github_url(&commit_hash);
+        println!("Downloading OUI db file from: {}", oui_db_url);
+        let save_file_path = config_dir.join(OUI_BIN_NAME);
+        // create a channel for progress
+        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::channel(100);
+        // spawn a task to handle the progress
+        tokio::spawn(async move {
+            let _ = nustat_core::net::http::download_file_with_progress(oui_db_url, save_file_path, progress_tx).await;
+        });
+        // Display progress with indicatif
+        let bar = ProgressBar::new(1000);
+        bar.set_style(indicatif::ProgressStyle::default_bar().template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})").progress_chars("#>-"));
+        while let Some(progress) = progress_rx.recv().await {
+            match progress {
+                DownloadProgress::ContentLength(content_length) => {
+                    println!("Content-Length: {}", content_length);
+                    bar.set_length(content_length);
+                }
+                DownloadProgress::Downloaded(downloaded) => {
+                    bar.set_position(downloaded);
+                }
+            }
+        }
+        bar.finish();
         println!("DB files downloaded successfully.")
     });
     Ok(())
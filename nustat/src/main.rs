@@ -3,6 +3,13 @@ mod app;
 mod terminal;
 mod ui;
 mod handler;
+mod output;
+mod sockets;
+mod record;
+mod report;
+mod stream;
+mod daemon;
+mod fleet;
 
 use std::fs::File;
 use std::path::Path;
@@ -13,6 +20,8 @@ use clap::{Arg, Command, ArgMatches};
 use clap::{crate_name, crate_version, crate_description, value_parser};
 use nustat_core::net::stat::NetStatStrage;
 use nustat_core::config::AppConfig;
+use nustat_core::api::ApiEndpoint;
+use nustat_core::source::{LocalSource, RemoteSource};
 use nustat_core::thread_log;
 use simplelog::WriteLogger;
 
@@ -28,6 +37,14 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
         return Ok(());
     }
+    // One-shot socket listing
+    if let Some(sockets_app) = app.subcommand_matches("sockets") {
+        return sockets::run(sockets_app);
+    }
+    // Query recorded history
+    if let Some(report_app) = app.subcommand_matches("report") {
+        return report::run(report_app);
+    }
 
     // Check .nustat directory
     match nustat_core::sys::get_config_dir_path() {
@@ -66,25 +83,74 @@ fn main() -> Result<(), Box<dyn Error>> {
         log_file,
     )?;
 
+    // Fleet aggregation server. No capture needed.
+    if let Some(aggregate_app) = app.subcommand_matches("aggregate") {
+        return fleet::run_aggregate(aggregate_app, &mut config);
+    }
+
+    // Attach to a running daemon instead of capturing
+    if let Some(endpoint) = app.get_one::<String>("connect") {
+        if app.subcommand().is_some() {
+            return Err("--connect only applies to the TUI".into());
+        }
+        let endpoint = if endpoint.is_empty() { config.api.endpoint()? } else { ApiEndpoint::parse(endpoint)? };
+        let source = RemoteSource::connect(endpoint)?;
+        crate::terminal::run(config, app.contains_id("enhanced_graphics"), Box::new(source))?;
+        return Ok(());
+    }
+
     // Start threads
+    let netstat_strage: Arc<NetStatStrage> = Arc::new(NetStatStrage::new());
+    let _threads: Vec<thread::JoinHandle<()>> = start_background_threads(&config, &netstat_strage);
+    let netstat_strage_ui = Arc::clone(&netstat_strage);
+
+    // Headless recording
+    if let Some(record_app) = app.subcommand_matches("record") {
+        return record::run(record_app, &netstat_strage);
+    }
+    // Headless streaming
+    if let Some(stream_app) = app.subcommand_matches("stream") {
+        return stream::run(stream_app, &config, &netstat_strage);
+    }
+    // Fleet agent
+    if let Some(agent_app) = app.subcommand_matches("agent") {
+        return fleet::run_agent(agent_app, &mut config, &netstat_strage);
+    }
+    // Daemon mode serving the API
+    if let Some(daemon_app) = app.subcommand_matches("daemon") {
+        return daemon::run(daemon_app, &config, &netstat_strage);
+    }
+
+    /* let ui_handler = thread::spawn(move || {
+        let _ = crate::terminal::run(tick_rate, cli.enhanced_graphics, &mut netstat_strage_ui);
+    });
+    threads.push(ui_handler); */
+    crate::terminal::run(config, app.contains_id("enhanced_graphics"), Box::new(LocalSource::new(netstat_strage_ui)))?;
+    Ok(())
+}
+
+/// Start the capture, socket and DNS threads feeding the strage.
+fn start_background_threads(config: &AppConfig, netstat_strage: &Arc<NetStatStrage>) -> Vec<thread::JoinHandle<()>> {
     let mut threads: Vec<thread::JoinHandle<()>> = vec![];
 
-    let netstat_strage: Arc<NetStatStrage> = Arc::new(NetStatStrage::new());
-    let mut netstat_strage_socket = Arc::clone(&netstat_strage);
-    let mut netstat_strage_ui = Arc::clone(&netstat_strage);
+    let mut netstat_strage_socket = Arc::clone(netstat_strage);
+    let socket_backend = nustat_core::socket::new_backend(config.network.socket_backend);
+    let socket_update_option = config.network.socket_update_option();
 
     let usable_interfaces = nustat_core::net::interface::get_usable_interfaces();
     let mut pcap_thread_index = 0;
     let pcap_handlers = usable_interfaces
         .iter()
         .map(|iface| {
-            let mut netstat_strage_pcap = Arc::clone(&netstat_strage);
+            let mut netstat_strage_pcap = Arc::clone(netstat_strage);
             let iface = iface.clone();
-            let pcap_option = nustat_core::pcap::PacketCaptureOptions::from_interface(&iface);
+            let mut pcap_option = nustat_core::pcap::PacketCaptureOptions::from_interface(&iface);
+            pcap_option.sampling = config.network.packet_sampling();
             let pcap_thread = thread::Builder::new().name(format!("pcap-thread-{}", iface.name.clone()));
             let pcap_handler = pcap_thread.spawn(move || {
                 if pcap_thread_index == 0 {
                     netstat_strage_pcap.load_ipdb_from_crate();
+                    netstat_strage_pcap.load_ouidb();
                 }
                 nustat_core::pcap::start_background_capture(pcap_option, &mut netstat_strage_pcap, iface);
             });
@@ -94,7 +160,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         .collect::<Vec<_>>();
 
     let socket_handler = thread::spawn(move || {
-        nustat_core::socket::start_socket_info_update(&mut netstat_strage_socket);
+        nustat_core::socket::start_socket_info_update_with_backend(&mut netstat_strage_socket, socket_backend, socket_update_option);
     });
 
     for pcap_handler in pcap_handlers {
@@ -109,20 +175,66 @@ fn main() -> Result<(), Box<dyn Error>> {
     }
     threads.push(socket_handler);
 
+    // Capture inside the selected network namespaces
+    for namespace in nustat_core::net::netns::select_namespaces(&config.network.netns) {
+        match nustat_core::net::netns::start_namespace_capture(namespace, Arc::clone(netstat_strage), config.network.packet_sampling()) {
+            Ok(handle) => {
+                threads.push(handle);
+            }
+            Err(e) => {
+                thread_log!(error, "Error: {}", e);
+            }
+        }
+    }
+
+    if config.metrics.enabled {
+        match config.metrics.listen_addr.parse() {
+            Ok(listen_addr) => {
+                match nustat_core::metrics::start_metrics_server(Arc::clone(netstat_strage), listen_addr, config.metrics.series_limit) {
+                    Ok(handle) => {
+                        threads.push(handle);
+                    }
+                    Err(e) => {
+                        thread_log!(error, "Error: {}", e);
+                    }
+                }
+            }
+            Err(e) => {
+                thread_log!(error, "Invalid metrics listen address {}: {}", config.metrics.listen_addr, e);
+            }
+        }
+    }
+
+    if config.sflow_export.enabled {
+        match config.sflow_export.to_export_option().and_then(|option| nustat_core::flow::sflow::start_sflow_export(Arc::clone(netstat_strage), option)) {
+            Ok(handle) => {
+                threads.push(handle);
+            }
+            Err(e) => {
+                thread_log!(error, "Error: {}", e);
+            }
+        }
+    }
+
+    if config.flow_export.enabled {
+        match config.flow_export.to_export_option().and_then(|option| nustat_core::flow::start_flow_export(Arc::clone(netstat_strage), option)) {
+            Ok(handle) => {
+                threads.push(handle);
+            }
+            Err(e) => {
+                thread_log!(error, "Error: {}", e);
+            }
+        }
+    }
+
     if config.network.reverse_dns {
-        let mut netstat_strage_dns = Arc::clone(&netstat_strage);
+        let mut netstat_strage_dns = Arc::clone(netstat_strage);
         let dns_handler = thread::spawn(move || {
             nustat_core::dns::start_dns_map_update(&mut netstat_strage_dns);
         });
         threads.push(dns_handler);
     }
-
-    /* let ui_handler = thread::spawn(move || {
-        let _ = crate::terminal::run(tick_rate, cli.enhanced_graphics, &mut netstat_strage_ui);
-    });
-    threads.push(ui_handler); */
-    crate::terminal::run(config, app.contains_id("enhanced_graphics"), &mut netstat_strage_ui)?;
-    Ok(())
+    threads
 }
 
 fn get_app_settings() -> ArgMatches {
@@ -135,6 +247,13 @@ fn get_app_settings() -> ArgMatches {
             .value_name("duration_ms")
             .value_parser(value_parser!(u64))
         )
+        .arg(Arg::new("connect")
+            .help("Attach to a running nustat daemon (e.g. unix:///run/nustat/nustatd.sock, 127.0.0.1:7391) instead of capturing. Defaults to api.listen in the config, or /run/nustat/nustatd.sock then ~/.nustat/nustatd.sock, if no endpoint is given")
+            .long("connect")
+            .value_name("endpoint")
+            .num_args(0..=1)
+            .default_missing_value("")
+        )
         .arg(Arg::new("enhanced_graphics")
             .help("Whether unicode symbols are used to improve the overall look of the app")
             .long("enhanced_graphics")
@@ -150,6 +269,259 @@ fn get_app_settings() -> ArgMatches {
                 .num_args(0)
             )
         )
+        // Sub-command for headless recording
+        .subcommand(Command::new("record")
+            .about("Record traffic to a history database without the TUI. nustat record --help for more information")
+            .arg(Arg::new("duration")
+                .help("Stop after the duration (e.g. 90s, 30m, 1h). Runs until SIGINT/SIGTERM if omitted")
+                .long("duration")
+                .short('d')
+                .value_name("duration")
+            )
+            .arg(Arg::new("interval")
+                .help("Time between two snapshots (e.g. 10s, 1m)")
+                .long("interval")
+                .short('i')
+                .value_name("duration")
+                .default_value("10s")
+            )
+            .arg(Arg::new("out")
+                .help("Path of the history database. Defaults to ~/.nustat/history.db")
+                .long("out")
+                .value_name("file_path")
+            )
+        )
+        // Sub-command for streaming traffic deltas
+        .subcommand(Command::new("stream")
+            .about("Stream the traffic delta of every interval as JSON Lines or InfluxDB line protocol. nustat stream --help for more information")
+            .arg(Arg::new("format")
+                .help("Output format: jsonl or influx. Defaults to stream.format in the config")
+                .long("format")
+                .short('f')
+                .value_name("format")
+            )
+            .arg(Arg::new("output")
+                .help("- for stdout, a file path, udp://host:port or unix:///path/to.sock")
+                .long("output")
+                .short('o')
+                .value_name("target")
+                .default_value("-")
+            )
+            .arg(Arg::new("interval")
+                .help("Time between two deltas (e.g. 1s, 10s). Defaults to the tick rate")
+                .long("interval")
+                .short('i')
+                .value_name("duration")
+            )
+            .arg(Arg::new("duration")
+                .help("Stop after the duration (e.g. 90s, 30m, 1h). Runs until SIGINT/SIGTERM if omitted")
+                .long("duration")
+                .short('d')
+                .value_name("duration")
+            )
+        )
+        // Sub-command for the daemon mode
+        .subcommand(Command::new("daemon")
+            .about("Run as nustatd: capture in the background and serve the local JSON API. nustat daemon --help for more information")
+            .arg(Arg::new("listen")
+                .help("unix:///path/to.sock or a loopback host:port. Defaults to api.listen in the config, or /run/nustat/nustatd.sock as root and ~/.nustat/nustatd.sock otherwise")
+                .long("listen")
+                .short('l')
+                .value_name("endpoint")
+            )
+        )
+        // Sub-commands for the fleet view
+        .subcommand(Command::new("agent")
+            .about("Send traffic deltas to a nustat aggregate server. nustat agent --help for more information")
+            .arg(Arg::new("server")
+                .help("Aggregation server (host:port). Defaults to fleet.server in the config")
+                .long("server")
+                .short('s')
+                .value_name("addr")
+            )
+            .arg(Arg::new("agent_id")
+                .help("Agent ID. Defaults to fleet.agent_id in the config or the hostname")
+                .long("agent-id")
+                .value_name("id")
+            )
+            .arg(Arg::new("interval")
+                .help("Time between two deltas (e.g. 10s, 1m). Defaults to fleet.interval in the config")
+                .long("interval")
+                .short('i')
+                .value_name("duration")
+            )
+            .arg(Arg::new("psk_file")
+                .help("File containing the pre-shared key. Defaults to fleet.psk_file or fleet.psk in the config")
+                .long("psk-file")
+                .value_name("file_path")
+            )
+        )
+        .subcommand(Command::new("aggregate")
+            .about("Aggregate the traffic of nustat agents and serve fleet-wide views. nustat aggregate --help for more information")
+            .arg(Arg::new("listen")
+                .help("Where agents connect. Defaults to fleet.listen_addr in the config (0.0.0.0:7392)")
+                .long("listen")
+                .short('l')
+                .value_name("addr")
+            )
+            .arg(Arg::new("api")
+                .help("Loopback address of the fleet JSON API. Defaults to fleet.api_addr in the config (127.0.0.1:7393)")
+                .long("api")
+                .value_name("addr")
+            )
+            .arg(Arg::new("psk_file")
+                .help("File containing the pre-shared key. Defaults to fleet.psk_file or fleet.psk in the config")
+                .long("psk-file")
+                .value_name("file_path")
+            )
+        )
+        // Sub-command for querying recorded history
+        .subcommand(Command::new("report")
+            .about("Report traffic recorded by nustat record. nustat report --help for more information")
+            .arg(Arg::new("db")
+                .help("Path of the history database. Defaults to ~/.nustat/history.db")
+                .long("db")
+                .value_name("file_path")
+            )
+            .arg(Arg::new("group")
+                .help("Group by host, asn, country, process, protocol or port")
+                .long("group")
+                .short('g')
+                .value_name("group")
+                .default_value("host")
+            )
+            .arg(Arg::new("since")
+                .help("Only include the last duration (e.g. 1h, 7d)")
+                .long("since")
+                .value_name("duration")
+            )
+            .arg(Arg::new("from")
+                .help("Start time (e.g. \"yesterday 14:00\", \"2024-01-31 09:00\")")
+                .long("from")
+                .value_name("time")
+            )
+            .arg(Arg::new("to")
+                .help("End time (e.g. \"yesterday 15:00\", now)")
+                .long("to")
+                .value_name("time")
+            )
+            .arg(Arg::new("host")
+                .help("Filter by remote IP address")
+                .long("host")
+                .value_name("ip_addr")
+            )
+            .arg(Arg::new("asn")
+                .help("Filter by AS number")
+                .long("asn")
+                .value_name("asn")
+                .value_parser(value_parser!(u32))
+            )
+            .arg(Arg::new("country")
+                .help("Filter by country code (e.g. US)")
+                .long("country")
+                .value_name("country_code")
+            )
+            .arg(Arg::new("process")
+                .help("Filter by process name (case-insensitive substring)")
+                .long("process")
+                .value_name("name")
+            )
+            .arg(Arg::new("pid")
+                .help("Filter by process ID")
+                .long("pid")
+                .value_name("pid")
+                .value_parser(value_parser!(u32))
+            )
+            .arg(Arg::new("protocol")
+                .help("Filter by protocol (tcp, udp)")
+                .long("protocol")
+                .short('p')
+                .value_name("protocol")
+            )
+            .arg(Arg::new("port")
+                .help("Filter by local or remote port")
+                .long("port")
+                .value_name("port")
+                .value_parser(value_parser!(u16))
+            )
+            .arg(Arg::new("sort")
+                .help("Sort by total, sent, received or packets")
+                .long("sort")
+                .short('s')
+                .value_name("key")
+                .default_value("total")
+            )
+            .arg(Arg::new("limit")
+                .help("Maximum number of rows. 0 for all")
+                .long("limit")
+                .short('n')
+                .value_name("count")
+                .value_parser(value_parser!(usize))
+                .default_value("20")
+            )
+            .arg(Arg::new("format")
+                .help("Output format (table, json, csv, markdown)")
+                .long("format")
+                .short('o')
+                .value_name("format")
+                .default_value("table")
+            )
+        )
+        // Sub-command for listing sockets
+        .subcommand(Command::new("sockets")
+            .about("Print the socket table and exit. nustat sockets --help for more information")
+            .arg(Arg::new("protocol")
+                .help("Filter by protocol (tcp, udp)")
+                .long("protocol")
+                .short('p')
+                .value_name("protocol")
+            )
+            .arg(Arg::new("family")
+                .help("Filter by address family (ipv4, ipv6)")
+                .long("family")
+                .short('f')
+                .value_name("family")
+            )
+            .arg(Arg::new("state")
+                .help("Filter by TCP state (e.g. listen, established, time_wait)")
+                .long("state")
+                .short('s')
+                .value_name("state")
+            )
+            .arg(Arg::new("port")
+                .help("Filter by local or remote port")
+                .long("port")
+                .value_name("port")
+                .value_parser(value_parser!(u16))
+            )
+            .arg(Arg::new("pid")
+                .help("Filter by process ID")
+                .long("pid")
+                .value_name("pid")
+                .value_parser(value_parser!(u32))
+            )
+            .arg(Arg::new("process")
+                .help("Filter by process name (case-insensitive substring)")
+                .long("process")
+                .value_name("name")
+            )
+            .arg(Arg::new("format")
+                .help("Output format (table, json, csv, markdown)")
+                .long("format")
+                .short('o')
+                .value_name("format")
+                .default_value("table")
+            )
+            .arg(Arg::new("watch")
+                .help("Keep running and print changes every N seconds")
+                .long("watch")
+                .short('w')
+                .value_name("duration_s")
+                .num_args(0..=1)
+                .default_missing_value("2")
+                .value_parser(value_parser!(u64))
+            )
+        )
         ;
     app.get_matches()
 }
@@ -0,0 +1,146 @@
+use serde::Serialize;
+
+/// Output format of the one-shot subcommands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl OutputFormat {
+    pub fn from_name(s: &str) -> Option<OutputFormat> {
+        match s.to_lowercase().as_str() {
+            "table" => Some(OutputFormat::Table),
+            "json" => Some(OutputFormat::Json),
+            "csv" => Some(OutputFormat::Csv),
+            "markdown" | "md" => Some(OutputFormat::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// Format rows as a plain text table. Columns are padded to the widest cell.
+pub fn format_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            if i < widths.len() {
+                widths[i] = widths[i].max(cell.chars().count());
+            }
+        }
+    }
+    let format_row = |cells: Vec<&str>| -> String {
+        let mut line = String::new();
+        for (i, cell) in cells.iter().enumerate() {
+            if i + 1 == cells.len() {
+                line.push_str(cell);
+            } else {
+                line.push_str(&format!("{:<width$}  ", cell, width = widths[i]));
+            }
+        }
+        line.trim_end().to_string()
+    };
+    let mut out = format_row(headers.to_vec());
+    out.push('\n');
+    for row in rows {
+        out.push_str(&format_row(row.iter().map(|c| c.as_str()).collect()));
+        out.push('\n');
+    }
+    out
+}
+
+/// Format rows as CSV (RFC 4180).
+pub fn format_csv(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut out = format_csv_row(headers);
+    for row in rows {
+        out.push_str(&format_csv_row(row));
+    }
+    out
+}
+
+/// Format a single CSV line including the trailing newline.
+pub fn format_csv_row<S: AsRef<str>>(row: &[S]) -> String {
+    let mut line = row.iter().map(|c| csv_escape(c.as_ref())).collect::<Vec<String>>().join(",");
+    line.push('\n');
+    line
+}
+
+/// Format rows as a GitHub-flavored Markdown table.
+pub fn format_markdown(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let escape = |s: &str| s.replace('|', "\\|");
+    let mut out = format!("| {} |\n", headers.iter().map(|h| escape(h)).collect::<Vec<String>>().join(" | "));
+    out.push_str(&format!("|{}\n", headers.iter().map(|_| " --- |").collect::<String>()));
+    for row in rows {
+        out.push_str(&format!("| {} |\n", row.iter().map(|c| escape(c)).collect::<Vec<String>>().join(" | ")));
+    }
+    out
+}
+
+/// Format rows in the given format. JSON output should use `format_json` with the typed values instead.
+pub fn format_rows(format: OutputFormat, headers: &[&str], rows: &[Vec<String>]) -> String {
+    match format {
+        OutputFormat::Table => format_table(headers, rows),
+        OutputFormat::Csv => format_csv(headers, rows),
+        OutputFormat::Markdown => format_markdown(headers, rows),
+        OutputFormat::Json => {
+            let objects: Vec<serde_json::Map<String, serde_json::Value>> = rows
+                .iter()
+                .map(|row| headers.iter().zip(row.iter()).map(|(h, c)| (h.to_string(), serde_json::Value::String(c.clone()))).collect())
+                .collect();
+            format_json(&objects)
+        }
+    }
+}
+
+pub fn format_json<T: Serialize>(value: &T) -> String {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => json,
+        Err(e) => format!("{{\"error\": \"{}\"}}", e),
+    }
+}
+
+fn csv_escape(s: &str) -> String {
+    if s.contains(',') || s.contains('"') || s.contains('\n') {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_format_from_name() {
+        assert_eq!(OutputFormat::from_name("CSV"), Some(OutputFormat::Csv));
+        assert_eq!(OutputFormat::from_name("md"), Some(OutputFormat::Markdown));
+        assert_eq!(OutputFormat::from_name("xml"), None);
+    }
+
+    #[test]
+    fn test_format_csv() {
+        let rows = vec![
+            vec![String::from("curl"), String::from("a,b")],
+            vec![String::from("say \"hi\""), String::from("line\nbreak")],
+        ];
+        assert_eq!(
+            format_csv(&["Process", "Note"], &rows),
+            "Process,Note\ncurl,\"a,b\"\n\"say \"\"hi\"\"\",\"line\nbreak\"\n"
+        );
+    }
+
+    #[test]
+    fn test_format_markdown() {
+        let rows = vec![vec![String::from("curl"), String::from("a|b")]];
+        assert_eq!(format_markdown(&["Process", "Note"], &rows), "| Process | Note |\n| --- | --- |\n| curl | a\\|b |\n");
+    }
+
+    #[test]
+    fn test_format_table() {
+        let rows = vec![vec![String::from("curl"), String::from("1")], vec![String::from("sshd-session"), String::from("22")]];
+        assert_eq!(format_table(&["Process", "Port"], &rows), "Process       Port\ncurl          1\nsshd-session  22\n");
+    }
+}
@@ -0,0 +1,75 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::Local;
+use clap::ArgMatches;
+use nustat_core::history::{HistoryDb, DEFAULT_HISTORY_DB_NAME};
+use nustat_core::net::stat::NetStatStrage;
+use nustat_core::thread_log;
+
+const DEFAULT_RECORD_INTERVAL: Duration = Duration::from_secs(10);
+/// How often the stop flag and deadline are checked between snapshots.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Entry point of `nustat record`.
+/// Persists the traffic delta of every interval until the duration elapses or SIGINT/SIGTERM is received.
+pub fn run(args: &ArgMatches, netstat_strage: &Arc<NetStatStrage>) -> Result<(), Box<dyn Error>> {
+    let interval = match args.get_one::<String>("interval") {
+        Some(interval) => crate::sys::parse_duration(interval)?,
+        None => DEFAULT_RECORD_INTERVAL,
+    };
+    if interval.is_zero() {
+        return Err("Interval must be greater than zero".into());
+    }
+    let duration: Option<Duration> = match args.get_one::<String>("duration") {
+        Some(duration) => Some(crate::sys::parse_duration(duration)?),
+        None => None,
+    };
+    let db_path: PathBuf = match args.get_one::<String>("out") {
+        Some(path) => PathBuf::from(path),
+        None => nustat_core::sys::get_user_file_path(DEFAULT_HISTORY_DB_NAME).ok_or("Could not get config directory path")?,
+    };
+    let mut db = HistoryDb::open(&db_path)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&stop))?;
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&stop))?;
+
+    println!("Recording to {} every {}s{}", db_path.display(), interval.as_secs_f64(), match duration {
+        Some(duration) => format!(" for {}s", duration.as_secs()),
+        None => String::new(),
+    });
+    let started = Instant::now();
+    // Discard traffic captured before recording started.
+    netstat_strage.reset_data();
+    let mut interval_start = Local::now();
+    let mut next_snapshot = started + interval;
+    let mut snapshots: usize = 0;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let now = Instant::now();
+        let finished = stop.load(Ordering::Relaxed) || duration.is_some_and(|duration| now.duration_since(started) >= duration);
+        if now < next_snapshot && !finished {
+            continue;
+        }
+        let interval_end = Local::now();
+        let data = netstat_strage.clone_data_and_reset();
+        match db.insert_snapshot(&data, interval_start, interval_end) {
+            Ok(_) => snapshots += 1,
+            Err(e) => {
+                thread_log!(error, "[record] insert error: {}", e);
+                eprintln!("Error: {}", e);
+            }
+        }
+        interval_start = interval_end;
+        next_snapshot += interval;
+        if finished {
+            break;
+        }
+    }
+    println!("Recorded {} snapshots in {}s", snapshots, started.elapsed().as_secs());
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::path::PathBuf;
+use clap::ArgMatches;
+use nustat_core::history::{HistoryDb, HistoryReport, ReportFilter, ReportGroup, ReportSort, DEFAULT_HISTORY_DB_NAME};
+use nustat_core::net::traffic::TrafficInfo;
+use nustat_core::socket::TransportProtocol;
+use crate::output::{self, OutputFormat};
+
+const TRAFFIC_HEADERS: [&str; 4] = ["Packets", "Bytes Received", "Bytes Sent", "Total Bytes"];
+
+fn traffic_cells(traffic: &TrafficInfo) -> Vec<String> {
+    vec![
+        traffic.total_packet().to_string(),
+        traffic.bytes_received.to_string(),
+        traffic.bytes_sent.to_string(),
+        traffic.total_bytes().to_string(),
+    ]
+}
+
+fn report_filter(args: &ArgMatches) -> Result<ReportFilter, String> {
+    let mut filter = ReportFilter::default();
+    let since = args.get_one::<String>("since");
+    let from = args.get_one::<String>("from");
+    if since.is_some() && from.is_some() {
+        return Err(String::from("--since and --from cannot be used together"));
+    }
+    if let Some(since) = since {
+        let duration = crate::sys::parse_duration(since)?;
+        let duration = chrono::Duration::from_std(duration).map_err(|e| e.to_string())?;
+        filter.start_time = Some(chrono::Local::now().checked_sub_signed(duration).ok_or(format!("Duration is too long: {}", since))?);
+    }
+    if let Some(from) = from {
+        filter.start_time = Some(crate::sys::parse_time(from)?);
+    }
+    if let Some(to) = args.get_one::<String>("to") {
+        filter.end_time = Some(crate::sys::parse_time(to)?);
+    }
+    if let Some(host) = args.get_one::<String>("host") {
+        filter.ip_addr = Some(host.parse().map_err(|_| format!("Invalid IP address: {}", host))?);
+    }
+    filter.asn = args.get_one::<u32>("asn").copied();
+    filter.country_code = args.get_one::<String>("country").cloned();
+    filter.process_name = args.get_one::<String>("process").cloned();
+    filter.pid = args.get_one::<u32>("pid").copied();
+    if let Some(protocol) = args.get_one::<String>("protocol") {
+        filter.protocol = match protocol.to_lowercase().as_str() {
+            "tcp" => Some(TransportProtocol::TCP),
+            "udp" => Some(TransportProtocol::UDP),
+            _ => return Err(format!("Invalid protocol: {}", protocol)),
+        };
+    }
+    filter.port = args.get_one::<u16>("port").copied();
+    Ok(filter)
+}
+
+/// Headers and rows of the report for the non-JSON formats.
+fn report_rows(report: &HistoryReport) -> (Vec<&'static str>, Vec<Vec<String>>) {
+    let (mut headers, rows): (Vec<&str>, Vec<Vec<String>>) = match report {
+        HistoryReport::Hosts(hosts) => (
+            vec!["IP Address", "Host Name", "Country", "ASN", "AS Name"],
+            hosts.iter().map(|host| {
+                let mut row = vec![host.ip_addr.to_string(), host.host_name.clone(), host.country_code.clone(), host.asn.to_string(), host.as_name.clone()];
+                row.extend(traffic_cells(&host.traffic));
+                row
+            }).collect(),
+        ),
+        HistoryReport::ASNs(asns) => (
+            vec!["ASN", "AS Name"],
+            asns.iter().map(|asn| {
+                let mut row = vec![asn.asn.to_string(), asn.as_name.clone()];
+                row.extend(traffic_cells(&asn.traffic));
+                row
+            }).collect(),
+        ),
+        HistoryReport::Countries(countries) => (
+            vec!["Country", "Country Name"],
+            countries.iter().map(|country| {
+                let mut row = vec![country.country_code.clone(), country.country_name.clone()];
+                row.extend(traffic_cells(&country.traffic));
+                row
+            }).collect(),
+        ),
+        HistoryReport::Processes(processes) => (
+            vec!["PID", "Name", "Unit"],
+            processes.iter().map(|process| {
+                let mut row = vec![process.pid.to_string(), process.name.clone(), process.unit.clone().unwrap_or_default()];
+                row.extend(traffic_cells(&process.traffic));
+                row
+            }).collect(),
+        ),
+        HistoryReport::Services(services) => (
+            vec!["Protocol", "Port", "Service"],
+            services.iter().map(|service| {
+                let port = if service.port == 0 { String::new() } else { service.port.to_string() };
+                let mut row = vec![service.protocol.clone(), port, service.name.clone()];
+                row.extend(traffic_cells(&service.traffic));
+                row
+            }).collect(),
+        ),
+    };
+    headers.extend(TRAFFIC_HEADERS);
+    (headers, rows)
+}
+
+/// Entry point of `nustat report`.
+pub fn run(args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let db_path: PathBuf = match args.get_one::<String>("db") {
+        Some(path) => PathBuf::from(path),
+        None => nustat_core::sys::get_user_file_path(DEFAULT_HISTORY_DB_NAME).ok_or("Could not get config directory path")?,
+    };
+    if !db_path.exists() {
+        return Err(format!("History database not found: {}. Record traffic with `nustat record` first.", db_path.display()).into());
+    }
+    let group = match args.get_one::<String>("group") {
+        Some(group) => ReportGroup::from_name(group).ok_or(format!("Invalid group: {}", group))?,
+        None => ReportGroup::Host,
+    };
+    let sort = match args.get_one::<String>("sort") {
+        Some(sort) => ReportSort::from_name(sort).ok_or(format!("Invalid sort key: {}", sort))?,
+        None => ReportSort::Total,
+    };
+    let format = match args.get_one::<String>("format") {
+        Some(format) => OutputFormat::from_name(format).ok_or(format!("Invalid format: {}", format))?,
+        None => OutputFormat::Table,
+    };
+    let limit: Option<usize> = match args.get_one::<usize>("limit") {
+        Some(0) => None,
+        Some(limit) => Some(*limit),
+        None => None,
+    };
+    let filter = report_filter(args)?;
+    let db = HistoryDb::open(&db_path)?;
+    let report = db.report(group, &filter, sort, limit)?;
+    match format {
+        OutputFormat::Json => println!("{}", output::format_json(&report)),
+        _ => {
+            let (headers, rows) = report_rows(&report);
+            print!("{}", output::format_rows(format, &headers, &rows));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{value_parser, Arg, Command};
+    use super::*;
+
+    fn report_args(argv: &[&str]) -> ArgMatches {
+        Command::new("report")
+            .arg(Arg::new("since").long("since"))
+            .arg(Arg::new("from").long("from"))
+            .arg(Arg::new("to").long("to"))
+            .arg(Arg::new("host").long("host"))
+            .arg(Arg::new("asn").long("asn").value_parser(value_parser!(u32)))
+            .arg(Arg::new("country").long("country"))
+            .arg(Arg::new("process").long("process"))
+            .arg(Arg::new("pid").long("pid").value_parser(value_parser!(u32)))
+            .arg(Arg::new("protocol").long("protocol"))
+            .arg(Arg::new("port").long("port").value_parser(value_parser!(u16)))
+            .try_get_matches_from(argv)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_report_filter() {
+        let filter = report_filter(&report_args(&["report", "--since", "1h", "--protocol", "TCP"])).unwrap();
+        assert!(filter.start_time.unwrap() < chrono::Local::now() - chrono::Duration::minutes(59));
+        assert_eq!(filter.protocol, Some(TransportProtocol::TCP));
+        let filter = report_filter(&report_args(&["report", "--from", "2024-01-31 09:00"])).unwrap();
+        assert_eq!(filter.start_time.unwrap().format("%Y-%m-%d %H:%M").to_string(), "2024-01-31 09:00");
+        // Both set the start time.
+        assert!(report_filter(&report_args(&["report", "--since", "1h", "--from", "2024-01-31"])).is_err());
+        assert!(report_filter(&report_args(&["report", "--since", "99999999999d"])).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::thread;
+use std::time::Duration;
+use clap::ArgMatches;
+use nustat_core::config::AppConfig;
+use nustat_core::socket::{AddressFamily, SocketBackend, SocketInfo, SocketInfoOption, TransportProtocol};
+use crate::output::{self, OutputFormat};
+
+const HEADERS: [&str; 6] = ["Proto", "Local Address", "Remote Address", "State", "PID", "Process"];
+
+/// Filters for the sockets subcommand. Unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct SocketFilter {
+    pub protocol: Option<TransportProtocol>,
+    pub family: Option<AddressFamily>,
+    /// Normalized state name. (e.g. `ESTABLISHED`, `TIME_WAIT`)
+    pub state: Option<String>,
+    pub port: Option<u16>,
+    pub pid: Option<u32>,
+    /// Lowercase process name substring.
+    pub process: Option<String>,
+}
+
+impl SocketFilter {
+    pub fn from_args(args: &ArgMatches) -> Result<SocketFilter, String> {
+        let mut filter = SocketFilter::default();
+        if let Some(protocol) = args.get_one::<String>("protocol") {
+            filter.protocol = match protocol.to_lowercase().as_str() {
+                "tcp" => Some(TransportProtocol::TCP),
+                "udp" => Some(TransportProtocol::UDP),
+                _ => return Err(format!("Invalid protocol: {}", protocol)),
+            };
+        }
+        if let Some(family) = args.get_one::<String>("family") {
+            filter.family = match family.to_lowercase().as_str() {
+                "ipv4" | "4" => Some(AddressFamily::IPv4),
+                "ipv6" | "6" => Some(AddressFamily::IPv6),
+                _ => return Err(format!("Invalid address family: {}", family)),
+            };
+        }
+        if let Some(state) = args.get_one::<String>("state") {
+            filter.state = Some(normalize_state(state));
+        }
+        filter.port = args.get_one::<u16>("port").copied();
+        filter.pid = args.get_one::<u32>("pid").copied();
+        filter.process = args.get_one::<String>("process").map(|name| name.to_lowercase());
+        Ok(filter)
+    }
+
+    /// Option passed to the socket backend. Protocol and family are filtered by the backend.
+    pub fn to_socket_info_option(&self) -> SocketInfoOption {
+        let address_family = match &self.family {
+            Some(family) => vec![family.clone()],
+            None => vec![AddressFamily::IPv4, AddressFamily::IPv6],
+        };
+        let transport_protocol = match self.protocol {
+            Some(protocol) => vec![protocol],
+            None => vec![TransportProtocol::TCP, TransportProtocol::UDP],
+        };
+        SocketInfoOption::new(address_family, transport_protocol)
+    }
+
+    pub fn matches(&self, socket: &SocketInfo) -> bool {
+        if let Some(protocol) = self.protocol {
+            if socket.protocol != protocol {
+                return false;
+            }
+        }
+        if let Some(state) = &self.state {
+            if normalize_state(&socket.status.to_string()) != *state {
+                return false;
+            }
+        }
+        if let Some(port) = self.port {
+            if socket.local_port != port && socket.remote_port != Some(port) {
+                return false;
+            }
+        }
+        if let Some(pid) = self.pid {
+            if socket.process.as_ref().map(|p| p.pid) != Some(pid) {
+                return false;
+            }
+        }
+        if let Some(name) = &self.process {
+            match &socket.process {
+                Some(process) => {
+                    if !process.name.to_lowercase().contains(name) {
+                        return false;
+                    }
+                }
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Accept `time-wait`, `time_wait` and `TIME_WAIT` alike.
+fn normalize_state(state: &str) -> String {
+    state.trim().to_uppercase().replace('-', "_")
+}
+
+/// Identifies a socket across snapshots in watch mode.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct SocketKey {
+    protocol: TransportProtocol,
+    local: String,
+    remote: String,
+    pid: Option<u32>,
+}
+
+fn socket_key(socket: &SocketInfo) -> SocketKey {
+    SocketKey {
+        protocol: socket.protocol,
+        local: format_addr(&socket.local_ip_addr.to_string(), Some(socket.local_port)),
+        remote: remote_addr(socket),
+        pid: socket.process.as_ref().map(|p| p.pid),
+    }
+}
+
+fn format_addr(ip_addr: &str, port: Option<u16>) -> String {
+    let port = match port {
+        Some(port) if port != 0 => port.to_string(),
+        _ => "*".to_string(),
+    };
+    if ip_addr.contains(':') {
+        format!("[{}]:{}", ip_addr, port)
+    } else {
+        format!("{}:{}", ip_addr, port)
+    }
+}
+
+fn remote_addr(socket: &SocketInfo) -> String {
+    match socket.remote_ip_addr {
+        Some(ip_addr) => format_addr(&ip_addr.to_string(), socket.remote_port),
+        None => "*:*".to_string(),
+    }
+}
+
+fn to_row(socket: &SocketInfo) -> Vec<String> {
+    vec![
+        socket.protocol.as_str().to_string(),
+        format_addr(&socket.local_ip_addr.to_string(), Some(socket.local_port)),
+        remote_addr(socket),
+        match socket.protocol {
+            TransportProtocol::TCP => socket.status.to_string(),
+            _ => String::new(),
+        },
+        socket.process.as_ref().map(|p| p.pid.to_string()).unwrap_or_default(),
+        socket.process.as_ref().map(|p| p.name.clone()).unwrap_or_default(),
+    ]
+}
+
+fn get_sockets(backend: &mut Box<dyn SocketBackend>, filter: &SocketFilter) -> Result<Vec<SocketInfo>, String> {
+    let mut sockets: Vec<SocketInfo> = backend
+        .get_sockets_info(&filter.to_socket_info_option())?
+        .into_iter()
+        .filter(|socket| filter.matches(socket))
+        .collect();
+    sockets.sort_by_key(socket_key);
+    Ok(sockets)
+}
+
+fn print_sockets(sockets: &[SocketInfo], format: OutputFormat) {
+    match format {
+        OutputFormat::Json => {
+            println!("{}", output::format_json(&sockets));
+        }
+        _ => {
+            let rows: Vec<Vec<String>> = sockets.iter().map(to_row).collect();
+            print!("{}", output::format_rows(format, &HEADERS, &rows));
+        }
+    }
+}
+
+/// Format the sockets that appeared (`+`) or disappeared (`-`) since the previous snapshot.
+/// Sockets whose TCP state changed are printed as `~`.
+fn format_diff(prev: &HashMap<SocketKey, SocketInfo>, current: &HashMap<SocketKey, SocketInfo>, format: OutputFormat, now: &str) -> String {
+    let mut changes: Vec<(&str, &SocketInfo)> = Vec::new();
+    for (key, socket) in current {
+        match prev.get(key) {
+            None => changes.push(("+", socket)),
+            Some(prev_socket) if prev_socket.status != socket.status => changes.push(("~", socket)),
+            Some(_) => {}
+        }
+    }
+    for (key, socket) in prev {
+        if !current.contains_key(key) {
+            changes.push(("-", socket));
+        }
+    }
+    changes.sort_by_key(|(_, socket)| socket_key(socket));
+    let mut out = String::new();
+    for (change, socket) in changes {
+        match format {
+            OutputFormat::Json => {
+                let event = serde_json::json!({
+                    "time": now,
+                    "change": change,
+                    "socket": socket,
+                });
+                out.push_str(&format!("{}\n", event));
+            }
+            OutputFormat::Csv => {
+                let mut row = vec![now.to_string(), change.to_string()];
+                row.extend(to_row(socket));
+                out.push_str(&output::format_csv_row(&row));
+            }
+            _ => {
+                out.push_str(&format!("{} {} {}\n", now, change, to_row(socket).join("  ").trim_end()));
+            }
+        }
+    }
+    out
+}
+
+fn print_diff(prev: &HashMap<SocketKey, SocketInfo>, current: &HashMap<SocketKey, SocketInfo>, format: OutputFormat) {
+    let now = chrono::Local::now().format("%H:%M:%S").to_string();
+    print!("{}", format_diff(prev, current, format, &now));
+}
+
+/// Entry point of `nustat sockets`.
+pub fn run(args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let filter = SocketFilter::from_args(args)?;
+    let format = match args.get_one::<String>("format") {
+        Some(format) => OutputFormat::from_name(format).ok_or(format!("Invalid format: {}", format))?,
+        None => OutputFormat::Table,
+    };
+    let config = AppConfig::load();
+    let mut backend = nustat_core::socket::new_backend(config.network.socket_backend);
+    let sockets = get_sockets(&mut backend, &filter)?;
+    let watch = args.get_one::<u64>("watch");
+    // In watch mode, CSV keeps a single schema: the initial snapshot is printed as added rows.
+    if watch.is_none() || format != OutputFormat::Csv {
+        print_sockets(&sockets, format);
+    }
+    let interval = match watch {
+        Some(interval) => Duration::from_secs((*interval).max(1)),
+        None => return Ok(()),
+    };
+    let mut prev: HashMap<SocketKey, SocketInfo> = sockets.into_iter().map(|s| (socket_key(&s), s)).collect();
+    if format == OutputFormat::Csv {
+        let mut headers = vec!["Time", "Change"];
+        headers.extend(HEADERS);
+        print!("{}", output::format_csv_row(&headers));
+        print_diff(&HashMap::new(), &prev, format);
+    }
+    loop {
+        thread::sleep(interval);
+        let current: HashMap<SocketKey, SocketInfo> = get_sockets(&mut backend, &filter)?
+            .into_iter()
+            .map(|s| (socket_key(&s), s))
+            .collect();
+        print_diff(&prev, &current, format);
+        prev = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use nustat_core::process::ProcessInfo;
+    use nustat_core::socket::SocketStatus;
+    use super::*;
+
+    fn socket(protocol: TransportProtocol, local_port: u16, status: SocketStatus, process: Option<(u32, &str)>) -> SocketInfo {
+        SocketInfo {
+            local_ip_addr: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)),
+            local_port,
+            remote_ip_addr: Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1))),
+            remote_port: Some(443),
+            protocol,
+            status,
+            ip_version: AddressFamily::IPv4,
+            process: process.map(|(pid, name)| ProcessInfo {
+                pid,
+                name: name.to_string(),
+                exe_path: String::new(),
+                cmd: vec![],
+                status: String::new(),
+                user_info: None,
+                start_time: chrono::Local::now(),
+                elapsed_time: 0,
+                ppid: None,
+                ancestors: vec![],
+                cgroup_path: None,
+                netns: None,
+                container: None,
+                systemd_unit: None,
+            }),
+            tcp_metrics: None,
+        }
+    }
+
+    fn socket_map(sockets: Vec<SocketInfo>) -> HashMap<SocketKey, SocketInfo> {
+        sockets.into_iter().map(|s| (socket_key(&s), s)).collect()
+    }
+
+    #[test]
+    fn test_normalize_state() {
+        assert_eq!(normalize_state("time-wait"), "TIME_WAIT");
+        assert_eq!(normalize_state(" time_wait "), "TIME_WAIT");
+        assert_eq!(normalize_state("ESTABLISHED"), "ESTABLISHED");
+    }
+
+    #[test]
+    fn test_socket_filter_matches() {
+        let curl = socket(TransportProtocol::TCP, 50000, SocketStatus::TimeWait, Some((4242, "Curl")));
+        let anonymous = socket(TransportProtocol::UDP, 5353, SocketStatus::Unknown, None);
+        assert!(SocketFilter::default().matches(&curl));
+        assert!(SocketFilter::default().matches(&anonymous));
+
+        let filter = SocketFilter { protocol: Some(TransportProtocol::UDP), ..Default::default() };
+        assert!(!filter.matches(&curl));
+        assert!(filter.matches(&anonymous));
+        let filter = SocketFilter { state: Some(normalize_state("time-wait")), ..Default::default() };
+        assert!(filter.matches(&curl));
+        assert!(!filter.matches(&anonymous));
+        // Either the local or the remote port
+        assert!(SocketFilter { port: Some(50000), ..Default::default() }.matches(&curl));
+        assert!(SocketFilter { port: Some(443), ..Default::default() }.matches(&curl));
+        assert!(!SocketFilter { port: Some(80), ..Default::default() }.matches(&curl));
+        assert!(SocketFilter { pid: Some(4242), ..Default::default() }.matches(&curl));
+        assert!(!SocketFilter { pid: Some(4242), ..Default::default() }.matches(&anonymous));
+        // Case-insensitive substring. Sockets without a process never match.
+        let filter = SocketFilter { process: Some(String::from("cur")), ..Default::default() };
+        assert!(filter.matches(&curl));
+        assert!(!filter.matches(&anonymous));
+    }
+
+    #[test]
+    fn test_format_diff() {
+        let prev = socket_map(vec![
+            socket(TransportProtocol::TCP, 50000, SocketStatus::Established, Some((4242, "curl"))),
+            socket(TransportProtocol::TCP, 50001, SocketStatus::Established, Some((4242, "curl"))),
+        ]);
+        let current = socket_map(vec![
+            socket(TransportProtocol::TCP, 50000, SocketStatus::TimeWait, Some((4242, "curl"))),
+            socket(TransportProtocol::TCP, 50002, SocketStatus::SynSent, Some((4242, "curl"))),
+        ]);
+        assert_eq!(
+            format_diff(&prev, &current, OutputFormat::Csv, "12:00:00"),
+            "12:00:00,~,TCP,192.168.1.10:50000,203.0.113.1:443,TIME_WAIT,4242,curl\n\
+             12:00:00,-,TCP,192.168.1.10:50001,203.0.113.1:443,ESTABLISHED,4242,curl\n\
+             12:00:00,+,TCP,192.168.1.10:50002,203.0.113.1:443,SYN_SENT,4242,curl\n"
+        );
+        assert_eq!(format_diff(&prev, &prev, OutputFormat::Table, "12:00:00"), "");
+        // The initial snapshot of watch mode is all added rows.
+        let initial = format_diff(&HashMap::new(), &prev, OutputFormat::Table, "12:00:00");
+        assert_eq!(initial.lines().count(), 2);
+        assert!(initial.lines().all(|line| line.starts_with("12:00:00 + TCP")));
+        let json = format_diff(&HashMap::new(), &prev, OutputFormat::Json, "12:00:00");
+        let event: serde_json::Value = serde_json::from_str(json.lines().next().unwrap()).unwrap();
+        assert_eq!(event["change"], "+");
+        assert_eq!(event["socket"]["local_port"], 50000);
+    }
+}
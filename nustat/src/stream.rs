@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::Local;
+use clap::ArgMatches;
+use nustat_core::config::AppConfig;
+use nustat_core::net::stat::NetStatStrage;
+use nustat_core::stream::{StreamFormat, StreamTarget, StreamWriter};
+use nustat_core::thread_log;
+
+/// How often the stop flag and deadline are checked between deltas.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Entry point of `nustat stream`.
+/// Writes the traffic delta of every interval until the duration elapses or SIGINT/SIGTERM is received.
+/// Status messages go to stderr since stdout may carry the stream.
+pub fn run(args: &ArgMatches, config: &AppConfig, netstat_strage: &Arc<NetStatStrage>) -> Result<(), Box<dyn Error>> {
+    let format = match args.get_one::<String>("format") {
+        Some(name) => StreamFormat::from_name(name).ok_or(format!("Invalid format: {}", name))?,
+        None => config.stream.format,
+    };
+    let target = StreamTarget::parse(args.get_one::<String>("output").map(|s| s.as_str()).unwrap_or("-"))?;
+    let interval = match args.get_one::<String>("interval") {
+        Some(interval) => crate::sys::parse_duration(interval)?,
+        None => Duration::from_millis(config.display.tick_rate),
+    };
+    if interval.is_zero() {
+        return Err("Interval must be greater than zero".into());
+    }
+    let duration: Option<Duration> = match args.get_one::<String>("duration") {
+        Some(duration) => Some(crate::sys::parse_duration(duration)?),
+        None => None,
+    };
+    let mut writer = StreamWriter::open(format, &target)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&stop))?;
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&stop))?;
+
+    eprintln!("Streaming {:?} to {:?} every {}s", format, target, interval.as_secs_f64());
+    let started = Instant::now();
+    // Discard traffic captured before streaming started.
+    netstat_strage.reset_data();
+    let mut interval_start = Local::now();
+    let mut next_delta = started + interval;
+    let mut errors: usize = 0;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let now = Instant::now();
+        let finished = stop.load(Ordering::Relaxed) || duration.is_some_and(|duration| now.duration_since(started) >= duration);
+        if now < next_delta && !finished {
+            continue;
+        }
+        let interval_end = Local::now();
+        let data = netstat_strage.clone_data_and_reset();
+        if let Err(e) = writer.write(&data, interval_start, interval_end) {
+            thread_log!(error, "[stream] write error: {}", e);
+            eprintln!("Error: {}", e);
+            errors += 1;
+        }
+        interval_start = interval_end;
+        next_delta += interval;
+        if finished {
+            break;
+        }
+    }
+    if errors > 0 {
+        eprintln!("{} write errors", errors);
+    }
+    Ok(())
+}
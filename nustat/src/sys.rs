@@ -3,3 +3,77 @@ use clap::{crate_name, crate_version};
 pub fn get_app_title() -> String {
     format!("{} v{}", crate_name!(), crate_version!())
 }
+
+/// Parse a duration such as `90`, `30s`, `15m`, `1h` or `2d`. A bare number is seconds.
+pub fn parse_duration(s: &str) -> Result<std::time::Duration, String> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => s.split_at(pos),
+        None => (s, "s"),
+    };
+    let value: u64 = num.parse().map_err(|_| format!("Invalid duration: {}", s))?;
+    let multiplier: u64 = match unit {
+        "ms" => return Ok(std::time::Duration::from_millis(value)),
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        _ => return Err(format!("Invalid duration unit: {}", s)),
+    };
+    let secs = value.checked_mul(multiplier).ok_or(format!("Duration is too long: {}", s))?;
+    Ok(std::time::Duration::from_secs(secs))
+}
+
+/// Parse a point in time in the local timezone.
+/// Accepts `now`, `today`, `yesterday`, `HH:MM[:SS]` (today), `YYYY-MM-DD`, `YYYY-MM-DD HH:MM[:SS]`
+/// and RFC 3339. `today` and `yesterday` may be followed by a time. (e.g. `yesterday 14:00`)
+pub fn parse_time(s: &str) -> Result<chrono::DateTime<chrono::Local>, String> {
+    use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("now") {
+        return Ok(Local::now());
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Ok(datetime.with_timezone(&Local));
+    }
+    let parse_clock = |t: &str| -> Result<NaiveTime, String> {
+        NaiveTime::parse_from_str(t, "%H:%M:%S")
+            .or_else(|_| NaiveTime::parse_from_str(t, "%H:%M"))
+            .map_err(|_| format!("Invalid time: {}", s))
+    };
+    let today = Local::now().date_naive();
+    let (date_part, time_part) = match s.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time.trim())),
+        None => (s, None),
+    };
+    let (date, time): (NaiveDate, NaiveTime) = match date_part.to_lowercase().as_str() {
+        "today" => (today, time_part.map(parse_clock).transpose()?.unwrap_or(NaiveTime::MIN)),
+        "yesterday" => (today.pred_opt().unwrap_or(today), time_part.map(parse_clock).transpose()?.unwrap_or(NaiveTime::MIN)),
+        _ => match NaiveDate::parse_from_str(date_part, "%Y-%m-%d") {
+            Ok(date) => (date, time_part.map(parse_clock).transpose()?.unwrap_or(NaiveTime::MIN)),
+            Err(_) if time_part.is_none() => (today, parse_clock(date_part)?),
+            Err(_) => return Err(format!("Invalid date: {}", s)),
+        },
+    };
+    Local.from_local_datetime(&NaiveDateTime::new(date, time))
+        .earliest()
+        .ok_or(format!("Invalid local time: {}", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(15 * 60));
+        assert_eq!(parse_duration("2d").unwrap(), Duration::from_secs(2 * 24 * 60 * 60));
+        assert!(parse_duration("1w").is_err());
+        assert!(parse_duration("h").is_err());
+        // Overflows are rejected instead of wrapping or panicking.
+        assert!(parse_duration(&format!("{}d", u64::MAX / 60)).is_err());
+    }
+}
@@ -8,11 +8,10 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::prelude::*;
-use std::sync::Arc;
-use nustat_core::{config::AppConfig, net::stat::NetStatStrage};
+use nustat_core::{config::AppConfig, source::DataSource};
 use crate::{app::App, sys, ui};
 
-pub fn run(app_config: AppConfig, enhanced_graphics: bool, netstat_strage: &mut Arc<NetStatStrage>) -> Result<(), Box<dyn Error>> {
+pub fn run(app_config: AppConfig, enhanced_graphics: bool, source: Box<dyn DataSource>) -> Result<(), Box<dyn Error>> {
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -22,8 +21,8 @@ pub fn run(app_config: AppConfig, enhanced_graphics: bool, netstat_strage: &mut
 
     // create app and run it
     let title = sys::get_app_title();
-    let app = App::new(&title, enhanced_graphics, app_config);
-    let res = run_app(&mut terminal, app, netstat_strage);
+    let app = App::new(&title, enhanced_graphics, app_config, source);
+    let res = run_app(&mut terminal, app);
 
     // restore terminal
     disable_raw_mode()?;
@@ -44,18 +43,23 @@ pub fn run(app_config: AppConfig, enhanced_graphics: bool, netstat_strage: &mut
 fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
     mut app: App,
-    netstat_strage: &mut Arc<NetStatStrage>
 ) -> io::Result<()> {
     let tick_rate = Duration::from_millis(app.config.display.tick_rate);
+    let save_interval = Duration::from_secs(app.config.state.save_interval.max(1));
     let mut last_tick = Instant::now();
+    let mut last_save = Instant::now();
     loop {
 
         if last_tick.elapsed() >= tick_rate {
             if !app.should_pause {
-                app.on_tick(netstat_strage.clone_data_and_reset());
+                app.on_tick();
             }
             last_tick = Instant::now();
         }
+        if last_save.elapsed() >= save_interval {
+            app.save_state();
+            last_save = Instant::now();
+        }
 
         terminal.draw(|f| ui::draw(f, &mut app))?;
 
@@ -68,6 +72,7 @@ fn run_app<B: Backend>(
                         KeyCode::Up | KeyCode::Char('w') => app.on_up(),
                         KeyCode::Right | KeyCode::Char('d') => app.on_right(),
                         KeyCode::Down | KeyCode::Char('s') => app.on_down(),
+                        KeyCode::Enter => app.on_enter(),
                         KeyCode::Char(c) => app.on_key(c),
                         _ => {}
                     }
@@ -76,6 +81,7 @@ fn run_app<B: Backend>(
         }
         
         if app.should_quit {
+            app.save_state();
             return Ok(());
         }
     }
@@ -3,6 +3,8 @@ use ratatui::{
     widgets::*,
 };
 
+use nustat_core::notification::Severity;
+use nustat_core::socket::listener::BindScope;
 use crate::app::App;
 
 pub fn draw(f: &mut Frame, app: &mut App) {
@@ -15,15 +17,19 @@ pub fn draw(f: &mut Frame, app: &mut App) {
         .iter()
         .map(|t| text::Line::from(Span::styled(*t, Style::default().fg(Color::Green))))
         .collect();
+    let since = match &app.source_error {
+        Some(e) => format!("{} [{}]", app.since.format("%Y-%m-%d %H:%M:%S"), e),
+        None => app.since.format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
     let tabs = if app.should_pause {
-        let pause_title = format!("{} [Paused] press <SPACE> to resume", app.title);
+        let pause_title = format!("{} since {} [Paused] press <SPACE> to resume", app.title, since);
         Tabs::new(titles)
         .block(Block::default().borders(Borders::ALL).title(pause_title).style(Style::default().fg(Color::Yellow)))
         .highlight_style(Style::default().fg(Color::LightBlue))
         .select(app.tabs.index)
     } else {
         Tabs::new(titles)
-        .block(Block::default().borders(Borders::ALL).title(app.title))
+        .block(Block::default().borders(Borders::ALL).title(format!("{} since {}", app.title, since)))
         .highlight_style(Style::default().fg(Color::LightBlue))
         .select(app.tabs.index)
     };
@@ -32,6 +38,9 @@ pub fn draw(f: &mut Frame, app: &mut App) {
         0 => draw_overview_tab(f, app, chunks[1]),
         1 => draw_remotehosts_tab(f, app, chunks[1]),
         2 => draw_connections_tab(f, app, chunks[1]),
+        3 => draw_containers_tab(f, app, chunks[1]),
+        4 => draw_processes_tab(f, app, chunks[1]),
+        5 => draw_listeners_tab(f, app, chunks[1]),
         _ => {}
     };
 }
@@ -56,7 +65,9 @@ fn draw_summary(f: &mut Frame, app: &mut App, area: Rect) {
         text::Line::from(format!("Packets: {}", app.netstat_data.traffic.packet_received)),
         text::Line::from(format!("Bytes: {}", app.netstat_data.traffic.bytes_received)),
     ];
-    let block2 = Block::default().borders(Borders::ALL).title("Total Ingress");
+    // Counters scaled up from sampled packets
+    let estimated = estimated_suffix(app.netstat_data.traffic.estimated);
+    let block2 = Block::default().borders(Borders::ALL).title(format!("Total Ingress{}", estimated));
     let paragraph2 = Paragraph::new(text2).block(block2).wrap(Wrap { trim: true });
     f.render_widget(paragraph2, chunks[1]);
 
@@ -65,12 +76,17 @@ fn draw_summary(f: &mut Frame, app: &mut App, area: Rect) {
         text::Line::from(format!("Packets: {}", app.netstat_data.traffic.packet_sent)),
         text::Line::from(format!("Bytes: {}", app.netstat_data.traffic.bytes_sent)),
     ];
-    let block3 = Block::default().borders(Borders::ALL).title("Total Egress");
+    let block3 = Block::default().borders(Borders::ALL).title(format!("Total Egress{}", estimated));
     let paragraph3 = Paragraph::new(text3).block(block3).wrap(Wrap { trim: true });
     f.render_widget(paragraph3, chunks[2]);
 
 }
 
+/// Title suffix for tables whose byte counts are scaled up from sampled packets.
+fn estimated_suffix(estimated: bool) -> &'static str {
+    if estimated { " (estimated)" } else { "" }
+}
+
 fn draw_top_data(f: &mut Frame, app: &mut App, area: Rect) {
     let area_chunks = Layout::default()
         .constraints(vec![Constraint::Percentage(100)])
@@ -82,7 +98,9 @@ fn draw_top_data(f: &mut Frame, app: &mut App, area: Rect) {
             .split(area_chunks[0]);
 
         // Draw top Remote Address Table        
-        let rows = app.remote_hosts.iter().take(app.config.display.top_remote_hosts).map(|host| {
+        let top_hosts = app.remote_hosts.iter().take(app.config.display.top_remote_hosts);
+        let estimated = estimated_suffix(top_hosts.clone().any(|host| host.traffic.estimated));
+        let rows = top_hosts.map(|host| {
             Row::new(vec![
                 host.ip_addr.to_string(),
                 host.asn.to_string(),
@@ -109,13 +127,15 @@ fn draw_top_data(f: &mut Frame, app: &mut App, area: Rect) {
                 .style(Style::new().bold())
                 //.bottom_margin(1),
         )
-        .block(Block::default().borders(Borders::ALL).title("Top Remote Addresses"))
+        .block(Block::default().borders(Borders::ALL).title(format!("Top Remote Addresses{}", estimated)))
         .highlight_style(Style::new().reversed())
         .highlight_symbol(">>");
 
         f.render_widget(table, inner_chunks[0]);
         
-        let rows = app.connections.iter().take(app.config.display.connection_count).map(|conn| {
+        let top_connections = app.connections.iter().take(app.config.display.connection_count);
+        let estimated = estimated_suffix(top_connections.clone().any(|conn| conn.traffic.estimated));
+        let rows = top_connections.map(|conn| {
             let remote_ip_string = if let Some(remote_ip_addr) = &conn.remote_ip_addr {
                 remote_ip_addr.to_string()
             } else {"".to_string()};
@@ -154,7 +174,7 @@ fn draw_top_data(f: &mut Frame, app: &mut App, area: Rect) {
                 .style(Style::new().bold())
                 //.bottom_margin(1),
         )
-        .block(Block::default().borders(Borders::ALL).title("Top Connections"))
+        .block(Block::default().borders(Borders::ALL).title(format!("Top Connections{}", estimated)))
         .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
         .highlight_symbol(">>");
         f.render_widget(table, inner_chunks[1]);
@@ -164,12 +184,19 @@ fn draw_top_data(f: &mut Frame, app: &mut App, area: Rect) {
 
 fn draw_remotehosts_table(f: &mut Frame, app: &mut App, area: Rect) {
     // Draw top Remote Address Table        
+    let estimated = estimated_suffix(app.remote_hosts.iter().any(|host| host.traffic.estimated));
     let rows = app.remote_hosts.iter().map(|host| {
+        let vendor_string = if host.mac_randomized {
+            "(randomized)".to_string()
+        } else {
+            host.mac_vendor.clone()
+        };
         Row::new(vec![
             host.ip_addr.to_string(),
             host.asn.to_string(),
             host.as_name.clone(),
             host.country_code.clone(),
+            vendor_string,
             host.traffic.bytes_received.to_string(),
             host.traffic.bytes_sent.to_string(),
         ])
@@ -179,6 +206,7 @@ fn draw_remotehosts_table(f: &mut Frame, app: &mut App, area: Rect) {
         Constraint::Length(8),
         Constraint::Length(24),
         Constraint::Length(8),
+        Constraint::Length(20),
         Constraint::Length(10),
         Constraint::Length(10),
     ];
@@ -188,11 +216,11 @@ fn draw_remotehosts_table(f: &mut Frame, app: &mut App, area: Rect) {
     .column_spacing(1)
     //.style(Style::new().blue())
     .header(
-        Row::new(vec!["IP Address", "ASN", "AS Name", "Country","↓ Bytes", "↑ Bytes"])
+        Row::new(vec!["IP Address", "ASN", "AS Name", "Country", "Vendor", "↓ Bytes", "↑ Bytes"])
             .style(Style::new().bold())
             //.bottom_margin(1),
     )
-    .block(Block::default().borders(Borders::ALL).title("Remote Addresses"))
+    .block(Block::default().borders(Borders::ALL).title(format!("Remote Addresses{}", estimated)))
     .highlight_style(Style::new().reversed())
     .highlight_symbol(">>");
 
@@ -201,6 +229,7 @@ fn draw_remotehosts_table(f: &mut Frame, app: &mut App, area: Rect) {
 }
 
 fn draw_connection_table(f: &mut Frame, app: &mut App, area: Rect) {
+    let estimated = estimated_suffix(app.connections.iter().any(|conn| conn.traffic.estimated));
     let rows = app.connections.iter().map(|conn| {
         let remote_ip_string = if let Some(remote_ip_addr) = &conn.remote_ip_addr {
             remote_ip_addr.to_string()
@@ -210,52 +239,266 @@ fn draw_connection_table(f: &mut Frame, app: &mut App, area: Rect) {
         } else {"".to_string()};
         let mut process_id_string = "".to_string();
         let mut process_name_string = "".to_string();
+        let mut user_name_string = "".to_string();
+        let mut unit_string = "".to_string();
         if let Some(process) = &conn.process {
             process_id_string = process.pid.to_string();
             process_name_string = process.name.clone();
+            if let Some(user_info) = &process.user_info {
+                user_name_string = user_info.user_name.clone();
+            }
+            if let Some(systemd_unit) = &process.systemd_unit {
+                unit_string = systemd_unit.unit.clone();
+            }
         }
+        let mut rtt_string = "".to_string();
+        let mut cwnd_string = "".to_string();
+        let mut retrans_string = "".to_string();
+        if let Some(tcp_metrics) = &conn.tcp_metrics {
+            rtt_string = format!("{:.1}", tcp_metrics.rtt_us as f64 / 1000.0);
+            cwnd_string = tcp_metrics.snd_cwnd.to_string();
+            retrans_string = tcp_metrics.total_retrans.to_string();
+        }
+        let interface_string = match conn.netns {
+            Some(netns) => format!("{}@{}", conn.interface_name, netns),
+            None => conn.interface_name.clone(),
+        };
         Row::new(vec![
-            format!("{}:{}", conn.interface_name, conn.local_port.to_string()),
+            format!("{}:{}", interface_string, conn.local_port.to_string()),
             format!("{}:{}", remote_ip_string, remote_port_string),
             conn.protocol.as_str().to_string(),
             conn.traffic.bytes_received.to_string(),
             conn.traffic.bytes_sent.to_string(),
+            rtt_string,
+            cwnd_string,
+            retrans_string,
             process_id_string,
             process_name_string,
+            user_name_string,
+            unit_string,
         ])
     }).collect::<Vec<Row>>();
     let widths = [
-        Constraint::Length(20),
+        Constraint::Length(32),
         Constraint::Length(45),
         Constraint::Length(8),
         Constraint::Length(8),
         Constraint::Length(8),
+        Constraint::Length(8),
+        Constraint::Length(6),
+        Constraint::Length(7),
         Constraint::Length(5),
         Constraint::Length(20),
+        Constraint::Length(12),
+        Constraint::Length(24),
     ];
     let table = Table::new(rows, widths)
     .column_spacing(1)
     .header(
-        Row::new(vec!["Local Socket", "Remote Socket", "Protocol", "↓ Bytes", "↑ Bytes", "PID", "Process Name"])
+        Row::new(vec!["Local Socket", "Remote Socket", "Protocol", "↓ Bytes", "↑ Bytes", "RTT(ms)", "Cwnd", "Retrans", "PID", "Process Name", "User", "Unit"])
             .style(Style::new().bold())
             //.bottom_margin(1),
     )
-    .block(Block::default().borders(Borders::ALL).title("Connections"))
+    .block(Block::default().borders(Borders::ALL).title(format!("Connections{}", estimated)))
     .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
     .highlight_symbol(">>");
     //f.render_widget(table, area);
     f.render_stateful_widget(table, area, &mut app.talbe_state);
 }
 
+fn draw_container_table(f: &mut Frame, app: &mut App, area: Rect) {
+    let rows = app.containers.iter().map(|container| {
+        let pod_string = match (&container.pod_namespace, &container.pod_name) {
+            (Some(namespace), Some(name)) => format!("{}/{}", namespace, name),
+            _ => "".to_string(),
+        };
+        Row::new(vec![
+            container.container_id.chars().take(12).collect::<String>(),
+            container.runtime.as_str().to_string(),
+            pod_string,
+            container.netns.map(|netns| netns.to_string()).unwrap_or_default(),
+            container.process_names.join(","),
+            container.traffic.bytes_received.to_string(),
+            container.traffic.bytes_sent.to_string(),
+        ])
+    }).collect::<Vec<Row>>();
+    let widths = [
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Length(40),
+        Constraint::Length(10),
+        Constraint::Length(24),
+        Constraint::Length(10),
+        Constraint::Length(10),
+    ];
+    let table = Table::new(rows, widths)
+    .column_spacing(1)
+    .header(
+        Row::new(vec!["Container", "Runtime", "Pod", "NetNS", "Processes", "↓ Bytes", "↑ Bytes"])
+            .style(Style::new().bold())
+    )
+    .block(Block::default().borders(Borders::ALL).title("Containers"))
+    .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+    .highlight_symbol(">>");
+    f.render_stateful_widget(table, area, &mut app.talbe_state);
+}
+
+fn draw_systemd_unit_table(f: &mut Frame, app: &mut App, area: Rect) {
+    let rows = app.systemd_units.iter().map(|unit| {
+        Row::new(vec![
+            unit.unit.clone(),
+            unit.slice.clone().unwrap_or_default(),
+            unit.process_names.join(","),
+            unit.traffic.bytes_received.to_string(),
+            unit.traffic.bytes_sent.to_string(),
+        ])
+    }).collect::<Vec<Row>>();
+    let widths = [
+        Constraint::Length(40),
+        Constraint::Length(24),
+        Constraint::Length(24),
+        Constraint::Length(10),
+        Constraint::Length(10),
+    ];
+    let table = Table::new(rows, widths)
+    .column_spacing(1)
+    .header(
+        Row::new(vec!["Unit", "Slice", "Processes", "↓ Bytes", "↑ Bytes"])
+            .style(Style::new().bold())
+    )
+    .block(Block::default().borders(Borders::ALL).title("systemd Units"));
+    f.render_widget(table, area);
+}
+
+fn draw_process_tree_table(f: &mut Frame, app: &mut App, area: Rect) {
+    let estimated = estimated_suffix(app.process_rows.iter().any(|row| row.node.cumulative_traffic.estimated));
+    let rows = app.process_rows.iter().map(|row| {
+        let marker = if !row.has_children {
+            " "
+        } else if row.collapsed {
+            "▸"
+        } else {
+            "▾"
+        };
+        Row::new(vec![
+            format!("{}{} {}", "  ".repeat(row.depth), marker, row.node.name),
+            row.node.pid.to_string(),
+            row.node.traffic.bytes_received.to_string(),
+            row.node.traffic.bytes_sent.to_string(),
+            row.node.cumulative_traffic.bytes_received.to_string(),
+            row.node.cumulative_traffic.bytes_sent.to_string(),
+        ])
+    }).collect::<Vec<Row>>();
+    let widths = [
+        Constraint::Length(40),
+        Constraint::Length(8),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(12),
+        Constraint::Length(12),
+    ];
+    let table = Table::new(rows, widths)
+    .column_spacing(1)
+    .header(
+        Row::new(vec!["Process", "PID", "↓ Bytes", "↑ Bytes", "↓ Total", "↑ Total"])
+            .style(Style::new().bold())
+    )
+    .block(Block::default().borders(Borders::ALL).title(format!("Processes{} (Enter: expand/collapse)", estimated)))
+    .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+    .highlight_symbol(">>");
+    f.render_stateful_widget(table, area, &mut app.talbe_state);
+}
+
+fn draw_listener_table(f: &mut Frame, app: &mut App, area: Rect) {
+    let rows = app.listeners.iter().map(|listener| {
+        let mut process_id_string = "".to_string();
+        let mut process_name_string = "".to_string();
+        if let Some(process) = &listener.process {
+            process_id_string = process.pid.to_string();
+            process_name_string = process.name.clone();
+        }
+        let row = Row::new(vec![
+            format!("{}:{}", listener.local_ip_addr, listener.local_port),
+            listener.protocol.as_str().to_string(),
+            listener.bind_scope.as_str().to_string(),
+            listener.interface_name.clone().unwrap_or_default(),
+            process_id_string,
+            process_name_string,
+            listener.traffic.bytes_received.to_string(),
+            listener.traffic.bytes_sent.to_string(),
+        ]);
+        // Highlight wildcard binds for auditing
+        if listener.bind_scope == BindScope::All {
+            row.style(Style::new().yellow())
+        } else {
+            row
+        }
+    }).collect::<Vec<Row>>();
+    let widths = [
+        Constraint::Length(45),
+        Constraint::Length(8),
+        Constraint::Length(10),
+        Constraint::Length(12),
+        Constraint::Length(8),
+        Constraint::Length(20),
+        Constraint::Length(10),
+        Constraint::Length(10),
+    ];
+    let table = Table::new(rows, widths)
+    .column_spacing(1)
+    .header(
+        Row::new(vec!["Local Address", "Protocol", "Scope", "Interface", "PID", "Process Name", "↓ Bytes", "↑ Bytes"])
+            .style(Style::new().bold())
+    )
+    .block(Block::default().borders(Borders::ALL).title("Listeners"))
+    .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+    .highlight_symbol(">>");
+    f.render_stateful_widget(table, area, &mut app.talbe_state);
+}
+
+fn draw_notifications(f: &mut Frame, app: &mut App, area: Rect) {
+    let notifications = app.alerts.notifications();
+    let mut lines: Vec<text::Line> = notifications.iter().take(area.height.saturating_sub(2) as usize).map(|notification| {
+        let time = match chrono::DateTime::parse_from_rfc3339(&notification.timestamp) {
+            Ok(time) => time.format("%H:%M:%S").to_string(),
+            Err(_) => notification.timestamp.clone(),
+        };
+        let color = match notification.severity {
+            Severity::Critical => Color::Red,
+            Severity::Warning => Color::Yellow,
+            Severity::Info => Color::Reset,
+        };
+        let count = if notification.count > 1 { format!(" (x{})", notification.count) } else { String::new() };
+        text::Line::from(vec![
+            Span::raw(format!("{} ", time)),
+            Span::styled(format!("{:<8} ", notification.severity.name()), Style::default().fg(color)),
+            Span::styled(format!("{}: ", notification.title), Style::new().bold()),
+            Span::raw(format!("{}{}", notification.body, count)),
+        ])
+    }).collect();
+    if lines.is_empty() {
+        lines.push(text::Line::from("No alerts"));
+    }
+    let block = Block::default().borders(Borders::ALL).title(format!("Notifications ({})", notifications.len()));
+    let paragraph = Paragraph::new(lines).block(block);
+    f.render_widget(paragraph, area);
+}
+
 fn draw_overview_tab(f: &mut Frame, app: &mut App, area: Rect) {
+    // The notifications panel is only shown if alert rules are configured.
+    let notification_height: u16 = if app.alerts.has_rules() { 7 } else { 0 };
     let chunks = Layout::default()
         .constraints([
             Constraint::Length(4),
             Constraint::Min(8),
+            Constraint::Length(notification_height),
         ])
         .split(area);
     draw_summary(f, app, chunks[0]);
     draw_top_data(f, app, chunks[1]);
+    if app.alerts.has_rules() {
+        draw_notifications(f, app, chunks[2]);
+    }
 }
 
 fn draw_remotehosts_tab(f: &mut Frame, app: &mut App, area: Rect) {
@@ -271,3 +514,25 @@ fn draw_connections_tab(f: &mut Frame, app: &mut App, area: Rect) {
         .split(area);
     draw_connection_table(f, app, chunks[0]);
 }
+
+fn draw_containers_tab(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .constraints(vec![Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(area);
+    draw_container_table(f, app, chunks[0]);
+    draw_systemd_unit_table(f, app, chunks[1]);
+}
+
+fn draw_processes_tab(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .constraints(vec![Constraint::Percentage(100)])
+        .split(area);
+    draw_process_tree_table(f, app, chunks[0]);
+}
+
+fn draw_listeners_tab(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .constraints(vec![Constraint::Percentage(100)])
+        .split(area);
+    draw_listener_table(f, app, chunks[0]);
+}